@@ -0,0 +1,99 @@
+//! Signature aggregators for the WSTS signing schemes of `frost_signer::scheme`.
+
+use frost_signer::{
+    scheme::{message_nonces, Scheme, WstsVersion},
+    signing_round::NonceResponse,
+};
+use wsts::{
+    common::{PolyCommitment, Signature, SignatureShare},
+    compute,
+    errors::AggregatorError,
+    v1, v2, Scalar,
+};
+
+pub trait Aggregator {
+    /// Aggregate the signature shares of the signers in `nonce_responses` for `msg`, which they
+    /// signed with their nonces at `index`
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        shares: &[SignatureShare],
+        index: usize,
+    ) -> Result<Signature, AggregatorError>;
+}
+
+/// Aggregator of the signature shares of the given scheme, for the key of the polynomials
+pub fn new_aggregator(
+    wsts_version: WstsVersion,
+    num_keys: u32,
+    threshold: u32,
+    polys: Vec<PolyCommitment>,
+) -> Result<Box<dyn Aggregator>, AggregatorError> {
+    Ok(match wsts_version {
+        WstsVersion::V1 => Box::new(V1Aggregator(v1::SignatureAggregator::new(
+            num_keys, threshold, polys,
+        )?)),
+        WstsVersion::V2 => {
+            let mut aggregator = v2::Aggregator::new(num_keys, threshold);
+            aggregator.init(polys)?;
+            Box::new(V2Aggregator(aggregator))
+        }
+    })
+}
+
+/// Sum signature shares already checked against the public keys of their key ids into a
+/// signature of the aggregate nonce
+#[allow(non_snake_case)]
+pub fn sum_shares(
+    scheme: &dyn Scheme,
+    msg: &[u8],
+    nonce_responses: &[NonceResponse],
+    shares: &[SignatureShare],
+    index: usize,
+) -> Signature {
+    let party_ids = scheme.party_ids(nonce_responses);
+    let (_, R) = compute::intermediate(msg, &party_ids, &message_nonces(nonce_responses, index));
+    let z = shares.iter().fold(Scalar::new(), |z, share| z + share.z_i);
+    Signature { R, z }
+}
+
+/// Checks every share against the polynomials while aggregating
+pub struct V1Aggregator(v1::SignatureAggregator);
+
+impl Aggregator for V1Aggregator {
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        shares: &[SignatureShare],
+        index: usize,
+    ) -> Result<Signature, AggregatorError> {
+        self.0
+            .sign(msg, &message_nonces(nonce_responses, index), shares)
+    }
+}
+
+/// Checks the share of every signer against the polynomials while aggregating
+pub struct V2Aggregator(v2::Aggregator);
+
+impl Aggregator for V2Aggregator {
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        shares: &[SignatureShare],
+        index: usize,
+    ) -> Result<Signature, AggregatorError> {
+        let key_ids = nonce_responses
+            .iter()
+            .flat_map(|nr| nr.key_ids.clone())
+            .collect::<Vec<u32>>();
+        self.0.sign(
+            msg,
+            &message_nonces(nonce_responses, index),
+            shares,
+            &key_ids,
+        )
+    }
+}
@@ -1,14 +1,19 @@
 use std::any::Any;
-use std::collections::BTreeMap;
-use std::time::Duration;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
 
-use frost_signer::config::{Config, Error as ConfigError};
+use frost_signer::config::{Config, Error as ConfigError, PublicKeys, SignerKeyIds};
 use frost_signer::{
+    complaint::{public_key_point, Blame, Complaint},
     net::{Error as HttpNetError, Message, NetListen},
+    scheme::{message_nonces, WstsVersion},
     signing_round::{
-        DkgBegin, DkgPublicShare, MessageTypes, NonceRequest, NonceResponse, Signable,
-        SignatureShareRequest,
+        DkgBegin, DkgFailure, DkgPrivateBegin, DkgPrivateShares, DkgPublicShare, DkgStatus,
+        MessageTypes, NonceBatch, NonceBatchRequest, NonceRequest, NonceResponse, RefreshBegin,
+        RefreshCommit, RefreshCommitAck, RefreshShares, Signable, SignatureShareRequest,
     },
+    taproot::TaprootTweak,
+    util::eval_public_share,
 };
 use hashbrown::HashSet;
 use p256k1::ecdsa::PublicKey;
@@ -18,9 +23,16 @@ use wsts::{
     common::{PolyCommitment, PublicNonce, Signature, SignatureShare},
     compute,
     errors::AggregatorError,
-    v1, Point, Scalar,
+    Point, Scalar,
 };
 
+use crate::aggregator::{new_aggregator, sum_shares};
+
+/// How long signers get to return their signature shares before signing is retried without them
+const DEFAULT_SIGN_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many times a key refresh commit is sent to signers that did not acknowledge it
+const REFRESH_COMMIT_ATTEMPTS: usize = 3;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -39,6 +51,14 @@ pub enum Error {
     ConfigError(#[from] ConfigError),
     #[error("Received invalid signer message.")]
     InvalidSignerMessage,
+    #[error("Too few keys left to sign after dropping signers {0:?}")]
+    InsufficientSigners(Vec<u32>),
+    #[error("Key refresh failed for signers {0:?}, who keep their old key shares")]
+    RefreshFailed(Vec<u32>),
+    #[error("Signers {0:?} did not acknowledge switching to their refreshed key shares")]
+    RefreshCommitUnacked(Vec<u32>),
+    #[error("DKG round #{0} failed: complaint of signer #{1} about key #{2} cannot be settled")]
+    UnsettledComplaint(u64, u32, u32),
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -55,16 +75,37 @@ pub struct Coordinator<Network: NetListen> {
     current_dkg_public_id: u64,
     current_sign_id: u64,
     current_sign_nonce_id: u64,
+    current_refresh_id: u64,
+    current_nonce_batch_id: u64,
     total_signers: u32, // Assuming the signers cover all id:s in {1, 2, ..., total_signers}
     total_keys: u32,
     threshold: u32,
     network: Network,
     dkg_public_shares: BTreeMap<u32, DkgPublicShare>,
+    /// Private shares of the current DKG round by key id, kept to settle complaints about them
+    dkg_private_shares: BTreeMap<u32, DkgPrivateShares>,
     public_nonces: BTreeMap<u32, NonceResponse>,
-    signature_shares: BTreeMap<u32, Vec<SignatureShare>>,
+    /// Nonces each signer published ahead of signing and that were not handed out yet
+    nonce_pool: BTreeMap<u32, NonceBatch>,
+    signature_shares: BTreeMap<u32, Vec<Vec<SignatureShare>>>,
     aggregate_public_key: Point,
     network_private_key: Scalar,
     public_key: PublicKey,
+    signer_key_ids: SignerKeyIds,
+    public_keys: PublicKeys,
+    dkg_public_timeout: Option<Duration>,
+    dkg_end_timeout: Option<Duration>,
+    sign_timeout: Duration,
+    wsts_version: WstsVersion,
+    nonce_batch_size: Option<u32>,
+    /// Signers taking part in the current DKG round
+    dkg_signer_ids: BTreeSet<u32>,
+    /// Signers dropped from the current DKG round for missing a deadline or misbehaving
+    dkg_dropped_signer_ids: BTreeSet<u32>,
+    /// Signers found sending bad private shares, left out of DKG until it succeeds
+    dkg_blamed_signer_ids: BTreeSet<u32>,
+    /// Signers yet to acknowledge the commit of the last key refresh
+    refresh_commit_signer_ids: BTreeSet<u32>,
 }
 
 impl<Network: NetListen> Coordinator<Network> {
@@ -75,16 +116,31 @@ impl<Network: NetListen> Coordinator<Network> {
             current_dkg_public_id: 0,
             current_sign_id: 1,
             current_sign_nonce_id: 1,
+            current_refresh_id: 0,
+            current_nonce_batch_id: 0,
             total_signers: config.total_signers,
             total_keys: config.total_keys,
             threshold: config.keys_threshold,
             network,
             dkg_public_shares: Default::default(),
+            dkg_private_shares: Default::default(),
             public_nonces: Default::default(),
+            nonce_pool: Default::default(),
             aggregate_public_key: Point::default(),
             signature_shares: Default::default(),
             network_private_key: config.network_private_key,
             public_key: config.coordinator_public_key,
+            signer_key_ids: config.signer_key_ids.clone(),
+            public_keys: config.public_keys.clone(),
+            dkg_public_timeout: config.dkg_public_timeout,
+            dkg_end_timeout: config.dkg_end_timeout,
+            sign_timeout: config.sign_timeout.unwrap_or(DEFAULT_SIGN_TIMEOUT),
+            wsts_version: config.wsts_version,
+            nonce_batch_size: config.nonce_batch_size,
+            dkg_signer_ids: (1..=config.total_signers).collect(),
+            dkg_dropped_signer_ids: Default::default(),
+            dkg_blamed_signer_ids: Default::default(),
+            refresh_commit_signer_ids: Default::default(),
         })
     }
 
@@ -104,9 +160,191 @@ impl<Network: NetListen> Coordinator<Network> {
         &self.dkg_public_shares
     }
 
+    /// Restore the public shares of a past DKG round, along with the round's id so signers pick
+    /// the key shares of that round
     pub fn set_dkg_public_shares(&mut self, dkg_public_shares: BTreeMap<u32, DkgPublicShare>) {
+        if let Some(dkg_public_share) = dkg_public_shares.values().next() {
+            self.current_dkg_id = dkg_public_share.dkg_id;
+        }
         self.dkg_public_shares = dkg_public_shares;
     }
+
+    pub fn get_dkg_id(&self) -> u64 {
+        self.current_dkg_id
+    }
+
+    /// Continue DKG round ids after another coordinator's, so signers taking part in both tell
+    /// their key shares apart
+    pub fn set_dkg_id(&mut self, dkg_id: u64) {
+        self.current_dkg_id = dkg_id;
+    }
+
+    /// Forget the preprocessed nonces of the signers, which a DKG round run by another
+    /// coordinator made them drop
+    pub fn discard_nonce_pool(&mut self) {
+        self.nonce_pool.clear();
+    }
+
+    /// Signers dropped from the last DKG round for missing a deadline
+    pub fn get_dkg_dropped_signer_ids(&self) -> &BTreeSet<u32> {
+        &self.dkg_dropped_signer_ids
+    }
+
+    /// Key ids held by the signers taking part in the current DKG round
+    fn dkg_key_ids(&self) -> BTreeSet<u32> {
+        self.dkg_signer_ids
+            .iter()
+            .filter_map(|signer_id| self.signer_key_ids.get(signer_id))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Signers of the current DKG round with a key id whose public share did not arrive
+    fn signers_missing_public_shares(&self) -> BTreeSet<u32> {
+        self.signers_missing_public_shares_of(self.dkg_signer_ids.iter().copied())
+    }
+
+    fn signers_missing_public_shares_of(
+        &self,
+        signer_ids: impl IntoIterator<Item = u32>,
+    ) -> BTreeSet<u32> {
+        signer_ids
+            .into_iter()
+            .filter(|signer_id| {
+                self.signer_key_ids.get(signer_id).map_or(true, |key_ids| {
+                    key_ids
+                        .iter()
+                        .any(|key_id| !self.dkg_public_shares.contains_key(key_id))
+                })
+            })
+            .collect()
+    }
+
+    /// Signers able to sign for the aggregate key, excluding `excluded_signer_ids`: those holding
+    /// a share of every one of their keys
+    fn signing_signer_ids(&self, excluded_signer_ids: &BTreeSet<u32>) -> BTreeSet<u32> {
+        // Without any public shares, as after a restart that did not keep them, signers are
+        // assumed to hold all of their keys
+        let missing_public_shares = if self.dkg_public_shares.is_empty() {
+            BTreeSet::new()
+        } else {
+            self.signers_missing_public_shares_of(1..=self.total_signers)
+        };
+        (1..=self.total_signers)
+            .filter(|signer_id| {
+                !excluded_signer_ids.contains(signer_id)
+                    && !self.dkg_dropped_signer_ids.contains(signer_id)
+                    && !missing_public_shares.contains(signer_id)
+            })
+            .collect()
+    }
+
+    /// Number of keys held by the given signers
+    fn num_keys(&self, signer_ids: &BTreeSet<u32>) -> usize {
+        signer_ids
+            .iter()
+            .filter_map(|signer_id| self.signer_key_ids.get(signer_id))
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Drop signers that missed a deadline from the rest of the DKG round, as long as the
+    /// remaining signers still hold enough keys to sign
+    fn drop_dkg_signers(&mut self, signer_ids: &BTreeSet<u32>) -> Result<(), Error> {
+        if signer_ids.is_empty() {
+            return Ok(());
+        }
+        warn!(
+            "DKG Round #{}: dropping non-responsive signers {:?}",
+            self.current_dkg_id, signer_ids
+        );
+        for signer_id in signer_ids {
+            self.dkg_signer_ids.remove(signer_id);
+            self.dkg_dropped_signer_ids.insert(*signer_id);
+        }
+        if self.dkg_key_ids().len() < usize::try_from(self.threshold).unwrap() {
+            return Err(Error::InsufficientSigners(
+                self.dkg_dropped_signer_ids.iter().copied().collect(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Settle the complaints a signer raised about its private shares, returning the signers
+    /// at fault: the senders of shares that really are bad, or the accuser if any complaint
+    /// does not hold up. A complaint that cannot be settled fails the round, as the key might
+    /// rest on a bad share.
+    fn adjudicate_complaints(
+        &self,
+        accuser_id: u32,
+        complaints: &[Complaint],
+    ) -> Result<BTreeSet<u32>, Error> {
+        let mut blamed_signer_ids = BTreeSet::new();
+        for complaint in complaints {
+            let src_key_id = complaint.src_key_id + 1;
+            let dst_key_id = complaint.dst_key_id + 1;
+            let accuser_owns_key = self
+                .signer_key_ids
+                .get(&accuser_id)
+                .map_or(false, |key_ids| key_ids.contains(&dst_key_id));
+            let sender_id = self
+                .signer_key_ids
+                .iter()
+                .find(|(_, key_ids)| key_ids.contains(&src_key_id))
+                .map(|(signer_id, _)| *signer_id);
+            let (Some(sender_id), true) = (sender_id, accuser_owns_key) else {
+                blamed_signer_ids.insert(accuser_id);
+                continue;
+            };
+            let (
+                Some(private_shares),
+                Some(public_share),
+                Some(dst_public_key),
+                Some(src_public_key),
+            ) = (
+                self.dkg_private_shares.get(&complaint.src_key_id),
+                self.dkg_public_shares.get(&src_key_id),
+                self.public_keys
+                    .key_ids
+                    .get(&dst_key_id)
+                    .and_then(public_key_point),
+                self.public_keys
+                    .key_ids
+                    .get(&src_key_id)
+                    .and_then(public_key_point),
+            )
+            else {
+                return Err(Error::UnsettledComplaint(
+                    self.current_dkg_id,
+                    accuser_id,
+                    src_key_id,
+                ));
+            };
+            match complaint.blame(
+                &dst_public_key,
+                &src_public_key,
+                private_shares,
+                &public_share.public_share,
+            ) {
+                Blame::Sender => {
+                    warn!(
+                        "DKG Round #{}: signer #{} sent key #{} a bad private share: {:?}",
+                        self.current_dkg_id, sender_id, dst_key_id, complaint.reason
+                    );
+                    blamed_signer_ids.insert(sender_id);
+                }
+                Blame::Accuser => {
+                    warn!(
+                        "DKG Round #{}: signer #{} complained about a valid private share from key #{}",
+                        self.current_dkg_id, accuser_id, src_key_id
+                    );
+                    blamed_signer_ids.insert(accuser_id);
+                }
+            }
+        }
+        Ok(blamed_signer_ids)
+    }
 }
 
 impl<Network: NetListen> Coordinator<Network>
@@ -138,17 +376,54 @@ where
     }
 
     pub fn run_distributed_key_generation(&mut self) -> Result<Point, Error> {
-        self.current_dkg_id = self.current_dkg_id.wrapping_add(1);
-        info!("Starting DKG round #{}", self.current_dkg_id);
-        self.start_public_shares()?;
-        let public_key = self.wait_for_public_shares()?;
-        self.start_private_shares()?;
-        self.wait_for_dkg_end()?;
-        Ok(public_key)
+        self.dkg_blamed_signer_ids.clear();
+        // Signers drop the nonces of their old key shares
+        self.nonce_pool.clear();
+        // and get new ones, refreshed or not
+        self.refresh_commit_signer_ids.clear();
+        loop {
+            self.current_dkg_id = self.current_dkg_id.wrapping_add(1);
+            info!("Starting DKG round #{}", self.current_dkg_id);
+            self.start_public_shares()?;
+            let public_key = self.wait_for_public_shares()?;
+            self.start_private_shares()?;
+            let blamed_signer_ids = match self.wait_for_dkg_end() {
+                Ok(blamed_signer_ids) => blamed_signer_ids,
+                Err(e) => {
+                    // Nothing may be signed for the key of a round that failed
+                    self.aggregate_public_key = Point::default();
+                    return Err(e);
+                }
+            };
+            if blamed_signer_ids.is_empty() {
+                if !self.dkg_dropped_signer_ids.is_empty() {
+                    warn!(
+                        "DKG Round #{}: completed without signers {:?}",
+                        self.current_dkg_id, self.dkg_dropped_signer_ids
+                    );
+                }
+                return Ok(public_key);
+            }
+            warn!(
+                "DKG Round #{} Failed: signers {:?} misbehaved or failed, re-running dkg without them",
+                self.current_dkg_id, blamed_signer_ids
+            );
+            self.dkg_blamed_signer_ids.extend(blamed_signer_ids);
+        }
     }
 
     fn start_public_shares(&mut self) -> Result<(), Error> {
         self.dkg_public_shares.clear();
+        self.dkg_private_shares.clear();
+        self.dkg_signer_ids = (1..=self.total_signers)
+            .filter(|signer_id| !self.dkg_blamed_signer_ids.contains(signer_id))
+            .collect();
+        self.dkg_dropped_signer_ids = self.dkg_blamed_signer_ids.clone();
+        if self.dkg_key_ids().len() < usize::try_from(self.threshold).unwrap() {
+            return Err(Error::InsufficientSigners(
+                self.dkg_dropped_signer_ids.iter().copied().collect(),
+            ));
+        }
         info!(
             "DKG Round #{}: Starting Public Share Distribution Round #{}",
             self.current_dkg_id, self.current_dkg_public_id
@@ -170,24 +445,35 @@ where
             "DKG Round #{}: Starting Private Share Distribution",
             self.current_dkg_id
         );
-        let dkg_begin = DkgBegin {
+        let dkg_private_begin = DkgPrivateBegin {
             dkg_id: self.current_dkg_id,
+            signer_ids: self.dkg_signer_ids.iter().copied().collect(),
+            key_ids: self.dkg_key_ids().into_iter().collect(),
         };
         let dkg_private_begin_msg = Message {
-            sig: dkg_begin.sign(&self.network_private_key).expect(""),
-            msg: MessageTypes::DkgPrivateBegin(dkg_begin),
+            sig: dkg_private_begin.sign(&self.network_private_key).expect(""),
+            msg: MessageTypes::DkgPrivateBegin(dkg_private_begin),
         };
         self.network.send_message(dkg_private_begin_msg)?;
         Ok(())
     }
 
-    fn collect_nonces(&mut self) -> Result<(), Error> {
+    /// Request nonces, collecting those of `signer_ids` until the responders hold enough keys to
+    /// sign, or until every one of them responded if `all` is set
+    fn collect_nonces(
+        &mut self,
+        num_messages: u32,
+        signer_ids: &BTreeSet<u32>,
+        all: bool,
+    ) -> Result<(), Error> {
         self.public_nonces.clear();
+        self.current_sign_nonce_id = self.current_sign_nonce_id.wrapping_add(1);
 
         let nonce_request = NonceRequest {
             dkg_id: self.current_dkg_id,
             sign_id: self.current_sign_id,
             sign_nonce_id: self.current_sign_nonce_id,
+            num_messages,
         };
 
         let nonce_request_message = Message {
@@ -198,8 +484,8 @@ where
         };
 
         debug!(
-            "dkg_id #{} sign_id #{} sign_nonce_id #{}. NonceRequest sent",
-            self.current_dkg_id, self.current_sign_id, self.current_sign_nonce_id
+            "dkg_id #{} sign_id #{} sign_nonce_id #{}. NonceRequest for {} messages sent",
+            self.current_dkg_id, self.current_sign_id, self.current_sign_nonce_id, num_messages
         );
         self.network.send_message(nonce_request_message)?;
 
@@ -208,6 +494,38 @@ where
                 MessageTypes::NonceRequest(_) => {}
                 MessageTypes::NonceResponse(nonce_response) => {
                     let signer_id = nonce_response.signer_id;
+                    if !signer_ids.contains(&signer_id) {
+                        debug!("Ignoring NonceResponse from signer #{}", signer_id);
+                        continue;
+                    }
+                    if nonce_response.sign_nonce_id != self.current_sign_nonce_id {
+                        debug!(
+                            "Ignoring NonceResponse from signer #{} for sign_nonce_id #{}",
+                            signer_id, nonce_response.sign_nonce_id
+                        );
+                        continue;
+                    }
+                    if nonce_response.nonces.len() != num_messages as usize {
+                        warn!(
+                            "NonceResponse from signer #{} has {} sets of nonces, expected {}",
+                            signer_id,
+                            nonce_response.nonces.len(),
+                            num_messages
+                        );
+                        continue;
+                    }
+                    let num_parties = self.wsts_version.scheme().parties(&nonce_response).len();
+                    if nonce_response
+                        .nonces
+                        .iter()
+                        .any(|nonces| nonces.len() != num_parties)
+                    {
+                        warn!(
+                            "NonceResponse from signer #{} does not have {} nonces per message",
+                            signer_id, num_parties
+                        );
+                        continue;
+                    }
                     self.public_nonces.insert(signer_id, nonce_response);
                     debug!(
                         "NonceResponse from signer #{:?}. Got {} nonce responses of threshold {}",
@@ -221,7 +539,18 @@ where
                 }
             }
 
-            if self.public_nonces.len() == usize::try_from(self.total_signers).unwrap() {
+            if all {
+                if self.public_nonces.len() == signer_ids.len() {
+                    break;
+                }
+            } else if self.num_keys(
+                &self
+                    .public_nonces
+                    .keys()
+                    .copied()
+                    .collect::<BTreeSet<u32>>(),
+            ) >= usize::try_from(self.threshold).unwrap()
+            {
                 debug!("Nonce threshold of {} met.", self.threshold);
                 break;
             }
@@ -229,41 +558,276 @@ where
         Ok(())
     }
 
+    /// Collect a set of nonces for each message whose aggregate nonce has an even y coordinate,
+    /// requesting fresh nonces only for the messages that did not get one. The first of
+    /// `signer_ids` to respond with enough keys to sign make up the signers of every message.
     #[allow(non_snake_case)]
-    fn compute_aggregate_nonce(&mut self, msg: &[u8]) -> Result<Point, Error> {
-        info!("Computing aggregate nonce...");
-        self.collect_nonces()?;
-        // XXX this needs to be key_ids for v1 and signer_ids for v2
-        let party_ids = self
-            .public_nonces
-            .values()
-            .flat_map(|pn| pn.key_ids.clone())
-            .collect::<Vec<u32>>();
-        let nonces = self
+    fn compute_aggregate_nonces(
+        &mut self,
+        msgs: &[Vec<u8>],
+        signer_ids: &BTreeSet<u32>,
+    ) -> Result<Vec<NonceResponse>, Error> {
+        info!("Computing aggregate nonces for {} messages...", msgs.len());
+        let mut message_nonces: Vec<Option<BTreeMap<u32, Vec<PublicNonce>>>> =
+            vec![None; msgs.len()];
+        let mut signer_ids = signer_ids.clone();
+        let mut all = false;
+
+        loop {
+            let pending: Vec<usize> = (0..msgs.len())
+                .filter(|index| message_nonces[*index].is_none())
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            self.collect_nonces(pending.len() as u32, &signer_ids, all)?;
+            signer_ids = self.public_nonces.keys().copied().collect();
+            all = true;
+
+            let party_ids = self
+                .wsts_version
+                .scheme()
+                .party_ids(&self.public_nonces.values().cloned().collect::<Vec<_>>());
+            for (nonce_index, msg_index) in pending.into_iter().enumerate() {
+                let nonces = self
+                    .public_nonces
+                    .iter()
+                    .map(|(signer_id, pn)| (*signer_id, pn.nonces[nonce_index].clone()))
+                    .collect::<BTreeMap<u32, Vec<PublicNonce>>>();
+                let (_, R) = compute::intermediate(
+                    &msgs[msg_index],
+                    &party_ids,
+                    &nonces.values().flatten().cloned().collect::<Vec<_>>(),
+                );
+                if R.has_even_y() {
+                    debug!("Success: R has even y coord: {}", &R);
+                    message_nonces[msg_index] = Some(nonces);
+                } else {
+                    warn!("Failure: R does not have even y coord: {}", R);
+                }
+            }
+        }
+
+        // Later nonce requests wait on the signers of the first, so all messages share them
+        Ok(self
             .public_nonces
             .values()
-            .flat_map(|pn| pn.nonces.clone())
-            .collect::<Vec<PublicNonce>>();
-        let (_, R) = compute::intermediate(msg, &party_ids, &nonces);
-        Ok(R)
+            .map(|pn| NonceResponse {
+                nonces: message_nonces
+                    .iter()
+                    .flatten()
+                    .map(|nonces| nonces[&pn.signer_id].clone())
+                    .collect(),
+                ..pn.clone()
+            })
+            .collect())
+    }
+
+    /// Nonce response of the signer of a nonce batch for the current signing round, without
+    /// any nonces yet
+    fn batch_nonce_response(&self, batch: &NonceBatch) -> NonceResponse {
+        NonceResponse {
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+            sign_nonce_id: self.current_sign_nonce_id,
+            signer_id: batch.signer_id,
+            key_ids: batch.key_ids.clone(),
+            nonces: vec![],
+        }
+    }
+
+    /// Take nonces for every message from the preprocessed nonces of the first of `signer_ids`
+    /// with any left, until they hold enough keys to sign. Nonces leave the pool once taken, so
+    /// they are never handed out twice. None if the pool cannot cover the messages.
+    #[allow(non_snake_case)]
+    fn take_preprocessed_nonces(
+        &mut self,
+        msgs: &[Vec<u8>],
+        signer_ids: &BTreeSet<u32>,
+    ) -> Option<Vec<NonceResponse>> {
+        let threshold = usize::try_from(self.threshold).unwrap();
+        let mut pool_signer_ids = BTreeSet::new();
+        for signer_id in signer_ids {
+            if self.num_keys(&pool_signer_ids) >= threshold {
+                break;
+            }
+            if self
+                .nonce_pool
+                .get(signer_id)
+                .map_or(false, |batch| !batch.nonces.is_empty())
+            {
+                pool_signer_ids.insert(*signer_id);
+            }
+        }
+        if self.num_keys(&pool_signer_ids) < threshold {
+            return None;
+        }
+
+        let batches = pool_signer_ids
+            .iter()
+            .map(|signer_id| &self.nonce_pool[signer_id])
+            .collect::<Vec<&NonceBatch>>();
+        let num_nonces = batches.iter().map(|batch| batch.nonces.len()).min()?;
+        let mut nonce_responses = batches
+            .iter()
+            .map(|batch| self.batch_nonce_response(batch))
+            .collect::<Vec<NonceResponse>>();
+        let party_ids = self.wsts_version.scheme().party_ids(&nonce_responses);
+
+        // Nonces skipped for giving an odd R stay in the pool, as they never signed
+        let mut indices: Vec<usize> = Vec::with_capacity(msgs.len());
+        for msg in msgs {
+            let index = (0..num_nonces)
+                .filter(|index| !indices.contains(index))
+                .find(|index| {
+                    let nonces = batches
+                        .iter()
+                        .flat_map(|batch| batch.nonces[*index].clone())
+                        .collect::<Vec<PublicNonce>>();
+                    let (_, R) = compute::intermediate(msg, &party_ids, &nonces);
+                    R.has_even_y()
+                });
+            match index {
+                Some(index) => indices.push(index),
+                None => {
+                    debug!("Preprocessed nonces ran out for {} messages", msgs.len());
+                    return None;
+                }
+            }
+        }
+        for (nonce_response, batch) in nonce_responses.iter_mut().zip(&batches) {
+            nonce_response.nonces = indices
+                .iter()
+                .map(|index| batch.nonces[*index].clone())
+                .collect();
+        }
+
+        indices.sort_unstable();
+        for signer_id in &pool_signer_ids {
+            if let Some(batch) = self.nonce_pool.get_mut(signer_id) {
+                for index in indices.iter().rev() {
+                    batch.nonces.remove(*index);
+                }
+            }
+        }
+        info!(
+            "Signing {} messages with preprocessed nonces of signers {:?}",
+            msgs.len(),
+            pool_signer_ids
+        );
+        self.public_nonces = nonce_responses
+            .iter()
+            .map(|nr| (nr.signer_id, nr.clone()))
+            .collect();
+        Some(nonce_responses)
+    }
+
+    /// Have the signers publish a batch of nonces while idle, so signing can skip requesting
+    /// nonces. Only runs with a nonce batch size configured, once the signers with nonces left
+    /// for half a batch of signing sessions no longer hold enough keys to sign.
+    pub fn preprocess_nonces(&mut self) -> Result<(), Error> {
+        let Some(batch_size) = self.nonce_batch_size else {
+            return Ok(());
+        };
+        if self.aggregate_public_key == Point::default() {
+            return Ok(());
+        }
+        let signer_ids = self.signing_signer_ids(&BTreeSet::new());
+        let stocked_signer_ids = signer_ids
+            .iter()
+            .filter(|signer_id| {
+                self.nonce_pool
+                    .get(signer_id)
+                    .map_or(0, |batch| batch.nonces.len())
+                    > usize::try_from(batch_size / 2).unwrap()
+            })
+            .copied()
+            .collect::<BTreeSet<u32>>();
+        if self.num_keys(&stocked_signer_ids) >= usize::try_from(self.threshold).unwrap() {
+            return Ok(());
+        }
+
+        self.current_nonce_batch_id = self.current_nonce_batch_id.wrapping_add(1);
+        let nonce_batch_request = NonceBatchRequest {
+            dkg_id: self.current_dkg_id,
+            batch_id: self.current_nonce_batch_id,
+            num_nonces: batch_size,
+        };
+        let nonce_batch_request_message = Message {
+            sig: nonce_batch_request
+                .sign(&self.network_private_key)
+                .expect("Failed to sign NonceBatchRequest"),
+            msg: MessageTypes::NonceBatchRequest(nonce_batch_request),
+        };
+        info!(
+            "Requesting nonce batch #{} of {} nonces",
+            self.current_nonce_batch_id, batch_size
+        );
+        self.network.send_message(nonce_batch_request_message)?;
+        // Signers replace their previous batch, so nonces left from it can no longer sign
+        self.nonce_pool.clear();
+
+        let mut ids_to_await = signer_ids;
+        let deadline = Some(Instant::now() + self.sign_timeout);
+        while !ids_to_await.is_empty() {
+            let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                break;
+            };
+            let MessageTypes::NonceBatch(batch) = message.msg else {
+                continue;
+            };
+            let signer_id = batch.signer_id;
+            if batch.batch_id != self.current_nonce_batch_id || !ids_to_await.remove(&signer_id) {
+                continue;
+            }
+            let num_parties = self
+                .wsts_version
+                .scheme()
+                .parties(&self.batch_nonce_response(&batch))
+                .len();
+            if batch.nonces.len() != batch_size as usize
+                || batch
+                    .nonces
+                    .iter()
+                    .any(|nonces| nonces.len() != num_parties)
+            {
+                warn!(
+                    "NonceBatch #{} from signer #{} does not have {} sets of {} nonces",
+                    batch.batch_id, signer_id, batch_size, num_parties
+                );
+                continue;
+            }
+            self.nonce_pool.insert(signer_id, batch);
+        }
+        if !ids_to_await.is_empty() {
+            warn!(
+                "Signers {:?} did not send nonce batch #{}",
+                ids_to_await, self.current_nonce_batch_id
+            );
+        }
+        Ok(())
     }
 
     fn request_signature_shares(
         &self,
         nonce_responses: &[NonceResponse],
-        msg: &[u8],
+        msgs: &[Vec<u8>],
+        taproot_tweak: Option<TaprootTweak>,
     ) -> Result<(), Error> {
         let signature_share_request = SignatureShareRequest {
             dkg_id: self.current_dkg_id,
             sign_id: self.current_sign_id,
             correlation_id: 0,
             nonce_responses: nonce_responses.to_vec(),
-            message: msg.to_vec(),
+            messages: msgs.to_vec(),
+            taproot_tweak,
         };
 
         info!(
-            "Sending SignShareRequest dkg_id #{} sign_id #{} to signers",
-            signature_share_request.dkg_id, signature_share_request.sign_id
+            "Sending SignShareRequest dkg_id #{} sign_id #{} for {} messages to signers",
+            signature_share_request.dkg_id,
+            signature_share_request.sign_id,
+            signature_share_request.messages.len()
         );
 
         let signature_share_request_message = Message {
@@ -278,23 +842,137 @@ where
         Ok(())
     }
 
-    fn collect_signature_shares(&mut self) -> Result<(), Error> {
+    /// Signers that sent a signature share which does not check out against the public keys of
+    /// its key ids, derived from the DKG public shares, and the nonce it committed to
+    #[allow(non_snake_case)]
+    fn signers_with_invalid_shares(
+        &self,
+        nonce_responses: &[NonceResponse],
+        msgs: &[Vec<u8>],
+        taproot_tweak: Option<TaprootTweak>,
+    ) -> BTreeSet<u32> {
+        let scheme = self.wsts_version.scheme();
+        let party_ids = scheme.party_ids(nonce_responses);
+        let key_ids = nonce_responses
+            .iter()
+            .flat_map(|nr| nr.key_ids.clone())
+            .collect::<Vec<u32>>();
+        let public_keys = key_ids
+            .iter()
+            .map(|key_id| {
+                let public_key = self
+                    .dkg_public_shares
+                    .values()
+                    .fold(Point::default(), |key, dps| {
+                        key + eval_public_share(&dps.public_share, *key_id)
+                    });
+                let public_key = match &taproot_tweak {
+                    Some(taproot_tweak) => {
+                        taproot_tweak.tweak_public_key(&self.aggregate_public_key, &public_key)
+                    }
+                    None => public_key,
+                };
+                (*key_id, public_key)
+            })
+            .collect::<BTreeMap<u32, Point>>();
+        let group_key = match &taproot_tweak {
+            Some(taproot_tweak) => {
+                let output_key = taproot_tweak.output_key(&self.aggregate_public_key);
+                if output_key.has_even_y() {
+                    output_key
+                } else {
+                    -output_key
+                }
+            }
+            None => self.aggregate_public_key,
+        };
+
+        let mut bad_signer_ids = BTreeSet::new();
+        for (index, msg) in msgs.iter().enumerate() {
+            let nonces = message_nonces(nonce_responses, index);
+            let (Rs, R) = compute::intermediate(msg, &party_ids, &nonces);
+            let c = compute::challenge(&group_key, &R, msg);
+
+            for nonce_response in nonce_responses {
+                let signer_id = nonce_response.signer_id;
+                let parties = scheme.parties(nonce_response);
+                let shares = &self.signature_shares[&signer_id][index];
+                let valid = shares.len() == parties.len()
+                    && shares.iter().all(|share| {
+                        let Some((_, party_key_ids)) =
+                            parties.iter().find(|(party_id, _)| *party_id == share.id)
+                        else {
+                            return false;
+                        };
+                        let Some(position) = party_ids.iter().position(|id| *id == share.id) else {
+                            return false;
+                        };
+                        // A party signs for the Lagrange weighted sum of the keys it holds
+                        let public_key =
+                            party_key_ids
+                                .iter()
+                                .fold(Point::default(), |public_key, key_id| {
+                                    let lambda = compute::lambda(*key_id, &key_ids);
+                                    public_key + &(c * lambda) * &public_keys[key_id]
+                                });
+                        Point::from(share.z_i) == Rs[position] + public_key
+                    });
+                if !valid {
+                    warn!(
+                        "Signer #{} sent an invalid signature share for message {}",
+                        signer_id, index
+                    );
+                    bad_signer_ids.insert(signer_id);
+                }
+            }
+        }
+        bad_signer_ids
+    }
+
+    /// Collect signature shares from the signers who responded with nonces, returning the
+    /// signers that did not send theirs in time or sent a malformed response
+    fn collect_signature_shares(&mut self, num_messages: usize) -> Result<BTreeSet<u32>, Error> {
+        self.signature_shares.clear();
         // get the parties who responded with a nonce
         let mut signers: HashSet<u32> = HashSet::from_iter(self.public_nonces.keys().cloned());
+        let mut failed_signer_ids = BTreeSet::new();
+        let deadline = Some(Instant::now() + self.sign_timeout);
         while !signers.is_empty() {
-            match self.wait_for_next_message()?.msg {
+            let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                break;
+            };
+            match message.msg {
                 MessageTypes::SignShareResponse(response) => {
-                    if let Some(_party_id) = signers.take(&response.signer_id) {
-                        info!(
-                            "Insert signature shares for signer_id {}",
-                            &response.signer_id
+                    if response.sign_id != self.current_sign_id {
+                        debug!(
+                            "Ignoring signature shares from signer #{} for sign_id #{}",
+                            response.signer_id, response.sign_id
+                        );
+                        continue;
+                    }
+                    let Some(signer_id) = signers.take(&response.signer_id) else {
+                        debug!(
+                            "Ignoring signature shares from signer #{}, who is not signing",
+                            response.signer_id
+                        );
+                        continue;
+                    };
+                    if response.signature_shares.len() != num_messages {
+                        warn!(
+                            "Signer #{} sent signature shares for {} messages, expected {}",
+                            signer_id,
+                            response.signature_shares.len(),
+                            num_messages
                         );
-                        self.signature_shares
-                            .insert(response.signer_id, response.signature_shares.clone());
+                        failed_signer_ids.insert(signer_id);
+                        continue;
                     }
+                    info!("Insert signature shares for signer_id {}", signer_id);
+                    self.signature_shares
+                        .insert(signer_id, response.signature_shares);
                     debug!(
                         "signature shares for {} received.  left to receive: {:?}",
-                        response.signer_id, signers
+                        signer_id, signers
                     );
                 }
                 MessageTypes::SignShareRequest(_) => {}
@@ -303,25 +981,53 @@ where
                 }
             }
         }
-        Ok(())
+        failed_signer_ids.extend(signers);
+        Ok(failed_signer_ids)
     }
 
-    #[allow(non_snake_case)]
     pub fn sign_message(&mut self, msg: &[u8]) -> Result<(Signature, SchnorrProof), Error> {
-        debug!("Attempting to Sign Message");
+        let mut signatures = self.sign_messages(&[msg.to_vec()])?;
+        Ok(signatures.remove(0))
+    }
+
+    /// Sign every message in a single signing round, each with its own nonces
+    pub fn sign_messages(
+        &mut self,
+        msgs: &[Vec<u8>],
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.sign_messages_with_tweak(msgs, None)
+    }
+
+    /// Sign every message for the taproot output key committing to the aggregate key and,
+    /// if given, the script tree with `merkle_root`
+    pub fn sign_taproot_messages(
+        &mut self,
+        msgs: &[Vec<u8>],
+        merkle_root: Option<[u8; 32]>,
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.sign_messages_with_tweak(msgs, Some(TaprootTweak::new(merkle_root)))
+    }
+
+    /// Sign every message for a script path spend through a tapscript checking the aggregate key
+    pub fn sign_tapscript_messages(
+        &mut self,
+        msgs: &[Vec<u8>],
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.sign_messages_with_tweak(msgs, Some(TaprootTweak::script_path()))
+    }
+
+    #[allow(non_snake_case)]
+    fn sign_messages_with_tweak(
+        &mut self,
+        msgs: &[Vec<u8>],
+        taproot_tweak: Option<TaprootTweak>,
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        debug!("Attempting to Sign {} Messages", msgs.len());
         if self.aggregate_public_key == Point::default() {
             return Err(Error::NoAggregatePublicKey);
         }
-
-        //Continually compute a new aggregate nonce until we have a valid even R
-        loop {
-            let R = self.compute_aggregate_nonce(msg)?;
-            if R.has_even_y() {
-                debug!("Success: R has even y coord: {}", &R);
-                break;
-            } else {
-                warn!("Failure: R does not have even y coord: {}", R);
-            }
+        if msgs.is_empty() {
+            return Ok(vec![]);
         }
 
         // make an array of dkg public share polys for SignatureAggregator
@@ -337,51 +1043,268 @@ where
             .collect();
 
         debug!(
-            "SignatureAggregator::new total_keys: {} threshold: {} commitments: {}",
+            "{:?} aggregator total_keys: {} threshold: {} commitments: {}",
+            self.wsts_version,
             self.total_keys,
             self.threshold,
             polys.len()
         );
 
-        let mut aggregator = v1::SignatureAggregator::new(self.total_keys, self.threshold, polys)?;
+        // Keys of signers dropped from DKG have no polynomial
+        let num_keys = u32::try_from(polys.len()).unwrap();
+        let mut aggregator = new_aggregator(self.wsts_version, num_keys, self.threshold, polys)?;
+
+        // Sign with the first signers to respond, retrying without any that fail to send valid
+        // shares
+        let mut excluded_signer_ids = BTreeSet::new();
+        let nonce_responses = loop {
+            let signer_ids = self.signing_signer_ids(&excluded_signer_ids);
+            if self.num_keys(&signer_ids) < usize::try_from(self.threshold).unwrap() {
+                return Err(Error::InsufficientSigners(
+                    excluded_signer_ids.into_iter().collect(),
+                ));
+            }
+            self.current_sign_id = self.current_sign_id.wrapping_add(1);
+
+            // Sign in a single round trip with preprocessed nonces, else continually compute new
+            // aggregate nonces until every message has a valid even R
+            let nonce_responses = match self.take_preprocessed_nonces(msgs, &signer_ids) {
+                Some(nonce_responses) => nonce_responses,
+                None => self.compute_aggregate_nonces(msgs, &signer_ids)?,
+            };
+
+            // request signature shares
+            self.request_signature_shares(&nonce_responses, msgs, taproot_tweak)?;
+            let missing_signer_ids = self.collect_signature_shares(msgs.len())?;
+            if !missing_signer_ids.is_empty() {
+                warn!(
+                    "Signers {:?} did not send valid signature shares, retrying without them",
+                    missing_signer_ids
+                );
+                for signer_id in &missing_signer_ids {
+                    // Such as a restarted signer, which lost the nonces it published
+                    self.nonce_pool.remove(signer_id);
+                }
+                excluded_signer_ids.extend(missing_signer_ids);
+                continue;
+            }
+            let bad_signer_ids =
+                self.signers_with_invalid_shares(&nonce_responses, msgs, taproot_tweak);
+            if bad_signer_ids.is_empty() {
+                break nonce_responses;
+            }
+            warn!(
+                "Signers {:?} sent invalid signature shares, retrying without them",
+                bad_signer_ids
+            );
+            for signer_id in &bad_signer_ids {
+                self.nonce_pool.remove(signer_id);
+            }
+            excluded_signer_ids.extend(bad_signer_ids);
+        };
 
-        let nonce_responses: Vec<NonceResponse> = self.public_nonces.values().cloned().collect();
+        let public_key = match &taproot_tweak {
+            Some(taproot_tweak) => taproot_tweak.output_key(&self.aggregate_public_key),
+            None => self.aggregate_public_key,
+        };
 
-        // request signature shares
-        self.request_signature_shares(&nonce_responses, msg)?;
-        self.collect_signature_shares()?;
+        let mut signatures = Vec::with_capacity(msgs.len());
+        for (index, msg) in msgs.iter().enumerate() {
+            let shares = nonce_responses
+                .iter()
+                .flat_map(|nr| self.signature_shares[&nr.signer_id][index].clone())
+                .collect::<Vec<SignatureShare>>();
+
+            info!("aggregator.sign({:?}, {:?})", msg, shares.len());
+
+            let sig = if taproot_tweak.is_some() {
+                // The aggregator checks shares against the untweaked key, so shares of a tweaked
+                // key, already checked against their tweaked public keys, are summed directly
+                sum_shares(
+                    self.wsts_version.scheme(),
+                    msg,
+                    &nonce_responses,
+                    &shares,
+                    index,
+                )
+            } else {
+                aggregator.sign(msg, &nonce_responses, &shares, index)?
+            };
 
-        let nonces = nonce_responses
-            .iter()
-            .flat_map(|nr| nr.nonces.clone())
-            .collect::<Vec<PublicNonce>>();
-        let shares = &self
-            .public_nonces
-            .iter()
-            .flat_map(|(i, _)| self.signature_shares[i].clone())
-            .collect::<Vec<SignatureShare>>();
+            info!("Signature ({}, {})", sig.R, sig.z);
+
+            let proof = SchnorrProof::new(&sig).map_err(Error::Bip340)?;
+
+            info!("SchnorrProof ({}, {})", proof.r, proof.s);
+
+            if !proof.verify(&public_key.x(), msg) {
+                warn!("SchnorrProof failed to verify!");
+                return Err(Error::SchnorrProofFailed);
+            }
 
+            signatures.push((sig, proof));
+        }
+
+        Ok(signatures)
+    }
+
+    /// Have the signers re-randomize their key shares without changing the aggregate key, so
+    /// shares leaked before the refresh are of no use combined with shares leaked after it
+    pub fn run_key_refresh(&mut self) -> Result<(), Error> {
+        if self.aggregate_public_key == Point::default() || self.dkg_public_shares.is_empty() {
+            return Err(Error::NoAggregatePublicKey);
+        }
+        // A new refresh would discard the refreshed key shares of signers that missed the last
+        // commit, leaving them with shares that no longer match the public shares
+        if !self.refresh_commit_signer_ids.is_empty() {
+            self.commit_key_refresh()?;
+        }
+        self.current_refresh_id = self.current_refresh_id.wrapping_add(1);
+        let key_ids = self
+            .dkg_public_shares
+            .keys()
+            .copied()
+            .collect::<BTreeSet<u32>>();
+        let mut ids_to_await: HashSet<u32> = self
+            .signer_key_ids
+            .iter()
+            .filter(|(_, signer_key_ids)| {
+                signer_key_ids.iter().any(|key_id| key_ids.contains(key_id))
+            })
+            .map(|(signer_id, _)| *signer_id)
+            .collect();
+        let refresh_signer_ids = ids_to_await.iter().copied().collect::<BTreeSet<u32>>();
         info!(
-            "aggregator.sign({:?}, {:?}, {:?})",
-            msg,
-            nonces.len(),
-            shares.len()
+            "Starting key refresh #{} of key ids {:?}",
+            self.current_refresh_id, key_ids
         );
 
-        let sig = aggregator.sign(msg, &nonces, shares)?;
+        let refresh_begin = RefreshBegin {
+            dkg_id: self.current_dkg_id,
+            refresh_id: self.current_refresh_id,
+            key_ids: key_ids.iter().copied().collect(),
+        };
+        let refresh_begin_message = Message {
+            sig: refresh_begin.sign(&self.network_private_key).expect(""),
+            msg: MessageTypes::RefreshBegin(refresh_begin),
+        };
+        self.network.send_message(refresh_begin_message)?;
 
-        info!("Signature ({}, {})", sig.R, sig.z);
+        let mut refresh_shares: BTreeMap<u32, RefreshShares> = BTreeMap::new();
+        let mut failed_signer_ids = BTreeSet::new();
+        let deadline = self.dkg_end_timeout.map(|timeout| Instant::now() + timeout);
+        while !ids_to_await.is_empty() {
+            let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                failed_signer_ids.extend(ids_to_await.drain());
+                break;
+            };
+            match message.msg {
+                MessageTypes::RefreshShares(shares)
+                    if shares.refresh_id == self.current_refresh_id =>
+                {
+                    refresh_shares.insert(shares.key_id + 1, shares);
+                }
+                MessageTypes::RefreshEnd(refresh_end)
+                    if refresh_end.refresh_id == self.current_refresh_id =>
+                {
+                    ids_to_await.remove(&refresh_end.signer_id);
+                    if let DkgStatus::Success = refresh_end.status {
+                        debug!(
+                            "Refresh_End #{} from signer #{}. Waiting on {:?}",
+                            refresh_end.refresh_id, refresh_end.signer_id, ids_to_await
+                        );
+                    } else {
+                        warn!(
+                            "Refresh_End #{} from signer #{} failed: {:?}",
+                            refresh_end.refresh_id, refresh_end.signer_id, refresh_end.status
+                        );
+                        failed_signer_ids.insert(refresh_end.signer_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Without the commitments of every key the refreshed public keys would be unknown
+        for (signer_id, signer_key_ids) in &self.signer_key_ids {
+            if signer_key_ids
+                .iter()
+                .any(|key_id| key_ids.contains(key_id) && !refresh_shares.contains_key(key_id))
+            {
+                failed_signer_ids.insert(*signer_id);
+            }
+        }
+        if !failed_signer_ids.is_empty() {
+            return Err(Error::RefreshFailed(
+                failed_signer_ids.into_iter().collect(),
+            ));
+        }
 
-        let proof = SchnorrProof::new(&sig).map_err(Error::Bip340)?;
+        // Every signer checked the refresh shares against their commitments, so adding the
+        // commitments to the public shares gives the public keys of the refreshed key shares
+        for (key_id, shares) in &refresh_shares {
+            if let Some(dkg_public_share) = self.dkg_public_shares.get_mut(key_id) {
+                for (a, commitment) in dkg_public_share
+                    .public_share
+                    .A
+                    .iter_mut()
+                    .skip(1)
+                    .zip(&shares.commitments)
+                {
+                    *a = *a + *commitment;
+                }
+            }
+        }
 
-        info!("SchnorrProof ({}, {})", proof.r, proof.s);
+        self.refresh_commit_signer_ids = refresh_signer_ids;
+        self.nonce_pool.clear();
+        self.commit_key_refresh()
+    }
 
-        if !proof.verify(&self.aggregate_public_key.x(), msg) {
-            warn!("SchnorrProof failed to verify!");
-            return Err(Error::SchnorrProofFailed);
+    /// Send the commit of the last key refresh until every signer that refreshed its key shares
+    /// acknowledges switching to them
+    fn commit_key_refresh(&mut self) -> Result<(), Error> {
+        for _ in 0..REFRESH_COMMIT_ATTEMPTS {
+            if self.refresh_commit_signer_ids.is_empty() {
+                break;
+            }
+            let refresh_commit = RefreshCommit {
+                dkg_id: self.current_dkg_id,
+                refresh_id: self.current_refresh_id,
+            };
+            let refresh_commit_message = Message {
+                sig: refresh_commit.sign(&self.network_private_key).expect(""),
+                msg: MessageTypes::RefreshCommit(refresh_commit),
+            };
+            self.network.send_message(refresh_commit_message)?;
+
+            let deadline = Some(Instant::now() + self.sign_timeout);
+            while !self.refresh_commit_signer_ids.is_empty() {
+                let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                    warn!(
+                        "Signers {:?} did not acknowledge the commit of key refresh #{}",
+                        self.refresh_commit_signer_ids, self.current_refresh_id
+                    );
+                    break;
+                };
+                if let MessageTypes::RefreshCommitAck(RefreshCommitAck {
+                    refresh_id,
+                    signer_id,
+                    ..
+                }) = message.msg
+                {
+                    if refresh_id == self.current_refresh_id {
+                        self.refresh_commit_signer_ids.remove(&signer_id);
+                    }
+                }
+            }
         }
-
-        Ok((sig, proof))
+        if !self.refresh_commit_signer_ids.is_empty() {
+            return Err(Error::RefreshCommitUnacked(
+                self.refresh_commit_signer_ids.iter().copied().collect(),
+            ));
+        }
+        info!("Key refresh #{} committed", self.current_refresh_id);
+        Ok(())
     }
 
     fn calculate_aggregate_public_key(&mut self) -> Result<Point, Error> {
@@ -393,7 +1316,10 @@ where
     }
 
     fn wait_for_public_shares(&mut self) -> Result<Point, Error> {
-        let mut ids_to_await: HashSet<u32> = (1..=self.total_signers).collect();
+        let mut ids_to_await: HashSet<u32> = self.dkg_signer_ids.iter().copied().collect();
+        let mut deadline = self
+            .dkg_public_timeout
+            .map(|timeout| Instant::now() + timeout);
 
         info!(
             "DKG Round #{}: waiting for Dkg Public Shares from signers {:?}",
@@ -402,6 +1328,10 @@ where
 
         loop {
             if ids_to_await.is_empty() {
+                // Only signers still taking part contribute to the key
+                let key_ids = self.dkg_key_ids();
+                self.dkg_public_shares
+                    .retain(|party_id, _| key_ids.contains(party_id));
                 let key = self.calculate_aggregate_public_key()?;
                 // check to see if aggregate public key has even y
                 if key.has_even_y() {
@@ -411,12 +1341,22 @@ where
                     return Ok(key);
                 } else {
                     warn!("DKG Round #{} Failed: Aggregate public key does not have even y coord, re-running dkg.", self.current_dkg_id);
-                    ids_to_await = (1..=self.total_signers).collect();
                     self.start_public_shares()?;
+                    ids_to_await = self.dkg_signer_ids.iter().copied().collect();
+                    deadline = self
+                        .dkg_public_timeout
+                        .map(|timeout| Instant::now() + timeout);
                 }
             }
 
-            match self.wait_for_next_message()?.msg {
+            let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                // Signers only end the public phase once they got every public share, so a
+                // single missing signer holds up everyone
+                self.drop_dkg_signers(&self.signers_missing_public_shares())?;
+                ids_to_await.clear();
+                continue;
+            };
+            match message.msg {
                 MessageTypes::DkgPublicEnd(dkg_end_msg) => {
                     ids_to_await.remove(&dkg_end_msg.signer_id);
                     debug!(
@@ -438,25 +1378,93 @@ where
         }
     }
 
-    fn wait_for_dkg_end(&mut self) -> Result<(), Error> {
-        let mut ids_to_await: HashSet<u32> = (1..=self.total_signers).collect();
+    /// Wait for every signer to end the DKG round, returning the signers found misbehaving
+    /// by the complaints they raised, along with those that failed to end it
+    fn wait_for_dkg_end(&mut self) -> Result<BTreeSet<u32>, Error> {
+        let mut ids_to_await: HashSet<u32> = self.dkg_signer_ids.iter().copied().collect();
+        let deadline = self.dkg_end_timeout.map(|timeout| Instant::now() + timeout);
+        let mut complaints = BTreeMap::new();
+        let mut failed_signer_ids = BTreeSet::new();
         info!(
             "DKG Round #{}: waiting for Dkg End from signers {:?}",
             self.current_dkg_id, ids_to_await
         );
         while !ids_to_await.is_empty() {
-            if let MessageTypes::DkgEnd(dkg_end_msg) = self.wait_for_next_message()?.msg {
-                ids_to_await.remove(&dkg_end_msg.signer_id);
-                debug!(
-                    "DKG_End round #{} from signer #{}. Waiting on {:?}",
-                    dkg_end_msg.dkg_id, dkg_end_msg.signer_id, ids_to_await
-                );
+            let Some(message) = self.wait_for_next_message_until(deadline)? else {
+                // The key is already fixed, dropped signers just won't hold their shares of it
+                self.drop_dkg_signers(&ids_to_await.into_iter().collect::<BTreeSet<u32>>())?;
+                break;
+            };
+            match message.msg {
+                MessageTypes::DkgPrivateShares(private_shares)
+                    if private_shares.dkg_id == self.current_dkg_id =>
+                {
+                    self.dkg_private_shares
+                        .insert(private_shares.key_id, private_shares);
+                }
+                MessageTypes::DkgEnd(dkg_end_msg) => {
+                    ids_to_await.remove(&dkg_end_msg.signer_id);
+                    match dkg_end_msg.status {
+                        DkgStatus::Success => {}
+                        // The signer might hold no valid key shares, so it is dropped like a
+                        // blamed one
+                        DkgStatus::Failure(DkgFailure::Error(reason)) => {
+                            warn!(
+                                "DKG_End round #{} from signer #{} failed: {}",
+                                dkg_end_msg.dkg_id, dkg_end_msg.signer_id, reason
+                            );
+                            if dkg_end_msg.dkg_id == self.current_dkg_id {
+                                failed_signer_ids.insert(dkg_end_msg.signer_id);
+                            }
+                        }
+                        DkgStatus::Failure(DkgFailure::BadPrivateShares(signer_complaints)) => {
+                            warn!(
+                                "DKG_End round #{} from signer #{} failed: {} bad private shares",
+                                dkg_end_msg.dkg_id,
+                                dkg_end_msg.signer_id,
+                                signer_complaints.len()
+                            );
+                            if dkg_end_msg.dkg_id == self.current_dkg_id {
+                                complaints.insert(dkg_end_msg.signer_id, signer_complaints);
+                            }
+                        }
+                    }
+                    debug!(
+                        "DKG_End round #{} from signer #{}. Waiting on {:?}",
+                        dkg_end_msg.dkg_id, dkg_end_msg.signer_id, ids_to_await
+                    );
+                }
+                _ => {}
             }
         }
-        Ok(())
+        for (accuser_id, complaints) in &complaints {
+            failed_signer_ids.extend(self.adjudicate_complaints(*accuser_id, complaints)?);
+        }
+        Ok(failed_signer_ids)
+    }
+
+    /// Wait for the next message, or none once the deadline passed
+    fn wait_for_next_message_until(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Message>, Error> {
+        let Some(deadline) = deadline else {
+            return self.wait_for_next_message().map(Some);
+        };
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.poll_next_message(Some(timeout)) {
+            Ok(message) => Ok(Some(message)),
+            Err(Error::Timeout) => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 
     fn wait_for_next_message(&mut self) -> Result<Message, Error> {
+        self.poll_next_message(None)
+    }
+
+    /// Poll for the next message, for up to `max_elapsed_time` if given
+    fn poll_next_message(&mut self, max_elapsed_time: Option<Duration>) -> Result<Message, Error> {
         let get_next_message = || {
             self.network.poll(self.id);
             // We only ever receive already verified messages. No need to check result.
@@ -470,10 +1478,14 @@ where
             debug!("No message. Next poll in {:?}", dur);
         };
 
-        let backoff_timer = backoff::ExponentialBackoffBuilder::new()
+        let mut backoff_builder = backoff::ExponentialBackoffBuilder::new();
+        backoff_builder
             .with_initial_interval(Duration::from_millis(2))
-            .with_max_interval(Duration::from_millis(128))
-            .build();
+            .with_max_interval(Duration::from_millis(128));
+        if max_elapsed_time.is_some() {
+            backoff_builder.with_max_elapsed_time(max_elapsed_time);
+        }
+        let backoff_timer = backoff_builder.build();
         backoff::retry_notify(backoff_timer, get_next_message, notify).map_err(|_| Error::Timeout)
     }
 
@@ -531,7 +1543,7 @@ mod test {
     fn integration_test_frost_coordinator_should_be_able_to_successfully_run_dkg_sign() {
         let relay_url = "http://127.0.0.1:9776".to_string();
         let (coordinator_config, coordinator_net_listen) =
-            spawn_processes_and_get_config(relay_url);
+            spawn_processes_and_get_config(relay_url, WstsVersion::V1);
 
         let mut coordinator = Coordinator::new(
             DEVNET_COORDINATOR_ID,
@@ -552,7 +1564,7 @@ mod test {
         let msg = vec![1, 3, 3, 7];
         let relay_url = "http://127.0.0.1:9777".to_string();
         let (coordinator_config, coordinator_net_listen) =
-            spawn_processes_and_get_config(relay_url);
+            spawn_processes_and_get_config(relay_url, WstsVersion::V1);
 
         let mut coordinator = Coordinator::new(
             DEVNET_COORDINATOR_ID,
@@ -567,12 +1579,116 @@ mod test {
         schnorr_proof.verify(&public_key.x(), &msg);
     }
 
+    #[test]
+    fn integration_test_frost_coordinator_should_provide_valid_v2_signatures_after_dkg() {
+        let msgs = vec![vec![1, 3, 3, 7], vec![7, 3, 3, 1]];
+        let relay_url = "http://127.0.0.1:9780".to_string();
+        let (coordinator_config, coordinator_net_listen) =
+            spawn_processes_and_get_config(relay_url, WstsVersion::V2);
+
+        let mut coordinator = Coordinator::new(
+            DEVNET_COORDINATOR_ID,
+            &coordinator_config,
+            coordinator_net_listen,
+        )
+        .unwrap();
+
+        let public_key = coordinator.run_distributed_key_generation().unwrap();
+        for (msg, (_, schnorr_proof)) in msgs.iter().zip(coordinator.sign_messages(&msgs).unwrap())
+        {
+            assert!(schnorr_proof.verify(&public_key.x(), msg));
+        }
+
+        let output_key = TaprootTweak::new(None).output_key(&public_key);
+        for (msg, (_, schnorr_proof)) in msgs
+            .iter()
+            .zip(coordinator.sign_taproot_messages(&msgs, None).unwrap())
+        {
+            assert!(schnorr_proof.verify(&output_key.x(), msg));
+        }
+    }
+
+    #[test]
+    fn integration_test_frost_coordinator_should_sign_with_preprocessed_nonces() {
+        let msgs = vec![vec![1, 3, 3, 7], vec![7, 3, 3, 1]];
+        let relay_url = "http://127.0.0.1:9781".to_string();
+        let (coordinator_config, coordinator_net_listen) =
+            spawn_processes_and_get_config(relay_url, WstsVersion::V1);
+
+        // Signers publish nonce batches whenever asked, whatever their own config
+        let mut coordinator = Coordinator::new(
+            DEVNET_COORDINATOR_ID,
+            &Config {
+                nonce_batch_size: Some(32),
+                ..coordinator_config
+            },
+            coordinator_net_listen,
+        )
+        .unwrap();
+
+        let public_key = coordinator.run_distributed_key_generation().unwrap();
+        coordinator.preprocess_nonces().unwrap();
+        let num_pooled_nonces = |coordinator: &Coordinator<HttpNetListen>| {
+            coordinator
+                .nonce_pool
+                .values()
+                .map(|batch| batch.nonces.len())
+                .sum::<usize>()
+        };
+        let num_nonces = num_pooled_nonces(&coordinator);
+        assert!(num_nonces > 0);
+
+        for (msg, (_, schnorr_proof)) in msgs.iter().zip(coordinator.sign_messages(&msgs).unwrap())
+        {
+            assert!(schnorr_proof.verify(&public_key.x(), msg));
+        }
+        // Each signer's nonces for both messages left the pool
+        let num_signers = coordinator.public_nonces.len();
+        assert_eq!(
+            num_pooled_nonces(&coordinator),
+            num_nonces - 2 * num_signers
+        );
+
+        // Nonces are never handed out twice, so signing again takes the next ones
+        let (_, schnorr_proof) = coordinator.sign_message(&msgs[0]).unwrap();
+        assert!(schnorr_proof.verify(&public_key.x(), &msgs[0]));
+        assert_eq!(
+            num_pooled_nonces(&coordinator),
+            num_nonces - 3 * num_signers
+        );
+    }
+
+    #[test]
+    fn integration_test_frost_coordinator_should_keep_aggregate_key_across_refresh() {
+        let msg = vec![1, 3, 3, 7];
+        let relay_url = "http://127.0.0.1:9779".to_string();
+        let (coordinator_config, coordinator_net_listen) =
+            spawn_processes_and_get_config(relay_url, WstsVersion::V1);
+
+        let mut coordinator = Coordinator::new(
+            DEVNET_COORDINATOR_ID,
+            &coordinator_config,
+            coordinator_net_listen,
+        )
+        .unwrap();
+
+        let public_key = coordinator.run_distributed_key_generation().unwrap();
+        coordinator.run_key_refresh().unwrap();
+        assert_eq!(
+            coordinator.calculate_aggregate_public_key().unwrap(),
+            public_key
+        );
+
+        let (_, schnorr_proof) = coordinator.sign_message(&msg).unwrap();
+        assert!(schnorr_proof.verify(&public_key.x(), &msg));
+    }
+
     #[test]
     fn integration_test_frost_coordinator_should_provide_valid_signatures_after_restart() {
         let msg = vec![1, 3, 3, 7];
         let relay_url = "http://127.0.0.1:9778".to_string();
         let (coordinator_config, coordinator_net_listen) =
-            spawn_processes_and_get_config(relay_url);
+            spawn_processes_and_get_config(relay_url, WstsVersion::V1);
 
         let mut coordinator = Coordinator::new(
             DEVNET_COORDINATOR_ID,
@@ -599,7 +1715,10 @@ mod test {
         schnorr_proof.verify(&public_key.x(), &msg);
     }
 
-    fn spawn_processes_and_get_config(relay_url: String) -> (Config, HttpNetListen) {
+    fn spawn_processes_and_get_config(
+        relay_url: String,
+        wsts_version: WstsVersion,
+    ) -> (Config, HttpNetListen) {
         env::set_var("RUST_LOG", "info");
 
         let num_signers = parse_env::<u32>("num_signers", 6);
@@ -620,18 +1739,22 @@ mod test {
             .map(|i| (i + 1, create_signer_key_ids(i, keys_per_signer)))
             .collect::<SignerKeyIds>();
         let public_keys = create_public_keys(&signer_private_keys, keys_per_signer);
-        let coordinator_config = Config::new(
-            keys_threshold,
-            coordinator_public_key,
-            public_keys.clone(),
-            signer_key_ids.clone(),
-            coordinator_private_key,
-            relay_url.clone(),
-        );
+        let coordinator_config = Config {
+            wsts_version,
+            ..Config::new(
+                keys_threshold,
+                coordinator_public_key,
+                public_keys.clone(),
+                signer_key_ids.clone(),
+                coordinator_private_key,
+                relay_url.clone(),
+            )
+        };
         let signer_configs = signer_private_keys
             .iter()
-            .map(|k| {
-                Config::new(
+            .map(|k| Config {
+                wsts_version,
+                ..Config::new(
                     keys_threshold,
                     coordinator_public_key,
                     public_keys.clone(),
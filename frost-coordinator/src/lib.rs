@@ -1,3 +1,4 @@
+pub mod aggregator;
 pub mod coordinator;
 
 use coordinator::{Coordinator, Error};
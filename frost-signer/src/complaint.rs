@@ -0,0 +1,278 @@
+//! Verifiable complaints about DKG private shares that fail to decrypt or verify.
+//!
+//! Private shares travel encrypted under a key derived from the Diffie-Hellman key `K = a*B`
+//! shared by the recipient `A = a*G` and the sender `B`. A complaint reveals `K` along with a
+//! proof that it has the same discrete log with respect to `B` as `A` has with respect to `G`,
+//! so anyone holding the signed private shares and the sender's public share can decrypt the
+//! disputed share and decide whether the sender or the accuser misbehaved. Revealing `K` only
+//! exposes the shares exchanged between those two signers.
+
+use p256k1::{
+    ecdsa,
+    point::{Compressed, Point},
+    scalar::Scalar,
+};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wsts::common::PolyCommitment;
+
+use crate::signing_round::DkgPrivateShares;
+use crate::util::{decrypt, eval_public_share, make_shared_secret_from_key};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ComplaintReason {
+    /// The share did not decrypt with the shared key
+    DecryptionFailed,
+    /// The decrypted share is not a scalar
+    InvalidScalar,
+    /// The share does not match the sender's public share
+    BadShare,
+}
+
+/// Who is at fault for a complaint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blame {
+    /// The share really is bad
+    Sender,
+    /// The share is fine, or the complaint does not hold up
+    Accuser,
+}
+
+/// Chaum-Pedersen proof that `A = a*G` and `K = a*B` share the discrete log `a`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DleqProof {
+    pub c: Scalar,
+    pub s: Scalar,
+}
+
+#[allow(non_snake_case)]
+impl DleqProof {
+    pub fn new<RNG: RngCore + CryptoRng>(a: &Scalar, B: &Point, rng: &mut RNG) -> Self {
+        let A = Point::from(*a);
+        let K = a * B;
+        let r = Scalar::random(rng);
+        let c = dleq_challenge(&A, B, &K, &Point::from(r), &(&r * B));
+        let s = r - c * *a;
+        Self { c, s }
+    }
+
+    pub fn verify(&self, A: &Point, B: &Point, K: &Point) -> bool {
+        let R1 = Point::from(self.s) + &self.c * A;
+        let R2 = &self.s * B + &self.c * K;
+        self.c == dleq_challenge(A, B, K, &R1, &R2)
+    }
+}
+
+#[allow(non_snake_case)]
+fn dleq_challenge(A: &Point, B: &Point, K: &Point, R1: &Point, R2: &Point) -> Scalar {
+    let mut hasher = Sha256::new();
+
+    hasher.update("DLEQ_PROOF/".as_bytes());
+    for point in [A, B, K, R1, R2] {
+        hasher.update(point.compress().as_bytes());
+    }
+
+    let hash = hasher.finalize();
+    let mut bytes = [0u8; 32];
+
+    bytes.clone_from_slice(hash.as_slice());
+    Scalar::from(bytes)
+}
+
+/// A recipient's complaint about a private share it got during DKG
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Complaint {
+    /// Key id whose private shares hold the bad share, 0 based like `DkgPrivateShares::key_id`
+    pub src_key_id: u32,
+    /// Key id of the accuser the share was meant for, 0 based
+    pub dst_key_id: u32,
+    pub reason: ComplaintReason,
+    /// Diffie-Hellman key shared by the sender and the accuser
+    pub shared_key: Point,
+    /// Proof that `shared_key` is the accuser's network private key times the sender's public key
+    pub proof: DleqProof,
+}
+
+impl Complaint {
+    pub fn new<RNG: RngCore + CryptoRng>(
+        network_private_key: &Scalar,
+        src_public_key: &Point,
+        src_key_id: u32,
+        dst_key_id: u32,
+        reason: ComplaintReason,
+        rng: &mut RNG,
+    ) -> Self {
+        Self {
+            src_key_id,
+            dst_key_id,
+            reason,
+            shared_key: network_private_key * src_public_key,
+            proof: DleqProof::new(network_private_key, src_public_key, rng),
+        }
+    }
+
+    pub fn hash(&self, hasher: &mut Sha256) {
+        hasher.update(self.src_key_id.to_be_bytes());
+        hasher.update(self.dst_key_id.to_be_bytes());
+        hasher.update([self.reason as u8]);
+        hasher.update(self.shared_key.compress().as_bytes());
+        hasher.update(self.proof.c.to_bytes());
+        hasher.update(self.proof.s.to_bytes());
+    }
+
+    /// Decide who is at fault, given the network public keys of the accuser and the sender,
+    /// the sender's signed private shares and its public share
+    pub fn blame(
+        &self,
+        dst_public_key: &Point,
+        src_public_key: &Point,
+        private_shares: &DkgPrivateShares,
+        public_share: &PolyCommitment,
+    ) -> Blame {
+        if private_shares.key_id != self.src_key_id
+            || !self
+                .proof
+                .verify(dst_public_key, src_public_key, &self.shared_key)
+        {
+            return Blame::Accuser;
+        }
+        let Some(encrypted_share) = private_shares.private_shares.get(&self.dst_key_id) else {
+            return Blame::Sender;
+        };
+        let shared_secret = make_shared_secret_from_key(&self.shared_key);
+        let Ok(plain) = decrypt(&shared_secret, encrypted_share) else {
+            return Blame::Sender;
+        };
+        match Scalar::try_from(&plain[..]) {
+            Ok(share) if verify_share(&share, self.dst_key_id, public_share) => Blame::Accuser,
+            _ => Blame::Sender,
+        }
+    }
+}
+
+/// Check a private share for the 0 based `dst_key_id` against the sender's public share
+pub fn verify_share(share: &Scalar, dst_key_id: u32, public_share: &PolyCommitment) -> bool {
+    Point::from(*share) == eval_public_share(public_share, dst_key_id)
+}
+
+/// The point of a network public key
+pub fn public_key_point(public_key: &ecdsa::PublicKey) -> Option<Point> {
+    Point::try_from(&Compressed::from(public_key.to_bytes())).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use hashbrown::HashMap;
+    use rand_core::OsRng;
+    use wsts::{traits::Signer, v1};
+
+    use super::*;
+    use crate::util::{encrypt, make_shared_secret};
+
+    struct Parties {
+        src_private_key: Scalar,
+        dst_private_key: Scalar,
+        public_share: PolyCommitment,
+        share: Scalar,
+    }
+
+    impl Parties {
+        fn new() -> Self {
+            let mut signer = v1::Signer::new(1, &[0], 2, 2, &mut OsRng);
+            let public_share = signer.get_poly_commitments(&mut OsRng).remove(0);
+            let share = signer.get_shares()[&0][&1];
+            Self {
+                src_private_key: Scalar::random(&mut OsRng),
+                dst_private_key: Scalar::random(&mut OsRng),
+                public_share,
+                share,
+            }
+        }
+
+        fn private_shares(&self, share: &[u8]) -> DkgPrivateShares {
+            let shared_secret =
+                make_shared_secret(&self.src_private_key, &Point::from(self.dst_private_key));
+            DkgPrivateShares {
+                dkg_id: 1,
+                key_id: 0,
+                private_shares: HashMap::from([(
+                    1,
+                    encrypt(&shared_secret, share, &mut OsRng).unwrap(),
+                )]),
+            }
+        }
+
+        fn complaint(&self, network_private_key: &Scalar, reason: ComplaintReason) -> Complaint {
+            Complaint::new(
+                network_private_key,
+                &Point::from(self.src_private_key),
+                0,
+                1,
+                reason,
+                &mut OsRng,
+            )
+        }
+
+        fn blame(&self, complaint: &Complaint, private_shares: &DkgPrivateShares) -> Blame {
+            complaint.blame(
+                &Point::from(self.dst_private_key),
+                &Point::from(self.src_private_key),
+                private_shares,
+                &self.public_share,
+            )
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn dleq_proof_should_only_verify_matching_logs() {
+        let a = Scalar::random(&mut OsRng);
+        let B = Point::from(Scalar::random(&mut OsRng));
+        let proof = DleqProof::new(&a, &B, &mut OsRng);
+
+        assert!(proof.verify(&Point::from(a), &B, &(&a * &B)));
+        assert!(!proof.verify(&Point::from(a), &B, &Point::from(a)));
+        let b = Scalar::random(&mut OsRng);
+        assert!(!proof.verify(&Point::from(b), &B, &(&b * &B)));
+    }
+
+    #[test]
+    fn complaint_should_blame_sender_of_bad_share() {
+        let parties = Parties::new();
+        assert!(verify_share(&parties.share, 1, &parties.public_share));
+
+        let bad_share = parties.share + Scalar::from(1);
+        let private_shares = parties.private_shares(&bad_share.to_bytes());
+        let complaint = parties.complaint(&parties.dst_private_key, ComplaintReason::BadShare);
+        assert_eq!(parties.blame(&complaint, &private_shares), Blame::Sender);
+
+        let private_shares = parties.private_shares(&[1, 2, 3]);
+        let complaint = parties.complaint(&parties.dst_private_key, ComplaintReason::InvalidScalar);
+        assert_eq!(parties.blame(&complaint, &private_shares), Blame::Sender);
+
+        // A share too short to even hold an AES-GCM nonce
+        let mut private_shares = parties.private_shares(&parties.share.to_bytes());
+        private_shares.private_shares.insert(1, vec![1, 2, 3]);
+        let complaint =
+            parties.complaint(&parties.dst_private_key, ComplaintReason::DecryptionFailed);
+        assert_eq!(parties.blame(&complaint, &private_shares), Blame::Sender);
+    }
+
+    #[test]
+    fn complaint_should_blame_accuser_of_good_share() {
+        let parties = Parties::new();
+        let private_shares = parties.private_shares(&parties.share.to_bytes());
+
+        let complaint = parties.complaint(&parties.dst_private_key, ComplaintReason::BadShare);
+        assert_eq!(parties.blame(&complaint, &private_shares), Blame::Accuser);
+
+        // A shared key the accuser cannot prove fails whatever the share
+        let private_shares = parties.private_shares(&[1, 2, 3]);
+        let complaint = parties.complaint(
+            &Scalar::random(&mut OsRng),
+            ComplaintReason::DecryptionFailed,
+        );
+        assert_eq!(parties.blame(&complaint, &private_shares), Blame::Accuser);
+    }
+}
@@ -5,10 +5,10 @@ use p256k1::{
     scalar::{Error as ScalarError, Scalar},
 };
 use serde::Deserialize;
-use std::fs;
+use std::{fs, time::Duration};
 use toml;
 
-use crate::util::parse_public_key;
+use crate::{scheme::WstsVersion, util::parse_public_key};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -55,6 +55,22 @@ struct RawConfig {
     pub http_relay_url: String,
     pub keys_threshold: u32,
     pub network_private_key: String,
+    /// Encrypted file the signer's DKG key shares are kept in across restarts
+    pub frost_state_file: Option<String>,
+    /// Seconds the coordinator waits for DKG public shares before dropping the signers that did
+    /// not send theirs. Default: no deadline
+    pub dkg_public_timeout: Option<u64>,
+    /// Seconds the coordinator waits for signers to end DKG before dropping the rest. Default: no deadline
+    pub dkg_end_timeout: Option<u64>,
+    /// Seconds the coordinator waits for signature shares before signing again without the
+    /// signers that did not send theirs. Default: 60
+    pub sign_timeout: Option<u64>,
+    /// WSTS signing scheme, "v1" for a nonce and signature share per key id or "v2" for one per
+    /// signer. Must match across the coordinator and signers. Default: v1
+    pub wsts_version: Option<WstsVersion>,
+    /// Number of nonces the coordinator has each signer publish ahead of time, so signing takes a
+    /// single round trip. Default: nonces are requested for every signing session
+    pub nonce_batch_size: Option<u32>,
     signers: Vec<RawSigners>,
     coordinator_public_key: String,
 }
@@ -121,6 +137,15 @@ pub struct Config {
     pub coordinator_public_key: ecdsa::PublicKey,
     pub total_signers: u32,
     pub total_keys: u32,
+    pub frost_state_file: Option<String>,
+    /// How long the coordinator waits for each phase of a DKG round before dropping non-responsive signers
+    pub dkg_public_timeout: Option<Duration>,
+    pub dkg_end_timeout: Option<Duration>,
+    /// How long the coordinator waits for signature shares before retrying without the missing signers
+    pub sign_timeout: Option<Duration>,
+    pub wsts_version: WstsVersion,
+    /// How many nonces signers publish ahead of signing, if they do
+    pub nonce_batch_size: Option<u32>,
 }
 
 impl Config {
@@ -141,6 +166,12 @@ impl Config {
             total_keys: public_keys.key_ids.len().try_into().unwrap(),
             public_keys,
             signer_key_ids,
+            frost_state_file: None,
+            dkg_public_timeout: None,
+            dkg_end_timeout: None,
+            sign_timeout: None,
+            wsts_version: WstsVersion::default(),
+            nonce_batch_size: None,
         }
     }
 
@@ -153,20 +184,29 @@ impl Config {
 impl TryFrom<&RawConfig> for Config {
     type Error = Error;
     fn try_from(raw_config: &RawConfig) -> Result<Self, Error> {
-        Ok(Config::new(
-            raw_config.keys_threshold,
-            raw_config.coordinator_public_key()?,
-            raw_config.public_keys()?,
-            raw_config.signer_key_ids(),
-            raw_config.network_private_key()?,
-            raw_config.http_relay_url.clone(),
-        ))
+        Ok(Config {
+            frost_state_file: raw_config.frost_state_file.clone(),
+            dkg_public_timeout: raw_config.dkg_public_timeout.map(Duration::from_secs),
+            dkg_end_timeout: raw_config.dkg_end_timeout.map(Duration::from_secs),
+            sign_timeout: raw_config.sign_timeout.map(Duration::from_secs),
+            wsts_version: raw_config.wsts_version.unwrap_or_default(),
+            nonce_batch_size: raw_config.nonce_batch_size.filter(|size| *size > 0),
+            ..Config::new(
+                raw_config.keys_threshold,
+                raw_config.coordinator_public_key()?,
+                raw_config.public_keys()?,
+                raw_config.signer_key_ids(),
+                raw_config.network_private_key()?,
+                raw_config.http_relay_url.clone(),
+            )
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::{Config, Error, RawConfig, RawSigners};
+    use crate::scheme::WstsVersion;
 
     #[test]
     fn try_from_raw_config_test() {
@@ -186,7 +226,32 @@ mod test {
         ));
 
         raw_config.network_private_key = "9aSCCR6eirt1NAHwJtSz4HMwBHTyMo62SyPMvVDt5DQn".to_string();
-        assert!(Config::try_from(&raw_config).is_ok());
+        assert_eq!(
+            Config::try_from(&raw_config).unwrap().wsts_version,
+            WstsVersion::V1
+        );
+
+        raw_config.wsts_version = Some(WstsVersion::V2);
+        assert_eq!(
+            Config::try_from(&raw_config).unwrap().wsts_version,
+            WstsVersion::V2
+        );
+        assert_eq!(
+            Config::try_from(&raw_config).unwrap().nonce_batch_size,
+            None
+        );
+
+        // A batch of no nonces leaves nonce preprocessing off
+        raw_config.nonce_batch_size = Some(0);
+        assert_eq!(
+            Config::try_from(&raw_config).unwrap().nonce_batch_size,
+            None
+        );
+        raw_config.nonce_batch_size = Some(16);
+        assert_eq!(
+            Config::try_from(&raw_config).unwrap().nonce_batch_size,
+            Some(16)
+        );
     }
 
     #[test]
@@ -1,9 +1,13 @@
+pub mod complaint;
 pub mod config;
 pub mod logging;
 pub mod net;
+pub mod scheme;
 pub mod signer;
 pub mod signing_round;
+pub mod state_file;
 pub mod state_machine;
+pub mod taproot;
 pub mod util;
 
 // set via _compile-time_ envars
@@ -0,0 +1,276 @@
+//! WSTS signing schemes, which differ in how many nonces and signature shares a signer holding
+//! several key ids sends.
+//!
+//! Both schemes sign with the key shares of the same DKG, which deals a polynomial per key id.
+//! Under v1 every key id is a party of its own, with its own nonce and signature share. Under v2
+//! the signer is a single `wsts::v2::Party` holding all its key ids, so it costs one nonce and
+//! one signature share however many keys it holds.
+//!
+//! v2 covers signing only: DKG still runs on `v1::Signer`, so its cost stays per key id. The v2
+//! party is assembled from the v1 signer's key shares each time it generates nonces.
+
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+use wsts::{
+    common::{PublicNonce, SignatureShare},
+    traits::{Signer as SignerTrait, SignerState},
+    v1, v2,
+};
+
+use crate::signing_round::NonceResponse;
+
+/// Signing scheme shared by the coordinator and the signers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WstsVersion {
+    /// A nonce and a signature share per key id
+    #[default]
+    V1,
+    /// A nonce and a signature share per signer
+    V2,
+}
+
+impl WstsVersion {
+    pub fn scheme(&self) -> &'static dyn Scheme {
+        match self {
+            WstsVersion::V1 => &V1,
+            WstsVersion::V2 => &V2,
+        }
+    }
+}
+
+pub trait Scheme: Send + Sync {
+    /// Ids of the parties of a signer, along with the 0 based key ids each of them signs for
+    fn parties(&self, nonce_response: &NonceResponse) -> Vec<(u32, Vec<u32>)>;
+
+    /// Generate the nonces of each party of the signer for one message, along with the signer
+    /// state holding their private nonces until it signs with them
+    fn gen_nonces(
+        &self,
+        signer: &mut v1::Signer,
+        rng: &mut dyn CryptoRngCore,
+    ) -> (Vec<PublicNonce>, SignerState);
+
+    /// Signature shares for `msg`, signed with `state`, the signer state that generated the
+    /// signer's nonces for the message at `index` of the nonce responses
+    fn sign(
+        &self,
+        state: &SignerState,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        index: usize,
+    ) -> Vec<SignatureShare>;
+
+    /// Ids of the parties making up the aggregate nonce, in the order of their nonces
+    fn party_ids(&self, nonce_responses: &[NonceResponse]) -> Vec<u32> {
+        nonce_responses
+            .iter()
+            .flat_map(|nr| self.parties(nr))
+            .map(|(party_id, _)| party_id)
+            .collect()
+    }
+}
+
+/// Nonces of every signer for the message at `index`, in the order of their parties
+pub fn message_nonces(nonce_responses: &[NonceResponse], index: usize) -> Vec<PublicNonce> {
+    nonce_responses
+        .iter()
+        .flat_map(|nr| nr.nonces[index].clone())
+        .collect()
+}
+
+/// Every key id is a party
+pub struct V1;
+
+impl Scheme for V1 {
+    fn parties(&self, nonce_response: &NonceResponse) -> Vec<(u32, Vec<u32>)> {
+        nonce_response
+            .key_ids
+            .iter()
+            .map(|key_id| (*key_id, vec![*key_id]))
+            .collect()
+    }
+
+    fn gen_nonces(
+        &self,
+        signer: &mut v1::Signer,
+        mut rng: &mut dyn CryptoRngCore,
+    ) -> (Vec<PublicNonce>, SignerState) {
+        let nonces = signer.gen_nonces(&mut rng);
+        (nonces, signer.save())
+    }
+
+    fn sign(
+        &self,
+        state: &SignerState,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        index: usize,
+    ) -> Vec<SignatureShare> {
+        let signer_ids = nonce_responses
+            .iter()
+            .map(|nr| nr.signer_id)
+            .collect::<Vec<u32>>();
+        let key_ids = self.party_ids(nonce_responses);
+        v1::Signer::load(state).sign(
+            msg,
+            &signer_ids,
+            &key_ids,
+            &message_nonces(nonce_responses, index),
+        )
+    }
+}
+
+/// Every signer is a single party holding all its key ids
+pub struct V2;
+
+impl V2 {
+    /// The key shares of every party of a v1 signer, held by a single v2 party. The party keeps
+    /// the polynomial of the signer's first key id, which signing never uses.
+    fn party(signer: &v1::Signer) -> v2::Party {
+        let mut state = signer.save();
+        let private_keys = state
+            .parties
+            .iter()
+            .flat_map(|(_, party)| party.private_keys.clone())
+            .collect();
+        state.parties.truncate(1);
+        if let Some((_, party)) = state.parties.first_mut() {
+            party.private_keys = private_keys;
+        }
+        <v2::Party as SignerTrait>::load(&state)
+    }
+}
+
+impl Scheme for V2 {
+    fn parties(&self, nonce_response: &NonceResponse) -> Vec<(u32, Vec<u32>)> {
+        vec![(nonce_response.signer_id, nonce_response.key_ids.clone())]
+    }
+
+    fn gen_nonces(
+        &self,
+        signer: &mut v1::Signer,
+        mut rng: &mut dyn CryptoRngCore,
+    ) -> (Vec<PublicNonce>, SignerState) {
+        let mut party = Self::party(signer);
+        let nonces = SignerTrait::gen_nonces(&mut party, &mut rng);
+        (nonces, SignerTrait::save(&party))
+    }
+
+    fn sign(
+        &self,
+        state: &SignerState,
+        msg: &[u8],
+        nonce_responses: &[NonceResponse],
+        index: usize,
+    ) -> Vec<SignatureShare> {
+        let party_ids = self.party_ids(nonce_responses);
+        let key_ids = nonce_responses
+            .iter()
+            .flat_map(|nr| nr.key_ids.clone())
+            .collect::<Vec<u32>>();
+        SignerTrait::sign(
+            &<v2::Party as SignerTrait>::load(state),
+            msg,
+            &party_ids,
+            &key_ids,
+            &message_nonces(nonce_responses, index),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hashbrown::HashMap;
+    use rand_core::OsRng;
+    use wsts::{bip340::test_helpers::dkg, Scalar};
+
+    use super::*;
+
+    fn respond_with_nonces(
+        scheme: &dyn Scheme,
+        signers: &mut [v1::Signer],
+    ) -> (Vec<NonceResponse>, Vec<SignerState>) {
+        let mut states = vec![];
+        let nonce_responses = signers
+            .iter_mut()
+            .map(|signer| {
+                let (nonces, state) = scheme.gen_nonces(signer, &mut OsRng);
+                states.push(state);
+                NonceResponse {
+                    dkg_id: 1,
+                    sign_id: 1,
+                    sign_nonce_id: 1,
+                    signer_id: signer.get_id(),
+                    key_ids: signer.get_key_ids(),
+                    nonces: vec![nonces],
+                }
+            })
+            .collect();
+        (nonce_responses, states)
+    }
+
+    fn private_keys(state: &SignerState) -> HashMap<u32, Scalar> {
+        state
+            .parties
+            .iter()
+            .flat_map(|(_, party)| party.private_keys.clone())
+            .collect()
+    }
+
+    #[test]
+    fn v2_party_should_round_trip_every_key_share_of_the_signer() {
+        let mut signers = [
+            v1::Signer::new(1, &[0, 1, 2], 4, 3, &mut OsRng),
+            v1::Signer::new(2, &[3], 4, 3, &mut OsRng),
+        ];
+        dkg(&mut signers[..], &mut OsRng).unwrap();
+
+        let (_, state) = V2.gen_nonces(&mut signers[0], &mut OsRng);
+        assert_eq!(state.parties.len(), 1);
+        assert_eq!(private_keys(&state), private_keys(&signers[0].save()));
+
+        let party = <v2::Party as SignerTrait>::load(&state);
+        assert_eq!(party.get_id(), 1);
+        assert_eq!(party.get_key_ids(), vec![0, 1, 2]);
+        assert_eq!(
+            bincode::serialize(&SignerTrait::save(&party)).unwrap(),
+            bincode::serialize(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn v2_should_send_a_single_nonce_and_share_per_signer() {
+        let mut signers = [
+            v1::Signer::new(1, &[0, 1, 2], 4, 3, &mut OsRng),
+            v1::Signer::new(2, &[3], 4, 3, &mut OsRng),
+        ];
+        let polys = dkg(&mut signers[..], &mut OsRng).unwrap();
+        let msg = "It was many and many a year ago".as_bytes();
+
+        let (nonce_responses, states) = respond_with_nonces(&V2, &mut signers);
+        assert!(nonce_responses.iter().all(|nr| nr.nonces[0].len() == 1));
+        assert_eq!(V2.party_ids(&nonce_responses), vec![1, 2]);
+
+        let mut shares = states
+            .iter()
+            .flat_map(|state| V2.sign(state, msg, &nonce_responses, 0))
+            .collect::<Vec<SignatureShare>>();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].key_ids, vec![0, 1, 2]);
+
+        // The shares are those of v2 parties, checked by the v2 aggregator
+        let nonces = message_nonces(&nonce_responses, 0);
+        let key_ids = [0, 1, 2, 3];
+        let mut aggregator = v2::Aggregator::new(4, 3);
+        aggregator.init(polys).unwrap();
+        assert!(aggregator.sign(msg, &nonces, &shares, &key_ids).is_ok());
+
+        shares[1].z_i = shares[1].z_i + Scalar::from(1);
+        assert!(aggregator.sign(msg, &nonces, &shares, &key_ids).is_err());
+
+        let (nonce_responses, _) = respond_with_nonces(&V1, &mut signers[..1]);
+        assert_eq!(nonce_responses[0].nonces[0].len(), 3);
+        assert_eq!(V1.party_ids(&nonce_responses), vec![0, 1, 2]);
+    }
+}
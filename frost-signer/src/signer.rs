@@ -1,12 +1,14 @@
 use crate::config::{Config, PublicKeys};
 use crate::net::{Error as HttpNetError, HttpNet, HttpNetListen, Message, Net, NetListen};
 use crate::signing_round::{Error as SigningRoundError, MessageTypes, Signable, SigningRound};
+use crate::state_file::{Error as StateFileError, StateFile};
+use crate::state_machine::States;
 use p256k1::ecdsa;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread::spawn;
 use std::{thread, time};
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 // on-disk format for frost save data
 #[derive(Clone)]
@@ -41,18 +43,62 @@ impl Signer {
     fn start_signing_round(&self, net: &HttpNet, rx: Receiver<Message>) -> Result<(), Error> {
         let network_private_key = self.config.network_private_key;
         let mut round = SigningRound::from(self);
+        let state_file = self
+            .config
+            .frost_state_file
+            .as_ref()
+            .map(|path| StateFile::new(path, &network_private_key));
+        if let Some(state_file) = &state_file {
+            if let Some(saved_state) = state_file.load()? {
+                info!(
+                    "Resuming signer #{} from DKG round #{}",
+                    self.signer_id, saved_state.dkg_id
+                );
+                round.restore(saved_state)?;
+            }
+        }
+        let mut saved_ids = (
+            round.dkg_id,
+            round.refresh_id,
+            round.pending_refresh_id,
+            round.sign_id,
+        );
         loop {
             // Retreive a message from coordinator
             let inbound = rx.recv()?; // blocking
-            let outbounds = round.process(inbound.msg)?;
+            let outbounds = match round.process(inbound.msg) {
+                // Such as a request to sign with batch nonces lost in a restart
+                Err(SigningRoundError::InvalidNonceResponse) => {
+                    warn!("Ignoring a signing request for nonces we cannot sign with");
+                    continue;
+                }
+                outbounds => outbounds?,
+            };
+            // Persist the key shares of a finished DKG round before reporting its end
+            if let Some(state_file) = &state_file {
+                let ids = (
+                    round.dkg_id,
+                    round.refresh_id,
+                    round.pending_refresh_id,
+                    round.sign_id,
+                );
+                if round.state == States::Idle && ids != saved_ids {
+                    state_file.save(&round.saved_state())?;
+                    saved_ids = ids;
+                }
+            }
             for out in outbounds {
                 let msg = Message {
                     msg: out.clone(),
                     sig: match out {
-                        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => msg
+                        MessageTypes::DkgBegin(msg) => msg
                             .sign(&network_private_key)
                             .expect("failed to sign DkgBegin")
                             .to_vec(),
+                        MessageTypes::DkgPrivateBegin(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign DkgPrivateBegin")
+                            .to_vec(),
                         MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => msg
                             .sign(&network_private_key)
                             .expect("failed to sign DkgEnd")
@@ -73,6 +119,14 @@ impl Signer {
                             .sign(&network_private_key)
                             .expect("failed to sign NonceResponse")
                             .to_vec(),
+                        MessageTypes::NonceBatchRequest(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign NonceBatchRequest")
+                            .to_vec(),
+                        MessageTypes::NonceBatch(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign NonceBatch")
+                            .to_vec(),
                         MessageTypes::SignShareRequest(msg) => msg
                             .sign(&network_private_key)
                             .expect("failed to sign SignShareRequest")
@@ -81,6 +135,26 @@ impl Signer {
                             .sign(&network_private_key)
                             .expect("failed to sign SignShareResponse")
                             .to_vec(),
+                        MessageTypes::RefreshBegin(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign RefreshBegin")
+                            .to_vec(),
+                        MessageTypes::RefreshShares(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign RefreshShares")
+                            .to_vec(),
+                        MessageTypes::RefreshEnd(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign RefreshEnd")
+                            .to_vec(),
+                        MessageTypes::RefreshCommit(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign RefreshCommit")
+                            .to_vec(),
+                        MessageTypes::RefreshCommitAck(msg) => msg
+                            .sign(&network_private_key)
+                            .expect("failed to sign RefreshCommitAck")
+                            .to_vec(),
                     },
                 };
                 net.send_message(msg)?;
@@ -97,6 +171,9 @@ pub enum Error {
     #[error("Signing Round Error: {0}")]
     SigningRoundError(#[from] SigningRoundError),
 
+    #[error("State File Error: {0}")]
+    StateFileError(#[from] StateFileError),
+
     #[error("Failed to retrieve message: {0}")]
     RecvError(#[from] mpsc::RecvError),
 
@@ -150,7 +227,13 @@ fn verify_msg(
     coordinator_public_key: &ecdsa::PublicKey,
 ) -> bool {
     match &m.msg {
-        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => {
+        MessageTypes::DkgBegin(msg) => {
+            if !msg.verify(&m.sig, coordinator_public_key) {
+                warn!("Received a DkgBegin message with an invalid signature.");
+                return false;
+            }
+        }
+        MessageTypes::DkgPrivateBegin(msg) => {
             if !msg.verify(&m.sig, coordinator_public_key) {
                 warn!("Received a DkgPrivateBegin message with an invalid signature.");
                 return false;
@@ -225,6 +308,26 @@ fn verify_msg(
                 return false;
             }
         }
+        MessageTypes::NonceBatchRequest(msg) => {
+            if !msg.verify(&m.sig, coordinator_public_key) {
+                warn!("Received a NonceBatchRequest message with an invalid signature.");
+                return false;
+            }
+        }
+        MessageTypes::NonceBatch(msg) => {
+            if let Some(public_key) = public_keys.signers.get(&msg.signer_id) {
+                if !msg.verify(&m.sig, public_key) {
+                    warn!("Received a NonceBatch message with an invalid signature.");
+                    return false;
+                }
+            } else {
+                warn!(
+                    "Received a NonceBatch message with an unknown id: {}",
+                    msg.signer_id
+                );
+                return false;
+            }
+        }
         MessageTypes::SignShareRequest(msg) => {
             if !msg.verify(&m.sig, coordinator_public_key) {
                 warn!("Received a SignShareRequest message with an invalid signature.");
@@ -245,6 +348,62 @@ fn verify_msg(
                 return false;
             }
         }
+        MessageTypes::RefreshBegin(msg) => {
+            if !msg.verify(&m.sig, coordinator_public_key) {
+                warn!("Received a RefreshBegin message with an invalid signature.");
+                return false;
+            }
+        }
+        MessageTypes::RefreshShares(msg) => {
+            // Refresh shares carry 0 based key ids like private shares
+            let key_id = msg.key_id + 1;
+            if let Some(public_key) = public_keys.key_ids.get(&key_id) {
+                if !msg.verify(&m.sig, public_key) {
+                    warn!("Received a RefreshShares message with an invalid signature.");
+                    return false;
+                }
+            } else {
+                warn!(
+                    "Received a RefreshShares message with an unknown id: {}",
+                    key_id
+                );
+                return false;
+            }
+        }
+        MessageTypes::RefreshEnd(msg) => {
+            if let Some(public_key) = public_keys.signers.get(&msg.signer_id) {
+                if !msg.verify(&m.sig, public_key) {
+                    warn!("Received a RefreshEnd message with an invalid signature.");
+                    return false;
+                }
+            } else {
+                warn!(
+                    "Received a RefreshEnd message with an unknown id: {}",
+                    msg.signer_id
+                );
+                return false;
+            }
+        }
+        MessageTypes::RefreshCommit(msg) => {
+            if !msg.verify(&m.sig, coordinator_public_key) {
+                warn!("Received a RefreshCommit message with an invalid signature.");
+                return false;
+            }
+        }
+        MessageTypes::RefreshCommitAck(msg) => {
+            if let Some(public_key) = public_keys.signers.get(&msg.signer_id) {
+                if !msg.verify(&m.sig, public_key) {
+                    warn!("Received a RefreshCommitAck message with an invalid signature.");
+                    return false;
+                }
+            } else {
+                warn!(
+                    "Received a RefreshCommitAck message with an unknown id: {}",
+                    msg.signer_id
+                );
+                return false;
+            }
+        }
     }
     true
 }
@@ -264,8 +423,9 @@ mod test {
         config::PublicKeys,
         net::Message,
         signing_round::{
-            DkgBegin, DkgEnd, DkgPrivateShares, DkgPublicShare, DkgStatus, MessageTypes,
-            NonceRequest, NonceResponse, Signable, SignatureShareRequest, SignatureShareResponse,
+            DkgBegin, DkgEnd, DkgPrivateBegin, DkgPrivateShares, DkgPublicShare, DkgStatus,
+            MessageTypes, NonceRequest, NonceResponse, Signable, SignatureShareRequest,
+            SignatureShareResponse,
         },
     };
 
@@ -324,6 +484,12 @@ mod test {
             sig: sig.clone(),
         };
         // DkgPrivateBegin
+        let inner = DkgPrivateBegin {
+            dkg_id: 0,
+            signer_ids: vec![1, 2],
+            key_ids: vec![1, 2, 3, 4],
+        };
+        let sig = inner.sign(&config.coordinator_sec_key).unwrap();
         let msg = MessageTypes::DkgPrivateBegin(inner);
         let dkg_private_begin = Message { msg, sig };
 
@@ -561,6 +727,7 @@ mod test {
             dkg_id: 0,
             sign_id: 0,
             sign_nonce_id: 0,
+            num_messages: 1,
         };
 
         let sig = inner.sign(&config.coordinator_sec_key).unwrap();
@@ -650,12 +817,13 @@ mod test {
                 sign_nonce_id: 0,
                 signer_id: 1,
                 key_ids: vec![0],
-                nonces: vec![PublicNonce {
+                nonces: vec![vec![PublicNonce {
                     D: Default::default(),
                     E: Default::default(),
-                }],
+                }]],
             }],
-            message: vec![],
+            messages: vec![vec![]],
+            taproot_tweak: None,
         };
         let sig = inner.sign(&config.coordinator_sec_key).unwrap();
         let msg = MessageTypes::SignShareRequest(inner);
@@ -12,15 +12,19 @@ use tracing::{debug, info, warn};
 pub use wsts;
 use wsts::{
     common::{PolyCommitment, PublicNonce, SignatureShare},
-    traits::Signer as SignerTrait,
+    traits::{Signer as SignerTrait, SignerState},
     v1,
 };
 
 use crate::{
+    complaint::{verify_share, Complaint, ComplaintReason},
     config::PublicKeys,
+    scheme::WstsVersion,
     signer::Signer as FrostSigner,
+    state_file::SavedState,
     state_machine::{Error as StateMachineError, StateMachine, States},
-    util::{decrypt, encrypt, make_shared_secret},
+    taproot::TaprootTweak,
+    util::{decrypt, encrypt, eval_commitments, make_shared_secret},
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -35,6 +39,8 @@ pub enum Error {
     InvalidNonceResponse,
     #[error("InvalidSignatureShare")]
     InvalidSignatureShare,
+    #[error("Saved state belongs to signer {0} or other key ids")]
+    SavedStateMismatch(u32),
     #[error("State Machine Error: {0}")]
     StateMachineError(#[from] StateMachineError),
 }
@@ -81,9 +87,37 @@ pub struct SigningRound {
     pub state: States,
     pub commitments: BTreeMap<u32, PolyCommitment>,
     pub shares: HashMap<u32, HashMap<u32, Vec<u8>>>,
+    /// Key ids taking part in the current DKG round, which excludes signers the coordinator dropped
+    pub dkg_key_ids: HashSet<u32>,
     pub public_nonces: Vec<PublicNonce>,
+    /// Signer state right after generating each set of nonces handed out for the current
+    /// `sign_id`, keyed by those nonces. A state is dropped once it signed, so nonces are never reused
+    pub nonce_states: HashMap<Vec<u8>, SignerState>,
+    /// Signer state behind each set of nonces of the last batch published ahead of signing. They
+    /// outlive signing sessions but are never saved, so a restarted signer cannot reuse them
+    pub batch_nonce_states: HashMap<Vec<u8>, SignerState>,
+    /// Last key refresh applied to the key shares
+    pub refresh_id: u64,
+    /// Key refresh in progress, which only applies once the coordinator commits it
+    pub pending_refresh_id: u64,
+    /// Key ids taking part in the key refresh in progress
+    pub refresh_key_ids: HashSet<u32>,
+    /// Refresh shares gathered for the key refresh in progress, by 0 based key id
+    pub refresh_shares: HashMap<u32, RefreshShares>,
+    /// Signer state with refreshed key shares, waiting on the coordinator's commit
+    pub refreshed_state: Option<SignerState>,
+    /// Scheme deciding how many nonces and signature shares our key ids cost
+    pub wsts_version: WstsVersion,
     pub network_private_key: Scalar,
     pub public_keys: PublicKeys,
+    /// DKG round our key shares come from, if one succeeded
+    pub key_dkg_id: Option<u64>,
+    /// Key shares replaced by a later DKG round by the round they come from. The outgoing
+    /// coordinator still signs the wallet handoff with them, so they are only dropped once a
+    /// coordinator asks for nonces of the current key shares
+    pub previous_keys: BTreeMap<u64, SignerState>,
+    /// Key shares a DKG round in progress replaces once it succeeds
+    pub replaced_key: Option<(u64, SignerState)>,
 }
 
 pub struct Signer {
@@ -112,6 +146,9 @@ impl StateMachine for SigningRound {
             States::DkgPrivateGather => prev_state == &States::DkgPrivateDistribute,
             States::SignGather => prev_state == &States::Idle,
             States::Signed => prev_state == &States::SignGather,
+            States::RefreshGather => {
+                prev_state == &States::Idle || prev_state == &States::RefreshGather
+            }
         };
         if accepted {
             info!("state change from {:?} to {:?}", prev_state, state);
@@ -128,21 +165,56 @@ impl StateMachine for SigningRound {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum DkgStatus {
     Success,
-    Failure(String),
+    Failure(DkgFailure),
+}
+
+/// Why a signer failed to end a DKG round
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum DkgFailure {
+    /// Private shares that failed to decrypt or verify, with proof for the coordinator to settle
+    BadPrivateShares(Vec<Complaint>),
+    /// A failure the signer cannot pin on another signer, such as failing to compute its key
+    /// shares from verified private shares
+    Error(String),
+}
+
+impl DkgStatus {
+    fn hash(&self, hasher: &mut Sha256) {
+        match self {
+            DkgStatus::Success => hasher.update([0]),
+            DkgStatus::Failure(DkgFailure::Error(reason)) => {
+                hasher.update([1]);
+                hasher.update(reason.as_bytes());
+            }
+            DkgStatus::Failure(DkgFailure::BadPrivateShares(complaints)) => {
+                hasher.update([2]);
+                for complaint in complaints {
+                    complaint.hash(hasher);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum MessageTypes {
     DkgBegin(DkgBegin),
-    DkgPrivateBegin(DkgBegin),
+    DkgPrivateBegin(DkgPrivateBegin),
     DkgEnd(DkgEnd),
     DkgPublicEnd(DkgEnd),
     DkgPublicShare(DkgPublicShare),
     DkgPrivateShares(DkgPrivateShares),
     NonceRequest(NonceRequest),
     NonceResponse(NonceResponse),
+    NonceBatchRequest(NonceBatchRequest),
+    NonceBatch(NonceBatch),
     SignShareRequest(SignatureShareRequest),
     SignShareResponse(SignatureShareResponse),
+    RefreshBegin(RefreshBegin),
+    RefreshShares(RefreshShares),
+    RefreshEnd(RefreshEnd),
+    RefreshCommit(RefreshCommit),
+    RefreshCommitAck(RefreshCommitAck),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -199,6 +271,28 @@ impl Signable for DkgBegin {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DkgPrivateBegin {
+    pub dkg_id: u64,
+    /// Signers whose public shares arrived in time, the only ones taking part in the rest of the round
+    pub signer_ids: Vec<u32>,
+    /// Key ids of those signers
+    pub key_ids: Vec<u32>,
+}
+
+impl Signable for DkgPrivateBegin {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("DKG_PRIVATE_BEGIN".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        for signer_id in &self.signer_ids {
+            hasher.update(signer_id.to_be_bytes());
+        }
+        for key_id in &self.key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DkgEnd {
     pub dkg_id: u64,
@@ -211,6 +305,108 @@ impl Signable for DkgEnd {
         hasher.update("DKG_END".as_bytes());
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.signer_id.to_be_bytes());
+        self.status.hash(hasher);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshBegin {
+    pub dkg_id: u64,
+    pub refresh_id: u64,
+    /// Key ids holding shares of the aggregate key, every one of which gets refreshed
+    pub key_ids: Vec<u32>,
+}
+
+impl Signable for RefreshBegin {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("REFRESH_BEGIN".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.refresh_id.to_be_bytes());
+        for key_id in &self.key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshShares {
+    pub dkg_id: u64,
+    pub refresh_id: u64,
+    /// Key id the shares come from, 0 based like `DkgPrivateShares::key_id`
+    pub key_id: u32,
+    /// Commitments to the coefficients of the refresh polynomial past its constant term, which
+    /// is zero so the shares still add up to the same aggregate key
+    pub commitments: Vec<Point>,
+    /// Encrypted shares of the refresh polynomial for each 0 based key id
+    pub private_shares: HashMap<u32, Vec<u8>>,
+}
+
+impl Signable for RefreshShares {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("REFRESH_SHARES".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.refresh_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
+        for commitment in &self.commitments {
+            hasher.update(commitment.compress().as_bytes());
+        }
+        let mut key_ids = self.private_shares.keys().collect::<Vec<&u32>>();
+        key_ids.sort();
+        for key_id in key_ids {
+            hasher.update(key_id.to_be_bytes());
+            hasher.update(&self.private_shares[key_id]);
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshEnd {
+    pub dkg_id: u64,
+    pub refresh_id: u64,
+    pub signer_id: u32,
+    pub status: DkgStatus,
+}
+
+impl Signable for RefreshEnd {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("REFRESH_END".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.refresh_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        self.status.hash(hasher);
+    }
+}
+
+/// Tells signers to switch to their refreshed key shares, once every signer refreshed them
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshCommit {
+    pub dkg_id: u64,
+    pub refresh_id: u64,
+}
+
+impl Signable for RefreshCommit {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("REFRESH_COMMIT".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.refresh_id.to_be_bytes());
+    }
+}
+
+/// Tells the coordinator a signer switched to its refreshed key shares, so it stops resending
+/// the commit
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RefreshCommitAck {
+    pub dkg_id: u64,
+    pub refresh_id: u64,
+    pub signer_id: u32,
+}
+
+impl Signable for RefreshCommitAck {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("REFRESH_COMMIT_ACK".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.refresh_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
     }
 }
 
@@ -219,6 +415,8 @@ pub struct NonceRequest {
     pub dkg_id: u64,
     pub sign_id: u64,
     pub sign_nonce_id: u64,
+    /// Number of messages to generate a set of nonces for
+    pub num_messages: u32,
 }
 
 impl Signable for NonceRequest {
@@ -227,6 +425,7 @@ impl Signable for NonceRequest {
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
         hasher.update(self.sign_nonce_id.to_be_bytes());
+        hasher.update(self.num_messages.to_be_bytes());
     }
 }
 
@@ -237,7 +436,9 @@ pub struct NonceResponse {
     pub sign_nonce_id: u64,
     pub signer_id: u32,
     pub key_ids: Vec<u32>,
-    pub nonces: Vec<PublicNonce>,
+    /// Nonces of each party of the signer for each requested message: one per key id under
+    /// WSTS v1, a single one under v2
+    pub nonces: Vec<Vec<PublicNonce>>,
 }
 
 impl Signable for NonceResponse {
@@ -252,9 +453,62 @@ impl Signable for NonceResponse {
             hasher.update(key_id.to_be_bytes());
         }
 
-        for nonce in &self.nonces {
-            hasher.update(nonce.D.compress().as_bytes());
-            hasher.update(nonce.E.compress().as_bytes());
+        for nonces in &self.nonces {
+            hasher.update((nonces.len() as u32).to_be_bytes());
+            for nonce in nonces {
+                hasher.update(nonce.D.compress().as_bytes());
+                hasher.update(nonce.E.compress().as_bytes());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NonceBatchRequest {
+    pub dkg_id: u64,
+    pub batch_id: u64,
+    /// Number of signing sessions to generate a set of nonces for
+    pub num_nonces: u32,
+}
+
+impl Signable for NonceBatchRequest {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("NONCE_BATCH_REQUEST".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.batch_id.to_be_bytes());
+        hasher.update(self.num_nonces.to_be_bytes());
+    }
+}
+
+/// Nonces a signer publishes ahead of time, so the coordinator can start signing sessions
+/// without asking for nonces first
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NonceBatch {
+    pub dkg_id: u64,
+    pub batch_id: u64,
+    pub signer_id: u32,
+    pub key_ids: Vec<u32>,
+    /// Nonces of each party of the signer for each signing session, each to be used once
+    pub nonces: Vec<Vec<PublicNonce>>,
+}
+
+impl Signable for NonceBatch {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("NONCE_BATCH".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.batch_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+
+        for key_id in &self.key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+
+        for nonces in &self.nonces {
+            hasher.update((nonces.len() as u32).to_be_bytes());
+            for nonce in nonces {
+                hasher.update(nonce.D.compress().as_bytes());
+                hasher.update(nonce.E.compress().as_bytes());
+            }
         }
     }
 }
@@ -264,8 +518,11 @@ pub struct SignatureShareRequest {
     pub dkg_id: u64,
     pub sign_id: u64,
     pub correlation_id: u64,
+    /// Nonces of every participating signer, whose nonces at index `i` sign `messages[i]`
     pub nonce_responses: Vec<NonceResponse>,
-    pub message: Vec<u8>,
+    pub messages: Vec<Vec<u8>>,
+    /// Sign for a taproot output or tapscript key instead of the untweaked aggregate key
+    pub taproot_tweak: Option<TaprootTweak>,
 }
 
 impl Signable for SignatureShareRequest {
@@ -279,7 +536,20 @@ impl Signable for SignatureShareRequest {
             nonce_response.hash(hasher);
         }
 
-        hasher.update(self.message.as_slice());
+        for message in &self.messages {
+            hasher.update((message.len() as u32).to_be_bytes());
+            hasher.update(message.as_slice());
+        }
+
+        if let Some(taproot_tweak) = &self.taproot_tweak {
+            hasher.update("TAPROOT_TWEAK".as_bytes());
+            if let Some(merkle_root) = &taproot_tweak.merkle_root {
+                hasher.update(merkle_root);
+            }
+            if taproot_tweak.script_path {
+                hasher.update("SCRIPT_PATH".as_bytes());
+            }
+        }
     }
 }
 
@@ -289,7 +559,8 @@ pub struct SignatureShareResponse {
     pub sign_id: u64,
     pub correlation_id: u64,
     pub signer_id: u32,
-    pub signature_shares: Vec<SignatureShare>,
+    /// Signature shares for each requested message, in request order
+    pub signature_shares: Vec<Vec<SignatureShare>>,
 }
 
 impl Signable for SignatureShareResponse {
@@ -300,9 +571,12 @@ impl Signable for SignatureShareResponse {
         hasher.update(self.correlation_id.to_be_bytes());
         hasher.update(self.signer_id.to_be_bytes());
 
-        for signature_share in &self.signature_shares {
-            hasher.update(signature_share.id.to_be_bytes());
-            hasher.update(signature_share.z_i.to_bytes());
+        for signature_shares in &self.signature_shares {
+            hasher.update((signature_shares.len() as u32).to_be_bytes());
+            for signature_share in signature_shares {
+                hasher.update(signature_share.id.to_be_bytes());
+                hasher.update(signature_share.z_i.to_bytes());
+            }
         }
     }
 }
@@ -337,9 +611,21 @@ impl SigningRound {
             state: States::Idle,
             commitments: BTreeMap::new(),
             shares: HashMap::new(),
+            dkg_key_ids: (1..=total_keys).collect(),
             public_nonces: vec![],
+            nonce_states: HashMap::new(),
+            batch_nonce_states: HashMap::new(),
+            refresh_id: 0,
+            pending_refresh_id: 0,
+            refresh_key_ids: HashSet::new(),
+            refresh_shares: HashMap::new(),
+            refreshed_state: None,
+            wsts_version: WstsVersion::default(),
             network_private_key,
             public_keys,
+            key_dkg_id: None,
+            previous_keys: BTreeMap::new(),
+            replaced_key: None,
         }
     }
 
@@ -348,14 +634,60 @@ impl SigningRound {
         self.dkg_public_id = 0;
         self.commitments.clear();
         self.shares.clear();
+        self.dkg_key_ids = (1..=self.total_keys).collect();
         self.public_nonces.clear();
+        self.nonce_states.clear();
+        self.batch_nonce_states.clear();
+        self.refresh_shares.clear();
+        self.refreshed_state = None;
         self.signer.frost_signer.reset_polys(rng);
     }
 
+    /// State to persist across restarts: the polynomials and key shares of the last DKG round
+    pub fn saved_state(&self) -> SavedState {
+        SavedState {
+            dkg_id: self.dkg_id,
+            sign_id: self.sign_id,
+            refresh_id: self.refresh_id,
+            signer: self.signer.frost_signer.save(),
+            key_dkg_id: self.key_dkg_id,
+            previous_keys: self.previous_keys.clone(),
+            pending_refresh_id: self.pending_refresh_id,
+            refreshed_state: self.refreshed_state.clone(),
+        }
+    }
+
+    /// Resume from a saved state, which must belong to the same signer and key ids
+    pub fn restore(&mut self, saved_state: SavedState) -> Result<(), Error> {
+        self.signer.frost_signer = self.load_signer(&saved_state.signer)?;
+        self.dkg_id = saved_state.dkg_id;
+        self.sign_id = saved_state.sign_id;
+        self.refresh_id = saved_state.refresh_id;
+        self.key_dkg_id = saved_state.key_dkg_id;
+        self.previous_keys = saved_state.previous_keys;
+        self.pending_refresh_id = saved_state.pending_refresh_id;
+        self.refreshed_state = saved_state.refreshed_state;
+        Ok(())
+    }
+
+    /// Load key shares of our own key ids. They are held per key id as dealt by DKG under
+    /// either WSTS version, the v2 scheme building its single party from them.
+    fn load_signer(&self, state: &SignerState) -> Result<v1::Signer, Error> {
+        let frost_signer = v1::Signer::load(state);
+        if frost_signer.get_id() != self.signer.frost_signer.get_id()
+            || frost_signer.get_key_ids() != self.signer.frost_signer.get_key_ids()
+        {
+            return Err(Error::SavedStateMismatch(frost_signer.get_id()));
+        }
+        Ok(frost_signer)
+    }
+
     pub fn process(&mut self, message: MessageTypes) -> Result<Vec<MessageTypes>, Error> {
         let out_msgs = match message {
             MessageTypes::DkgBegin(dkg_begin) => self.dkg_begin(dkg_begin),
-            MessageTypes::DkgPrivateBegin(_) => self.dkg_private_begin(),
+            MessageTypes::DkgPrivateBegin(dkg_private_begin) => {
+                self.dkg_private_begin(dkg_private_begin)
+            }
             MessageTypes::DkgPublicShare(dkg_public_shares) => {
                 self.dkg_public_share(dkg_public_shares)
             }
@@ -366,6 +698,12 @@ impl SigningRound {
                 self.sign_share_request(sign_share_request)
             }
             MessageTypes::NonceRequest(nonce_request) => self.nonce_request(nonce_request),
+            MessageTypes::NonceBatchRequest(nonce_batch_request) => {
+                self.nonce_batch_request(nonce_batch_request)
+            }
+            MessageTypes::RefreshBegin(refresh_begin) => self.refresh_begin(refresh_begin),
+            MessageTypes::RefreshShares(refresh_shares) => self.refresh_shares(refresh_shares),
+            MessageTypes::RefreshCommit(refresh_commit) => self.refresh_commit(refresh_commit),
             _ => Ok(vec![]), // TODO
         };
 
@@ -388,6 +726,14 @@ impl SigningRound {
                     let dkg_end_msgs = self.dkg_ended()?;
                     out.push(dkg_end_msgs);
                     self.move_to(States::Idle)?;
+                } else if self.can_refresh_end() {
+                    debug!(
+                        "can_refresh_end==true. refresh shares {}",
+                        self.refresh_shares.len()
+                    );
+                    let refresh_end_msg = self.refresh_ended()?;
+                    out.push(refresh_end_msg);
+                    self.move_to(States::Idle)?;
                 }
                 Ok(out)
             }
@@ -416,7 +762,7 @@ impl SigningRound {
 
         // go through private shares, and decrypt any for owned keys, leaving the rest as zero scalars
         let key_ids: HashSet<u32> = self.signer.frost_signer.get_key_ids().into_iter().collect();
-        let mut invalid_dkg_private_shares = Vec::new();
+        let mut complaints = Vec::new();
 
         for (src_key_id, encrypted_shares) in &self.shares {
             let mut decrypted_key_shares = HashMap::new();
@@ -433,20 +779,39 @@ impl SigningRound {
                     let shared_secret =
                         make_shared_secret(&self.network_private_key, &src_public_key);
 
-                    match decrypt(&shared_secret, private_share) {
+                    let reason = match decrypt(&shared_secret, private_share) {
                         Ok(plain) => match Scalar::try_from(&plain[..]) {
-                            Ok(s) => {
-                                decrypted_key_shares.insert(*dst_key_id, s);
-                            }
+                            Ok(s) => match self.commitments.get(&(src_key_id + 1)) {
+                                Some(public_share)
+                                    if !verify_share(&s, *dst_key_id, public_share) =>
+                                {
+                                    warn!("Dkg private share from key_id {} to key_id {} does not match its public share", src_key_id, dst_key_id);
+                                    Some(ComplaintReason::BadShare)
+                                }
+                                _ => {
+                                    decrypted_key_shares.insert(*dst_key_id, s);
+                                    None
+                                }
+                            },
                             Err(e) => {
                                 warn!("Failed to parse Scalar for dkg private share from key_id {} to key_id {}: {:?}", src_key_id, dst_key_id, e);
-                                invalid_dkg_private_shares.push(*src_key_id);
+                                Some(ComplaintReason::InvalidScalar)
                             }
                         },
                         Err(e) => {
                             warn!("Failed to decrypt dkg private share from key_id {} to key_id {}: {:?}", src_key_id, dst_key_id, e);
-                            invalid_dkg_private_shares.push(*src_key_id);
+                            Some(ComplaintReason::DecryptionFailed)
                         }
+                    };
+                    if let Some(reason) = reason {
+                        complaints.push(Complaint::new(
+                            &self.network_private_key,
+                            &src_public_key,
+                            *src_key_id,
+                            *dst_key_id,
+                            reason,
+                            &mut OsRng,
+                        ));
                     }
                 } else {
                     decrypted_key_shares.insert(*dst_key_id, Scalar::new());
@@ -456,28 +821,34 @@ impl SigningRound {
             decrypted_shares.insert(*src_key_id, decrypted_key_shares);
         }
 
-        let dkg_end = if invalid_dkg_private_shares.is_empty() {
+        let dkg_end = if complaints.is_empty() {
             match self
                 .signer
                 .frost_signer
                 .compute_secrets(&decrypted_shares, &polys)
             {
-                Ok(()) => DkgEnd {
-                    dkg_id: self.dkg_id,
-                    signer_id: self.signer.signer_id,
-                    status: DkgStatus::Success,
-                },
+                Ok(()) => {
+                    if let Some((dkg_id, state)) = self.replaced_key.take() {
+                        self.previous_keys.insert(dkg_id, state);
+                    }
+                    self.key_dkg_id = Some(self.dkg_id);
+                    DkgEnd {
+                        dkg_id: self.dkg_id,
+                        signer_id: self.signer.signer_id,
+                        status: DkgStatus::Success,
+                    }
+                }
                 Err(dkg_error_map) => DkgEnd {
                     dkg_id: self.dkg_id,
                     signer_id: self.signer.signer_id,
-                    status: DkgStatus::Failure(format!("{:?}", dkg_error_map)),
+                    status: DkgStatus::Failure(DkgFailure::Error(format!("{:?}", dkg_error_map))),
                 },
             }
         } else {
             DkgEnd {
                 dkg_id: self.dkg_id,
                 signer_id: self.signer.signer_id,
-                status: DkgStatus::Failure(format!("{:?}", invalid_dkg_private_shares)),
+                status: DkgStatus::Failure(DkgFailure::BadPrivateShares(complaints)),
             }
         };
 
@@ -507,8 +878,18 @@ impl SigningRound {
             self.shares.len()
         );
         self.state == States::DkgPrivateGather
-            && self.commitments.len() == usize::try_from(self.total_keys).unwrap()
-            && self.shares.len() == usize::try_from(self.total_keys).unwrap()
+            && self.commitments.len() == self.dkg_key_ids.len()
+            && self.shares.len() == self.dkg_key_ids.len()
+    }
+
+    /// Signer holding the key shares of a DKG round that a later round replaced, or none to use
+    /// the current key shares. Asking for the current ones means the handoff to them completed.
+    fn previous_signer(&mut self, dkg_id: u64) -> Option<v1::Signer> {
+        if Some(dkg_id) == self.key_dkg_id {
+            self.previous_keys.clear();
+            return None;
+        }
+        self.previous_keys.get(&dkg_id).map(v1::Signer::load)
     }
 
     fn nonce_request(&mut self, nonce_request: NonceRequest) -> Result<Vec<MessageTypes>, Error> {
@@ -516,7 +897,24 @@ impl SigningRound {
         let mut msgs = vec![];
         let signer_id = self.signer.signer_id;
         let key_ids = self.signer.frost_signer.get_key_ids();
-        let nonces = self.signer.frost_signer.gen_nonces(&mut rng);
+
+        // Nonces handed out for an earlier signing session will never be asked to sign
+        if nonce_request.sign_id != self.sign_id {
+            self.nonce_states.clear();
+            self.sign_id = nonce_request.sign_id;
+        }
+        let scheme = self.wsts_version.scheme();
+        let mut previous_signer = self.previous_signer(nonce_request.dkg_id);
+        let frost_signer = previous_signer
+            .as_mut()
+            .unwrap_or(&mut self.signer.frost_signer);
+        let nonces = (0..nonce_request.num_messages)
+            .map(|_| {
+                let (nonces, state) = scheme.gen_nonces(frost_signer, &mut rng);
+                self.nonce_states.insert(nonce_key(&nonces), state);
+                nonces
+            })
+            .collect();
 
         let response = NonceResponse {
             dkg_id: nonce_request.dkg_id,
@@ -530,14 +928,49 @@ impl SigningRound {
         let response = MessageTypes::NonceResponse(response);
 
         info!(
-            "nonce request with dkg_id {:?}. response sent from signer_id {}",
-            nonce_request.dkg_id, signer_id
+            "nonce request with dkg_id {:?} for {} messages. response sent from signer_id {}",
+            nonce_request.dkg_id, nonce_request.num_messages, signer_id
         );
         msgs.push(response);
 
         Ok(msgs)
     }
 
+    fn nonce_batch_request(
+        &mut self,
+        nonce_batch_request: NonceBatchRequest,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        let mut rng = OsRng;
+        let signer_id = self.signer.signer_id;
+
+        // The coordinator replaces its pool of our nonces with the new batch
+        self.batch_nonce_states.clear();
+        let scheme = self.wsts_version.scheme();
+        let mut previous_signer = self.previous_signer(nonce_batch_request.dkg_id);
+        let frost_signer = previous_signer
+            .as_mut()
+            .unwrap_or(&mut self.signer.frost_signer);
+        let nonces = (0..nonce_batch_request.num_nonces)
+            .map(|_| {
+                let (nonces, state) = scheme.gen_nonces(frost_signer, &mut rng);
+                self.batch_nonce_states.insert(nonce_key(&nonces), state);
+                nonces
+            })
+            .collect();
+
+        info!(
+            "nonce batch #{} of {} nonces sent from signer_id {}",
+            nonce_batch_request.batch_id, nonce_batch_request.num_nonces, signer_id
+        );
+        Ok(vec![MessageTypes::NonceBatch(NonceBatch {
+            dkg_id: nonce_batch_request.dkg_id,
+            batch_id: nonce_batch_request.batch_id,
+            signer_id,
+            key_ids: self.signer.frost_signer.get_key_ids(),
+            nonces,
+        })])
+    }
+
     fn sign_share_request(
         &mut self,
         sign_request: SignatureShareRequest,
@@ -552,24 +985,41 @@ impl SigningRound {
 
         info!("Got SignatureShareRequest for signer_ids {:?}", signer_ids);
 
-        for signer_id in &signer_ids {
+        for (position, signer_id) in signer_ids.iter().enumerate() {
             if *signer_id == self.signer.signer_id {
-                let key_ids: Vec<u32> = sign_request
-                    .nonce_responses
-                    .iter()
-                    .flat_map(|nr| nr.key_ids.iter().copied())
-                    .collect::<Vec<u32>>();
-                let nonces = sign_request
-                    .nonce_responses
-                    .iter()
-                    .flat_map(|nr| nr.nonces.clone())
-                    .collect::<Vec<PublicNonce>>();
-                let signature_shares = self.signer.frost_signer.sign(
-                    &sign_request.message,
-                    &signer_ids,
-                    &key_ids,
-                    &nonces,
-                );
+                let mut signature_shares = vec![];
+                for (index, message) in sign_request.messages.iter().enumerate() {
+                    let nonces = sign_request
+                        .nonce_responses
+                        .iter()
+                        .map(|nr| nr.nonces.get(index).cloned())
+                        .collect::<Option<Vec<Vec<PublicNonce>>>>()
+                        .ok_or(Error::InvalidNonceResponse)?;
+
+                    // Sign with the state that generated our nonces for this message
+                    let key = nonce_key(&nonces[position]);
+                    let Some(state) = self
+                        .nonce_states
+                        .remove(&key)
+                        .or_else(|| self.batch_nonce_states.remove(&key))
+                    else {
+                        warn!(
+                            "SignShareRequest for message {} uses nonces we did not hand out",
+                            index
+                        );
+                        return Err(Error::InvalidNonceResponse);
+                    };
+                    let state = match &sign_request.taproot_tweak {
+                        Some(taproot_tweak) => taproot_tweak.tweak_signer_state(&state),
+                        None => state,
+                    };
+                    signature_shares.push(self.wsts_version.scheme().sign(
+                        &state,
+                        message,
+                        &sign_request.nonce_responses,
+                        index,
+                    ));
+                }
 
                 let response = SignatureShareResponse {
                     dkg_id: sign_request.dkg_id,
@@ -597,6 +1047,12 @@ impl SigningRound {
     fn dkg_begin(&mut self, dkg_begin: DkgBegin) -> Result<Vec<MessageTypes>, Error> {
         let mut rng = OsRng;
 
+        // Rounds retried after a failure replace the same key shares
+        if let Some(key_dkg_id) = self.key_dkg_id {
+            if self.replaced_key.as_ref().map(|(dkg_id, _)| *dkg_id) != Some(key_dkg_id) {
+                self.replaced_key = Some((key_dkg_id, self.signer.frost_signer.save()));
+            }
+        }
         self.reset(dkg_begin.dkg_id, &mut rng);
         self.move_to(States::DkgPublicDistribute)?;
 
@@ -633,7 +1089,34 @@ impl SigningRound {
         Ok(msgs)
     }
 
-    fn dkg_private_begin(&mut self) -> Result<Vec<MessageTypes>, Error> {
+    fn dkg_private_begin(
+        &mut self,
+        dkg_private_begin: DkgPrivateBegin,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        if !dkg_private_begin
+            .signer_ids
+            .contains(&self.signer.signer_id)
+        {
+            warn!(
+                "signer {} was dropped from DKG round #{}",
+                self.signer.signer_id, dkg_private_begin.dkg_id
+            );
+            self.move_to(States::Idle)?;
+            return Ok(vec![]);
+        }
+        // Key ids of dropped signers (1-based, unlike the 0-based key ids of private shares)
+        // contribute nothing to the key
+        self.dkg_key_ids = dkg_private_begin.key_ids.into_iter().collect();
+        let dkg_key_ids = &self.dkg_key_ids;
+        self.commitments
+            .retain(|party_id, _| dkg_key_ids.contains(party_id));
+        self.shares
+            .retain(|key_id, _| dkg_key_ids.contains(&(key_id + 1)));
+        // Signers still missing public shares of dropped signers never ended the public phase
+        if self.state == States::DkgPublicGather {
+            self.move_to(States::DkgPrivateDistribute)?;
+        }
+
         let mut rng = OsRng;
         let mut msgs = vec![];
         for (key_id, private_shares) in &self.signer.frost_signer.get_shares() {
@@ -673,6 +1156,209 @@ impl SigningRound {
         Ok(msgs)
     }
 
+    /// Send each key id a share of a random polynomial with a zero constant term for each of
+    /// our keys, which re-randomizes the key shares without changing the aggregate key
+    fn refresh_begin(&mut self, refresh_begin: RefreshBegin) -> Result<Vec<MessageTypes>, Error> {
+        let key_ids = self.signer.frost_signer.get_key_ids();
+        if !key_ids
+            .iter()
+            .any(|key_id| refresh_begin.key_ids.contains(&(key_id + 1)))
+        {
+            debug!(
+                "RefreshBegin #{} does not refresh our keys",
+                refresh_begin.refresh_id
+            );
+            return Ok(vec![]);
+        }
+        self.move_to(States::RefreshGather)?;
+        self.pending_refresh_id = refresh_begin.refresh_id;
+        self.refresh_key_ids = refresh_begin.key_ids.into_iter().collect();
+        self.refresh_shares.clear();
+        self.refreshed_state = None;
+
+        let mut rng = OsRng;
+        let mut msgs = vec![];
+        for key_id in key_ids {
+            let coefficients = (1..self.threshold)
+                .map(|_| Scalar::random(&mut rng))
+                .collect::<Vec<Scalar>>();
+            let mut private_shares = HashMap::new();
+            for dst_key_id in &self.refresh_key_ids {
+                let share = refresh_share(&coefficients, dst_key_id - 1);
+                let compressed = Compressed::from(self.public_keys.key_ids[dst_key_id].to_bytes());
+                let dst_public_key = Point::try_from(&compressed).unwrap();
+                let shared_secret = make_shared_secret(&self.network_private_key, &dst_public_key);
+                let encrypted_share = encrypt(&shared_secret, &share.to_bytes(), &mut rng).unwrap();
+                private_shares.insert(dst_key_id - 1, encrypted_share);
+            }
+            info!(
+                "signer {} sending refresh shares for key_id #{}",
+                self.signer.signer_id, key_id
+            );
+            msgs.push(MessageTypes::RefreshShares(RefreshShares {
+                dkg_id: self.dkg_id,
+                refresh_id: self.pending_refresh_id,
+                key_id,
+                commitments: coefficients.iter().map(|a| Point::from(*a)).collect(),
+                private_shares,
+            }));
+        }
+        Ok(msgs)
+    }
+
+    fn refresh_shares(
+        &mut self,
+        refresh_shares: RefreshShares,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        if self.state != States::RefreshGather
+            || refresh_shares.refresh_id != self.pending_refresh_id
+            || !self.refresh_key_ids.contains(&(refresh_shares.key_id + 1))
+        {
+            debug!(
+                "ignoring RefreshShares #{} from key #{}",
+                refresh_shares.refresh_id, refresh_shares.key_id
+            );
+            return Ok(vec![]);
+        }
+        self.refresh_shares
+            .insert(refresh_shares.key_id, refresh_shares);
+        info!(
+            "received RefreshShares {}/{}",
+            self.refresh_shares.len(),
+            self.refresh_key_ids.len()
+        );
+        Ok(vec![])
+    }
+
+    fn can_refresh_end(&self) -> bool {
+        self.state == States::RefreshGather
+            && self.refresh_shares.len() == self.refresh_key_ids.len()
+    }
+
+    /// Add up the refresh shares for our keys into refreshed key shares, kept until the
+    /// coordinator commits the refresh
+    fn refresh_ended(&mut self) -> Result<MessageTypes, Error> {
+        let key_ids = self.signer.frost_signer.get_key_ids();
+        let mut deltas: HashMap<u32, Scalar> = key_ids
+            .iter()
+            .map(|key_id| (*key_id, Scalar::new()))
+            .collect();
+        let mut bad_key_ids = vec![];
+
+        for (src_key_id, refresh_shares) in &self.refresh_shares {
+            if refresh_shares.commitments.len() + 1 != usize::try_from(self.threshold).unwrap() {
+                warn!(
+                    "Refresh polynomial of key_id {} has the wrong degree",
+                    src_key_id
+                );
+                bad_key_ids.push(*src_key_id);
+                continue;
+            }
+            let compressed =
+                Compressed::from(self.public_keys.key_ids[&(src_key_id + 1)].to_bytes());
+            let src_public_key = Point::try_from(&compressed).unwrap();
+            let shared_secret = make_shared_secret(&self.network_private_key, &src_public_key);
+
+            for key_id in &key_ids {
+                let share = refresh_shares
+                    .private_shares
+                    .get(key_id)
+                    .and_then(|encrypted_share| decrypt(&shared_secret, encrypted_share).ok())
+                    .and_then(|plain| Scalar::try_from(&plain[..]).ok())
+                    .filter(|share| {
+                        Point::from(*share)
+                            == &Scalar::from(key_id + 1)
+                                * &eval_commitments(&refresh_shares.commitments, *key_id)
+                    });
+                match (share, deltas.get_mut(key_id)) {
+                    (Some(share), Some(delta)) => *delta = *delta + share,
+                    _ => {
+                        warn!(
+                            "Bad refresh share from key_id {} to key_id {}",
+                            src_key_id, key_id
+                        );
+                        bad_key_ids.push(*src_key_id);
+                    }
+                }
+            }
+        }
+        self.refresh_shares.clear();
+
+        let status = if bad_key_ids.is_empty() {
+            let mut state = self.signer.frost_signer.save();
+            for (_, party) in state.parties.iter_mut() {
+                for (key_id, private_key) in party.private_keys.iter_mut() {
+                    if let Some(delta) = deltas.get(&*key_id) {
+                        *private_key = *private_key + *delta;
+                    }
+                }
+            }
+            self.refreshed_state = Some(state);
+            DkgStatus::Success
+        } else {
+            bad_key_ids.sort();
+            bad_key_ids.dedup();
+            DkgStatus::Failure(DkgFailure::Error(format!(
+                "Bad refresh shares from key ids {:?}",
+                bad_key_ids
+            )))
+        };
+        info!(
+            "REFRESH_END round #{} signer_id {}",
+            self.pending_refresh_id, self.signer.signer_id
+        );
+        Ok(MessageTypes::RefreshEnd(RefreshEnd {
+            dkg_id: self.dkg_id,
+            refresh_id: self.pending_refresh_id,
+            signer_id: self.signer.signer_id,
+            status,
+        }))
+    }
+
+    fn refresh_commit(
+        &mut self,
+        refresh_commit: RefreshCommit,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        let ack = MessageTypes::RefreshCommitAck(RefreshCommitAck {
+            dkg_id: refresh_commit.dkg_id,
+            refresh_id: refresh_commit.refresh_id,
+            signer_id: self.signer.signer_id,
+        });
+        if refresh_commit.refresh_id != self.pending_refresh_id {
+            debug!(
+                "ignoring RefreshCommit #{} while refresh #{} is pending",
+                refresh_commit.refresh_id, self.pending_refresh_id
+            );
+            return Ok(vec![]);
+        }
+        let Some(state) = self.refreshed_state.take() else {
+            // The coordinator resends the commit until every signer acknowledges it
+            if refresh_commit.refresh_id == self.refresh_id {
+                debug!(
+                    "RefreshCommit #{} already applied, acknowledging it again",
+                    refresh_commit.refresh_id
+                );
+                return Ok(vec![ack]);
+            }
+            warn!(
+                "RefreshCommit #{} without refreshed key shares",
+                refresh_commit.refresh_id
+            );
+            return Ok(vec![]);
+        };
+        self.signer.frost_signer = self.load_signer(&state)?;
+        self.refresh_id = refresh_commit.refresh_id;
+        // Nonces handed out before the refresh sign with the old key shares
+        self.public_nonces.clear();
+        self.nonce_states.clear();
+        self.batch_nonce_states.clear();
+        info!(
+            "signer {} switched to key shares of refresh #{}",
+            self.signer.signer_id, self.refresh_id
+        );
+        Ok(vec![ack])
+    }
+
     fn dkg_public_share(
         &mut self,
         dkg_public_share: DkgPublicShare,
@@ -692,6 +1378,13 @@ impl SigningRound {
         &mut self,
         dkg_private_shares: DkgPrivateShares,
     ) -> Result<Vec<MessageTypes>, Error> {
+        if !self.dkg_key_ids.contains(&(dkg_private_shares.key_id + 1)) {
+            warn!(
+                "ignoring DkgPrivateShares from dropped key #{}",
+                dkg_private_shares.key_id
+            );
+            return Ok(vec![]);
+        }
         let shares_clone = dkg_private_shares.private_shares.clone();
         self.shares
             .insert(dkg_private_shares.key_id, dkg_private_shares.private_shares);
@@ -706,6 +1399,27 @@ impl SigningRound {
     }
 }
 
+/// Share of the refresh polynomial with the given coefficients past its zero constant term for
+/// the 0 based `key_id`
+fn refresh_share(coefficients: &[Scalar], key_id: u32) -> Scalar {
+    let x = Scalar::from(key_id + 1);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::new(), |acc, a| acc * x + *a)
+        * x
+}
+
+/// Identifies the signer state that generated a set of nonces
+fn nonce_key(nonces: &[PublicNonce]) -> Vec<u8> {
+    let mut key = vec![];
+    for nonce in nonces {
+        key.extend_from_slice(nonce.D.compress().as_bytes());
+        key.extend_from_slice(nonce.E.compress().as_bytes());
+    }
+    key
+}
+
 impl From<&FrostSigner> for SigningRound {
     fn from(signer: &FrostSigner) -> Self {
         let signer_id = signer.signer_id;
@@ -743,23 +1457,50 @@ impl From<&FrostSigner> for SigningRound {
             state: States::Idle,
             commitments: BTreeMap::new(),
             shares: HashMap::new(),
+            dkg_key_ids: (1..=signer.config.total_keys).collect(),
             public_nonces: vec![],
+            nonce_states: HashMap::new(),
+            batch_nonce_states: HashMap::new(),
+            refresh_id: 0,
+            pending_refresh_id: 0,
+            refresh_key_ids: HashSet::new(),
+            refresh_shares: HashMap::new(),
+            refreshed_state: None,
+            wsts_version: signer.config.wsts_version,
             network_private_key,
             public_keys,
+            key_dkg_id: None,
+            previous_keys: BTreeMap::new(),
+            replaced_key: None,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use hashbrown::HashMap;
+    use hashbrown::{HashMap, HashSet};
+    use p256k1::ecdsa;
     use rand_core::{CryptoRng, OsRng, RngCore};
-    use wsts::{common::PolyCommitment, schnorr::ID, Scalar};
+    use wsts::{
+        bip340::SchnorrProof,
+        common::{PolyCommitment, Signature},
+        compute,
+        schnorr::ID,
+        traits::{Signer, SignerState},
+        v1, Point, Scalar,
+    };
 
+    use crate::complaint::{Blame, ComplaintReason};
+    use crate::config::PublicKeys;
+    use crate::scheme::WstsVersion;
     use crate::signing_round::{
-        DkgPrivateShares, DkgPublicShare, DkgStatus, MessageTypes, SigningRound,
+        DkgBegin, DkgEnd, DkgFailure, DkgPrivateBegin, DkgPrivateShares, DkgPublicShare, DkgStatus,
+        Error, MessageTypes, NonceBatchRequest, NonceRequest, NonceResponse, RefreshBegin,
+        RefreshCommit, RefreshCommitAck, RefreshEnd, SignatureShareRequest, SigningRound,
     };
     use crate::state_machine::States;
+    use crate::taproot::TaprootTweak;
+    use crate::util::{encrypt, make_shared_secret};
 
     fn get_rng() -> impl RngCore + CryptoRng {
         let rnd = OsRng;
@@ -852,7 +1593,7 @@ mod test {
         match signing_round.dkg_ended() {
             Ok(dkg_end) => match dkg_end {
                 MessageTypes::DkgEnd(dkg_end) => match dkg_end.status {
-                    DkgStatus::Failure(_) => assert!(true),
+                    DkgStatus::Failure(DkgFailure::Error(_)) => assert!(true),
                     _ => assert!(false),
                 },
                 _ => assert!(false),
@@ -860,4 +1601,724 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn sign_share_request_should_sign_every_message() {
+        let mut rnd = get_rng();
+        let mut signing_round =
+            SigningRound::new(1, 1, 1, 1, vec![0], Default::default(), Default::default());
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+
+        let nonce_request = NonceRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            sign_nonce_id: 1,
+            num_messages: 2,
+        };
+        let MessageTypes::NonceResponse(nonce_response) = signing_round
+            .nonce_request(nonce_request)
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a NonceResponse");
+        };
+        // A separate set of nonces for each message
+        assert_eq!(nonce_response.nonces.len(), 2);
+        assert_eq!(signing_round.nonce_states.len(), 2);
+
+        let messages = vec![b"first".to_vec(), b"second".to_vec()];
+        let sign_request = SignatureShareRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            correlation_id: 0,
+            nonce_responses: vec![nonce_response.clone()],
+            messages: messages.clone(),
+            taproot_tweak: None,
+        };
+        let MessageTypes::SignShareResponse(response) = signing_round
+            .sign_share_request(sign_request.clone())
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a SignShareResponse");
+        };
+        assert_eq!(response.signature_shares.len(), 2);
+
+        let public_key = polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0]);
+        let mut aggregator = v1::SignatureAggregator::new(1, 1, polys).unwrap();
+        for (index, message) in messages.iter().enumerate() {
+            let signature = aggregator
+                .sign(
+                    message,
+                    &nonce_response.nonces[index],
+                    &response.signature_shares[index],
+                )
+                .unwrap();
+            assert!(signature.verify(&public_key, message));
+        }
+
+        // Nonces are never used to sign twice
+        assert!(signing_round.sign_share_request(sign_request).is_err());
+    }
+
+    #[test]
+    fn sign_share_request_should_sign_with_batch_nonces_once() {
+        let mut rnd = get_rng();
+        let mut signing_round =
+            SigningRound::new(1, 1, 1, 1, vec![0], Default::default(), Default::default());
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+
+        let nonce_batch_request = NonceBatchRequest {
+            dkg_id: 0,
+            batch_id: 1,
+            num_nonces: 3,
+        };
+        let MessageTypes::NonceBatch(batch) = signing_round
+            .process(MessageTypes::NonceBatchRequest(nonce_batch_request.clone()))
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a NonceBatch");
+        };
+        assert_eq!(batch.nonces.len(), 3);
+        assert_eq!(signing_round.batch_nonce_states.len(), 3);
+
+        // Batch nonces outlive the signing sessions that request nonces of their own
+        signing_round
+            .nonce_request(NonceRequest {
+                dkg_id: 0,
+                sign_id: 2,
+                sign_nonce_id: 1,
+                num_messages: 1,
+            })
+            .unwrap();
+        assert_eq!(signing_round.batch_nonce_states.len(), 3);
+
+        let message = b"preprocessed".to_vec();
+        let sign_request = |nonces| SignatureShareRequest {
+            dkg_id: 0,
+            sign_id: 3,
+            correlation_id: 0,
+            nonce_responses: vec![NonceResponse {
+                dkg_id: 0,
+                sign_id: 3,
+                sign_nonce_id: 1,
+                signer_id: batch.signer_id,
+                key_ids: batch.key_ids.clone(),
+                nonces: vec![nonces],
+            }],
+            messages: vec![message.clone()],
+            taproot_tweak: None,
+        };
+        let MessageTypes::SignShareResponse(response) = signing_round
+            .sign_share_request(sign_request(batch.nonces[1].clone()))
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a SignShareResponse");
+        };
+
+        let public_key = polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0]);
+        let signature = v1::SignatureAggregator::new(1, 1, polys)
+            .unwrap()
+            .sign(&message, &batch.nonces[1], &response.signature_shares[0])
+            .unwrap();
+        assert!(signature.verify(&public_key, &message));
+
+        // Nonces are never used to sign twice
+        assert!(signing_round
+            .sign_share_request(sign_request(batch.nonces[1].clone()))
+            .is_err());
+        assert_eq!(signing_round.batch_nonce_states.len(), 2);
+
+        // A new batch replaces the nonces of the previous one
+        signing_round
+            .process(MessageTypes::NonceBatchRequest(NonceBatchRequest {
+                batch_id: 2,
+                ..nonce_batch_request
+            }))
+            .unwrap();
+        assert_eq!(signing_round.batch_nonce_states.len(), 3);
+        assert!(signing_round
+            .sign_share_request(sign_request(batch.nonces[0].clone()))
+            .is_err());
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn sign_share_request_should_send_one_share_per_signer_under_v2() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(
+            2,
+            1,
+            2,
+            1,
+            vec![0, 1],
+            Default::default(),
+            Default::default(),
+        );
+        signing_round.wsts_version = WstsVersion::V2;
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+
+        let nonce_request = NonceRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            sign_nonce_id: 1,
+            num_messages: 1,
+        };
+        let MessageTypes::NonceResponse(nonce_response) = signing_round
+            .nonce_request(nonce_request)
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a NonceResponse");
+        };
+        // A single nonce covers both key ids
+        assert_eq!(nonce_response.nonces[0].len(), 1);
+
+        let message = b"weighted".to_vec();
+        let sign_request = SignatureShareRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            correlation_id: 0,
+            nonce_responses: vec![nonce_response.clone()],
+            messages: vec![message.clone()],
+            taproot_tweak: None,
+        };
+        let MessageTypes::SignShareResponse(response) = signing_round
+            .sign_share_request(sign_request)
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a SignShareResponse");
+        };
+        assert_eq!(response.signature_shares[0].len(), 1);
+
+        let public_key = polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0]);
+        let (_, R) = compute::intermediate(&message, &[1], &nonce_response.nonces[0]);
+        let signature = Signature {
+            R,
+            z: response.signature_shares[0][0].z_i,
+        };
+        assert!(signature.verify(&public_key, &message));
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn sign_share_request_should_sign_for_taproot_output_key() {
+        let mut rnd = get_rng();
+        let mut signing_round =
+            SigningRound::new(1, 1, 1, 1, vec![0], Default::default(), Default::default());
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+        let public_key = polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0]);
+
+        // Like the coordinator, retry until the aggregate nonce has an even y coordinate
+        let message = b"taproot".to_vec();
+        let nonce_response = loop {
+            let nonce_request = NonceRequest {
+                dkg_id: 0,
+                sign_id: 1,
+                sign_nonce_id: 1,
+                num_messages: 1,
+            };
+            let MessageTypes::NonceResponse(nonce_response) = signing_round
+                .nonce_request(nonce_request)
+                .unwrap()
+                .remove(0)
+            else {
+                panic!("Expected a NonceResponse");
+            };
+            let (_, R) =
+                compute::intermediate(&message, &nonce_response.key_ids, &nonce_response.nonces[0]);
+            if R.has_even_y() {
+                break nonce_response;
+            }
+        };
+
+        let taproot_tweak = TaprootTweak::new(Some([1; 32]));
+        let sign_request = SignatureShareRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            correlation_id: 0,
+            nonce_responses: vec![nonce_response.clone()],
+            messages: vec![message.clone()],
+            taproot_tweak: Some(taproot_tweak),
+        };
+        let MessageTypes::SignShareResponse(response) = signing_round
+            .sign_share_request(sign_request)
+            .unwrap()
+            .remove(0)
+        else {
+            panic!("Expected a SignShareResponse");
+        };
+
+        let (_, R) =
+            compute::intermediate(&message, &nonce_response.key_ids, &nonce_response.nonces[0]);
+        let z = response.signature_shares[0]
+            .iter()
+            .fold(Scalar::new(), |z, share| z + share.z_i);
+        let proof = SchnorrProof::new(&Signature { R, z }).unwrap();
+        assert!(proof.verify(&taproot_tweak.output_key(&public_key).x(), &message));
+        assert!(!proof.verify(&public_key.x(), &message));
+    }
+
+    #[test]
+    fn restore_should_resume_key_shares_of_same_signer() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(
+            1,
+            1,
+            2,
+            1,
+            vec![0, 1],
+            Default::default(),
+            Default::default(),
+        );
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+        signing_round.dkg_id = 5;
+        signing_round.sign_id = 9;
+        let saved_state = signing_round.saved_state();
+
+        let mut restarted = SigningRound::new(
+            1,
+            1,
+            2,
+            1,
+            vec![0, 1],
+            Default::default(),
+            Default::default(),
+        );
+        restarted.restore(saved_state.clone()).unwrap();
+        assert_eq!(restarted.dkg_id, 5);
+        assert_eq!(restarted.sign_id, 9);
+        assert_eq!(
+            restarted.signer.frost_signer.get_poly_commitments(&mut rnd)[0].A,
+            polys[0].A
+        );
+
+        let mut other_signer =
+            SigningRound::new(1, 2, 2, 2, vec![1], Default::default(), Default::default());
+        assert!(matches!(
+            other_signer.restore(saved_state),
+            Err(Error::SavedStateMismatch(1))
+        ));
+    }
+
+    #[test]
+    fn dkg_should_keep_replaced_key_shares_until_current_ones_sign() {
+        let mut rnd = get_rng();
+        let network_private_key = Scalar::random(&mut rnd);
+        let public_key = ecdsa::PublicKey::new(&network_private_key).unwrap();
+        let public_keys = PublicKeys {
+            signers: HashMap::from([(1, public_key.clone())]),
+            key_ids: HashMap::from([(1, public_key.clone()), (2, public_key)]),
+        };
+        let mut signing_round =
+            SigningRound::new(2, 1, 2, 1, vec![0, 1], network_private_key, public_keys);
+        let run_dkg = |signing_round: &mut SigningRound, dkg_id: u64| {
+            let public_shares = signing_round
+                .process(MessageTypes::DkgBegin(DkgBegin { dkg_id }))
+                .unwrap();
+            for public_share in public_shares {
+                signing_round.process(public_share).unwrap();
+            }
+            let private_shares = signing_round
+                .process(MessageTypes::DkgPrivateBegin(DkgPrivateBegin {
+                    dkg_id,
+                    signer_ids: vec![1],
+                    key_ids: vec![1, 2],
+                }))
+                .unwrap();
+            let dkg_end = private_shares
+                .into_iter()
+                .flat_map(|private_shares| signing_round.process(private_shares).unwrap())
+                .collect::<Vec<MessageTypes>>();
+            assert!(matches!(
+                &dkg_end[..],
+                [MessageTypes::DkgEnd(DkgEnd {
+                    status: DkgStatus::Success,
+                    ..
+                })]
+            ));
+            signing_round.signer.frost_signer.save()
+        };
+        let private_keys = |state: &SignerState| {
+            state
+                .parties
+                .iter()
+                .flat_map(|(_, party)| party.private_keys.clone())
+                .collect::<HashMap<u32, Scalar>>()
+        };
+        let nonce_request = |dkg_id: u64| {
+            MessageTypes::NonceRequest(NonceRequest {
+                dkg_id,
+                sign_id: 1,
+                sign_nonce_id: 1,
+                num_messages: 1,
+            })
+        };
+
+        let old_state = run_dkg(&mut signing_round, 1);
+        let new_state = run_dkg(&mut signing_round, 2);
+        assert_eq!(signing_round.key_dkg_id, Some(2));
+        assert_ne!(private_keys(&old_state), private_keys(&new_state));
+        assert_eq!(
+            signing_round
+                .saved_state()
+                .previous_keys
+                .keys()
+                .collect::<Vec<&u64>>(),
+            vec![&1]
+        );
+
+        // The outgoing coordinator gets nonces of the replaced key shares
+        signing_round.process(nonce_request(1)).unwrap();
+        assert!(signing_round
+            .nonce_states
+            .values()
+            .all(|state| private_keys(state) == private_keys(&old_state)));
+
+        // Which are dropped once the incoming coordinator signs
+        signing_round.nonce_states.clear();
+        signing_round.process(nonce_request(2)).unwrap();
+        assert!(signing_round.previous_keys.is_empty());
+        assert!(signing_round
+            .nonce_states
+            .values()
+            .all(|state| private_keys(state) == private_keys(&new_state)));
+    }
+
+    #[test]
+    fn dkg_private_begin_should_drop_excluded_signers() {
+        let mut rnd = get_rng();
+        let network_private_keys = (0..3)
+            .map(|_| Scalar::random(&mut rnd))
+            .collect::<Vec<Scalar>>();
+        let key_ids = network_private_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, ecdsa::PublicKey::new(key).unwrap()))
+            .collect::<HashMap<u32, ecdsa::PublicKey>>();
+        let public_keys = PublicKeys {
+            signers: key_ids.clone(),
+            key_ids,
+        };
+        let signing_round = |signer_id: u32| {
+            SigningRound::new(
+                2,
+                3,
+                3,
+                signer_id,
+                vec![signer_id - 1],
+                network_private_keys[signer_id as usize - 1],
+                public_keys.clone(),
+            )
+        };
+        let mut signing_rounds = [signing_round(1), signing_round(2)];
+
+        // Signer 3 never sends its public share
+        let public_shares = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process(MessageTypes::DkgBegin(DkgBegin { dkg_id: 1 }))
+                    .unwrap()
+            })
+            .collect::<Vec<MessageTypes>>();
+        for public_share in public_shares {
+            assert!(signing_rounds[0].process(public_share).unwrap().is_empty());
+        }
+        assert_eq!(signing_rounds[0].state, States::DkgPublicGather);
+
+        let dkg_private_begin = DkgPrivateBegin {
+            dkg_id: 1,
+            signer_ids: vec![1, 2],
+            key_ids: vec![1, 2],
+        };
+        let private_shares = signing_rounds[0]
+            .process(MessageTypes::DkgPrivateBegin(dkg_private_begin.clone()))
+            .unwrap();
+        assert_eq!(private_shares.len(), 1);
+        assert_eq!(signing_rounds[0].state, States::DkgPrivateGather);
+        assert_eq!(signing_rounds[0].dkg_key_ids, HashSet::from([1, 2]));
+
+        // A signer that was dropped sits the rest of the round out
+        let mut dropped = signing_round(3);
+        dropped
+            .process(MessageTypes::DkgBegin(DkgBegin { dkg_id: 1 }))
+            .unwrap();
+        assert!(dropped
+            .process(MessageTypes::DkgPrivateBegin(dkg_private_begin))
+            .unwrap()
+            .is_empty());
+        assert_eq!(dropped.state, States::Idle);
+    }
+
+    #[test]
+    fn dkg_ended_should_complain_about_bad_private_shares() {
+        let mut rnd = get_rng();
+        let network_private_keys = (0..2)
+            .map(|_| Scalar::random(&mut rnd))
+            .collect::<Vec<Scalar>>();
+        let key_ids = network_private_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, ecdsa::PublicKey::new(key).unwrap()))
+            .collect::<HashMap<u32, ecdsa::PublicKey>>();
+        let public_keys = PublicKeys {
+            signers: key_ids.clone(),
+            key_ids,
+        };
+        let mut signing_rounds = [1, 2].map(|signer_id: u32| {
+            SigningRound::new(
+                2,
+                2,
+                2,
+                signer_id,
+                vec![signer_id - 1],
+                network_private_keys[signer_id as usize - 1],
+                public_keys.clone(),
+            )
+        });
+
+        let public_shares = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process(MessageTypes::DkgBegin(DkgBegin { dkg_id: 1 }))
+                    .unwrap()
+            })
+            .collect::<Vec<MessageTypes>>();
+        for public_share in &public_shares {
+            signing_rounds[1].process(public_share.clone()).unwrap();
+        }
+        let MessageTypes::DkgPublicShare(sender_public_share) = &public_shares[0] else {
+            panic!("Expected a DkgPublicShare");
+        };
+
+        let dkg_private_begin = DkgPrivateBegin {
+            dkg_id: 1,
+            signer_ids: vec![1, 2],
+            key_ids: vec![1, 2],
+        };
+        let mut private_shares = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process(MessageTypes::DkgPrivateBegin(dkg_private_begin.clone()))
+                    .unwrap()
+            })
+            .collect::<Vec<MessageTypes>>();
+
+        // Signer 1 sends signer 2 a share that does not match its public share
+        let MessageTypes::DkgPrivateShares(bad_private_shares) = &mut private_shares[0] else {
+            panic!("Expected DkgPrivateShares");
+        };
+        let shared_secret = make_shared_secret(
+            &network_private_keys[0],
+            &Point::from(network_private_keys[1]),
+        );
+        let bad_share = Scalar::random(&mut rnd);
+        bad_private_shares.private_shares.insert(
+            1,
+            encrypt(&shared_secret, &bad_share.to_bytes(), &mut rnd).unwrap(),
+        );
+        let bad_private_shares = bad_private_shares.clone();
+
+        let dkg_end = private_shares
+            .into_iter()
+            .flat_map(|private_shares| signing_rounds[1].process(private_shares).unwrap())
+            .collect::<Vec<MessageTypes>>();
+        let [MessageTypes::DkgEnd(DkgEnd {
+            status: DkgStatus::Failure(DkgFailure::BadPrivateShares(complaints)),
+            ..
+        })] = &dkg_end[..]
+        else {
+            panic!("Expected a DkgEnd with complaints, got {:?}", dkg_end);
+        };
+        assert_eq!(complaints.len(), 1);
+        assert_eq!(complaints[0].src_key_id, 0);
+        assert_eq!(complaints[0].dst_key_id, 1);
+        assert_eq!(complaints[0].reason, ComplaintReason::BadShare);
+        assert_eq!(
+            complaints[0].blame(
+                &Point::from(network_private_keys[1]),
+                &Point::from(network_private_keys[0]),
+                &bad_private_shares,
+                &sender_public_share.public_share,
+            ),
+            Blame::Sender
+        );
+    }
+
+    #[test]
+    fn refresh_should_rerandomize_key_shares_of_same_key() {
+        let mut rnd = get_rng();
+        let network_private_key = Scalar::random(&mut rnd);
+        let public_key = ecdsa::PublicKey::new(&network_private_key).unwrap();
+        let public_keys = PublicKeys {
+            signers: HashMap::from([(1, public_key.clone())]),
+            key_ids: HashMap::from([(1, public_key.clone()), (2, public_key)]),
+        };
+        let mut signing_round =
+            SigningRound::new(2, 1, 2, 1, vec![0, 1], network_private_key, public_keys);
+        let frost_signer = &mut signing_round.signer.frost_signer;
+        let polys = frost_signer.get_poly_commitments(&mut rnd);
+        let shares = frost_signer.get_shares();
+        frost_signer.compute_secrets(&shares, &polys).unwrap();
+        let group_key = polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0]);
+        let private_keys = |signing_round: &SigningRound| {
+            signing_round
+                .signer
+                .frost_signer
+                .save()
+                .parties
+                .iter()
+                .flat_map(|(_, party)| party.private_keys.clone())
+                .collect::<HashMap<u32, Scalar>>()
+        };
+        let old_private_keys = private_keys(&signing_round);
+
+        let refresh_shares = signing_round
+            .process(MessageTypes::RefreshBegin(RefreshBegin {
+                dkg_id: 0,
+                refresh_id: 1,
+                key_ids: vec![1, 2],
+            }))
+            .unwrap();
+        assert_eq!(refresh_shares.len(), 2);
+        assert_eq!(signing_round.state, States::RefreshGather);
+        let refresh_end = refresh_shares
+            .into_iter()
+            .flat_map(|refresh_shares| signing_round.process(refresh_shares).unwrap())
+            .collect::<Vec<MessageTypes>>();
+        assert!(matches!(
+            &refresh_end[..],
+            [MessageTypes::RefreshEnd(RefreshEnd {
+                status: DkgStatus::Success,
+                ..
+            })]
+        ));
+        // Nothing changes until the coordinator commits the refresh
+        assert_eq!(private_keys(&signing_round), old_private_keys);
+
+        signing_round
+            .process(MessageTypes::RefreshCommit(RefreshCommit {
+                dkg_id: 0,
+                refresh_id: 1,
+            }))
+            .unwrap();
+        let new_private_keys = private_keys(&signing_round);
+        assert_eq!(signing_round.refresh_id, 1);
+        assert_ne!(new_private_keys, old_private_keys);
+
+        // The refreshed shares still interpolate to the same aggregate key
+        let secret =
+            new_private_keys
+                .iter()
+                .fold(Scalar::new(), |secret, (key_id, private_key)| {
+                    secret + *private_key * compute::lambda(*key_id, &[0, 1])
+                });
+        assert_eq!(Point::from(secret), group_key);
+
+        #[test]
+        fn refresh_commit_should_be_acknowledged_after_a_restart() {
+            let mut rnd = get_rng();
+            let network_private_key = Scalar::random(&mut rnd);
+            let public_key = ecdsa::PublicKey::new(&network_private_key).unwrap();
+            let public_keys = PublicKeys {
+                signers: HashMap::from([(1, public_key.clone())]),
+                key_ids: HashMap::from([(1, public_key.clone()), (2, public_key)]),
+            };
+            let new_signing_round = || {
+                SigningRound::new(
+                    2,
+                    1,
+                    2,
+                    1,
+                    vec![0, 1],
+                    network_private_key,
+                    public_keys.clone(),
+                )
+            };
+            let mut signing_round = new_signing_round();
+            let frost_signer = &mut signing_round.signer.frost_signer;
+            let polys = frost_signer.get_poly_commitments(&mut rnd);
+            let shares = frost_signer.get_shares();
+            frost_signer.compute_secrets(&shares, &polys).unwrap();
+
+            let refresh_shares = signing_round
+                .process(MessageTypes::RefreshBegin(RefreshBegin {
+                    dkg_id: 0,
+                    refresh_id: 1,
+                    key_ids: vec![1, 2],
+                }))
+                .unwrap();
+            for refresh_shares in refresh_shares {
+                signing_round.process(refresh_shares).unwrap();
+            }
+            let refreshed_state = signing_round.refreshed_state.clone().unwrap();
+
+            // A restart between the refresh and its commit keeps the refreshed shares
+            let mut restarted = new_signing_round();
+            restarted.restore(signing_round.saved_state()).unwrap();
+            let refresh_commit = RefreshCommit {
+                dkg_id: 0,
+                refresh_id: 1,
+            };
+            let ack = restarted
+                .process(MessageTypes::RefreshCommit(refresh_commit.clone()))
+                .unwrap();
+            assert!(matches!(
+                &ack[..],
+                [MessageTypes::RefreshCommitAck(RefreshCommitAck {
+                    refresh_id: 1,
+                    signer_id: 1,
+                    ..
+                })]
+            ));
+            assert_eq!(restarted.refresh_id, 1);
+            assert_eq!(
+                bincode::serialize(&restarted.signer.frost_signer.save()).unwrap(),
+                bincode::serialize(&refreshed_state).unwrap()
+            );
+
+            // A resent commit, as when the ack got lost, is acknowledged again
+            let ack = restarted
+                .process(MessageTypes::RefreshCommit(refresh_commit))
+                .unwrap();
+            assert!(matches!(
+                &ack[..],
+                [MessageTypes::RefreshCommitAck(RefreshCommitAck {
+                    refresh_id: 1,
+                    ..
+                })]
+            ));
+        }
+    }
 }
@@ -0,0 +1,192 @@
+//! Encrypted on-disk state of a signer, so its DKG key shares survive restarts.
+//!
+//! The state is sealed with AES-GCM under a key derived from the signer's network private key,
+//! and replaced atomically so a crash mid-write never leaves a truncated file behind.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use p256k1::scalar::Scalar;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wsts::traits::SignerState;
+
+use crate::util::{decrypt, encrypt};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+    #[error("Serialization Error: {0}")]
+    SerializationError(#[from] bincode::Error),
+    #[error("Failed to encrypt signer state")]
+    EncryptionError,
+    #[error(
+        "Failed to decrypt signer state in {0}. Was it written with another network_private_key?"
+    )]
+    DecryptionError(String),
+}
+
+/// What a signer needs to resume signing with the key of its last DKG round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedState {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Last key refresh applied to the key shares
+    pub refresh_id: u64,
+    /// Polynomials and private key shares of the signer's parties
+    pub signer: SignerState,
+    /// DKG round the key shares come from, if one succeeded
+    pub key_dkg_id: Option<u64>,
+    /// Key shares replaced by a later DKG round, kept until the wallet handoff to it completes
+    pub previous_keys: BTreeMap<u64, SignerState>,
+    /// Key refresh in progress, which only applies once the coordinator commits it
+    pub pending_refresh_id: u64,
+    /// Refreshed key shares of the pending key refresh, kept so a commit arriving after a
+    /// restart still applies
+    pub refreshed_state: Option<SignerState>,
+}
+
+pub struct StateFile {
+    path: PathBuf,
+    key: [u8; 32],
+}
+
+impl StateFile {
+    pub fn new(path: impl AsRef<Path>, network_private_key: &Scalar) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            key: make_state_key(network_private_key),
+        }
+    }
+
+    /// Load the saved state, or none if nothing was saved yet
+    pub fn load(&self) -> Result<Option<SavedState>, Error> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&self.path)?;
+        let plain = decrypt(&self.key, &data)
+            .map_err(|_| Error::DecryptionError(self.path.display().to_string()))?;
+        Ok(Some(bincode::deserialize(&plain)?))
+    }
+
+    pub fn save(&self, state: &SavedState) -> Result<(), Error> {
+        let plain = bincode::serialize(state)?;
+        let data = encrypt(&self.key, &plain, &mut OsRng).map_err(|_| Error::EncryptionError)?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Derive the key sealing the state file from the network private key
+fn make_state_key(network_private_key: &Scalar) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+
+    hasher.update("SIGNER_STATE_KEY/".as_bytes());
+    hasher.update(network_private_key.to_bytes());
+
+    let hash = hasher.finalize();
+    let mut bytes = [0u8; 32];
+
+    bytes.clone_from_slice(hash.as_slice());
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::OsRng;
+    use wsts::{traits::Signer as SignerTrait, v1};
+
+    use super::*;
+
+    fn state_file_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "frost-signer-{}-{}.state.bin",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn saved_state() -> SavedState {
+        let signer = v1::Signer::new(1, &[0, 1], 4, 3, &mut OsRng);
+        SavedState {
+            dkg_id: 3,
+            sign_id: 7,
+            refresh_id: 2,
+            signer: signer.save(),
+            key_dkg_id: Some(3),
+            previous_keys: BTreeMap::from([(1, signer.save())]),
+            pending_refresh_id: 3,
+            refreshed_state: Some(signer.save()),
+        }
+    }
+
+    #[test]
+    fn should_round_trip_saved_state() {
+        let path = state_file_path("round-trip");
+        let network_private_key = Scalar::random(&mut OsRng);
+        let state_file = StateFile::new(&path, &network_private_key);
+        assert!(state_file.load().unwrap().is_none());
+
+        let state = saved_state();
+        state_file.save(&state).unwrap();
+        let loaded = StateFile::new(&path, &network_private_key)
+            .load()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded.dkg_id, state.dkg_id);
+        assert_eq!(loaded.sign_id, state.sign_id);
+        assert_eq!(loaded.refresh_id, state.refresh_id);
+        assert_eq!(loaded.key_dkg_id, state.key_dkg_id);
+        assert_eq!(
+            bincode::serialize(&loaded.previous_keys).unwrap(),
+            bincode::serialize(&state.previous_keys).unwrap()
+        );
+        assert_eq!(loaded.pending_refresh_id, state.pending_refresh_id);
+        assert_eq!(
+            bincode::serialize(&loaded.refreshed_state).unwrap(),
+            bincode::serialize(&state.refreshed_state).unwrap()
+        );
+        assert_eq!(
+            bincode::serialize(&loaded.signer).unwrap(),
+            bincode::serialize(&state.signer).unwrap()
+        );
+        // The shares are not stored in the clear
+        assert_ne!(
+            fs::read(&path).unwrap(),
+            bincode::serialize(&state).unwrap()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_not_load_state_with_another_key() {
+        let path = state_file_path("other-key");
+        StateFile::new(&path, &Scalar::random(&mut OsRng))
+            .save(&saved_state())
+            .unwrap();
+
+        assert!(matches!(
+            StateFile::new(&path, &Scalar::random(&mut OsRng)).load(),
+            Err(Error::DecryptionError(_))
+        ));
+
+        fs::write(&path, [1, 2, 3]).unwrap();
+        assert!(matches!(
+            StateFile::new(&path, &Scalar::random(&mut OsRng)).load(),
+            Err(Error::DecryptionError(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
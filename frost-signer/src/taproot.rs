@@ -0,0 +1,195 @@
+//! BIP-341 tweaks of the FROST aggregate key.
+//!
+//! A taproot output key commits to the internal key `P` and an optional script tree through
+//! `Q = P + t*G`, where `t = H_TapTweak(P || merkle_root)`. Signers produce shares for `Q` by
+//! adding `t` to each of their private keys, which keeps the shares summing to a valid
+//! signature since the Lagrange coefficients of any signing set sum to one.
+//!
+//! Script path spends instead check a signature against a key in the tapscript itself, so
+//! signers only normalize the internal key to its even y form, without any tweak.
+
+use p256k1::{point::Point, scalar::Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wsts::traits::SignerState;
+
+/// Tweak applied to the aggregate key before signing a taproot spend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaprootTweak {
+    /// Root of the script tree the output key commits to, or none for a key-only tweak
+    pub merkle_root: Option<[u8; 32]>,
+    /// Sign for the untweaked internal key checked by a tapscript instead of the output key
+    #[serde(default)]
+    pub script_path: bool,
+}
+
+impl TaprootTweak {
+    pub fn new(merkle_root: Option<[u8; 32]>) -> Self {
+        Self {
+            merkle_root,
+            script_path: false,
+        }
+    }
+
+    /// Tweak for signing a script path spend, whose tapscript holds the internal key
+    pub fn script_path() -> Self {
+        Self {
+            merkle_root: None,
+            script_path: true,
+        }
+    }
+
+    /// The tweak scalar `t` for the given internal key
+    pub fn scalar(&self, internal_key: &Point) -> Scalar {
+        if self.script_path {
+            return Scalar::new();
+        }
+        let mut data = internal_key.x().to_bytes().to_vec();
+        if let Some(merkle_root) = &self.merkle_root {
+            data.extend_from_slice(merkle_root);
+        }
+        Scalar::from(tagged_hash("TapTweak", &data))
+    }
+
+    /// The output key `Q` committing to the internal key and the script tree.
+    /// Only its x coordinate is committed to on chain.
+    pub fn output_key(&self, internal_key: &Point) -> Point {
+        let internal_key = even_y(internal_key);
+        internal_key + Point::from(self.scalar(&internal_key))
+    }
+
+    /// A copy of the signer state whose private keys sign for the even y output key
+    pub fn tweak_signer_state(&self, state: &SignerState) -> SignerState {
+        // BIP-340 keys are x only, so keys with an odd y coordinate sign as their negation
+        let negate_internal_key = !state.group_key.has_even_y();
+        let internal_key = even_y(&state.group_key);
+        let tweak = self.scalar(&internal_key);
+        let output_key = internal_key + Point::from(tweak);
+        let negate_output_key = !output_key.has_even_y();
+
+        let mut state = state.clone();
+        for (_, party) in state.parties.iter_mut() {
+            for (_, private_key) in party.private_keys.iter_mut() {
+                let key = if negate_internal_key {
+                    -*private_key
+                } else {
+                    *private_key
+                };
+                *private_key = if negate_output_key {
+                    -(key + tweak)
+                } else {
+                    key + tweak
+                };
+            }
+        }
+        state.group_key = even_y(&output_key);
+        state
+    }
+
+    /// The public key of a private key share once `tweak_signer_state` tweaked it, to check
+    /// signature shares of the output key against
+    pub fn tweak_public_key(&self, group_key: &Point, public_key: &Point) -> Point {
+        let internal_key = even_y(group_key);
+        let tweak = Point::from(self.scalar(&internal_key));
+        let key = if group_key.has_even_y() {
+            *public_key
+        } else {
+            -*public_key
+        };
+        if (internal_key + tweak).has_even_y() {
+            key + tweak
+        } else {
+            -(key + tweak)
+        }
+    }
+}
+
+/// BIP-340 tagged hash of `data`
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn even_y(point: &Point) -> Point {
+    if point.has_even_y() {
+        *point
+    } else {
+        -*point
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wsts::field::Element;
+
+    fn decode(hex: &str) -> [u8; 32] {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn key_only_tweak_should_match_bip341_vector() {
+        // First scriptPubKey test vector of BIP-341, which has no script tree
+        let internal_key = Point::lift_x(&Element::from(decode(
+            "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+        )))
+        .unwrap();
+        let tweak = TaprootTweak::new(None);
+
+        assert_eq!(
+            tweak.scalar(&internal_key).to_bytes(),
+            decode("b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70")
+        );
+        assert_eq!(
+            tweak.output_key(&internal_key).x().to_bytes(),
+            decode("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343")
+        );
+    }
+
+    #[test]
+    fn tweak_should_commit_to_merkle_root() {
+        let internal_key = Point::from(Scalar::from(7));
+        let key_only = TaprootTweak::new(None).output_key(&internal_key);
+        let with_scripts = TaprootTweak::new(Some([1; 32])).output_key(&internal_key);
+
+        assert_ne!(key_only.x().to_bytes(), with_scripts.x().to_bytes());
+    }
+
+    #[test]
+    fn script_path_tweak_should_only_normalize_internal_key() {
+        // 3*G has an odd y coordinate, so its signers have to sign as its negation
+        let internal_key = Point::from(Scalar::from(3));
+        assert!(!internal_key.has_even_y());
+        let tweak = TaprootTweak::script_path();
+
+        let output_key = tweak.output_key(&internal_key);
+        assert!(output_key.has_even_y());
+        assert_eq!(output_key.x().to_bytes(), internal_key.x().to_bytes());
+    }
+
+    #[test]
+    fn tweaked_public_key_of_sole_share_should_be_output_key() {
+        for (internal_key, tweak) in [
+            (Point::from(Scalar::from(3)), TaprootTweak::new(None)),
+            (
+                Point::from(Scalar::from(7)),
+                TaprootTweak::new(Some([1; 32])),
+            ),
+            (Point::from(Scalar::from(3)), TaprootTweak::script_path()),
+        ] {
+            let output_key = even_y(&tweak.output_key(&internal_key));
+            assert_eq!(
+                tweak.tweak_public_key(&internal_key, &internal_key),
+                output_key
+            );
+        }
+    }
+}
@@ -2,6 +2,7 @@ use aes_gcm::{aead::Aead, Aes256Gcm, Error as AesGcmError, KeyInit, Nonce};
 use p256k1::{ecdsa, point::Point, scalar::Scalar};
 use rand_core::{CryptoRng, RngCore};
 use sha2::{Digest, Sha256};
+use wsts::common::PolyCommitment;
 
 pub const AES_GCM_NONCE_SIZE: usize = 12;
 
@@ -12,8 +13,12 @@ pub fn parse_public_key(public_key: &str) -> Result<ecdsa::PublicKey, ecdsa::Err
 
 /// Do a Diffie-Hellman key exchange to create a shared secret from the passed private and public keys
 pub fn make_shared_secret(private_key: &Scalar, public_key: &Point) -> [u8; 32] {
+    make_shared_secret_from_key(&(private_key * public_key))
+}
+
+/// Create the shared secret derived from an already computed Diffie-Hellman key
+pub fn make_shared_secret_from_key(shared_key: &Point) -> [u8; 32] {
     let mut hasher = Sha256::new();
-    let shared_key = private_key * public_key;
 
     hasher.update("DH_SHARED_SECRET_KEY/".as_bytes());
     hasher.update(shared_key.compress().as_bytes());
@@ -25,6 +30,21 @@ pub fn make_shared_secret(private_key: &Scalar, public_key: &Point) -> [u8; 32]
     bytes
 }
 
+/// Evaluate a public share at the 0 based `key_id`, giving the public key of the private share
+/// it commits to for that key
+pub fn eval_public_share(public_share: &PolyCommitment, key_id: u32) -> Point {
+    eval_commitments(&public_share.A, key_id)
+}
+
+/// Evaluate the polynomial whose coefficients `commitments` commit to at the 0 based `key_id`
+pub fn eval_commitments(commitments: &[Point], key_id: u32) -> Point {
+    let x = Scalar::from(key_id + 1);
+    commitments
+        .iter()
+        .rev()
+        .fold(Point::default(), |acc, a| &x * &acc + *a)
+}
+
 /// Encrypt the passed data using the key
 pub fn encrypt<RNG: RngCore + CryptoRng>(
     key: &[u8; 32],
@@ -47,8 +67,11 @@ pub fn encrypt<RNG: RngCore + CryptoRng>(
     Ok(bytes)
 }
 
-/// Decrypt the passed data using the key
+/// Decrypt the passed data using the key, failing on data too short to hold a nonce
 pub fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, AesGcmError> {
+    if data.len() < AES_GCM_NONCE_SIZE {
+        return Err(AesGcmError);
+    }
     let nonce_vec = data[..AES_GCM_NONCE_SIZE].to_vec();
     let cipher_vec = data[AES_GCM_NONCE_SIZE..].to_vec();
     let nonce = Nonce::from_slice(&nonce_vec);
@@ -100,5 +123,9 @@ mod test {
         let plain = decrypt(&yx, &cipher).unwrap();
 
         assert_eq!(msg.as_bytes(), &plain);
+
+        // Data truncated before the end of the nonce fails instead of panicking
+        assert!(decrypt(&yx, &cipher[..AES_GCM_NONCE_SIZE - 1]).is_err());
+        assert!(decrypt(&yx, &[]).is_err());
     }
 }
@@ -43,12 +43,13 @@ fn signature_share() {
             sign_nonce_id: 0,
             signer_id: 0,
             key_ids: vec![0],
-            nonces: vec![PublicNonce {
+            nonces: vec![vec![PublicNonce {
                 D: Default::default(),
                 E: Default::default(),
-            }],
+            }]],
         }],
-        message: vec![],
+        messages: vec![vec![]],
+        taproot_tweak: None,
     };
 
     let msg_share = MessageTypes::SignShareRequest(share);
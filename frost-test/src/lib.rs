@@ -1,3 +1,4 @@
+pub mod signers;
 mod sync_test;
 mod v1;
 
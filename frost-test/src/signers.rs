@@ -0,0 +1,99 @@
+use frost_coordinator::aggregator::new_aggregator;
+use frost_signer::{
+    scheme::{message_nonces, WstsVersion},
+    signing_round::NonceResponse,
+};
+use rand_core::OsRng;
+use wsts::{
+    bip340::{test_helpers::dkg, SchnorrProof},
+    common::PolyCommitment,
+    compute,
+    traits::Signer as SignerTrait,
+    v1, Point,
+};
+
+/// Signers running DKG and signing in process, under either WSTS signing scheme
+pub struct Signers {
+    wsts_version: WstsVersion,
+    threshold: u32,
+    total_keys: u32,
+    signers: [v1::Signer; 3],
+    polys: Vec<PolyCommitment>,
+}
+
+impl Signers {
+    pub fn new(wsts_version: WstsVersion) -> Self {
+        let threshold = 3;
+        let total_keys = 4;
+        let signers = [
+            v1::Signer::new(1, &[0, 1], total_keys, threshold, &mut OsRng),
+            v1::Signer::new(2, &[2], total_keys, threshold, &mut OsRng),
+            v1::Signer::new(3, &[3], total_keys, threshold, &mut OsRng),
+        ];
+
+        Self {
+            wsts_version,
+            threshold,
+            total_keys,
+            signers,
+            polys: vec![],
+        }
+    }
+
+    /// Run DKG, returning the aggregate public key
+    pub fn run_distributed_key_generation(&mut self) -> Point {
+        self.polys = dkg(&mut self.signers[..], &mut OsRng)
+            .expect("Failed to run distributed key generation.");
+        self.polys
+            .iter()
+            .fold(Point::default(), |key, poly| key + poly.A[0])
+    }
+
+    /// Sign with the first two signers, holding key ids 0 and 1, and 2
+    #[allow(non_snake_case)]
+    pub fn sign(&mut self, msg: &[u8]) -> SchnorrProof {
+        let scheme = self.wsts_version.scheme();
+        loop {
+            let mut states = vec![];
+            let nonce_responses = self.signers[..2]
+                .iter_mut()
+                .map(|signer| {
+                    let (nonces, state) = scheme.gen_nonces(signer, &mut OsRng);
+                    states.push(state);
+                    NonceResponse {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        sign_nonce_id: 1,
+                        signer_id: signer.get_id(),
+                        key_ids: signer.get_key_ids(),
+                        nonces: vec![nonces],
+                    }
+                })
+                .collect::<Vec<NonceResponse>>();
+
+            // BIP-340 signatures need an aggregate nonce with an even y coordinate
+            let party_ids = scheme.party_ids(&nonce_responses);
+            let (_, R) =
+                compute::intermediate(msg, &party_ids, &message_nonces(&nonce_responses, 0));
+            if !R.has_even_y() {
+                continue;
+            }
+
+            let shares = states
+                .iter()
+                .flat_map(|state| scheme.sign(state, msg, &nonce_responses, 0))
+                .collect::<Vec<_>>();
+            let signature = new_aggregator(
+                self.wsts_version,
+                self.total_keys,
+                self.threshold,
+                self.polys.clone(),
+            )
+            .expect("Failed to create signature aggregator.")
+            .sign(msg, &nonce_responses, &shares, 0)
+            .expect("Failed to create signature.");
+
+            return SchnorrProof::new(&signature).expect("Failed to create Schnorr proof.");
+        }
+    }
+}
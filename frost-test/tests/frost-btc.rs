@@ -1,10 +1,12 @@
 use bitcoin::consensus::{Decodable, Encodable};
 use bitcoin::secp256k1::Message;
 use bitcoin::{EcdsaSighashType, OutPoint};
+use frost_signer::scheme::WstsVersion;
+use frost_test::signers::Signers;
 use test_utils::{
     build_transaction_deposit, build_transaction_withdrawal, generate_wallet, get_raw_transaction,
-    mine_and_get_coinbase_txid, sign_transaction_ecdsa, sign_transaction_taproot, BitcoinProcess,
-    SignerHelper,
+    mine_and_get_coinbase_txid, sign_transaction_ecdsa, sign_transaction_taproot_with,
+    BitcoinProcess,
 };
 
 #[test]
@@ -101,11 +103,22 @@ fn blog_post() {
 
 #[test]
 fn frost_btc() {
+    frost_btc_with(WstsVersion::V1);
+}
+
+#[test]
+fn frost_btc_v2() {
+    frost_btc_with(WstsVersion::V2);
+}
+
+fn frost_btc_with(wsts_version: WstsVersion) {
     // Singer setup
-    let mut signer = SignerHelper::default();
+    let mut signers = Signers::new(wsts_version);
     // DKG (Distributed Key Generation)
-    let (public_commitments, deposit_wallet_public_key_point, deposit_wallet_public_key) =
-        signer.run_distributed_key_generation();
+    let deposit_wallet_public_key_point = signers.run_distributed_key_generation();
+    let deposit_wallet_public_key =
+        bitcoin::PublicKey::from_slice(deposit_wallet_public_key_point.compress().as_bytes())
+            .expect("Failed to create public key from DKG result.");
 
     // bitcoind regtest
     let btcd = BitcoinProcess::new();
@@ -178,12 +191,11 @@ fn frost_btc() {
     let mut withdrawal_tx =
         build_transaction_withdrawal(withdrawal_amount, source_public_key, deposit_utxo_point);
 
-    let withdrawal_bytes_hex = sign_transaction_taproot(
+    let withdrawal_bytes_hex = sign_transaction_taproot_with(
         &mut withdrawal_tx,
         &deposit_utxo,
-        &mut signer,
         &deposit_wallet_public_key_point,
-        public_commitments,
+        |signing_payload| signers.sign(signing_payload),
     );
     println!(
         "withdrawal tx id {} outputs {:?}",
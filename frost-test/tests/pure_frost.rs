@@ -1,37 +1,25 @@
-use rand_core::OsRng;
-use wsts::bip340::test_helpers::{dkg, sign};
-use wsts::bip340::SchnorrProof;
-use wsts::v1::{self, SignatureAggregator};
+use frost_signer::scheme::WstsVersion;
+use frost_test::signers::Signers;
 
-#[test]
-#[allow(non_snake_case)]
-fn pure_frost_test() {
-    let T = 3;
-    let N = 4;
-    let mut rng = OsRng;
-    let mut signers = [
-        v1::Signer::new(1, &[0, 1], N, T, &mut rng),
-        v1::Signer::new(2, &[2], N, T, &mut rng),
-        v1::Signer::new(3, &[3], N, T, &mut rng),
-    ];
+fn pure_frost(wsts_version: WstsVersion) {
+    let mut signers = Signers::new(wsts_version);
 
     // DKG (Distributed Key Generation)
-    let A = dkg(&mut signers[..], &mut rng).unwrap();
+    let group_key = signers.run_distributed_key_generation();
 
     // signing. Signers: 0 (parties: 0, 1) and 1 (parties: 2)
-    let result = {
-        // decide which signers will be used
-        let mut signers = [signers[0].clone(), signers[1].clone()];
+    const MSG: &[u8] = "It was many and many a year ago".as_bytes();
+    let proof = signers.sign(MSG);
 
-        const MSG: &[u8] = "It was many and many a year ago".as_bytes();
-
-        // get nonces and shares
-        let (nonces, shares) = sign(MSG, &mut signers, &mut rng);
+    assert!(proof.verify(&group_key.x(), MSG));
+}
 
-        SignatureAggregator::new(N, T, A.clone())
-            .unwrap()
-            .sign(&MSG, &nonces, &shares)
-    };
+#[test]
+fn pure_frost_test() {
+    pure_frost(WstsVersion::V1);
+}
 
-    assert!(SchnorrProof::new(&result.unwrap()).is_ok());
+#[test]
+fn pure_frost_v2_test() {
+    pure_frost(WstsVersion::V2);
 }
@@ -1,4 +1,4 @@
-use std::{io::stdout, iter::once, str::FromStr};
+use std::{io::stdout, str::FromStr};
 
 use anyhow::anyhow;
 use bdk::{database::MemoryDatabase, SignOptions, Wallet};
@@ -6,8 +6,12 @@ use bitcoin::{
     psbt::{serialize::Serialize, PartiallySignedTransaction},
     Address as BitcoinAddress, Network, PrivateKey,
 };
-use blockstack_lib::types::{chainstate::StacksAddress, Address};
+use blockstack_lib::{
+    types::{chainstate::StacksAddress, Address},
+    vm::types::{PrincipalData, StandardPrincipalData},
+};
 use clap::Parser;
+use sbtc_core::{Deposit, Payload};
 
 use crate::commands::utils;
 
@@ -68,7 +72,14 @@ fn deposit_psbt(
 ) -> anyhow::Result<PartiallySignedTransaction> {
     let mut tx_builder = wallet.build_tx();
 
-    let op_return_script = utils::build_op_return_script(&deposit_data(recipient, network));
+    let op_return_script = Deposit {
+        recipient: PrincipalData::Standard(StandardPrincipalData(
+            recipient.version,
+            recipient.bytes.0,
+        )),
+        memo: vec![],
+    }
+    .to_op_return_script(*network);
     let dkg_script = dkg_address.script_pubkey();
     let dust_amount = dkg_script.dust_value().to_sat();
 
@@ -93,12 +104,3 @@ fn deposit_psbt(
 
     Ok(partial_tx)
 }
-
-fn deposit_data(recipient: &StacksAddress, network: &Network) -> Vec<u8> {
-    utils::magic_bytes(network)
-        .into_iter()
-        .chain(once(b'<'))
-        .chain(once(recipient.version))
-        .chain(recipient.to_bytes())
-        .collect()
-}
@@ -4,10 +4,7 @@ use bdk::{
     blockchain::ElectrumBlockchain, database::MemoryDatabase, electrum_client::Client,
     template::P2Wpkh, SyncOptions, Wallet,
 };
-use bitcoin::{
-    blockdata::{opcodes, script::Builder},
-    Network, PrivateKey, Script, TxOut,
-};
+use bitcoin::{PrivateKey, Script, TxOut};
 use serde::Serialize;
 
 pub fn init_blockchain() -> anyhow::Result<ElectrumBlockchain> {
@@ -30,13 +27,6 @@ pub fn setup_wallet(private_key: PrivateKey) -> anyhow::Result<Wallet<MemoryData
     Ok(wallet)
 }
 
-pub fn build_op_return_script(data: &[u8]) -> Script {
-    Builder::new()
-        .push_opcode(opcodes::all::OP_RETURN)
-        .push_slice(data)
-        .into_script()
-}
-
 pub fn reorder_outputs(
     outputs: impl IntoIterator<Item = TxOut>,
     order: impl IntoIterator<Item = (Script, u64)>,
@@ -62,14 +52,6 @@ pub fn reorder_outputs(
     outputs_ordered.into_values().collect()
 }
 
-pub fn magic_bytes(network: &Network) -> [u8; 2] {
-    match network {
-        Network::Bitcoin => [b'X', b'2'],
-        Network::Testnet => [b'T', b'2'],
-        _ => [b'i', b'd'],
-    }
-}
-
 #[derive(Serialize)]
 pub struct TransactionData {
     pub tx_id: String,
@@ -1,16 +1,16 @@
-use std::{io::stdout, iter::once, str::FromStr};
+use std::{io::stdout, str::FromStr};
 
 use anyhow::anyhow;
 use bdk::{database::MemoryDatabase, SignOptions, Wallet};
 use bitcoin::{
     psbt::{serialize::Serialize, PartiallySignedTransaction},
-    secp256k1::{Message, Secp256k1},
     Address as BitcoinAddress, Network, PrivateKey,
 };
 use clap::Parser;
+use sbtc_core::{Payload, WithdrawalRequest};
 
 use crate::commands::utils::TransactionData;
-use crate::commands::utils::{build_op_return_script, magic_bytes, reorder_outputs, setup_wallet};
+use crate::commands::utils::{reorder_outputs, setup_wallet};
 
 #[derive(Parser, Debug, Clone)]
 pub struct WithdrawalArgs {
@@ -96,12 +96,9 @@ fn withdrawal_psbt(
         ));
     }
 
-    let op_return_script = build_op_return_script(&withdrawal_data(
-        recipient,
-        amount,
-        sender_private_key,
-        network,
-    ));
+    let op_return_script =
+        WithdrawalRequest::new(amount, &recipient_script, &sender_private_key.inner)
+            .to_op_return_script(*network);
 
     let mut tx_builder = wallet.build_tx();
 
@@ -122,29 +119,3 @@ fn withdrawal_psbt(
 
     Ok(partial_tx)
 }
-
-fn withdrawal_data(
-    recipient: &BitcoinAddress,
-    amount: u64,
-    sender_private_key: &PrivateKey,
-    network: &Network,
-) -> Vec<u8> {
-    let mut msg = amount.to_be_bytes().to_vec();
-    msg.extend_from_slice(recipient.script_pubkey().as_bytes());
-
-    let msg_hash = sha256::digest(msg.as_slice());
-    let msg_hash_bytes = array_bytes::hex2bytes(msg_hash).unwrap();
-    let msg_ecdsa = Message::from_slice(&msg_hash_bytes).unwrap();
-
-    let (recovery_id, signature) = Secp256k1::new()
-        .sign_ecdsa_recoverable(&msg_ecdsa, &sender_private_key.inner)
-        .serialize_compact();
-
-    magic_bytes(network)
-        .into_iter()
-        .chain(once(b'>'))
-        .chain(amount.to_be_bytes())
-        .chain(once(recovery_id.to_i32() as u8))
-        .chain(signature)
-        .collect()
-}
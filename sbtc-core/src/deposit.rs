@@ -0,0 +1,143 @@
+//! Deposits of BTC into the peg wallet, minting sBTC to a Stacks principal.
+
+use blockstack_lib::vm::{
+    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
+    ContractName,
+};
+
+use crate::{Error, Payload, Reader};
+
+const STANDARD_PRINCIPAL: u8 = 0x05;
+const CONTRACT_PRINCIPAL: u8 = 0x06;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deposit {
+    /// Standard or contract principal receiving the minted sBTC
+    pub recipient: PrincipalData,
+    /// Whatever follows the recipient, without its zero padding
+    pub memo: Vec<u8>,
+}
+
+impl Payload for Deposit {
+    const OPCODE: u8 = b'<';
+
+    fn encode_payload(&self) -> Vec<u8> {
+        let mut payload = vec![];
+        match &self.recipient {
+            PrincipalData::Standard(StandardPrincipalData(version, hash)) => {
+                payload.extend([STANDARD_PRINCIPAL, *version]);
+                payload.extend(hash);
+            }
+            PrincipalData::Contract(QualifiedContractIdentifier {
+                issuer: StandardPrincipalData(version, hash),
+                name,
+            }) => {
+                payload.extend([CONTRACT_PRINCIPAL, *version]);
+                payload.extend(hash);
+                payload.push(name.as_str().len() as u8);
+                payload.extend(name.as_str().as_bytes());
+            }
+        }
+        payload.extend(&self.memo);
+        payload
+    }
+
+    fn decode_payload(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(payload);
+        let [principal_type, version] = reader.take_array("principal")?;
+        let issuer = StandardPrincipalData(version, reader.take_array("principal hash")?);
+
+        let recipient = match principal_type {
+            STANDARD_PRINCIPAL => PrincipalData::Standard(issuer),
+            CONTRACT_PRINCIPAL => {
+                let [name_len] = reader.take_array("contract name length")?;
+                let name =
+                    String::from_utf8(reader.take(name_len as usize, "contract name")?.to_vec())
+                        .map_err(|e| Error::InvalidContractName(e.to_string()))?;
+                let name = ContractName::try_from(name.clone())
+                    .map_err(|_| Error::InvalidContractName(name))?;
+                PrincipalData::Contract(QualifiedContractIdentifier::new(issuer, name))
+            }
+            other => return Err(Error::UnknownPrincipalType(other)),
+        };
+
+        let memo_len = reader
+            .0
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |last| last + 1);
+        Ok(Self {
+            recipient,
+            memo: reader.0[..memo_len].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use test_vectors::{peg_in::generate_peg_in_test_vector, serialize_tx};
+
+    use super::*;
+    use crate::{test::decode_test_vector, DATA_LEN};
+
+    #[test]
+    fn should_round_trip_test_vector() {
+        let tx = decode_test_vector(serialize_tx(generate_peg_in_test_vector()));
+        let script = &tx.output[0].script_pubkey;
+
+        let (network, deposit) = Deposit::from_op_return_script(script).unwrap();
+
+        assert_eq!(network, Network::Regtest);
+        assert_eq!(
+            deposit.recipient,
+            PrincipalData::Contract(QualifiedContractIdentifier::new(
+                StandardPrincipalData(26, [1; 20]),
+                ContractName::from("sbtc-receiver-contract"),
+            ))
+        );
+        assert!(deposit.memo.is_empty());
+        assert_eq!(&deposit.to_op_return_script(network), script);
+    }
+
+    #[test]
+    fn should_round_trip_standard_principal_with_memo() {
+        let deposit = Deposit {
+            recipient: PrincipalData::Standard(StandardPrincipalData(22, [7; 20])),
+            memo: b"memo".to_vec(),
+        };
+
+        let data = deposit.encode(Network::Bitcoin);
+
+        assert_eq!(data.len(), DATA_LEN);
+        assert_eq!(&data[..4], b"X2<\x05");
+        assert_eq!(Deposit::decode(&data), Ok((Network::Bitcoin, deposit)));
+    }
+
+    #[test]
+    fn should_reject_malformed_deposits() {
+        assert_eq!(
+            Deposit::decode(b"id>"),
+            Err(Error::UnexpectedOp {
+                expected: '<',
+                found: b'>'
+            })
+        );
+        assert_eq!(
+            Deposit::decode(b"id<\x05\x1a"),
+            Err(Error::MissingField("principal hash"))
+        );
+        assert_eq!(
+            Deposit::decode_payload(&[0x07; 22]),
+            Err(Error::UnknownPrincipalType(0x07))
+        );
+
+        let mut payload = vec![CONTRACT_PRINCIPAL, 26];
+        payload.extend([1; 20]);
+        payload.extend([4, b'b', b'a', b'd', b'!']);
+        assert!(matches!(
+            Deposit::decode_payload(&payload),
+            Err(Error::InvalidContractName(_))
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+//! Wire formats of the sBTC ops that wallets write to `OP_RETURN` outputs.
+//!
+//! The data of every op starts with two magic bytes naming the network and a byte naming the
+//! op, followed by the op's payload and zero padding up to a fixed length.
+
+use bitcoin::{
+    blockdata::{
+        opcodes,
+        script::{Builder, Instruction},
+    },
+    Network, Script,
+};
+
+pub mod deposit;
+pub mod wallet_handoff;
+pub mod withdrawal_request;
+
+pub use deposit::Deposit;
+pub use wallet_handoff::WalletHandoff;
+pub use withdrawal_request::WithdrawalRequest;
+
+/// Length ops are padded to, magic bytes included
+pub const DATA_LEN: usize = 78;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Script is not an OP_RETURN script")]
+    NotOpReturn,
+    #[error("Unknown magic bytes: {0:?}")]
+    UnknownMagicBytes([u8; 2]),
+    #[error("Expected op '{expected}' but found {found:#04x}")]
+    UnexpectedOp { expected: char, found: u8 },
+    #[error("Op data is missing its {0}")]
+    MissingField(&'static str),
+    #[error("Unknown principal type: {0:#04x}")]
+    UnknownPrincipalType(u8),
+    #[error("Invalid contract name: {0}")]
+    InvalidContractName(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(#[from] bitcoin::secp256k1::Error),
+}
+
+/// Magic bytes prefixing the ops of the given network
+pub fn magic_bytes(network: Network) -> [u8; 2] {
+    match network {
+        Network::Bitcoin => *b"X2",
+        Network::Testnet | Network::Signet => *b"T2",
+        Network::Regtest => *b"id",
+    }
+}
+
+/// Network whose ops are prefixed with the given magic bytes.
+/// Testnet and signet share their magic bytes, which are read as testnet.
+pub fn network_from_magic_bytes(magic_bytes: [u8; 2]) -> Result<Network, Error> {
+    match &magic_bytes {
+        b"X2" => Ok(Network::Bitcoin),
+        b"T2" => Ok(Network::Testnet),
+        b"id" => Ok(Network::Regtest),
+        _ => Err(Error::UnknownMagicBytes(magic_bytes)),
+    }
+}
+
+/// Script of an `OP_RETURN` output carrying the given data
+pub fn op_return_script(data: &[u8]) -> Script {
+    Builder::new()
+        .push_opcode(opcodes::all::OP_RETURN)
+        .push_slice(data)
+        .into_script()
+}
+
+/// Data carried by an `OP_RETURN` script. Data split across several pushes is joined back up.
+pub fn op_return_data(script: &Script) -> Result<Vec<u8>, Error> {
+    let mut instructions = script.instructions();
+    match instructions.next() {
+        Some(Ok(Instruction::Op(op))) if op == opcodes::all::OP_RETURN => {}
+        _ => return Err(Error::NotOpReturn),
+    }
+    let mut data = vec![];
+    for instruction in instructions {
+        match instruction {
+            Ok(Instruction::PushBytes(bytes)) => data.extend_from_slice(bytes),
+            _ => return Err(Error::NotOpReturn),
+        }
+    }
+    Ok(data)
+}
+
+/// The payload of an sBTC op, which follows the magic bytes and the op byte
+pub trait Payload: Sized {
+    /// Byte naming the op
+    const OPCODE: u8;
+
+    /// Length the op data is padded to, magic bytes included
+    const DATA_LEN: usize = DATA_LEN;
+
+    fn encode_payload(&self) -> Vec<u8>;
+
+    /// Decode a payload, ignoring whatever padding follows it
+    fn decode_payload(payload: &[u8]) -> Result<Self, Error>;
+
+    /// Encode the op for the given network
+    fn encode(&self, network: Network) -> Vec<u8> {
+        let mut data = magic_bytes(network).to_vec();
+        data.push(Self::OPCODE);
+        data.extend(self.encode_payload());
+        if data.len() < Self::DATA_LEN {
+            data.resize(Self::DATA_LEN, 0);
+        }
+        data
+    }
+
+    /// Decode an op, along with the network its magic bytes belong to
+    fn decode(data: &[u8]) -> Result<(Network, Self), Error> {
+        let mut reader = Reader(data);
+        let network = network_from_magic_bytes(reader.take_array("magic bytes")?)?;
+        let [op] = reader.take_array("op")?;
+        if op != Self::OPCODE {
+            return Err(Error::UnexpectedOp {
+                expected: Self::OPCODE as char,
+                found: op,
+            });
+        }
+        Ok((network, Self::decode_payload(reader.0)?))
+    }
+
+    fn to_op_return_script(&self, network: Network) -> Script {
+        op_return_script(&self.encode(network))
+    }
+
+    fn from_op_return_script(script: &Script) -> Result<(Network, Self), Error> {
+        Self::decode(&op_return_data(script)?)
+    }
+}
+
+/// Reads consecutive fields off op data
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize, field: &'static str) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::MissingField(field));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn take_array<const N: usize>(
+        &mut self,
+        field: &'static str,
+    ) -> Result<[u8; N], Error> {
+        Ok(self
+            .take(N, field)?
+            .try_into()
+            .expect("Took exactly N bytes"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use bitcoin::{consensus::deserialize, Transaction};
+
+    /// Decode a test vector, which is built with a newer version of the bitcoin crate
+    pub fn decode_test_vector(tx_hex: String) -> Transaction {
+        deserialize(&array_bytes::hex2bytes(tx_hex).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_bytes_should_identify_network() {
+        for network in [Network::Bitcoin, Network::Testnet, Network::Regtest] {
+            assert_eq!(network_from_magic_bytes(magic_bytes(network)), Ok(network));
+        }
+        assert_eq!(
+            network_from_magic_bytes(magic_bytes(Network::Signet)),
+            Ok(Network::Testnet)
+        );
+        assert_eq!(
+            network_from_magic_bytes(*b"X3"),
+            Err(Error::UnknownMagicBytes(*b"X3"))
+        );
+    }
+
+    #[test]
+    fn op_return_data_should_join_pushes() {
+        let script = Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(b"id")
+            .push_slice(b"H")
+            .into_script();
+        assert_eq!(op_return_data(&script), Ok(b"idH".to_vec()));
+        assert_eq!(op_return_data(&op_return_script(&[1; 80])), Ok(vec![1; 80]));
+
+        let script = Builder::new().push_slice(b"idH").into_script();
+        assert_eq!(op_return_data(&script), Err(Error::NotOpReturn));
+    }
+}
@@ -0,0 +1,58 @@
+//! Handoffs of the peg wallet's funds to the wallet of the signers of a new reward cycle.
+//!
+//! The new peg wallet is the output following the `OP_RETURN` output, so the payload only
+//! names the reward cycle taking over.
+
+use crate::{Error, Payload, Reader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalletHandoff {
+    /// Reward cycle of the signers receiving the funds
+    pub reward_cycle: u64,
+}
+
+impl Payload for WalletHandoff {
+    const OPCODE: u8 = b'H';
+
+    // The reward cycle is followed by 68 bytes of padding
+    const DATA_LEN: usize = 79;
+
+    fn encode_payload(&self) -> Vec<u8> {
+        self.reward_cycle.to_be_bytes().to_vec()
+    }
+
+    fn decode_payload(payload: &[u8]) -> Result<Self, Error> {
+        let reward_cycle = u64::from_be_bytes(Reader(payload).take_array("reward cycle")?);
+        Ok(Self { reward_cycle })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use test_vectors::{peg_handoff::generate_peg_handoff_test_vector, serialize_tx};
+
+    use super::*;
+    use crate::{op_return_data, test::decode_test_vector};
+
+    #[test]
+    fn should_round_trip_test_vector() {
+        let tx = decode_test_vector(serialize_tx(generate_peg_handoff_test_vector()));
+        let script = &tx.output[0].script_pubkey;
+
+        let (network, handoff) = WalletHandoff::from_op_return_script(script).unwrap();
+
+        assert_eq!(network, Network::Regtest);
+        assert_eq!(handoff, WalletHandoff { reward_cycle: 67 });
+        // The test vector pushes each field separately, so only the data matches
+        assert_eq!(handoff.encode(network), op_return_data(script).unwrap());
+    }
+
+    #[test]
+    fn should_reject_truncated_reward_cycle() {
+        assert_eq!(
+            WalletHandoff::decode(b"X2H\x00\x01"),
+            Err(Error::MissingField("reward cycle"))
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Requests to burn sBTC in exchange for BTC paid out of the peg wallet.
+//!
+//! The request is signed by the Stacks account whose sBTC is burned, over the amount and the
+//! script pubkey of the output the BTC should be paid to.
+
+use bitcoin::{
+    hashes::{sha256, Hash},
+    secp256k1::{
+        ecdsa::{RecoverableSignature, RecoveryId},
+        Message, PublicKey, Secp256k1, SecretKey,
+    },
+    Script,
+};
+
+use crate::{Error, Payload, Reader};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawalRequest {
+    /// Amount of sBTC to burn, in sats
+    pub amount: u64,
+    pub signature: RecoverableSignature,
+}
+
+impl WithdrawalRequest {
+    /// Sign a request to withdraw `amount` sats to `recipient` with the sender's key
+    pub fn new(amount: u64, recipient: &Script, sender_key: &SecretKey) -> Self {
+        let signature = Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&signature_message(amount, recipient), sender_key);
+        Self { amount, signature }
+    }
+
+    /// Recover the key of the sender, given the script pubkey of the output receiving the BTC
+    pub fn recover_sender(&self, recipient: &Script) -> Result<PublicKey, Error> {
+        Ok(Secp256k1::verification_only()
+            .recover_ecdsa(&signature_message(self.amount, recipient), &self.signature)?)
+    }
+}
+
+fn signature_message(amount: u64, recipient: &Script) -> Message {
+    let mut msg = amount.to_be_bytes().to_vec();
+    msg.extend_from_slice(recipient.as_bytes());
+    Message::from_slice(&sha256::Hash::hash(&msg)[..]).expect("SHA256 digests are 32 bytes")
+}
+
+impl Payload for WithdrawalRequest {
+    const OPCODE: u8 = b'>';
+
+    fn encode_payload(&self) -> Vec<u8> {
+        let (recovery_id, signature) = self.signature.serialize_compact();
+        let mut payload = self.amount.to_be_bytes().to_vec();
+        payload.push(recovery_id.to_i32() as u8);
+        payload.extend(signature);
+        payload
+    }
+
+    fn decode_payload(payload: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(payload);
+        let amount = u64::from_be_bytes(reader.take_array("amount")?);
+        let [recovery_id] = reader.take_array("recovery id")?;
+        let signature = reader.take(64, "signature")?;
+        let signature = RecoverableSignature::from_compact(
+            signature,
+            RecoveryId::from_i32(recovery_id.into())?,
+        )?;
+        Ok(Self { amount, signature })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Network;
+    use test_vectors::{peg_out::generate_peg_out_request_test_vector, serialize_tx};
+
+    use super::*;
+    use crate::{test::decode_test_vector, DATA_LEN};
+
+    /// Key signing the test vector
+    fn sender_key() -> SecretKey {
+        let secret_key_hex = "42faca653724860da7a41bfcef7e6ba78db55146f6900de8cb2a9f760ffac70c01";
+        SecretKey::from_slice(&array_bytes::hex2bytes(secret_key_hex).unwrap()[..32]).unwrap()
+    }
+
+    #[test]
+    fn should_round_trip_test_vector() {
+        let tx = decode_test_vector(serialize_tx(generate_peg_out_request_test_vector()));
+        let script = &tx.output[0].script_pubkey;
+        let recipient = &tx.output[1].script_pubkey;
+
+        let (network, request) = WithdrawalRequest::from_op_return_script(script).unwrap();
+
+        assert_eq!(network, Network::Regtest);
+        assert_eq!(request.amount, 1337);
+        assert_eq!(
+            request.recover_sender(recipient),
+            Ok(PublicKey::from_secret_key(&Secp256k1::new(), &sender_key()))
+        );
+        assert_eq!(&request.to_op_return_script(network), script);
+    }
+
+    #[test]
+    fn should_recover_sender_only_for_signed_recipient() {
+        let recipient = Script::from(
+            vec![0x00, 0x14]
+                .into_iter()
+                .chain([5; 20])
+                .collect::<Vec<_>>(),
+        );
+        let request = WithdrawalRequest::new(1000, &recipient, &sender_key());
+        let sender = PublicKey::from_secret_key(&Secp256k1::new(), &sender_key());
+
+        let data = request.encode(Network::Testnet);
+        assert_eq!(data.len(), DATA_LEN);
+        let (network, decoded) = WithdrawalRequest::decode(&data).unwrap();
+        assert_eq!(network, Network::Testnet);
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.recover_sender(&recipient), Ok(sender));
+
+        // Paying out to any other output recovers some other key
+        assert_ne!(decoded.recover_sender(&Script::new()), Ok(sender));
+    }
+
+    #[test]
+    fn should_reject_invalid_recovery_id() {
+        let mut payload = 1000_u64.to_be_bytes().to_vec();
+        payload.push(4);
+        payload.extend([1; 64]);
+        assert!(matches!(
+            WithdrawalRequest::decode_payload(&payload),
+            Err(Error::InvalidSignature(_))
+        ));
+        assert_eq!(
+            WithdrawalRequest::decode_payload(&payload[..40]),
+            Err(Error::MissingField("signature"))
+        );
+    }
+}
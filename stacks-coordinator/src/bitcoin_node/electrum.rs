@@ -0,0 +1,196 @@
+//! A `BitcoinNode` backed by an Electrum server, which indexes every script
+//! and so does not need a wallet to track the peg wallet's utxos.
+
+use bdk::electrum_client::{Client, ElectrumApi, Error as ElectrumError, Param};
+use bitcoin::{BlockHash, Txid};
+use tracing::debug;
+use url::Url;
+
+use super::{
+    btc_per_kvb_to_sat_per_vb, confirmations_at_height, BitcoinNode, BitcoinTransaction, Error,
+    UTXO,
+};
+
+pub struct ElectrumBitcoinNode {
+    client: Client,
+}
+
+impl BitcoinNode for ElectrumBitcoinNode {
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error> {
+        Ok(self.client.transaction_broadcast(tx)?)
+    }
+
+    fn load_wallet(&self, address: &bitcoin::Address) -> Result<(), Error> {
+        debug!(
+            "Electrum indexes every script, nothing to load for {}",
+            address
+        );
+        Ok(())
+    }
+
+    fn list_unspent(&self, address: &bitcoin::Address) -> Result<Vec<UTXO>, Error> {
+        debug!("Retrieving utxos...");
+        let script_pubkey = address.script_pubkey();
+        let utxos = self.client.script_list_unspent(&script_pubkey)?;
+        let tip_height = self.tip_height()?;
+
+        Ok(utxos
+            .into_iter()
+            .map(|utxo| UTXO {
+                txid: utxo.tx_hash.to_string(),
+                vout: utxo.tx_pos as u32,
+                address: address.to_string(),
+                scriptPubKey: hex::encode(script_pubkey.as_bytes()),
+                amount: utxo.value,
+                // Unconfirmed utxos are reported at height 0
+                confirmations: if utxo.height > 0 {
+                    confirmations_at_height(tip_height, utxo.height as u64)
+                } else {
+                    0
+                },
+                safe: utxo.height > 0,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn block_hash(&self, block_height: u64) -> Result<BlockHash, Error> {
+        Ok(self
+            .client
+            .block_header(block_height as usize)?
+            .block_hash())
+    }
+
+    fn transaction_confirmations(&self, txid: &Txid) -> Result<Option<i64>, Error> {
+        debug!("Retrieving confirmations of transaction {}...", txid);
+        let params = vec![Param::String(txid.to_string()), Param::Bool(true)];
+        let response = match self.client.raw_call("blockchain.transaction.get", params) {
+            Ok(response) => response,
+            Err(ElectrumError::Protocol(error)) if is_unknown_transaction(&error) => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // Unconfirmed transactions carry no confirmations field
+        Ok(Some(response["confirmations"].as_i64().unwrap_or(0)))
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        debug!("Estimating fee rate...");
+        let btc_per_kvb = self.client.estimate_fee(conf_target as usize)?;
+        // Servers without enough data to estimate report -1
+        if btc_per_kvb <= 0.0 {
+            return Err(Error::NoFeeEstimate(format!(
+                "Electrum estimated {} BTC/kvB",
+                btc_per_kvb
+            )));
+        }
+        btc_per_kvb_to_sat_per_vb(btc_per_kvb)
+    }
+}
+
+impl ElectrumBitcoinNode {
+    /// Connect to the Electrum server at a `tcp://` or `ssl://` url
+    pub fn new(electrum_url: &Url) -> Result<Self, Error> {
+        Ok(Self {
+            client: Client::new(electrum_url.as_str())?,
+        })
+    }
+
+    fn tip_height(&self) -> Result<u64, Error> {
+        Ok(self.client.block_headers_subscribe()?.height as u64)
+    }
+}
+
+fn is_unknown_transaction(error: &serde_json::Value) -> bool {
+    let message = error.to_string().to_lowercase();
+    message.contains("no such mempool or blockchain transaction") || message.contains("not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::mock_electrum_server;
+    use bitcoin::{Address, Network};
+    use serde_json::json;
+    use std::str::FromStr;
+
+    const TXID: &str = "19b7fb5fd6dc25b76aeedb812b7fdc7bf8fac343913706c8b39d23ef7375860c";
+
+    /// Header of the regtest genesis block
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000";
+
+    fn electrum(responses: Vec<(&'static str, serde_json::Value)>) -> ElectrumBitcoinNode {
+        ElectrumBitcoinNode::new(&mock_electrum_server(responses)).unwrap()
+    }
+
+    #[test]
+    fn should_list_unspent() {
+        let node = electrum(vec![
+            (
+                "blockchain.scripthash.listunspent",
+                json!([
+                    {"tx_hash": TXID, "tx_pos": 1, "height": 98, "value": 5000},
+                    {"tx_hash": TXID, "tx_pos": 2, "height": 0, "value": 700},
+                ]),
+            ),
+            (
+                "blockchain.headers.subscribe",
+                json!({"height": 100, "hex": GENESIS_HEADER}),
+            ),
+        ]);
+        let address = Address::from_str("bcrt1qykqup0h6ry9x3c89llzpznrvm9nfd7fqwnt0hu").unwrap();
+
+        let utxos = node.list_unspent(&address).unwrap();
+
+        assert_eq!(utxos.len(), 2);
+        assert_eq!(utxos[0].txid, TXID);
+        assert_eq!(utxos[0].vout, 1);
+        assert_eq!(utxos[0].amount, 5000);
+        assert_eq!(utxos[0].confirmations, 3);
+        assert_eq!(
+            utxos[0].scriptPubKey,
+            "00142581c0befa190a68e0e5ffc4114c6cd96696f920"
+        );
+        assert_eq!(utxos[1].confirmations, 0);
+    }
+
+    #[test]
+    fn should_count_transaction_confirmations() {
+        let txid = Txid::from_str(TXID).unwrap();
+        let node = electrum(vec![(
+            "blockchain.transaction.get",
+            json!({"txid": TXID, "confirmations": 10}),
+        )]);
+        assert_eq!(node.transaction_confirmations(&txid).unwrap(), Some(10));
+
+        let node = electrum(vec![("blockchain.transaction.get", json!({"txid": TXID}))]);
+        assert_eq!(node.transaction_confirmations(&txid).unwrap(), Some(0));
+
+        // The mock server reports unknown methods as missing transactions
+        let node = electrum(vec![]);
+        assert_eq!(node.transaction_confirmations(&txid).unwrap(), None);
+    }
+
+    #[test]
+    fn should_get_block_hash() {
+        let node = electrum(vec![("blockchain.block.header", json!(GENESIS_HEADER))]);
+
+        assert_eq!(
+            node.block_hash(0).unwrap(),
+            bitcoin::blockdata::constants::genesis_block(Network::Regtest).block_hash()
+        );
+    }
+
+    #[test]
+    fn should_estimate_fee_rate() {
+        let node = electrum(vec![("blockchain.estimatefee", json!(0.00012))]);
+        assert_eq!(node.estimate_fee_rate(3).unwrap(), 12);
+
+        let node = electrum(vec![("blockchain.estimatefee", json!(-1))]);
+        assert!(matches!(
+            node.estimate_fee_rate(3),
+            Err(Error::NoFeeEstimate(_))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! A `BitcoinNode` backed by the REST API of an Esplora server, which indexes every address
+//! and so does not need a wallet to track the peg wallet's utxos.
+
+use std::str::FromStr;
+
+use bitcoin::{consensus::encode::serialize_hex, BlockHash, Txid};
+use serde::Deserialize;
+use tracing::debug;
+use url::Url;
+
+use super::{
+    confirmations_at_height, parse_rpc_error, BitcoinNode, BitcoinTransaction, Error, UTXO,
+};
+
+#[derive(Debug, Deserialize)]
+struct TransactionStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraUTXO {
+    txid: String,
+    vout: u32,
+    status: TransactionStatus,
+    value: u64,
+}
+
+pub struct EsploraBitcoinNode {
+    esplora_api: Url,
+}
+
+impl BitcoinNode for EsploraBitcoinNode {
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error> {
+        let txid = ureq::post(&self.url("tx"))
+            .send_string(&serialize_hex(tx))
+            .map_err(|e| Error::RPCError(parse_rpc_error(e)))?
+            .into_string()?;
+
+        Txid::from_str(txid.trim()).map_err(|_| Error::InvalidTxHash)
+    }
+
+    fn load_wallet(&self, address: &bitcoin::Address) -> Result<(), Error> {
+        debug!(
+            "Esplora indexes every address, nothing to load for {}",
+            address
+        );
+        Ok(())
+    }
+
+    fn list_unspent(&self, address: &bitcoin::Address) -> Result<Vec<UTXO>, Error> {
+        debug!("Retrieving utxos...");
+        let utxos: Vec<EsploraUTXO> = self.get_json(&format!("address/{}/utxo", address))?;
+        let tip_height = self.tip_height()?;
+        let script_pubkey = hex::encode(address.script_pubkey().as_bytes());
+
+        Ok(utxos
+            .into_iter()
+            .map(|utxo| UTXO {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                address: address.to_string(),
+                scriptPubKey: script_pubkey.clone(),
+                amount: utxo.value,
+                confirmations: confirmations(&utxo.status, tip_height) as u64,
+                safe: utxo.status.confirmed,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn block_hash(&self, block_height: u64) -> Result<BlockHash, Error> {
+        let block_hash = self.get(&format!("block-height/{}", block_height))?;
+        BlockHash::from_str(block_hash.trim()).map_err(|_| Error::InvalidBlockHash)
+    }
+
+    fn transaction_confirmations(&self, txid: &Txid) -> Result<Option<i64>, Error> {
+        debug!("Retrieving confirmations of transaction {}...", txid);
+        let status: TransactionStatus =
+            match ureq::get(&self.url(&format!("tx/{}/status", txid))).call() {
+                Ok(response) => response.into_json()?,
+                Err(ureq::Error::Status(404, _)) => return Ok(None),
+                Err(e) => return Err(Error::RPCError(parse_rpc_error(e))),
+            };
+        // Esplora drops conflicting transactions, which then show up as unknown
+        Ok(Some(confirmations(&status, self.tip_height()?)))
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        debug!("Estimating fee rate...");
+        // Fee rates in sat/vB keyed by confirmation target
+        let estimates: serde_json::Map<String, serde_json::Value> =
+            self.get_json("fee-estimates")?;
+
+        // Use the estimate for the longest target that still confirms in time
+        let (_, sat_per_vb) = estimates
+            .iter()
+            .filter_map(|(target, fee_rate)| {
+                Some((target.parse::<u16>().ok()?, fee_rate.as_f64()?))
+            })
+            .filter(|(target, _)| *target <= conf_target)
+            .max_by_key(|(target, _)| *target)
+            .ok_or_else(|| {
+                Error::NoFeeEstimate(format!("No estimate within {} blocks", conf_target))
+            })?;
+        Ok(sat_per_vb.ceil() as u64)
+    }
+}
+
+impl EsploraBitcoinNode {
+    pub fn new(esplora_api: Url) -> Self {
+        Self { esplora_api }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.esplora_api.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// Make a GET request to the given API path, returning the response body
+    fn get(&self, path: &str) -> Result<String, Error> {
+        debug!("Making Esplora request GET /{}...", path);
+        Ok(ureq::get(&self.url(path))
+            .call()
+            .map_err(|e| Error::RPCError(parse_rpc_error(e)))?
+            .into_string()?)
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        serde_json::from_str(&self.get(path)?)
+            .map_err(|e| Error::InvalidResponseJSON(e.to_string()))
+    }
+
+    fn tip_height(&self) -> Result<u64, Error> {
+        self.get("blocks/tip/height")?
+            .trim()
+            .parse()
+            .map_err(|_| Error::InvalidResponseJSON("Could not parse tip height".to_string()))
+    }
+}
+
+fn confirmations(status: &TransactionStatus, tip_height: u64) -> i64 {
+    match (status.confirmed, status.block_height) {
+        (true, Some(block_height)) => confirmations_at_height(tip_height, block_height) as i64,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::mock_http_server;
+    use bitcoin::Address;
+
+    const TXID: &str = "19b7fb5fd6dc25b76aeedb812b7fdc7bf8fac343913706c8b39d23ef7375860c";
+    const ADDRESS: &str = "bcrt1qykqup0h6ry9x3c89llzpznrvm9nfd7fqwnt0hu";
+
+    fn esplora(routes: Vec<(&'static str, u16, String)>) -> EsploraBitcoinNode {
+        EsploraBitcoinNode::new(mock_http_server(routes))
+    }
+
+    #[test]
+    fn should_list_unspent() {
+        let node = esplora(vec![
+            (
+                "GET /address/bcrt1qykqup0h6ry9x3c89llzpznrvm9nfd7fqwnt0hu/utxo",
+                200,
+                format!(
+                    r#"[
+                        {{"txid": "{TXID}", "vout": 1, "value": 5000, "status": {{"confirmed": true, "block_height": 98}}}},
+                        {{"txid": "{TXID}", "vout": 2, "value": 700, "status": {{"confirmed": false}}}}
+                    ]"#
+                ),
+            ),
+            ("GET /blocks/tip/height", 200, "100".to_string()),
+        ]);
+        let address = Address::from_str(ADDRESS).unwrap();
+
+        let utxos = node.list_unspent(&address).unwrap();
+
+        assert_eq!(utxos.len(), 2);
+        assert_eq!(utxos[0].txid, TXID);
+        assert_eq!(utxos[0].vout, 1);
+        assert_eq!(utxos[0].amount, 5000);
+        assert_eq!(utxos[0].confirmations, 3);
+        assert_eq!(
+            utxos[0].scriptPubKey,
+            "00142581c0befa190a68e0e5ffc4114c6cd96696f920"
+        );
+        assert_eq!(utxos[1].confirmations, 0);
+    }
+
+    #[test]
+    fn should_count_transaction_confirmations() {
+        let node = esplora(vec![
+            (
+                "GET /tx/19b7fb5fd6dc25b76aeedb812b7fdc7bf8fac343913706c8b39d23ef7375860c/status",
+                200,
+                r#"{"confirmed": true, "block_height": 91}"#.to_string(),
+            ),
+            ("GET /blocks/tip/height", 200, "100".to_string()),
+        ]);
+        let txid = Txid::from_str(TXID).unwrap();
+        assert_eq!(node.transaction_confirmations(&txid).unwrap(), Some(10));
+
+        // Unknown transactions are not found
+        let node = esplora(vec![]);
+        assert_eq!(node.transaction_confirmations(&txid).unwrap(), None);
+    }
+
+    #[test]
+    fn should_broadcast_transaction() {
+        let node = esplora(vec![("POST /tx", 200, TXID.to_string())]);
+        let tx = BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![],
+        };
+
+        assert_eq!(
+            node.broadcast_transaction(&tx).unwrap(),
+            Txid::from_str(TXID).unwrap()
+        );
+
+        let node = esplora(vec![("POST /tx", 400, "bad-txns-vin-empty".to_string())]);
+        assert!(matches!(
+            node.broadcast_transaction(&tx),
+            Err(Error::RPCError(message)) if message.contains("bad-txns-vin-empty")
+        ));
+    }
+
+    #[test]
+    fn should_get_block_hash() {
+        let block_hash = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+        let node = esplora(vec![("GET /block-height/0", 200, block_hash.to_string())]);
+
+        assert_eq!(
+            node.block_hash(0).unwrap(),
+            BlockHash::from_str(block_hash).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_estimate_fee_rate_within_target() {
+        let node = esplora(vec![(
+            "GET /fee-estimates",
+            200,
+            r#"{"1": 20.5, "2": 12.1, "6": 4.0, "144": 1.0}"#.to_string(),
+        )]);
+
+        assert_eq!(node.estimate_fee_rate(3).unwrap(), 13);
+        assert_eq!(node.estimate_fee_rate(6).unwrap(), 4);
+        assert!(matches!(
+            esplora(vec![("GET /fee-estimates", 200, "{}".to_string())]).estimate_fee_rate(3),
+            Err(Error::NoFeeEstimate(_))
+        ));
+    }
+}
@@ -1,12 +1,16 @@
+pub mod electrum;
+pub mod esplora;
+
 use std::{borrow::Cow, str::FromStr};
 
 use bdk::descriptor::calc_checksum;
-use bitcoin::{consensus::Encodable, hashes::sha256d::Hash, util::amount::Amount, Txid};
+use bitcoin::{consensus::Encodable, hashes::sha256d::Hash, util::amount::Amount, BlockHash, Txid};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{debug, info, warn};
 use url::Url;
 
+#[cfg_attr(test, mockall::automock)]
 pub trait BitcoinNode {
     /// Broadcast the BTC transaction to the bitcoin node
     fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error>;
@@ -14,6 +18,40 @@ pub trait BitcoinNode {
     fn load_wallet(&self, address: &bitcoin::Address) -> Result<(), Error>;
     /// Get all utxos from the given address
     fn list_unspent(&self, address: &bitcoin::Address) -> Result<Vec<UTXO>, Error>;
+    /// Get the hash of the block at the given height on the node's best chain
+    fn block_hash(&self, block_height: u64) -> Result<BlockHash, Error>;
+    /// Get the number of confirmations of a transaction involving the loaded wallet.
+    /// `None` if the node does not know the transaction, negative if it conflicts
+    /// with a transaction that has that many confirmations.
+    fn transaction_confirmations(&self, txid: &Txid) -> Result<Option<i64>, Error>;
+    /// Estimate the fee rate in sat/vB needed to confirm within the given number of blocks
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error>;
+}
+
+impl<T: BitcoinNode + ?Sized> BitcoinNode for Box<T> {
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error> {
+        (**self).broadcast_transaction(tx)
+    }
+
+    fn load_wallet(&self, address: &bitcoin::Address) -> Result<(), Error> {
+        (**self).load_wallet(address)
+    }
+
+    fn list_unspent(&self, address: &bitcoin::Address) -> Result<Vec<UTXO>, Error> {
+        (**self).list_unspent(address)
+    }
+
+    fn block_hash(&self, block_height: u64) -> Result<BlockHash, Error> {
+        (**self).block_hash(block_height)
+    }
+
+    fn transaction_confirmations(&self, txid: &Txid) -> Result<Option<i64>, Error> {
+        (**self).transaction_confirmations(txid)
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        (**self).estimate_fee_rate(conf_target)
+    }
 }
 
 pub type BitcoinTransaction = bitcoin::Transaction;
@@ -30,10 +68,16 @@ pub enum Error {
     InvalidUTXO(String),
     #[error("Invalid transaction hash")]
     InvalidTxHash,
+    #[error("Invalid block hash")]
+    InvalidBlockHash,
+    #[error("No fee estimate available: {0}")]
+    NoFeeEstimate(String),
     #[error("Could not compute descriptor checksum: {0}")]
     DescriptorError(#[from] bdk::descriptor::error::Error),
     #[error("URL Parse error: {0}")]
     UrlParseError(#[from] url::ParseError),
+    #[error("Electrum Error: {0}")]
+    ElectrumError(#[from] bdk::electrum_client::Error),
 }
 
 #[allow(non_snake_case)]
@@ -124,6 +168,50 @@ impl BitcoinNode for LocalhostBitcoinNode {
 
         result
     }
+
+    fn block_hash(&self, block_height: u64) -> Result<BlockHash, Error> {
+        let result = self
+            .call("getblockhash", [block_height])?
+            .as_str()
+            .ok_or(Error::InvalidResponseJSON(
+                "No block hash in getblockhash response".to_string(),
+            ))?
+            .to_string();
+
+        BlockHash::from_str(&result).map_err(|_| Error::InvalidBlockHash)
+    }
+
+    fn transaction_confirmations(&self, txid: &Txid) -> Result<Option<i64>, Error> {
+        debug!("Retrieving confirmations of transaction {}...", txid);
+        let include_watchonly = true;
+        let params = (txid.to_string(), include_watchonly);
+
+        let response = match self.call_wallet("gettransaction", params) {
+            Ok(response) => response,
+            Err(Error::RPCError(message)) if message.contains("Invalid or non-wallet") => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        response["confirmations"]
+            .as_i64()
+            .map(Some)
+            .ok_or(Error::InvalidResponseJSON(
+                "Could not parse confirmations".to_string(),
+            ))
+    }
+
+    fn estimate_fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        debug!("Estimating fee rate...");
+        let response = self.call("estimatesmartfee", [conf_target])?;
+
+        // Nodes without enough data to estimate report errors instead of a fee rate
+        let btc_per_kvb = response["feerate"]
+            .as_f64()
+            .ok_or_else(|| Error::NoFeeEstimate(response["errors"].to_string()))?;
+        btc_per_kvb_to_sat_per_vb(btc_per_kvb)
+    }
 }
 
 impl LocalhostBitcoinNode {
@@ -318,6 +406,24 @@ impl LocalhostBitcoinNode {
     }
 }
 
+/// Convert a fee rate in BTC/kvB to sat/vB, rounding up
+fn btc_per_kvb_to_sat_per_vb(btc_per_kvb: f64) -> Result<u64, Error> {
+    let sat_per_kvb = Amount::from_btc(btc_per_kvb)
+        .map_err(|_| {
+            Error::InvalidResponseJSON(format!(
+                "Could not parse the float {} as a bitcoin amount",
+                btc_per_kvb
+            ))
+        })?
+        .to_sat();
+    Ok((sat_per_kvb + 999) / 1000)
+}
+
+/// Number of confirmations of a transaction mined at `block_height` given the chain tip height
+fn confirmations_at_height(tip_height: u64, block_height: u64) -> u64 {
+    (tip_height + 1).saturating_sub(block_height)
+}
+
 fn parse_rpc_error(err: ureq::Error) -> String {
     match err {
         ureq::Error::Status(status, response) => format!(
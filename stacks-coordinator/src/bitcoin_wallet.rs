@@ -1,14 +1,33 @@
 use std::iter::repeat;
 
 use crate::bitcoin_node::UTXO;
+use crate::coin_selection::{fee_for_weight, select_coins, CoinSelectionParams};
+use crate::commit_reveal::{reveal_marker_output, Commit};
 use crate::peg_wallet::{BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError};
 use crate::stacks_node::PegOutRequestOp;
 use bitcoin::blockdata::opcodes;
 use bitcoin::TxOut;
 use bitcoin::{
-    blockdata::script, hashes::hex::FromHex, schnorr::TweakedPublicKey, Address, Network, OutPoint,
-    Script, Transaction, TxIn, XOnlyPublicKey,
+    blockdata::{constants::WITNESS_SCALE_FACTOR, script},
+    consensus::encode::serialize,
+    hashes::{hex::FromHex, Hash},
+    schnorr::TweakedPublicKey,
+    secp256k1::Secp256k1,
+    util::{
+        address::WitnessVersion,
+        taproot::{TapBranchHash, TaprootBuilder, TaprootSpendInfo},
+    },
+    Address, Network, OutPoint, PubkeyHash, Script, ScriptHash, Sequence, Transaction, TxIn,
+    Witness, XOnlyPublicKey,
 };
+use blockstack_lib::{
+    address::{
+        AddressHashMode, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    },
+    chainstate::stacks::address::{PoxAddress, PoxAddressType20, PoxAddressType32},
+};
+use sbtc_core::{Payload, WalletHandoff};
 use tracing::{debug, warn};
 
 #[derive(thiserror::Error, Debug, PartialEq)]
@@ -23,20 +42,93 @@ pub enum Error {
     MissingFulfillmentUTXO,
     #[error("Fulfillment UTXO amount does not equal the fulfillment fee.")]
     MismatchedFulfillmentFee,
+    #[error("Transaction fee {0} exceeds the fulfillment fee {1}.")]
+    FeeExceedsFulfillmentFee(u64, u64),
+    #[error("Invalid taproot script tree: {0}")]
+    InvalidTaprootScripts(String),
+    #[error("Committed amount {0} can not pay the reveal fee {1}.")]
+    CommitBelowRevealFee(u64, u64),
 }
 
+/// Weight of a P2TR key path spend: 41 bytes of outpoint, script and sequence,
+/// plus a witness holding a single 64 byte schnorr signature
+const P2TR_KEY_SPEND_INPUT_WEIGHT: u64 = (41 * WITNESS_SCALE_FACTOR + 66) as u64;
+
+/// Lowest fee rate in sat/vB nodes relay transactions at
+const MIN_RELAY_FEE_RATE: u64 = 1;
+
+/// Sequence number signaling replaceability (BIP 125)
+const RBF_SEQUENCE: Sequence = Sequence(0xFFFFFFFD);
+
 pub struct BitcoinWallet {
     address: Address,
     public_key: XOnlyPublicKey,
+    network: Network,
+    /// Set once the address commits to a script tree
+    spend_info: Option<TaprootSpendInfo>,
+    taproot_scripts: Vec<Script>,
+    rbf: bool,
 }
 
 impl BitcoinWallet {
+    /// Wallet paying to the untweaked FROST aggregate public key, the address every signer and
+    /// existing wallet already derives
     pub fn new(public_key: XOnlyPublicKey, network: Network) -> Self {
         let tweaked_public_key = TweakedPublicKey::dangerous_assume_tweaked(public_key);
         let address = Address::p2tr_tweaked(tweaked_public_key, network);
         Self {
             address,
             public_key,
+            network,
+            spend_info: None,
+            taproot_scripts: vec![],
+            rbf: true,
+        }
+    }
+
+    /// Commit the wallet address to a script tree of the given tapscripts, all at equal weight,
+    /// so coins can also be spent through a script path such as a timelocked recovery key.
+    /// Scripts move the wallet to a BIP-341 tweaked address, while without any it stays at the
+    /// untweaked one.
+    pub fn set_taproot_scripts(&mut self, scripts: &[Script]) -> Result<(), Error> {
+        self.spend_info = if scripts.is_empty() {
+            None
+        } else {
+            let secp = Secp256k1::verification_only();
+            Some(
+                TaprootBuilder::with_huffman_tree(scripts.iter().map(|script| (1, script.clone())))
+                    .map_err(|e| Error::InvalidTaprootScripts(e.to_string()))?
+                    .finalize(&secp, self.public_key)
+                    .map_err(|_| Error::InvalidTaprootScripts("incomplete tree".to_string()))?,
+            )
+        };
+        let output_key = match &self.spend_info {
+            Some(spend_info) => spend_info.output_key(),
+            None => TweakedPublicKey::dangerous_assume_tweaked(self.public_key),
+        };
+        self.address = Address::p2tr_tweaked(output_key, self.network);
+        self.taproot_scripts = scripts.to_vec();
+        Ok(())
+    }
+
+    /// Wallet of another aggregate public key, committing to the same script tree
+    pub fn with_public_key(&self, public_key: XOnlyPublicKey) -> Result<Self, Error> {
+        let mut wallet = Self::new(public_key, self.network);
+        wallet.set_rbf(self.rbf);
+        wallet.set_taproot_scripts(&self.taproot_scripts)?;
+        Ok(wallet)
+    }
+
+    /// Whether built transactions signal that they can be replaced to bump their fee
+    pub fn set_rbf(&mut self, rbf: bool) {
+        self.rbf = rbf;
+    }
+
+    fn sequence(&self) -> Sequence {
+        if self.rbf {
+            RBF_SEQUENCE
+        } else {
+            Sequence::MAX
         }
     }
 }
@@ -47,83 +139,226 @@ impl BitcoinWalletTrait for BitcoinWallet {
         &self,
         op: &PegOutRequestOp,
         available_utxos: Vec<UTXO>,
+        fee_rate: u64,
     ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
-        // Create an empty transaction
+        self.fulfill_peg_outs(std::slice::from_ref(op), available_utxos, fee_rate)
+    }
+
+    fn fulfill_peg_outs(
+        &self,
+        ops: &[PegOutRequestOp],
+        available_utxos: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
+        // The fulfillment utxos pay for the fulfillment and must always be spent
+        let (fulfillment_utxos, candidate_utxos): (Vec<_>, Vec<_>) =
+            available_utxos.into_iter().partition(|utxo| {
+                utxo.vout == 2 && ops.iter().any(|op| utxo.txid == op.txid.to_string())
+            });
+
         let mut tx = Transaction {
             version: 2,
             lock_time: bitcoin::PackedLockTime(0),
             input: vec![],
-            output: vec![],
+            output: vec![withdrawal_data_output()],
         };
-        // Consume UTXOs until we have enough to cover the total spend (fulfillment fee and peg out amount)
-        let mut total_consumed = 0;
         let mut prevouts = vec![];
-        let mut found_fulfillment_utxo = false;
-        for utxo in available_utxos.into_iter() {
-            if utxo.txid == op.txid.to_string() && utxo.vout == 2 {
-                // This is the fulfillment utxo.
-                if utxo.amount != op.fulfillment_fee {
-                    // Something is wrong. The fulfillment fee should match the fulfillment utxo amount.
-                    // Malformed Peg Request Op
-                    return Err(PegWalletError::from(Error::MismatchedFulfillmentFee));
-                }
-                tx.input.push(utxo_to_input(&utxo)?);
-                prevouts.push(utxo_to_output(&utxo)?);
-                found_fulfillment_utxo = true;
-            } else if total_consumed < op.amount {
-                total_consumed += utxo.amount;
-                tx.input.push(utxo_to_input(&utxo)?);
-                prevouts.push(utxo_to_output(&utxo)?);
-            } else if found_fulfillment_utxo {
-                // We have consumed enough to cover the total spend
-                // i.e. have found the fulfillment utxo and covered the peg out amount
-                break;
+        for op in ops {
+            let Some(fulfillment_utxo) = fulfillment_utxos
+                .iter()
+                .find(|utxo| utxo.txid == op.txid.to_string())
+            else {
+                warn!("Failed to find fulfillment utxo of op {}.", op.txid);
+                return Err(PegWalletError::from(Error::MissingFulfillmentUTXO));
+            };
+            if fulfillment_utxo.amount != op.fulfillment_fee {
+                // Something is wrong. The fulfillment fee should match the fulfillment utxo amount.
+                // Malformed Peg Request Op
+                return Err(PegWalletError::from(Error::MismatchedFulfillmentFee));
             }
+            tx.input
+                .push(utxo_to_input(fulfillment_utxo, self.sequence())?);
+            prevouts.push(utxo_to_output(fulfillment_utxo)?);
+            tx.output.push(bitcoin::TxOut {
+                value: op.amount,
+                script_pubkey: recipient_script_pubkey(&op.recipient),
+            });
         }
-        // Sanity check all the things!
-        // If we did not find the fulfillment utxo, something went wrong
-        if !found_fulfillment_utxo {
-            warn!("Failed to find fulfillment utxo.");
-            return Err(PegWalletError::from(Error::MissingFulfillmentUTXO));
-        }
-        // Check that we have sufficient funds and didn't just run out of available utxos.
-        if total_consumed < op.amount {
+        let amount: u64 = ops.iter().map(|op| op.amount).sum();
+        let fulfillment_fee: u64 = ops.iter().map(|op| op.fulfillment_fee).sum();
+
+        let change_output = bitcoin::TxOut {
+            value: 0,
+            script_pubkey: self.address.script_pubkey(),
+        };
+        let params = CoinSelectionParams {
+            target: amount,
+            fee_rate,
+            base_weight: signed_weight(&tx),
+            input_weight: P2TR_KEY_SPEND_INPUT_WEIGHT,
+            change_weight: (serialize(&change_output).len() * WITNESS_SCALE_FACTOR) as u64,
+            change_dust: change_output.script_pubkey.dust_value().to_sat(),
+        };
+        let amounts: Vec<u64> = candidate_utxos.iter().map(|utxo| utxo.amount).collect();
+        let selection = select_coins(fulfillment_fee, &amounts, &params).ok_or_else(|| {
             warn!(
-                "Consumed total {} is less than intended spend: {}",
-                total_consumed, op.amount
+                "Available utxos can not cover the spend of {} at {} sat/vB",
+                amount, fee_rate
             );
-            return Err(PegWalletError::from(Error::InsufficientFunds));
+            Error::InsufficientFunds
+        })?;
+        if selection.fee > fulfillment_fee {
+            return Err(PegWalletError::from(Error::FeeExceedsFulfillmentFee(
+                selection.fee,
+                fulfillment_fee,
+            )));
         }
 
-        // Get the transaction change amount
-        let change_amount = total_consumed - op.amount;
+        for index in selection.selected {
+            let utxo = &candidate_utxos[index];
+            tx.input.push(utxo_to_input(utxo, self.sequence())?);
+            prevouts.push(utxo_to_output(utxo)?);
+        }
+        if let Some(change_amount) = selection.change {
+            tx.output.push(bitcoin::TxOut {
+                value: change_amount,
+                ..change_output
+            });
+        } else {
+            debug!("Not enough change to clear dust limit. Not adding change address.");
+        }
         debug!(
-            "change_amount: {:?}, total_consumed: {:?}, op.amount: {:?}",
-            change_amount, total_consumed, op.amount
+            "fee: {}, change: {:?}, amount: {}",
+            selection.fee, selection.change, amount
         );
-        // Do not want to use Script::new_v1_p2tr because it will tweak our key when we don't want it to
-        let public_key_tweaked = TweakedPublicKey::dangerous_assume_tweaked(self.public_key);
-        let script_pubkey = Script::new_v1_p2tr_tweaked(public_key_tweaked);
 
-        tx.output.push(withdrawal_data_output());
+        Ok((tx, prevouts))
+    }
 
-        let withdrawal_output = bitcoin::TxOut {
-            value: op.amount,
-            script_pubkey: script_pubkey.clone(),
-        };
-        tx.output.push(withdrawal_output);
+    fn bump_fee(
+        &self,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+        fee_rate: u64,
+        max_fee: u64,
+    ) -> Result<Transaction, PegWalletError> {
+        let input_value: u64 = prevouts.iter().map(|prevout| prevout.value).sum();
+        let output_value: u64 = tx.output.iter().map(|output| output.value).sum();
+        let fee = input_value.saturating_sub(output_value);
+        let weight = signed_weight(tx);
+
+        // A replacement must pay for its own relay on top of the fee it replaces (BIP 125)
+        let bumped_fee =
+            fee_for_weight(weight, fee_rate).max(fee + fee_for_weight(weight, MIN_RELAY_FEE_RATE));
+        if bumped_fee > max_fee {
+            return Err(PegWalletError::from(Error::FeeExceedsFulfillmentFee(
+                bumped_fee, max_fee,
+            )));
+        }
 
+        // Change is always the last output, following the withdrawal data and at least one recipient
+        let script_pubkey = self.address.script_pubkey();
+        let change_index = tx.output.len().saturating_sub(1);
+        if change_index < 2 || tx.output[change_index].script_pubkey != script_pubkey {
+            return Err(PegWalletError::from(Error::InsufficientFunds));
+        }
+        let change_amount = tx.output[change_index]
+            .value
+            .checked_sub(bumped_fee - fee)
+            .ok_or(Error::InsufficientFunds)?;
+
+        let mut bumped_tx = tx.clone();
         if change_amount >= script_pubkey.dust_value().to_sat() {
-            let change_output = bitcoin::TxOut {
-                value: change_amount,
-                script_pubkey,
-            };
-            tx.output.push(change_output);
+            bumped_tx.output[change_index].value = change_amount;
         } else {
-            // Instead of leaving that change to the BTC miner, we could / should bump the sortition fee
-            debug!("Not enough change to clear dust limit. Not adding change address.");
+            debug!("Bumped change is below the dust limit. Removing change output.");
+            bumped_tx.output.remove(change_index);
         }
+        for input in bumped_tx.input.iter_mut() {
+            input.witness = Witness::default();
+        }
+        Ok(bumped_tx)
+    }
+
+    fn reveal_commit(&self, commit: &Commit, fee_rate: u64) -> Result<Transaction, PegWalletError> {
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: commit.outpoint,
+                script_sig: Default::default(),
+                sequence: self.sequence(),
+                witness: Default::default(),
+            }],
+            output: vec![
+                reveal_marker_output(self.network),
+                TxOut {
+                    value: 0,
+                    script_pubkey: self.address.script_pubkey(),
+                },
+            ],
+        };
+
+        // The witness holds the signature, then the reveal script and its control block
+        let mut signed_tx = tx.clone();
+        signed_tx.input[0].witness = Witness::from_vec(vec![
+            vec![0; 64],
+            commit.reveal_script.to_bytes(),
+            commit.control_block.serialize(),
+        ]);
+        let fee = fee_for_weight(signed_tx.weight() as u64, fee_rate);
+        let amount = commit.amount.saturating_sub(fee);
+        if amount < tx.output[1].script_pubkey.dust_value().to_sat() {
+            return Err(PegWalletError::from(Error::CommitBelowRevealFee(
+                commit.amount,
+                fee,
+            )));
+        }
+        tx.output[1].value = amount;
+        Ok(tx)
+    }
 
+    fn handoff(
+        &self,
+        new_public_key: &XOnlyPublicKey,
+        reward_cycle: u64,
+        utxos: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), PegWalletError> {
+        let new_wallet = self.with_public_key(*new_public_key)?;
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: WalletHandoff { reward_cycle }.to_op_return_script(self.network),
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: new_wallet.address.script_pubkey(),
+                },
+            ],
+        };
+        let mut prevouts = vec![];
+        for utxo in &utxos {
+            tx.input.push(utxo_to_input(utxo, self.sequence())?);
+            prevouts.push(utxo_to_output(utxo)?);
+        }
+
+        let amount: u64 = utxos.iter().map(|utxo| utxo.amount).sum();
+        let fee = fee_for_weight(signed_weight(&tx), fee_rate);
+        tx.output[1].value = amount
+            .checked_sub(fee)
+            .filter(|swept| *swept >= tx.output[1].script_pubkey.dust_value().to_sat())
+            .ok_or_else(|| {
+                warn!(
+                    "Peg wallet balance of {} can not pay a handoff fee of {}",
+                    amount, fee
+                );
+                Error::InsufficientFunds
+            })?;
         Ok((tx, prevouts))
     }
 
@@ -131,6 +366,12 @@ impl BitcoinWalletTrait for BitcoinWallet {
         &self.address
     }
 
+    fn merkle_root(&self) -> Option<TapBranchHash> {
+        self.spend_info
+            .as_ref()
+            .and_then(|spend_info| spend_info.merkle_root())
+    }
+
     fn x_only_pub_key(&self) -> &XOnlyPublicKey {
         &self.public_key
     }
@@ -154,8 +395,47 @@ fn withdrawal_data_output() -> TxOut {
     }
 }
 
+/// Script paying the recipient of a peg-out request. The request's recipient output
+/// was parsed into a `PoxAddress`, so the script is rebuilt from its hash bytes.
+fn recipient_script_pubkey(recipient: &PoxAddress) -> Script {
+    match recipient {
+        PoxAddress::Standard(address, hash_mode) => {
+            let is_p2pkh = match hash_mode {
+                Some(hash_mode) => *hash_mode == AddressHashMode::SerializeP2PKH,
+                None => {
+                    address.version == C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+                        || address.version == C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+                }
+            };
+            if is_p2pkh {
+                Script::new_p2pkh(&PubkeyHash::from_inner(address.bytes.0))
+            } else {
+                Script::new_p2sh(&ScriptHash::from_inner(address.bytes.0))
+            }
+        }
+        PoxAddress::Addr20(_, PoxAddressType20::P2WPKH, bytes) => {
+            Script::new_witness_program(WitnessVersion::V0, bytes)
+        }
+        PoxAddress::Addr32(_, PoxAddressType32::P2WSH, bytes) => {
+            Script::new_witness_program(WitnessVersion::V0, bytes)
+        }
+        PoxAddress::Addr32(_, PoxAddressType32::P2TR, bytes) => {
+            Script::new_witness_program(WitnessVersion::V1, bytes)
+        }
+    }
+}
+
+/// Weight of the transaction once every input carries a key path signature
+fn signed_weight(tx: &Transaction) -> u64 {
+    let mut signed_tx = tx.clone();
+    for input in signed_tx.input.iter_mut() {
+        input.witness = Witness::from_vec(vec![vec![0; 64]]);
+    }
+    signed_tx.weight() as u64
+}
+
 // Helper function to convert a utxo to an unsigned input
-fn utxo_to_input(utxo: &UTXO) -> Result<TxIn, Error> {
+fn utxo_to_input(utxo: &UTXO, sequence: Sequence) -> Result<TxIn, Error> {
     let input = TxIn {
         previous_output: OutPoint {
             txid: bitcoin::Txid::from_hex(&utxo.txid)
@@ -163,7 +443,7 @@ fn utxo_to_input(utxo: &UTXO) -> Result<TxIn, Error> {
             vout: utxo.vout,
         },
         script_sig: Default::default(),
-        sequence: bitcoin::Sequence(0xFFFFFFFD), // allow RBF
+        sequence,
         witness: Default::default(),
     };
     Ok(input)
@@ -183,13 +463,32 @@ fn utxo_to_output(utxo: &UTXO) -> Result<TxOut, Error> {
 
 #[cfg(test)]
 mod tests {
-    use super::{BitcoinWallet, Error};
+    use super::{recipient_script_pubkey, signed_weight, BitcoinWallet, Error};
     use crate::bitcoin_node::UTXO;
+    use crate::coin_selection::fee_for_weight;
+    use crate::commit_reveal::{reveal_marker_output, Commit};
     use crate::peg_wallet::{BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError};
+    use crate::stacks_node::PegOutRequestOp;
     use crate::util::test::{build_peg_out_request_op, PRIVATE_KEY_HEX};
-    use bitcoin::XOnlyPublicKey;
+    use bitcoin::{
+        hashes::Hash,
+        schnorr::TweakedPublicKey,
+        secp256k1::Secp256k1,
+        util::taproot::{LeafVersion, TapBranchHash, TapLeafHash, TaprootBuilder},
+        Address, Network, OutPoint, Script, Sequence, Transaction, TxOut, Witness, XOnlyPublicKey,
+    };
+    use blockstack_lib::{
+        address::{
+            AddressHashMode, C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+        },
+        chainstate::stacks::address::{PoxAddress, PoxAddressType20, PoxAddressType32},
+        types::chainstate::StacksAddress,
+        util::hash::Hash160,
+    };
     use hex::encode;
     use rand::Rng;
+    use sbtc_core::{Payload, WalletHandoff};
     use std::str::FromStr;
 
     /// Helper function to build a valid bitcoin wallet
@@ -221,6 +520,23 @@ mod tests {
         }
     }
 
+    /// Helper function for building the P2TR script of the recipient output of a generated op
+    fn recipient_script(op: &PegOutRequestOp) -> Script {
+        let PoxAddress::Addr32(_, PoxAddressType32::P2TR, recipient) = op.recipient else {
+            panic!("Expected a P2TR recipient, found {:?}", op.recipient);
+        };
+        // OP_1 OP_PUSHBYTES_32 <recipient>
+        let mut script_pubkey = vec![0x51, 0x20];
+        script_pubkey.extend_from_slice(&recipient);
+        Script::from(script_pubkey)
+    }
+
+    /// Helper function for computing the fee paid by a transaction spending the given prevouts
+    fn fee(tx: &Transaction, prevouts: &[TxOut]) -> u64 {
+        prevouts.iter().map(|prevout| prevout.value).sum::<u64>()
+            - tx.output.iter().map(|output| output.value).sum::<u64>()
+    }
+
     /// Helper function for building a vector of nmb utxos with amounts increasing by 10000
     fn build_utxos(nmb: u32) -> Vec<UTXO> {
         (1..=nmb)
@@ -236,12 +552,12 @@ mod tests {
         // (1+2+3+4+5)*10000 = 1500000 < 200000. Insufficient funds.
         let mut txouts = build_utxos(5);
 
-        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1000);
         // Build a fulfillment utxo that matches the generated op
-        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
+        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1000);
         txouts.push(fulfillment_utxo);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 1);
         assert!(result.is_err());
         assert_eq!(
             result.err().unwrap(),
@@ -253,39 +569,210 @@ mod tests {
     fn fulfill_peg_out_change() {
         let wallet = bitcoin_wallet();
         let amount = 200000;
+        let fee_rate = 1;
 
-        // (1+2+3+4+5)*10000 = 210000 > 200000. We have change of 10000
-        let mut txouts = build_utxos(6); // (1+2+3+4+5+6)*10000 = 210000
+        // The five largest utxos and the fulfillment utxo cover the amount and fee:
+        // 1000 + (2+3+4+5+6)*10000 = 201000 > 200000 + 490. We have change of 510
+        let mut txouts = build_utxos(6);
 
-        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1000);
         // Build a fulfillment utxo that matches the generated op
-        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
+        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1000);
         txouts.push(fulfillment_utxo);
 
-        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts).unwrap();
-        assert_eq!(btc_tx.input.len(), 7);
+        let (btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, fee_rate).unwrap();
+        assert_eq!(btc_tx.input.len(), 6);
+        assert_eq!(prevouts.len(), 6);
         assert_eq!(btc_tx.output.len(), 3); // We have change!
         assert_eq!(btc_tx.output[0].value, 0);
         assert_eq!(btc_tx.output[1].value, amount);
-        assert_eq!(btc_tx.output[2].value, 10000);
+        assert_eq!(btc_tx.output[1].script_pubkey, recipient_script(&op));
+        assert_eq!(btc_tx.output[2].value, 510);
+        assert_eq!(
+            btc_tx.output[2].script_pubkey,
+            wallet.address().script_pubkey()
+        );
+
+        // The fee covers the signed transaction at the requested fee rate
+        let fee = fee(&btc_tx, &prevouts);
+        assert_eq!(fee, 490);
+        assert!(fee >= (signed_weight(&btc_tx) + 3) / 4 * fee_rate);
+        assert!(btc_tx.is_explicitly_rbf());
     }
 
     #[test]
     fn fulfill_peg_out_no_change() {
         let wallet = bitcoin_wallet();
-        let amount = 9999;
+        let amount = 10000;
 
-        // 1*10000 = 10000 > 9999. We only have change of 1...not enough to cover dust
-        let mut txouts = build_utxos(1); // 1*10000 = 10000
+        // 300 + 10000 = 10300 > 10000 + 215. Change of 85 is not enough to cover dust
+        let mut txouts = build_utxos(1);
 
-        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 300);
         // Build a fulfillment utxo that matches the generated op
-        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 1);
+        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 300);
         txouts.push(fulfillment_utxo);
 
-        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts).unwrap();
+        let (btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, 1).unwrap();
         assert_eq!(btc_tx.input.len(), 2);
         assert_eq!(btc_tx.output.len(), 2); // No change!
+        assert_eq!(btc_tx.output[1].script_pubkey, recipient_script(&op));
+        assert_eq!(fee(&btc_tx, &prevouts), 300);
+    }
+
+    #[test]
+    fn fulfill_peg_out_fee_exceeds_fulfillment_fee() {
+        let wallet = bitcoin_wallet();
+        let amount = 200000;
+
+        let mut txouts = build_utxos(6);
+
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 100);
+        // Build a fulfillment utxo that matches the generated op
+        let fulfillment_utxo = build_utxo(op.txid.to_string(), 2, 100);
+        txouts.push(fulfillment_utxo);
+
+        let result = wallet.fulfill_peg_out(&op, txouts, 1);
+        assert!(matches!(
+            result,
+            Err(PegWalletError::BitcoinWalletError(
+                Error::FeeExceedsFulfillmentFee(_, 100)
+            ))
+        ));
+    }
+
+    #[test]
+    fn fulfill_peg_out_without_rbf() {
+        let mut wallet = bitcoin_wallet();
+        wallet.set_rbf(false);
+
+        let mut txouts = build_utxos(1);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, 9000, 1, 1000);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1000));
+
+        let (btc_tx, _) = wallet.fulfill_peg_out(&op, txouts, 1).unwrap();
+        assert!(!btc_tx.is_explicitly_rbf());
+        assert!(btc_tx
+            .input
+            .iter()
+            .all(|input| input.sequence == Sequence::MAX));
+    }
+
+    #[test]
+    fn fulfill_peg_outs_should_pay_every_recipient() {
+        let wallet = bitcoin_wallet();
+        let fee_rate = 2;
+
+        let mut txouts = build_utxos(6);
+        let ops = vec![
+            build_peg_out_request_op(PRIVATE_KEY_HEX, 50000, 1, 1000),
+            build_peg_out_request_op(PRIVATE_KEY_HEX, 60000, 1, 1000),
+        ];
+        for op in &ops {
+            txouts.push(build_utxo(op.txid.to_string(), 2, 1000));
+        }
+
+        let (btc_tx, prevouts) = wallet
+            .fulfill_peg_outs(&ops, txouts.clone(), fee_rate)
+            .unwrap();
+        // Both fulfillment utxos are spent first
+        for (input, op) in btc_tx.input.iter().zip(&ops) {
+            assert_eq!(input.previous_output.txid.to_string(), op.txid.to_string());
+            assert_eq!(input.previous_output.vout, 2);
+        }
+        assert_eq!(btc_tx.output.len(), 4);
+        for (output, op) in btc_tx.output[1..3].iter().zip(&ops) {
+            assert_eq!(output.value, op.amount);
+            assert_eq!(output.script_pubkey, recipient_script(op));
+        }
+        assert_eq!(
+            btc_tx.output[3].script_pubkey,
+            wallet.address().script_pubkey()
+        );
+
+        // A single fee covers the whole batch, paid out of the combined fulfillment fees
+        let fee = fee(&btc_tx, &prevouts);
+        assert!(fee <= 2000);
+        assert!(fee >= (signed_weight(&btc_tx) + 3) / 4 * fee_rate);
+
+        // Every op of the batch needs its fulfillment utxo
+        let other_op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10000, 1, 1000);
+        let result = wallet.fulfill_peg_outs(&[ops[0].clone(), other_op], txouts, fee_rate);
+        assert_eq!(
+            result.err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::MissingFulfillmentUTXO)
+        );
+    }
+
+    #[test]
+    fn bump_fee_should_pay_from_change() {
+        let wallet = bitcoin_wallet();
+        let amount = 190000;
+
+        let mut txouts = build_utxos(6);
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, amount, 1, 1000);
+        txouts.push(build_utxo(op.txid.to_string(), 2, 1000));
+
+        let (mut btc_tx, prevouts) = wallet.fulfill_peg_out(&op, txouts, 1).unwrap();
+        assert_eq!(btc_tx.output[2].value, 10510);
+        for input in btc_tx.input.iter_mut() {
+            input.witness = Witness::from_vec(vec![vec![1; 64]]);
+        }
+
+        // 488 vbytes at 5 sat/vB
+        let bumped_tx = wallet.bump_fee(&btc_tx, &prevouts, 5, 5000).unwrap();
+        assert_eq!(fee(&bumped_tx, &prevouts), 2440);
+        assert_eq!(bumped_tx.output[1], btc_tx.output[1]);
+        assert_eq!(bumped_tx.output[2].value, 10510 - (2440 - 490));
+        assert!(bumped_tx.input.iter().all(|input| input.witness.is_empty()));
+
+        let result = wallet.bump_fee(&btc_tx, &prevouts, 5, 1000);
+        assert_eq!(
+            result.err().unwrap(),
+            PegWalletError::BitcoinWalletError(Error::FeeExceedsFulfillmentFee(2440, 1000))
+        );
+    }
+
+    #[test]
+    fn recipient_script_pubkey_should_match_address_type() {
+        let hash = [7; 20];
+        let mut p2pkh = vec![0x76, 0xa9, 0x14];
+        p2pkh.extend_from_slice(&hash);
+        p2pkh.extend_from_slice(&[0x88, 0xac]);
+        let mut p2sh = vec![0xa9, 0x14];
+        p2sh.extend_from_slice(&hash);
+        p2sh.push(0x87);
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend_from_slice(&hash);
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend_from_slice(&[7; 32]);
+
+        let singlesig = StacksAddress::new(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, Hash160(hash));
+        let multisig = StacksAddress::new(C32_ADDRESS_VERSION_TESTNET_MULTISIG, Hash160(hash));
+
+        assert_eq!(
+            recipient_script_pubkey(&PoxAddress::Standard(singlesig, None)),
+            Script::from(p2pkh)
+        );
+        assert_eq!(
+            recipient_script_pubkey(&PoxAddress::Standard(multisig.clone(), None)),
+            Script::from(p2sh.clone())
+        );
+        assert_eq!(
+            recipient_script_pubkey(&PoxAddress::Standard(
+                multisig,
+                Some(AddressHashMode::SerializeP2WPKH)
+            )),
+            Script::from(p2sh)
+        );
+        assert_eq!(
+            recipient_script_pubkey(&PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, hash)),
+            Script::from(p2wpkh)
+        );
+        assert_eq!(
+            recipient_script_pubkey(&PoxAddress::Addr32(true, PoxAddressType32::P2WSH, [7; 32])),
+            Script::from(p2wsh)
+        );
     }
 
     #[test]
@@ -303,7 +790,7 @@ mod tests {
         txouts.push(fulfillment_utxo_invalid_vout);
         txouts.push(fulfillment_utxo_invalid_txid);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 1);
 
         assert!(result.is_err());
         assert_eq!(
@@ -324,7 +811,7 @@ mod tests {
         let fulfillment_utxo_invalid_amount = build_utxo(op.txid.to_string(), 2, 1);
         txouts.push(fulfillment_utxo_invalid_amount);
 
-        let result = wallet.fulfill_peg_out(&op, txouts);
+        let result = wallet.fulfill_peg_out(&op, txouts, 1);
 
         assert!(result.is_err());
         assert_eq!(
@@ -332,4 +819,126 @@ mod tests {
             PegWalletError::BitcoinWalletError(Error::MismatchedFulfillmentFee)
         );
     }
+
+    #[test]
+    fn address_should_commit_to_taproot_scripts() {
+        let mut wallet = bitcoin_wallet();
+        let public_key = *wallet.x_only_pub_key();
+        let secp = Secp256k1::verification_only();
+
+        // Without scripts the address pays to the untweaked key
+        assert_eq!(wallet.merkle_root(), None);
+        assert_eq!(
+            *wallet.address(),
+            Address::p2tr_tweaked(
+                TweakedPublicKey::dangerous_assume_tweaked(public_key),
+                Network::Testnet
+            )
+        );
+
+        // A single leaf is the root of its tree
+        let script = Script::from(vec![0x51]);
+        wallet.set_taproot_scripts(&[script.clone()]).unwrap();
+        let merkle_root = TapBranchHash::from_inner(
+            TapLeafHash::from_script(&script, LeafVersion::TapScript).into_inner(),
+        );
+        assert_eq!(wallet.merkle_root(), Some(merkle_root));
+        assert_eq!(
+            *wallet.address(),
+            Address::p2tr(&secp, public_key, Some(merkle_root), Network::Testnet)
+        );
+
+        // Dropping the scripts goes back to the untweaked address
+        wallet.set_taproot_scripts(&[]).unwrap();
+        assert_eq!(wallet.merkle_root(), None);
+        assert_eq!(*wallet.address(), bitcoin_wallet().address().clone());
+    }
+
+    #[test]
+    fn reveal_commit_should_pay_committed_amount_less_fee_to_wallet() {
+        let wallet = bitcoin_wallet();
+        let secp = Secp256k1::verification_only();
+        let reveal_script = Script::from(vec![0x51]);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())
+            .unwrap()
+            .finalize(&secp, *wallet.x_only_pub_key())
+            .unwrap_or_else(|_| panic!("Incomplete script tree"));
+        let commit = Commit {
+            outpoint: OutPoint::null(),
+            amount: 10_000,
+            address: Address::p2tr_tweaked(spend_info.output_key(), Network::Testnet),
+            reveal_script: reveal_script.clone(),
+            control_block: spend_info
+                .control_block(&(reveal_script, LeafVersion::TapScript))
+                .unwrap(),
+        };
+
+        let tx = wallet.reveal_commit(&commit, 2).unwrap();
+
+        assert_eq!(tx.input.len(), 1);
+        assert_eq!(tx.input[0].previous_output, commit.outpoint);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0], reveal_marker_output(Network::Testnet));
+        assert_eq!(tx.output[1].script_pubkey, wallet.address().script_pubkey());
+        let fee = commit.amount - tx.output[1].value;
+        assert!(fee > 0 && fee < 1_000);
+
+        let dust_commit = Commit {
+            amount: 300,
+            ..commit
+        };
+        assert!(matches!(
+            wallet.reveal_commit(&dust_commit, 2),
+            Err(PegWalletError::BitcoinWalletError(
+                Error::CommitBelowRevealFee(300, _)
+            ))
+        ));
+    }
+
+    #[test]
+    fn handoff_should_sweep_every_utxo_to_new_wallet() {
+        let mut wallet = bitcoin_wallet();
+        let recovery_script = Script::from(vec![0x51]);
+        wallet
+            .set_taproot_scripts(&[recovery_script.clone()])
+            .unwrap();
+        let new_public_key = XOnlyPublicKey::from_str(
+            "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+        )
+        .unwrap();
+        let utxos = build_utxos(3);
+
+        let (tx, prevouts) = wallet
+            .handoff(&new_public_key, 67, utxos.clone(), 2)
+            .unwrap();
+
+        assert_eq!(tx.input.len(), 3);
+        assert_eq!(prevouts.len(), 3);
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(
+            WalletHandoff::from_op_return_script(&tx.output[0].script_pubkey),
+            Ok((Network::Testnet, WalletHandoff { reward_cycle: 67 }))
+        );
+        // The new wallet commits to the same scripts as the old one
+        let mut new_wallet = BitcoinWallet::new(new_public_key, Network::Testnet);
+        new_wallet.set_taproot_scripts(&[recovery_script]).unwrap();
+        assert_eq!(
+            tx.output[1].script_pubkey,
+            new_wallet.address().script_pubkey()
+        );
+        assert_eq!(fee(&tx, &prevouts), fee_for_weight(signed_weight(&tx), 2));
+        assert_eq!(tx.output[1].value, 60000 - fee(&tx, &prevouts));
+
+        // Dust can not pay for its own sweep
+        assert_eq!(
+            wallet.handoff(
+                &new_public_key,
+                67,
+                vec![build_utxo(generate_txid(), 0, 500)],
+                2
+            ),
+            Err(PegWalletError::BitcoinWalletError(Error::InsufficientFunds))
+        );
+    }
 }
@@ -33,4 +33,34 @@ pub enum Command {
     Dkg,
     // Run distributed key generation round then sign a message
     DkgSign,
+    /// Sweep the peg wallet to the aggregate public key of the next signer set
+    Handoff {
+        /// Hex encoded x-only aggregate public key of the next signer set
+        #[arg(long)]
+        public_key: String,
+        /// Reward cycle the next signer set signs for
+        #[arg(long)]
+        reward_cycle: u64,
+    },
+    /// Queue a commit-reveal deposit to be revealed to the peg wallet
+    Commit {
+        /// Id of the transaction paying to the commit address
+        #[arg(long)]
+        txid: String,
+        /// Output of the commit transaction paying to the commit address
+        #[arg(long)]
+        vout: u32,
+        /// Committed amount in sats
+        #[arg(long)]
+        amount: u64,
+        /// Taproot address committing to the reveal script
+        #[arg(long)]
+        address: String,
+        /// Hex encoded reveal script
+        #[arg(long)]
+        reveal_script: String,
+        /// Hex encoded control block proving the reveal script is committed to by the address
+        #[arg(long)]
+        control_block: String,
+    },
 }
@@ -0,0 +1,225 @@
+//! Coin selection for transactions spending from the peg wallet.
+//!
+//! Selection first looks for a changeless set of coins with branch and bound, and falls back
+//! to spending the largest coins first when no such set exists.
+
+/// Number of branches explored before branch and bound gives up
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Shape of the transaction coins are selected for. All weights are in weight units.
+#[derive(Debug, Clone)]
+pub struct CoinSelectionParams {
+    /// Value of every output other than change
+    pub target: u64,
+    /// Fee rate in sat/vB
+    pub fee_rate: u64,
+    /// Weight of the transaction with its mandatory inputs and without a change output
+    pub base_weight: u64,
+    /// Weight added by each selected input
+    pub input_weight: u64,
+    /// Weight added by a change output
+    pub change_weight: u64,
+    /// Change below this value is left to the miner instead of creating an output
+    pub change_dust: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CoinSelection {
+    /// Indices into the candidate coins that should be spent
+    pub selected: Vec<usize>,
+    /// Value of the change output, if one is worth creating
+    pub change: Option<u64>,
+    /// Fee paid by the transaction
+    pub fee: u64,
+}
+
+/// Fee in sats for the given weight at a fee rate in sat/vB
+pub fn fee_for_weight(weight: u64, fee_rate: u64) -> u64 {
+    (weight + 3) / 4 * fee_rate
+}
+
+/// Select from `candidates` (coin values in sats) enough coins to pay `params.target` and the fee,
+/// on top of the `mandatory_value` already spent by the transaction's mandatory inputs.
+/// Returns `None` if the candidates can not cover the spend.
+pub fn select_coins(
+    mandatory_value: u64,
+    candidates: &[u64],
+    params: &CoinSelectionParams,
+) -> Option<CoinSelection> {
+    let input_fee = fee_for_weight(params.input_weight, params.fee_rate);
+    let change_fee = fee_for_weight(params.change_weight, params.fee_rate);
+    let needed = params
+        .target
+        .checked_add(fee_for_weight(params.base_weight, params.fee_rate))?;
+
+    // Coins that cost more to spend than they are worth are never selected
+    let mut pool: Vec<(usize, u64)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| {
+            value
+                .checked_sub(input_fee)
+                .filter(|effective_value| *effective_value > 0)
+                .map(|effective_value| (index, effective_value))
+        })
+        .collect();
+    pool.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    // Anything less than the cost of creating and later spending change is better left as fee
+    let cost_of_change = change_fee + input_fee;
+    let selected = branch_and_bound(&pool, mandatory_value, needed, needed + cost_of_change)
+        .or_else(|| largest_first(&pool, mandatory_value, needed, needed + change_fee))?;
+
+    let total = mandatory_value + selected.iter().map(|index| candidates[*index]).sum::<u64>();
+    let fee_without_change =
+        fee_for_weight(params.base_weight, params.fee_rate) + selected.len() as u64 * input_fee;
+    let change = total
+        .checked_sub(params.target + fee_without_change + change_fee)
+        .filter(|change| *change >= params.change_dust);
+    let fee = total - params.target - change.unwrap_or(0);
+
+    Some(CoinSelection {
+        selected,
+        change,
+        fee,
+    })
+}
+
+/// Depth first search for the set of coins whose effective value lands within
+/// `[lower, upper]` with the least excess
+fn branch_and_bound(
+    pool: &[(usize, u64)],
+    mandatory_value: u64,
+    lower: u64,
+    upper: u64,
+) -> Option<Vec<usize>> {
+    let mut search = BranchAndBound {
+        pool,
+        lower,
+        upper,
+        tries: BNB_MAX_TRIES,
+        selection: vec![],
+        best: None,
+    };
+    let available = pool.iter().map(|(_, value)| value).sum();
+    search.explore(0, mandatory_value, available);
+    search.best.map(|(_, selection)| selection)
+}
+
+struct BranchAndBound<'a> {
+    pool: &'a [(usize, u64)],
+    lower: u64,
+    upper: u64,
+    tries: usize,
+    selection: Vec<usize>,
+    best: Option<(u64, Vec<usize>)>,
+}
+
+impl BranchAndBound<'_> {
+    fn explore(&mut self, depth: usize, current: u64, available: u64) {
+        if self.tries == 0 || matches!(self.best, Some((0, _))) || current > self.upper {
+            return;
+        }
+        self.tries -= 1;
+
+        if current >= self.lower {
+            // Adding more coins can only increase the excess
+            let excess = current - self.lower;
+            if self.best.as_ref().map_or(true, |(best, _)| excess < *best) {
+                self.best = Some((excess, self.selection.clone()));
+            }
+            return;
+        }
+        if depth == self.pool.len() || current + available < self.lower {
+            return;
+        }
+
+        let (index, value) = self.pool[depth];
+        self.selection.push(index);
+        self.explore(depth + 1, current + value, available - value);
+        self.selection.pop();
+        self.explore(depth + 1, current, available - value);
+    }
+}
+
+/// Spend the largest coins until `target` is reached, or at least `lower` once coins run out
+fn largest_first(
+    pool: &[(usize, u64)],
+    mandatory_value: u64,
+    lower: u64,
+    target: u64,
+) -> Option<Vec<usize>> {
+    let mut current = mandatory_value;
+    let mut selection = vec![];
+    for (index, value) in pool {
+        if current >= target {
+            break;
+        }
+        current += value;
+        selection.push(*index);
+    }
+    (current >= lower).then_some(selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(target: u64) -> CoinSelectionParams {
+        CoinSelectionParams {
+            target,
+            fee_rate: 1,
+            base_weight: 400,
+            input_weight: 232,
+            change_weight: 172,
+            change_dust: 330,
+        }
+    }
+
+    #[test]
+    fn should_find_changeless_selection() {
+        // Base fee 100, input fee 58. Only 10100 + 20100 covers the target and fees
+        // without leaving enough excess to be worth a change output.
+        let candidates = [50_000, 10_100, 7_000, 20_100];
+        let selection = select_coins(0, &candidates, &params(29_984)).unwrap();
+
+        let mut selected = selection.selected.clone();
+        selected.sort();
+        assert_eq!(selected, vec![1, 3]);
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.fee, 10_100 + 20_100 - 29_984);
+    }
+
+    #[test]
+    fn should_fall_back_to_largest_first_with_change() {
+        let candidates = [10_000, 60_000, 20_000];
+        let selection = select_coins(0, &candidates, &params(50_000)).unwrap();
+
+        assert_eq!(selection.selected, vec![1]);
+        // Base fee 100, one input 58, change output 43
+        assert_eq!(selection.fee, 100 + 58 + 43);
+        assert_eq!(selection.change, Some(60_000 - 50_000 - selection.fee));
+    }
+
+    #[test]
+    fn should_count_mandatory_value() {
+        let selection = select_coins(100_000, &[10_000], &params(50_000)).unwrap();
+
+        assert!(selection.selected.is_empty());
+        assert_eq!(selection.fee, 100 + 43);
+        assert_eq!(selection.change, Some(100_000 - 50_000 - 143));
+    }
+
+    #[test]
+    fn should_skip_coins_not_worth_spending() {
+        let selection = select_coins(0, &[58, 20_000], &params(10_000)).unwrap();
+        assert_eq!(selection.selected, vec![1]);
+
+        assert!(select_coins(0, &[58, 58], &params(10)).is_none());
+    }
+
+    #[test]
+    fn should_fail_with_insufficient_funds() {
+        assert!(select_coins(1_000, &[10_000, 20_000], &params(31_000)).is_none());
+    }
+}
@@ -0,0 +1,692 @@
+//! Commit-reveal sBTC ops, for wallets that can not write an `OP_RETURN` output.
+//!
+//! A depositor commits funds to a taproot address whose script tree holds a reveal script
+//! `<data> OP_DROP <lock>`, where `data` carries the op that would otherwise be written to an
+//! `OP_RETURN` output. Spending the commit through that script reveals the op on chain, in a
+//! transaction whose first output only marks it as a reveal with the magic bytes and `w`.
+
+use std::convert::TryFrom;
+
+use bitcoin::{
+    blockdata::{opcodes, script::Instruction},
+    secp256k1::Secp256k1,
+    util::{
+        address::{Payload, WitnessVersion},
+        taproot::ControlBlock,
+    },
+    Address, Network, OutPoint, Script, Transaction, TxOut, XOnlyPublicKey,
+};
+use blockstack_lib::{
+    util::secp256k1::MessageSignature,
+    vm::{
+        types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
+        ContractName,
+    },
+};
+use sbtc_core::magic_bytes;
+
+/// Length of the op data of a reveal script, excluding the optional fee subsidy
+const REVEAL_DATA_LEN: usize = 78;
+
+/// Op byte of the `OP_RETURN` output marking a reveal transaction
+const REVEAL_OP: u8 = b'w';
+
+const PEG_IN_OP: u8 = b'<';
+const PEG_OUT_REQUEST_OP: u8 = b'>';
+
+const STANDARD_PRINCIPAL: u8 = 0x05;
+const CONTRACT_PRINCIPAL: u8 = 0x06;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Reveal transaction has no {0}")]
+    MissingOutput(&'static str),
+    #[error("Reveal transaction does not spend a commit through a script path")]
+    NotScriptPathSpend,
+    #[error("Invalid reveal script: {0}")]
+    InvalidRevealScript(String),
+    #[error("Invalid reveal data: {0}")]
+    InvalidRevealData(String),
+    #[error("Unknown reveal op: {0:#04x}")]
+    UnknownOp(u8),
+    #[error("Invalid contract name: {0}")]
+    InvalidContractName(String),
+    #[error("Commit address {0} is not a taproot address")]
+    NotTaprootAddress(Address),
+    #[error("Commit address does not commit to the reveal script")]
+    UncommittedRevealScript,
+    #[error("Reveal script does not lock the commit to the peg wallet")]
+    ForeignLockScript,
+    #[error("Commit reveals a peg-out request instead of a peg-in")]
+    NotPegIn,
+}
+
+/// Peg-in fields of the data in a reveal script
+#[derive(Debug, Clone, PartialEq)]
+pub struct PegInData {
+    /// Standard or contract principal receiving the minted sBTC
+    pub recipient: PrincipalData,
+    /// Whatever follows the recipient, without its zero padding
+    pub memo: Vec<u8>,
+    pub fee_subsidy: Option<u64>,
+}
+
+/// Peg-out request fields of the data in a reveal script
+#[derive(Debug, Clone, PartialEq)]
+pub struct PegOutRequestData {
+    pub amount: u64,
+    /// Recoverable signature over the amount and the recipient's script pubkey
+    pub signature: MessageSignature,
+    pub fee_subsidy: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevealData {
+    PegIn(PegInData),
+    PegOutRequest(PegOutRequestData),
+}
+
+/// A reveal script split into the op data it carries and the script locking the commit
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevealScript {
+    pub data: RevealData,
+    pub lock_script: Script,
+}
+
+/// An sBTC op revealed on chain, along with the outputs it is made of
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevealOp {
+    PegIn {
+        data: PegInData,
+        amount: u64,
+        peg_wallet: Script,
+    },
+    PegOutRequest {
+        data: PegOutRequestData,
+        recipient: Script,
+        dust_amount: u64,
+        fulfillment_fee: u64,
+        peg_wallet: Script,
+    },
+}
+
+/// Funds committed to a taproot address, along with what it takes to reveal them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub outpoint: OutPoint,
+    pub amount: u64,
+    pub address: Address,
+    pub reveal_script: Script,
+    /// Proof that the commit address commits to the reveal script
+    pub control_block: ControlBlock,
+}
+
+impl Commit {
+    /// The output being committed to
+    pub fn prevout(&self) -> TxOut {
+        TxOut {
+            value: self.amount,
+            script_pubkey: self.address.script_pubkey(),
+        }
+    }
+
+    /// Check that the commit can be revealed to the peg wallet of the given key,
+    /// returning the peg-in it carries
+    pub fn verify(&self, peg_wallet_key: &XOnlyPublicKey) -> Result<PegInData, Error> {
+        let output_key = match &self.address.payload {
+            Payload::WitnessProgram {
+                version: WitnessVersion::V1,
+                program,
+            } => XOnlyPublicKey::from_slice(program)
+                .map_err(|_| Error::NotTaprootAddress(self.address.clone()))?,
+            _ => return Err(Error::NotTaprootAddress(self.address.clone())),
+        };
+        let secp = Secp256k1::verification_only();
+        if !self
+            .control_block
+            .verify_taproot_commitment(&secp, &output_key, &self.reveal_script)
+        {
+            return Err(Error::UncommittedRevealScript);
+        }
+
+        let reveal_script = parse_reveal_script(&self.reveal_script)?;
+        if reveal_script.lock_script != reveal_lock_script(peg_wallet_key) {
+            return Err(Error::ForeignLockScript);
+        }
+        match reveal_script.data {
+            RevealData::PegIn(data) => Ok(data),
+            RevealData::PegOutRequest(_) => Err(Error::NotPegIn),
+        }
+    }
+}
+
+/// Output marking a transaction as a reveal
+pub fn reveal_marker_output(network: Network) -> TxOut {
+    let [first, second] = magic_bytes(network);
+    TxOut {
+        value: 0,
+        script_pubkey: bitcoin::blockdata::script::Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .push_slice(&[first, second, REVEAL_OP])
+            .into_script(),
+    }
+}
+
+/// Lock of a reveal script that the peg wallet of the given key can spend through FROST
+pub fn reveal_lock_script(peg_wallet_key: &XOnlyPublicKey) -> Script {
+    bitcoin::blockdata::script::Builder::new()
+        .push_slice(&peg_wallet_key.serialize())
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Parse the op revealed by a transaction spending a commit through its reveal script
+pub fn parse_reveal_tx(tx: &Transaction, network: Network) -> Result<RevealOp, Error> {
+    let marker = tx
+        .output
+        .first()
+        .ok_or(Error::MissingOutput("reveal marker output"))?;
+    if marker.script_pubkey != reveal_marker_output(network).script_pubkey {
+        return Err(Error::MissingOutput("reveal marker output"));
+    }
+
+    // A script path spend ends with the script and its control block, unless there is an annex
+    let witness = &tx.input.first().ok_or(Error::NotScriptPathSpend)?.witness;
+    let mut elements: Vec<&[u8]> = witness.iter().collect();
+    if elements.last().and_then(|last| last.first()) == Some(&0x50) {
+        elements.pop();
+    }
+    if elements.len() < 2 {
+        return Err(Error::NotScriptPathSpend);
+    }
+    let script = Script::from(elements[elements.len() - 2].to_vec());
+
+    let output =
+        |index: usize, name: &'static str| tx.output.get(index).ok_or(Error::MissingOutput(name));
+    Ok(match parse_reveal_script(&script)?.data {
+        RevealData::PegIn(data) => {
+            let peg_wallet_output = output(1, "peg wallet output")?;
+            RevealOp::PegIn {
+                data,
+                amount: peg_wallet_output.value,
+                peg_wallet: peg_wallet_output.script_pubkey.clone(),
+            }
+        }
+        RevealData::PegOutRequest(data) => {
+            let recipient_output = output(1, "recipient output")?;
+            let peg_wallet_output = output(2, "peg wallet output")?;
+            RevealOp::PegOutRequest {
+                data,
+                recipient: recipient_output.script_pubkey.clone(),
+                dust_amount: recipient_output.value,
+                fulfillment_fee: peg_wallet_output.value,
+                peg_wallet: peg_wallet_output.script_pubkey.clone(),
+            }
+        }
+    })
+}
+
+/// Split a reveal script `<data> OP_DROP <lock>` and parse the op data it carries
+pub fn parse_reveal_script(script: &Script) -> Result<RevealScript, Error> {
+    let mut instructions = script.instructions_minimal();
+    let data = match instructions.next() {
+        Some(Ok(Instruction::PushBytes(data))) => data,
+        _ => {
+            return Err(Error::InvalidRevealScript(
+                "does not start with a data push".to_string(),
+            ))
+        }
+    };
+    match instructions.next() {
+        Some(Ok(Instruction::Op(op))) if op == opcodes::all::OP_DROP => {}
+        _ => {
+            return Err(Error::InvalidRevealScript(
+                "data is not followed by OP_DROP".to_string(),
+            ))
+        }
+    }
+
+    // Pushes of more than 75 bytes need OP_PUSHDATA1 (0x4c) or wider length prefixes
+    let push_len = match data.len() {
+        len if len < 0x4c => 1,
+        len if len <= u8::MAX as usize => 2,
+        len if len <= u16::MAX as usize => 3,
+        _ => 5,
+    };
+    let lock_script = Script::from(script.as_bytes()[push_len + data.len() + 1..].to_vec());
+
+    Ok(RevealScript {
+        data: parse_reveal_data(data)?,
+        lock_script,
+    })
+}
+
+/// Parse the op data of a reveal script, which lacks the magic bytes of `OP_RETURN` data
+pub fn parse_reveal_data(data: &[u8]) -> Result<RevealData, Error> {
+    let (payload, fee_subsidy) = if data.len() > REVEAL_DATA_LEN {
+        let fee_subsidy = <[u8; 8]>::try_from(&data[REVEAL_DATA_LEN..]).map_err(|_| {
+            Error::InvalidRevealData(format!(
+                "{} bytes of fee subsidy",
+                data.len() - REVEAL_DATA_LEN
+            ))
+        })?;
+        (
+            &data[..REVEAL_DATA_LEN],
+            Some(u64::from_be_bytes(fee_subsidy)),
+        )
+    } else {
+        (data, None)
+    };
+
+    let (&op, payload) = payload
+        .split_first()
+        .ok_or_else(|| Error::InvalidRevealData("empty".to_string()))?;
+    match op {
+        PEG_IN_OP => parse_peg_in_data(payload, fee_subsidy).map(RevealData::PegIn),
+        PEG_OUT_REQUEST_OP => {
+            parse_peg_out_request_data(payload, fee_subsidy).map(RevealData::PegOutRequest)
+        }
+        op => Err(Error::UnknownOp(op)),
+    }
+}
+
+fn parse_peg_in_data(payload: &[u8], fee_subsidy: Option<u64>) -> Result<PegInData, Error> {
+    let mut reader = Reader(payload);
+    let principal_type = reader.take(1, "principal type")?[0];
+    let version = reader.take(1, "address version")?[0];
+    let hash_bytes =
+        <[u8; 20]>::try_from(reader.take(20, "address hash")?).expect("Took exactly 20 bytes");
+    let address = StandardPrincipalData(version, hash_bytes);
+
+    let recipient = match principal_type {
+        STANDARD_PRINCIPAL => PrincipalData::Standard(address),
+        CONTRACT_PRINCIPAL => {
+            let name_len = reader.take(1, "contract name length")?[0] as usize;
+            let name = String::from_utf8(reader.take(name_len, "contract name")?.to_vec())
+                .map_err(|e| Error::InvalidContractName(e.to_string()))?;
+            let contract_name = ContractName::try_from(name.clone())
+                .map_err(|_| Error::InvalidContractName(name))?;
+            PrincipalData::Contract(QualifiedContractIdentifier::new(address, contract_name))
+        }
+        other => {
+            return Err(Error::InvalidRevealData(format!(
+                "unknown principal type {:#04x}",
+                other
+            )))
+        }
+    };
+
+    let memo_len = reader
+        .0
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |last| last + 1);
+    Ok(PegInData {
+        recipient,
+        memo: reader.0[..memo_len].to_vec(),
+        fee_subsidy,
+    })
+}
+
+fn parse_peg_out_request_data(
+    payload: &[u8],
+    fee_subsidy: Option<u64>,
+) -> Result<PegOutRequestData, Error> {
+    let mut reader = Reader(payload);
+    let amount = u64::from_be_bytes(
+        <[u8; 8]>::try_from(reader.take(8, "amount")?).expect("Took exactly 8 bytes"),
+    );
+    // Stacks signatures lead with the recovery id, just like the reveal data
+    let signature = MessageSignature(
+        <[u8; 65]>::try_from(reader.take(65, "signature")?).expect("Took exactly 65 bytes"),
+    );
+    Ok(PegOutRequestData {
+        amount,
+        signature,
+        fee_subsidy,
+    })
+}
+
+/// Reads consecutive fields off the op data
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, field: &str) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::InvalidRevealData(format!("missing {}", field)));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test::PUBLIC_KEY_HEX;
+    use bitcoin::{
+        blockdata::script::Builder,
+        consensus::deserialize,
+        util::taproot::{LeafVersion, TaprootBuilder},
+        PackedLockTime, Sequence, TxIn, Witness,
+    };
+    use std::{iter::repeat, str::FromStr};
+    use test_vectors::{
+        peg_in::generate_peg_in_reveal_test_vector,
+        peg_out::generate_peg_out_request_reveal_test_vector, serialize_tx,
+    };
+
+    fn peg_in_data(contract_name: Option<&str>, memo: &[u8]) -> Vec<u8> {
+        let mut data = vec![PEG_IN_OP];
+        match contract_name {
+            Some(name) => {
+                data.extend([CONTRACT_PRINCIPAL, 26]);
+                data.extend([1; 20]);
+                data.push(name.len() as u8);
+                data.extend(name.as_bytes());
+            }
+            None => {
+                data.extend([STANDARD_PRINCIPAL, 26]);
+                data.extend([1; 20]);
+            }
+        }
+        data.extend(memo);
+        data.extend(repeat(0).take(REVEAL_DATA_LEN - data.len()));
+        data
+    }
+
+    fn reveal_script(data: &[u8], lock_script: &Script) -> Script {
+        let mut script = Builder::new()
+            .push_slice(data)
+            .push_opcode(opcodes::all::OP_DROP)
+            .into_script()
+            .to_bytes();
+        script.extend(lock_script.as_bytes());
+        Script::from(script)
+    }
+
+    /// Reveal transaction laid out like the test vectors, whose control block is a placeholder
+    fn reveal_tx(script: &Script, outputs: Vec<TxOut>) -> Transaction {
+        let mut output = vec![reveal_marker_output(Network::Regtest)];
+        output.extend(outputs);
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_vec(vec![script.to_bytes(), [60; 97].to_vec()]),
+            }],
+            output,
+        }
+    }
+
+    fn peg_wallet_key() -> XOnlyPublicKey {
+        XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap()
+    }
+
+    #[test]
+    fn should_parse_peg_in_reveal_to_contract_principal() {
+        let lock_script = reveal_lock_script(&peg_wallet_key());
+        let script = reveal_script(
+            &peg_in_data(Some("sbtc-receiver-contract"), b"memo"),
+            &lock_script,
+        );
+        let peg_wallet = Script::new_v1_p2tr_tweaked(
+            bitcoin::util::schnorr::TweakedPublicKey::dangerous_assume_tweaked(peg_wallet_key()),
+        );
+        let tx = reveal_tx(
+            &script,
+            vec![TxOut {
+                value: 1337,
+                script_pubkey: peg_wallet.clone(),
+            }],
+        );
+
+        let RevealOp::PegIn {
+            data,
+            amount,
+            peg_wallet: revealed_peg_wallet,
+        } = parse_reveal_tx(&tx, Network::Regtest).unwrap()
+        else {
+            panic!("Expected a peg-in reveal");
+        };
+        assert_eq!(amount, 1337);
+        assert_eq!(revealed_peg_wallet, peg_wallet);
+        assert_eq!(
+            data.recipient,
+            PrincipalData::Contract(QualifiedContractIdentifier::new(
+                StandardPrincipalData(26, [1; 20]),
+                ContractName::from("sbtc-receiver-contract"),
+            ))
+        );
+        assert_eq!(data.memo, b"memo".to_vec());
+        assert_eq!(data.fee_subsidy, None);
+        assert_eq!(
+            parse_reveal_script(&script).unwrap().lock_script,
+            lock_script
+        );
+    }
+
+    /// Reveal transaction of the shared test vectors, which are built with another version of
+    /// the bitcoin crate
+    fn decode_test_vector(tx_hex: String) -> Transaction {
+        deserialize(&hex::decode(tx_hex).unwrap()).unwrap()
+    }
+
+    /// Peg wallet output of the test vectors, a witness v1 program of `[4; 32]`
+    fn test_vector_peg_wallet() -> Script {
+        Builder::new()
+            .push_int(1)
+            .push_slice(&[4; 32])
+            .into_script()
+    }
+
+    #[test]
+    fn should_parse_peg_in_reveal_test_vector() {
+        let tx = decode_test_vector(serialize_tx(generate_peg_in_reveal_test_vector()));
+
+        let RevealOp::PegIn {
+            data,
+            amount,
+            peg_wallet,
+        } = parse_reveal_tx(&tx, Network::Regtest).unwrap()
+        else {
+            panic!("Expected a peg-in reveal");
+        };
+        assert_eq!(
+            data,
+            PegInData {
+                recipient: PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    StandardPrincipalData(26, [1; 20]),
+                    ContractName::from("sbtc-receiver-contract"),
+                )),
+                memo: vec![],
+                fee_subsidy: None,
+            }
+        );
+        assert_eq!(amount, 1337);
+        assert_eq!(peg_wallet, test_vector_peg_wallet());
+
+        // The data push needs OP_PUSHDATA1, which must not leak into the lock script
+        let witness_script = Script::from(tx.input[0].witness.to_vec()[0].clone());
+        assert_eq!(
+            parse_reveal_script(&witness_script).unwrap().lock_script,
+            Builder::new()
+                .push_opcode(opcodes::all::OP_DUP)
+                .push_opcode(opcodes::all::OP_HASH160)
+                .push_slice(&[4; 32])
+                .push_opcode(opcodes::all::OP_EQUAL)
+                .push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+                .into_script()
+        );
+    }
+
+    #[test]
+    fn should_parse_peg_out_request_reveal_test_vector() {
+        let tx = decode_test_vector(serialize_tx(generate_peg_out_request_reveal_test_vector()));
+
+        let RevealOp::PegOutRequest {
+            data,
+            recipient,
+            dust_amount,
+            fulfillment_fee,
+            peg_wallet,
+        } = parse_reveal_tx(&tx, Network::Regtest).unwrap()
+        else {
+            panic!("Expected a peg-out request reveal");
+        };
+        assert_eq!(data.amount, 1337);
+        assert_eq!(data.fee_subsidy, None);
+        assert_eq!(recipient, tx.output[1].script_pubkey);
+        assert_eq!(dust_amount, 21);
+        assert_eq!(fulfillment_fee, 42);
+        assert_eq!(peg_wallet, test_vector_peg_wallet());
+    }
+
+    #[test]
+    fn should_parse_peg_in_data_with_fee_subsidy() {
+        let mut data = peg_in_data(None, &[]);
+        data.extend(21_u64.to_be_bytes());
+
+        assert_eq!(
+            parse_reveal_data(&data).unwrap(),
+            RevealData::PegIn(PegInData {
+                recipient: PrincipalData::Standard(StandardPrincipalData(26, [1; 20])),
+                memo: vec![],
+                fee_subsidy: Some(21),
+            })
+        );
+        // A fee subsidy is a full u64
+        data.pop();
+        assert!(matches!(
+            parse_reveal_data(&data),
+            Err(Error::InvalidRevealData(_))
+        ));
+    }
+
+    #[test]
+    fn should_parse_peg_out_request_reveal() {
+        let mut data = vec![PEG_OUT_REQUEST_OP];
+        data.extend(1337_u64.to_be_bytes());
+        data.push(1);
+        data.extend([7; 64]);
+        data.extend(repeat(0).take(REVEAL_DATA_LEN - data.len()));
+        let recipient = Script::from(
+            vec![0x00, 0x14]
+                .into_iter()
+                .chain([5; 20])
+                .collect::<Vec<_>>(),
+        );
+        let tx = reveal_tx(
+            &reveal_script(&data, &reveal_lock_script(&peg_wallet_key())),
+            vec![
+                TxOut {
+                    value: 21,
+                    script_pubkey: recipient.clone(),
+                },
+                TxOut {
+                    value: 42,
+                    script_pubkey: Script::new(),
+                },
+            ],
+        );
+
+        let RevealOp::PegOutRequest {
+            data,
+            recipient: revealed_recipient,
+            dust_amount,
+            fulfillment_fee,
+            ..
+        } = parse_reveal_tx(&tx, Network::Regtest).unwrap()
+        else {
+            panic!("Expected a peg-out request reveal");
+        };
+        assert_eq!(data.amount, 1337);
+        assert_eq!(data.signature.0[0], 1);
+        assert_eq!(data.signature.0[1..], [7; 64]);
+        assert_eq!(revealed_recipient, recipient);
+        assert_eq!(dust_amount, 21);
+        assert_eq!(fulfillment_fee, 42);
+    }
+
+    #[test]
+    fn should_reject_malformed_reveals() {
+        let data = peg_in_data(None, &[]);
+        let lock_script = reveal_lock_script(&peg_wallet_key());
+
+        // The data must be dropped before the lock
+        let script = Builder::new().push_slice(&data).into_script();
+        assert!(matches!(
+            parse_reveal_script(&script),
+            Err(Error::InvalidRevealScript(_))
+        ));
+
+        // Reveals on other networks carry other magic bytes
+        let tx = reveal_tx(&reveal_script(&data, &lock_script), vec![]);
+        assert_eq!(
+            parse_reveal_tx(&tx, Network::Testnet),
+            Err(Error::MissingOutput("reveal marker output"))
+        );
+        assert_eq!(
+            parse_reveal_tx(&tx, Network::Regtest),
+            Err(Error::MissingOutput("peg wallet output"))
+        );
+
+        let mut data = data;
+        data[0] = b'?';
+        assert_eq!(parse_reveal_data(&data), Err(Error::UnknownOp(b'?')));
+    }
+
+    #[test]
+    fn commit_should_verify_against_peg_wallet_key() {
+        let secp = Secp256k1::verification_only();
+        let script = reveal_script(
+            &peg_in_data(None, &[]),
+            &reveal_lock_script(&peg_wallet_key()),
+        );
+        let reclaim_script = Builder::new()
+            .push_opcode(opcodes::all::OP_RETURN)
+            .into_script();
+        let internal_key = XOnlyPublicKey::from_str(
+            "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+        )
+        .unwrap();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, script.clone())
+            .unwrap()
+            .add_leaf(1, reclaim_script)
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap_or_else(|_| panic!("Incomplete script tree"));
+        let commit = Commit {
+            outpoint: OutPoint::null(),
+            amount: 10_000,
+            address: Address::p2tr_tweaked(spend_info.output_key(), Network::Regtest),
+            reveal_script: script.clone(),
+            control_block: spend_info
+                .control_block(&(script, LeafVersion::TapScript))
+                .unwrap(),
+        };
+
+        let data = commit.verify(&peg_wallet_key()).unwrap();
+        assert_eq!(
+            data.recipient,
+            PrincipalData::Standard(StandardPrincipalData(26, [1; 20]))
+        );
+        assert_eq!(commit.verify(&internal_key), Err(Error::ForeignLockScript));
+
+        // A reveal script outside of the commit's script tree can never be spent
+        let other_address = Address::p2tr(&secp, internal_key, None, Network::Regtest);
+        assert_eq!(
+            Commit {
+                address: other_address,
+                ..commit
+            }
+            .verify(&peg_wallet_key()),
+            Err(Error::UncommittedRevealScript)
+        );
+    }
+}
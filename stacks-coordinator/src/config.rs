@@ -1,3 +1,4 @@
+use bitcoin::Script;
 use blockstack_lib::{
     address::AddressHashMode,
     burnchains::Address,
@@ -5,13 +6,30 @@ use blockstack_lib::{
     types::chainstate::{StacksAddress, StacksPrivateKey, StacksPublicKey},
     vm::ContractName,
 };
+use frost_signer::scheme::WstsVersion;
+use std::time::Duration;
 use url::Url;
 
-use crate::util::address_version;
+use crate::{stacks_node::RequestPolicy, stacks_wallet::StacksFeePolicy, util::address_version};
 
 /// Default polling interval in seconds
 const DEFAULT_POLLING_INTERVAL: u64 = 5;
 
+/// Default number of burn block confirmations before acting on an sBTC op
+const DEFAULT_MIN_CONFIRMATIONS: u64 = 1;
+
+/// Default number of peg-out requests fulfilled by a single transaction
+const DEFAULT_PEG_OUT_BATCH_SIZE: usize = 1;
+
+/// Default number of burn blocks a peg-out request waits for its batch to fill up
+const DEFAULT_PEG_OUT_BATCH_MAX_AGE: u64 = 6;
+
+/// Default highest fee in microSTX paid by a Stacks transaction
+const DEFAULT_STACKS_MAX_FEE: u64 = 1_000_000;
+
+/// Default percentage by which replacements of stuck Stacks transactions raise their fee
+const DEFAULT_STACKS_FEE_BUMP_PERCENT: u64 = 25;
+
 /// Errors associated with reading the Config file
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -34,26 +52,85 @@ pub enum Network {
     Testnet,
 }
 
+/// Backend used to query the bitcoin network
+#[derive(serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BitcoinNodeType {
+    /// bitcoind JSON-RPC with the wallet RPCs enabled
+    #[default]
+    Bitcoind,
+    /// Esplora REST API
+    Esplora,
+    /// Electrum server over tcp:// or ssl://
+    Electrum,
+}
+
 #[derive(serde::Deserialize, Default)]
 pub struct RawConfig {
     pub sbtc_contract: String,
     pub stacks_private_key: String,
     pub stacks_node_rpc_url: String,
     pub bitcoin_node_rpc_url: String,
+    /// The kind of server at bitcoin_node_rpc_url ('bitcoind', 'esplora' or 'electrum'). Default: 'bitcoind'
+    pub bitcoin_node_type: Option<BitcoinNodeType>,
     pub frost_dkg_round_id: u64,
     pub signer_config_path: Option<String>,
     pub start_block_height: Option<u64>,
     pub data_directory: Option<String>,
     /// The network version we are using ('mainnet' or 'testnet'). Default: 'mainnet'
     pub network: Option<Network>,
-    /// The transaction fee in Satoshis used to broadcast transactions to the stacks node
+    /// The transaction fee in microSTX used when the stacks node can not estimate one
     pub transaction_fee: u64,
+    /// Lowest fee in microSTX paid by a Stacks transaction. Default: 0
+    pub stacks_min_fee: Option<u64>,
+    /// Highest fee in microSTX paid by a Stacks transaction, including replacements. Default: 1000000
+    pub stacks_max_fee: Option<u64>,
+    /// Percentage by which replacements of stuck Stacks transactions raise their fee. Default: 25
+    pub stacks_fee_bump_percent: Option<u64>,
     /// Frost specific config options. Must be specified if signer_config_path is not used
     pub http_relay_url: Option<String>,
     pub frost_state_file: Option<String>,
     pub network_private_key: Option<String>,
     /// Controls how many seconds to wait between polls
     pub polling_interval: Option<u64>,
+    /// Number of burn blocks, including its own, that must contain an sBTC op before it is processed
+    pub min_confirmations: Option<u64>,
+    /// Fee rate in sat/vB paid by fulfillment transactions. Estimated by the bitcoin node if not set
+    pub bitcoin_fee_rate: Option<u64>,
+    /// Whether fulfillment transactions signal replaceability so their fee can be bumped. Default: true
+    pub bitcoin_rbf: Option<bool>,
+    /// Max number of peg-out requests fulfilled by a single transaction. Default: 1 (no batching)
+    pub peg_out_batch_size: Option<usize>,
+    /// Burn blocks after which a partial batch of peg-out requests is fulfilled anyway. Default: 6
+    pub peg_out_batch_max_age: Option<u64>,
+    /// Hex encoded tapscripts the peg wallet address commits to, moving it to a tweaked address.
+    /// Default: none (the untweaked aggregate key)
+    pub taproot_scripts: Option<Vec<String>>,
+    /// Seconds a single stacks node request may take. Default: 30
+    pub stacks_node_request_timeout: Option<u64>,
+    /// Seconds spent retrying a stacks node that can not be reached. Default: 5
+    pub stacks_node_retry_timeout: Option<u64>,
+    /// Max number of burn blocks whose ops are fetched from the stacks node at once. Default: 16
+    pub stacks_node_max_concurrent_blocks: Option<usize>,
+    /// Seconds to wait for DKG public shares before dropping the signers that did not send theirs.
+    /// Only used if signer_config_path is not. Default: no deadline
+    pub dkg_public_timeout: Option<u64>,
+    /// Seconds to wait for signers to end DKG before dropping the rest. Only used if
+    /// signer_config_path is not. Default: no deadline
+    pub dkg_end_timeout: Option<u64>,
+    /// Seconds to wait for signature shares before signing again without the signers that did not
+    /// send theirs. Only used if signer_config_path is not. Default: 60
+    pub sign_timeout: Option<u64>,
+    /// WSTS signing scheme of the signer set, "v1" or "v2". Only used if signer_config_path is
+    /// not. Default: v1
+    pub wsts_version: Option<WstsVersion>,
+    /// Number of nonces each signer publishes ahead of signing, so signing takes a single round
+    /// trip. Only used if signer_config_path is not. Default: nonces are requested for every
+    /// signing session
+    pub nonce_batch_size: Option<u32>,
+    /// Seconds between refreshes of the signers' key shares, which keep the peg wallet key.
+    /// Default: never
+    pub key_refresh_interval: Option<u64>,
 }
 
 impl RawConfig {
@@ -98,6 +175,50 @@ impl RawConfig {
         Ok((sender_key, address))
     }
 
+    pub fn parse_taproot_scripts(&self) -> Result<Vec<Script>, Error> {
+        self.taproot_scripts
+            .iter()
+            .flatten()
+            .map(|script| {
+                hex::decode(script).map(Script::from).map_err(|e| {
+                    Error::InvalidConfig(format!("Invalid taproot script {}: {}", script, e))
+                })
+            })
+            .collect()
+    }
+
+    pub fn parse_stacks_fee_policy(&self) -> Result<StacksFeePolicy, Error> {
+        let fee_policy = StacksFeePolicy {
+            fallback_fee: self.transaction_fee,
+            min_fee: self.stacks_min_fee.unwrap_or(0),
+            max_fee: self.stacks_max_fee.unwrap_or(DEFAULT_STACKS_MAX_FEE),
+            bump_percent: self
+                .stacks_fee_bump_percent
+                .unwrap_or(DEFAULT_STACKS_FEE_BUMP_PERCENT),
+        };
+        if fee_policy.min_fee > fee_policy.max_fee {
+            return Err(Error::InvalidConfig(
+                "stacks_min_fee must not exceed stacks_max_fee.".to_string(),
+            ));
+        }
+        Ok(fee_policy)
+    }
+
+    pub fn parse_request_policy(&self) -> RequestPolicy {
+        let default = RequestPolicy::default();
+        RequestPolicy {
+            request_timeout: self
+                .stacks_node_request_timeout
+                .map_or(default.request_timeout, Duration::from_secs),
+            retry_timeout: self
+                .stacks_node_retry_timeout
+                .map_or(default.retry_timeout, Duration::from_secs),
+            max_concurrent_blocks: self
+                .stacks_node_max_concurrent_blocks
+                .unwrap_or(default.max_concurrent_blocks),
+        }
+    }
+
     pub fn parse_version(&self) -> (TransactionVersion, bitcoin::Network) {
         // Determine what network we are running on
         match self.network.as_ref().unwrap_or(&Network::Mainnet) {
@@ -114,24 +235,71 @@ pub struct Config {
     pub stacks_address: StacksAddress,
     pub stacks_node_rpc_url: Url,
     pub bitcoin_node_rpc_url: Url,
+    /// The kind of server at bitcoin_node_rpc_url
+    pub bitcoin_node_type: BitcoinNodeType,
     pub frost_dkg_round_id: u64,
     pub signer_config_path: Option<String>,
     pub start_block_height: Option<u64>,
     pub data_directory: Option<String>,
     pub bitcoin_network: bitcoin::Network,
     pub stacks_version: TransactionVersion,
-    /// The transaction fee in Satoshis used to broadcast transactions to the stacks node
-    pub transaction_fee: u64,
+    /// How fees of Stacks transactions are picked
+    pub stacks_fee_policy: StacksFeePolicy,
     /// Frost specific config options. Must be specified if signer_config_path is not used
     pub http_relay_url: Option<String>,
     pub network_private_key: Option<String>,
     /// Controls how many seconds to wait between polls
     pub polling_interval: u64,
+    /// Number of burn blocks, including its own, that must contain an sBTC op before it is processed
+    pub min_confirmations: u64,
+    /// Fee rate in sat/vB paid by fulfillment transactions. Estimated by the bitcoin node if not set
+    pub bitcoin_fee_rate: Option<u64>,
+    /// Whether fulfillment transactions signal replaceability so their fee can be bumped
+    pub bitcoin_rbf: bool,
+    /// Max number of peg-out requests fulfilled by a single transaction
+    pub peg_out_batch_size: usize,
+    /// Burn blocks after which a partial batch of peg-out requests is fulfilled anyway
+    pub peg_out_batch_max_age: u64,
+    /// Tapscripts the peg wallet address commits to
+    pub taproot_scripts: Vec<Script>,
+    /// How requests to the stacks node are timed out and retried
+    pub stacks_node_request_policy: RequestPolicy,
+    /// How long each phase of a DKG round waits for signers before dropping non-responsive ones
+    pub dkg_public_timeout: Option<Duration>,
+    pub dkg_end_timeout: Option<Duration>,
+    /// How long signers get to send signature shares before signing is retried without them
+    pub sign_timeout: Option<Duration>,
+    /// Signing scheme shared with the signers
+    pub wsts_version: WstsVersion,
+    /// How many nonces signers publish ahead of signing, if they do
+    pub nonce_batch_size: Option<u32>,
+    /// How often the signers' key shares are refreshed, if ever
+    pub key_refresh_interval: Option<Duration>,
 }
 
 impl TryFrom<RawConfig> for Config {
     type Error = Error;
     fn try_from(config: RawConfig) -> Result<Self, Error> {
+        if config.min_confirmations == Some(0) {
+            return Err(Error::InvalidConfig(
+                "min_confirmations must be at least 1.".to_string(),
+            ));
+        }
+        if config.bitcoin_fee_rate == Some(0) {
+            return Err(Error::InvalidConfig(
+                "bitcoin_fee_rate must be at least 1 sat/vB.".to_string(),
+            ));
+        }
+        if config.peg_out_batch_size == Some(0) {
+            return Err(Error::InvalidConfig(
+                "peg_out_batch_size must be at least 1.".to_string(),
+            ));
+        }
+        if config.stacks_node_max_concurrent_blocks == Some(0) {
+            return Err(Error::InvalidConfig(
+                "stacks_node_max_concurrent_blocks must be at least 1.".to_string(),
+            ));
+        }
         if config.signer_config_path.is_none() {
             if config.http_relay_url.is_none() {
                 return Err(Error::InvalidConfig(
@@ -154,6 +322,9 @@ impl TryFrom<RawConfig> for Config {
         let (contract_name, contract_address) = config.parse_contract()?;
         let (stacks_version, bitcoin_network) = config.parse_version();
         let (stacks_private_key, stacks_address) = config.parse_stacks_private_key()?;
+        let taproot_scripts = config.parse_taproot_scripts()?;
+        let stacks_node_request_policy = config.parse_request_policy();
+        let stacks_fee_policy = config.parse_stacks_fee_policy()?;
 
         Ok(Self {
             contract_name,
@@ -165,16 +336,36 @@ impl TryFrom<RawConfig> for Config {
             bitcoin_node_rpc_url: Url::parse(&config.bitcoin_node_rpc_url).map_err(|e| {
                 Error::InvalidConfig(format!("Invalid bitcoin_node_rpc_url: {}", e))
             })?,
+            bitcoin_node_type: config.bitcoin_node_type.unwrap_or_default(),
             frost_dkg_round_id: config.frost_dkg_round_id,
             signer_config_path: config.signer_config_path,
             start_block_height: config.start_block_height,
             data_directory: config.data_directory,
             bitcoin_network,
             stacks_version,
-            transaction_fee: config.transaction_fee,
+            stacks_fee_policy,
             http_relay_url: config.http_relay_url,
             network_private_key: config.network_private_key,
             polling_interval: config.polling_interval.unwrap_or(DEFAULT_POLLING_INTERVAL),
+            min_confirmations: config
+                .min_confirmations
+                .unwrap_or(DEFAULT_MIN_CONFIRMATIONS),
+            bitcoin_fee_rate: config.bitcoin_fee_rate,
+            bitcoin_rbf: config.bitcoin_rbf.unwrap_or(true),
+            peg_out_batch_size: config
+                .peg_out_batch_size
+                .unwrap_or(DEFAULT_PEG_OUT_BATCH_SIZE),
+            peg_out_batch_max_age: config
+                .peg_out_batch_max_age
+                .unwrap_or(DEFAULT_PEG_OUT_BATCH_MAX_AGE),
+            taproot_scripts,
+            stacks_node_request_policy,
+            dkg_public_timeout: config.dkg_public_timeout.map(Duration::from_secs),
+            dkg_end_timeout: config.dkg_end_timeout.map(Duration::from_secs),
+            sign_timeout: config.sign_timeout.map(Duration::from_secs),
+            wsts_version: config.wsts_version.unwrap_or_default(),
+            nonce_batch_size: config.nonce_batch_size.filter(|size| *size > 0),
+            key_refresh_interval: config.key_refresh_interval.map(Duration::from_secs),
         })
     }
 }
@@ -258,6 +449,14 @@ transaction_fee = 2000
         assert_eq!(config.contract_name.to_string(), sbtc_contract_name);
         assert_eq!(config.contract_address.to_string(), sbtc_contract_address);
         assert_eq!(config.stacks_private_key.to_hex(), stacks_private_key);
+        assert_eq!(config.min_confirmations, DEFAULT_MIN_CONFIRMATIONS);
+        assert_eq!(config.bitcoin_fee_rate, None);
+        assert!(config.bitcoin_rbf);
+        assert_eq!(config.peg_out_batch_size, DEFAULT_PEG_OUT_BATCH_SIZE);
+        assert_eq!(config.peg_out_batch_max_age, DEFAULT_PEG_OUT_BATCH_MAX_AGE);
+        assert!(config.taproot_scripts.is_empty());
+        assert_eq!(config.bitcoin_node_type, BitcoinNodeType::Bitcoind);
+        assert_eq!(config.stacks_node_request_policy, RequestPolicy::default());
 
         // Test config with no signer_config_file
         let config = write_new_config(
@@ -420,6 +619,62 @@ transaction_fee = 2000
         assert_eq!(stacks_version, TransactionVersion::Mainnet);
         assert_eq!(bitcoin_network, BitcoinNetwork::Bitcoin);
     }
+    #[test]
+    fn parse_stacks_fee_policy_test() {
+        let mut config = RawConfig {
+            transaction_fee: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.parse_stacks_fee_policy().unwrap(),
+            StacksFeePolicy {
+                fallback_fee: 10,
+                min_fee: 0,
+                max_fee: DEFAULT_STACKS_MAX_FEE,
+                bump_percent: DEFAULT_STACKS_FEE_BUMP_PERCENT,
+            }
+        );
+
+        config.stacks_min_fee = Some(500);
+        config.stacks_max_fee = Some(100);
+        assert!(matches!(
+            config.parse_stacks_fee_policy(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn parse_request_policy_test() {
+        let mut config = RawConfig::default();
+        assert_eq!(config.parse_request_policy(), RequestPolicy::default());
+
+        config.stacks_node_request_timeout = Some(10);
+        config.stacks_node_max_concurrent_blocks = Some(4);
+        let policy = config.parse_request_policy();
+        assert_eq!(policy.request_timeout, Duration::from_secs(10));
+        assert_eq!(policy.retry_timeout, RequestPolicy::default().retry_timeout);
+        assert_eq!(policy.max_concurrent_blocks, 4);
+    }
+
+    #[test]
+    fn parse_taproot_scripts_test() {
+        let mut config = RawConfig::default();
+        assert!(config.parse_taproot_scripts().unwrap().is_empty());
+
+        // OP_TRUE
+        config.taproot_scripts = Some(vec!["51".to_string()]);
+        assert_eq!(
+            config.parse_taproot_scripts().unwrap(),
+            vec![Script::from(vec![0x51])]
+        );
+
+        config.taproot_scripts = Some(vec!["not hex".to_string()]);
+        assert!(matches!(
+            config.parse_taproot_scripts(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
     #[test]
     fn parse_contract_test() {
         let mut config = RawConfig::default();
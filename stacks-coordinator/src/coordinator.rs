@@ -1,35 +1,50 @@
 use bitcoin::{
+    hashes::Hash,
     psbt::Prevouts,
     util::{
         base58,
         sighash::{Error as SighashError, SighashCache},
+        taproot::{LeafVersion, TapLeafHash},
     },
-    SchnorrSighashType, XOnlyPublicKey,
+    SchnorrSighashType, TxOut, Witness, XOnlyPublicKey,
+};
+use blockstack_lib::{
+    burnchains::Txid,
+    chainstate::stacks::StacksTransaction,
+    types::chainstate::{BurnchainHeaderHash, StacksAddress},
+    util::secp256k1::Secp256k1PublicKey,
 };
-use blockstack_lib::{types::chainstate::StacksAddress, util::secp256k1::Secp256k1PublicKey};
 use frost_coordinator::{
     coordinator::Error as FrostCoordinatorError, create_coordinator, create_coordinator_from_path,
 };
 use frost_signer::{
-    config::Config as SignerConfig,
+    config::{Config as SignerConfig, PublicKeys},
     net::{Error as HttpNetError, HttpNetListen},
+    scheme::WstsVersion,
     signing_round::DkgPublicShare,
 };
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
-    fs::File,
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
     path::{Path, PathBuf},
+    str::FromStr,
     sync::mpsc::RecvError,
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tracing::{debug, info, warn};
-use wsts::{bip340::SchnorrProof, common::Signature, field::Element, Point, Scalar};
+use tracing::{debug, error, info, warn};
+use wsts::{bip340::SchnorrProof, common::Signature, ecdsa, field::Element, Point, Scalar};
 
 use crate::bitcoin_wallet::BitcoinWallet;
+use crate::commit_reveal::{Commit, Error as CommitRevealError};
+use crate::nonce_manager::{Error as NonceManagerError, NonceManager, SqliteNonceManager};
 use crate::stacks_node::{self, Error as StacksNodeError};
 use crate::stacks_wallet::StacksWallet;
-use crate::{config::Config, stacks_node::client::BroadcastError};
+use crate::{
+    config::{BitcoinNodeType, Config},
+    stacks_node::client::BroadcastError,
+};
 use crate::{
     peg_wallet::{
         BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError, PegWallet,
@@ -40,24 +55,32 @@ use crate::{
 
 // Traits in scope
 use crate::bitcoin_node::{
-    BitcoinNode, BitcoinTransaction, Error as BitcoinNodeError, LocalhostBitcoinNode,
+    electrum::ElectrumBitcoinNode, esplora::EsploraBitcoinNode, BitcoinNode, BitcoinTransaction,
+    Error as BitcoinNodeError, LocalhostBitcoinNode,
 };
 use crate::peg_queue::{
-    Error as PegQueueError, PegQueue, SbtcOp, SqlitePegQueue, SqlitePegQueueError,
+    Entry as PegQueueEntry, Error as PegQueueError, Handoff, HandoffStatus, PegQueue, SbtcOp,
+    SqlitePegQueue, SqlitePegQueueError, Status as PegQueueStatus,
 };
-use crate::stacks_node::{client::NodeClient, StacksNode};
+use crate::stacks_node::{client::NodeClient, PoxInfo, SignerSet, StacksNode, TransactionStatus};
 
 type FrostCoordinator = frost_coordinator::coordinator::Coordinator<HttpNetListen>;
 
 /// Helper that uses this module's error type
 pub type Result<T> = std::result::Result<T, Error>;
 
-// The max number of nonce retries we should attempt before erroring out
+// The max number of nonces tried for a transaction before erroring out
 const MAX_NONCE_RETRIES: u64 = 10;
 
 // The max number of retries for invalid fee's we should attempt before erroring out
 const MAX_FEE_RETRIES: u64 = 2;
 
+// The number of blocks fulfillment transactions should confirm within
+const FEE_ESTIMATE_CONF_TARGET: u16 = 3;
+
+// The fee rate in sat/vB used when the bitcoin node can not estimate one
+const FALLBACK_FEE_RATE: u64 = 1;
+
 /// Kinds of common errors used by stacks coordinator
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -80,8 +103,13 @@ pub enum Error {
     UnexpectedSenderDisconnect(#[from] RecvError),
     #[error("Stacks Node Error: {0}")]
     StacksNodeError(#[from] StacksNodeError),
+    /// Error occurred in the Nonce Manager
+    #[error("Nonce Manager Error: {0}")]
+    NonceManagerError(#[from] NonceManagerError),
     #[error("Bitcoin Node Error: {0}")]
     BitcoinNodeError(#[from] BitcoinNodeError),
+    #[error("Commit Reveal Error: {0}")]
+    CommitRevealError(#[from] CommitRevealError),
     #[error("{0}")]
     ConfigError(String),
     #[error("Invalid bitcoin wallet public key: {0}")]
@@ -96,6 +124,14 @@ pub enum Error {
     MaxNonceRetriesExceeded,
     #[error("Point error: {0}")]
     PointError(String),
+    #[error("Peg queue entry {0} in status {1:?} is missing its {2}")]
+    IncompletePegQueueEntry(Txid, PegQueueStatus, &'static str),
+    #[error("Handoff of reward cycle {0} in status {1:?} is missing its {2}")]
+    IncompleteHandoff(u64, HandoffStatus, &'static str),
+    #[error("Handoff of reward cycle {0} to {1} is still in progress")]
+    HandoffInProgress(u64, XOnlyPublicKey),
+    #[error("Handoff of reward cycle {0} failed and needs manual intervention")]
+    HandoffFailed(u64),
 }
 
 pub trait Coordinator: Sized {
@@ -103,6 +139,7 @@ pub trait Coordinator: Sized {
     type FeeWallet: PegWallet;
     type StacksNode: StacksNode;
     type BitcoinNode: BitcoinNode;
+    type NonceManager: NonceManager;
 
     // Required methods
     fn peg_queue(&self) -> &Self::PegQueue;
@@ -111,21 +148,62 @@ pub trait Coordinator: Sized {
     fn frost_coordinator(&self) -> &FrostCoordinator;
     fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator;
     fn stacks_node(&self) -> &Self::StacksNode;
-    fn stacks_node_mut(&mut self) -> &mut Self::StacksNode;
     fn bitcoin_node(&self) -> &Self::BitcoinNode;
+    fn nonce_manager(&self) -> &Self::NonceManager;
+    /// Number of blocks a fulfillment must be buried under before its op is acknowledged
+    fn min_confirmations(&self) -> u64;
+    /// Fixed fee rate in sat/vB for fulfillments, if the bitcoin node should not estimate it
+    fn bitcoin_fee_rate(&self) -> Option<u64>;
+    /// Max number of peg-out requests fulfilled by a single transaction
+    fn peg_out_batch_size(&self) -> usize;
+    /// Burn blocks after which a partial batch of peg-out requests is fulfilled anyway
+    fn peg_out_batch_max_age(&self) -> u64;
+    /// Follow the signer set of the sBTC contract into a new reward cycle. A changed set runs
+    /// DKG, then the peg wallet is handed off to its key.
+    fn rotate_signer_set(&mut self) -> Result<()>;
+    /// Refresh the signers' key shares if the refresh interval passed. The aggregate key, and so
+    /// the peg wallet, stays the same.
+    fn refresh_keys(&mut self) -> Result<()>;
+    /// Top up the nonces signers publish ahead of signing, if configured to
+    fn preprocess_nonces(&mut self) -> Result<()>;
+    /// Switch over to the wallet of a new aggregate public key once the sBTC contract holds it
+    fn complete_handoff(
+        &mut self,
+        new_public_key: &XOnlyPublicKey,
+        reward_cycle: u64,
+    ) -> Result<()>;
 
     // Provided methods
     fn run(mut self, polling_interval: u64) -> Result<()> {
+        self.resume_unfinished_ops()?;
         loop {
             info!("Polling for withdrawal and deposit requests to process...");
-            self.peg_queue().poll(self.stacks_node())?;
+            self.peg_queue()
+                .poll(self.stacks_node(), self.bitcoin_node())?;
+            self.nonce_manager().reconcile(self.stacks_node())?;
+            // Dropped transactions reclaim their nonces before new ops are handed any
+            self.monitor_transactions()?;
+            // A handoff waiting on its sweep is retried next pass rather than stopping the loop
+            if let Err(e) = self.follow_up_handoff() {
+                warn!("Failed to follow up on the wallet handoff: {}", e);
+            }
+            // A rotation that failed part way resumes from its persisted progress next pass
+            if let Err(e) = self.rotate_signer_set() {
+                warn!("Failed to rotate the signer set: {}", e);
+            }
+            self.refresh_keys()?;
+            self.reveal_commits()?;
             self.process_queue()?;
+            self.preprocess_nonces()?;
 
             sleep(Duration::from_secs(polling_interval));
         }
     }
 
     fn process_queue(&mut self) -> Result<()> {
+        if self.peg_out_batch_size() > 1 {
+            return self.process_queue_batched();
+        }
         loop {
             match self.peg_queue().sbtc_op()? {
                 Some(SbtcOp::PegIn(op)) => {
@@ -140,13 +218,58 @@ pub trait Coordinator: Sized {
             }
         }
     }
+
+    /// Hand the peg wallet over to the aggregate public key of a new signer set that has run DKG.
+    /// Every utxo of the current wallet is swept to the new one in a transaction the current
+    /// signers sign through FROST. The sBTC contract is only pointed at the new key once the sweep
+    /// has `min_confirmations`, which the running coordinator follows up on every pass. Each step
+    /// is persisted, so an interrupted handoff resumes instead of sweeping again.
+    fn handoff(&mut self, new_public_key: &XOnlyPublicKey, reward_cycle: u64) -> Result<()> {
+        match self.peg_queue().handoff()? {
+            Some(handoff) if handoff.public_key == *new_public_key => {
+                if handoff.status == HandoffStatus::Failed {
+                    return Err(Error::HandoffFailed(handoff.reward_cycle));
+                }
+                info!(
+                    "Resuming handoff to {} from status {:?}",
+                    new_public_key, handoff.status
+                );
+            }
+            Some(handoff) if handoff.status != HandoffStatus::Failed => {
+                return Err(Error::HandoffInProgress(
+                    handoff.reward_cycle,
+                    handoff.public_key,
+                ));
+            }
+            _ => self.sweep_peg_wallet(new_public_key, reward_cycle)?,
+        }
+        self.follow_up_handoff()
+    }
+
+    /// Process peg-ins one by one, then fulfill peg-out requests in batches
+    fn process_queue_batched(&mut self) -> Result<()> {
+        while let Some(op) = self.peg_queue().peg_in_op()? {
+            debug!("Processing peg in request: {:?}", op);
+            self.peg_in(op)?;
+        }
+        loop {
+            let ops = self
+                .peg_queue()
+                .peg_out_request_batch(self.peg_out_batch_size(), self.peg_out_batch_max_age())?;
+            if ops.is_empty() {
+                return Ok(());
+            }
+            debug!("Processing batch of {} peg out requests", ops.len());
+            self.peg_out_batch(ops)?;
+        }
+    }
 }
 
 // Private helper functions
 trait CoordinatorHelpers: Coordinator {
     fn peg_in(&mut self, op: stacks_node::PegInOp) -> Result<()> {
         // Build a transaction from the peg in op and broadcast it to the node with reattempts
-        self.try_broadcast_transaction(&op)
+        self.try_broadcast_transaction(&op, &op.txid, &op.burn_header_hash)
     }
 
     fn peg_out(&mut self, op: stacks_node::PegOutRequestOp) -> Result<()> {
@@ -154,79 +277,895 @@ trait CoordinatorHelpers: Coordinator {
         // This ensures that if either of the transactions fail to build, neither of them will be broadcast
 
         // Build and sign a fulfilled bitcoin transaction
-        let fulfill_tx = self.fulfill_peg_out(&op)?;
+        let (fulfill_tx, prevouts) = self.fulfill_peg_out(&op)?;
+        self.peg_queue().record_fulfillment_tx(
+            &op.txid,
+            &op.burn_header_hash,
+            &fulfill_tx,
+            &prevouts,
+        )?;
 
-        // Build a transaction from the peg out request op and broadcast it to the node with reattempts
-        self.try_broadcast_transaction(&op)?;
+        self.complete_peg_out(&op, &fulfill_tx)
+    }
+
+    /// Fulfill several peg-out requests with a single transaction, burning the sBTC of each.
+    /// The fulfillment goes out before the burns, so a burn failing part way through the batch
+    /// leaves no burned request without its BTC. Requests not burned yet are resumed like any
+    /// other signed peg-out.
+    fn peg_out_batch(&mut self, ops: Vec<stacks_node::PegOutRequestOp>) -> Result<()> {
+        let (fulfill_tx, prevouts) = self.fulfill_peg_outs(&ops)?;
+        for op in &ops {
+            self.peg_queue().record_fulfillment_tx(
+                &op.txid,
+                &op.burn_header_hash,
+                &fulfill_tx,
+                &prevouts,
+            )?;
+        }
 
-        // Broadcast the BTC transaction to the Bitcoin node
         self.bitcoin_node().broadcast_transaction(&fulfill_tx)?;
         info!(
-            "Broadcasted fulfilled BTC transaction: {}",
-            fulfill_tx.txid()
+            "Broadcasted fulfilled BTC transaction {} for {} peg out requests",
+            fulfill_tx.txid(),
+            ops.len()
+        );
+
+        for op in &ops {
+            self.try_broadcast_transaction(op, &op.txid, &op.burn_header_hash)?;
+            self.peg_queue()
+                .record_bitcoin_broadcast(&op.txid, &op.burn_header_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Burn the sBTC of a peg-out request whose fulfillment is already signed, then broadcast the fulfillment
+    fn complete_peg_out(
+        &mut self,
+        op: &stacks_node::PegOutRequestOp,
+        fulfill_tx: &BitcoinTransaction,
+    ) -> Result<()> {
+        // Build a transaction from the peg out request op and broadcast it to the node with reattempts
+        self.try_broadcast_transaction(op, &op.txid, &op.burn_header_hash)?;
+
+        self.broadcast_fulfillment(op, fulfill_tx)
+    }
+
+    fn broadcast_fulfillment(
+        &mut self,
+        op: &stacks_node::PegOutRequestOp,
+        fulfill_tx: &BitcoinTransaction,
+    ) -> Result<()> {
+        self.peg_queue()
+            .record_bitcoin_broadcast(&op.txid, &op.burn_header_hash)?;
+
+        // Broadcast the BTC transaction to the Bitcoin node. A batched fulfillment is shared
+        // with other requests, which may have broadcast it already
+        match self.bitcoin_node().broadcast_transaction(fulfill_tx) {
+            Ok(_) => info!(
+                "Broadcasted fulfilled BTC transaction: {}",
+                fulfill_tx.txid()
+            ),
+            Err(BitcoinNodeError::RPCError(message)) if is_already_broadcast(&message) => {
+                info!(
+                    "Fulfilled BTC transaction {} was already broadcast: {}",
+                    fulfill_tx.txid(),
+                    message
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Reveal every pending commit whose funds confirmed, moving them into the peg wallet with
+    /// a single FROST signing round. The revealed peg-ins then come back from the stacks node.
+    /// Commits that can never be revealed are rejected.
+    fn reveal_commits(&mut self) -> Result<()> {
+        let peg_wallet_key = *self.fee_wallet().bitcoin().x_only_pub_key();
+        let mut reveals = vec![];
+        for commit in self.peg_queue().pending_commits()? {
+            if let Err(e) = commit.verify(&peg_wallet_key) {
+                warn!("Rejecting commit {}: {}", commit.outpoint, e);
+                self.peg_queue().reject_commit(&commit.outpoint)?;
+                continue;
+            }
+            let confirmed = self
+                .bitcoin_node()
+                .list_unspent(&commit.address)?
+                .iter()
+                .any(|utxo| {
+                    utxo.txid == commit.outpoint.txid.to_string()
+                        && utxo.vout == commit.outpoint.vout
+                        && utxo.amount == commit.amount
+                        && utxo.confirmations > 0
+                });
+            if !confirmed {
+                debug!("Commit {} is not confirmed yet", commit.outpoint);
+                continue;
+            }
+
+            let fee_rate = self.fee_rate()?;
+            match self.fee_wallet().bitcoin().reveal_commit(&commit, fee_rate) {
+                Ok(tx) => reveals.push((commit, tx)),
+                Err(e) => {
+                    warn!("Rejecting commit {}: {}", commit.outpoint, e);
+                    self.peg_queue().reject_commit(&commit.outpoint)?;
+                }
+            }
+        }
+        if reveals.is_empty() {
+            return Ok(());
+        }
+
+        let tapscript_sighashes = reveals
+            .iter()
+            .map(|(commit, tx)| {
+                let prevouts = [commit.prevout()];
+                SighashCache::new(tx)
+                    .taproot_script_spend_signature_hash(
+                        0,
+                        &Prevouts::All(&prevouts),
+                        TapLeafHash::from_script(&commit.reveal_script, LeafVersion::TapScript),
+                        SchnorrSighashType::Default,
+                    )
+                    .map(|sighash| sighash.as_hash().to_vec())
+                    .map_err(Error::SigningError)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let signatures = self
+            .frost_coordinator_mut()
+            .sign_tapscript_messages(&tapscript_sighashes)?;
+
+        for ((commit, mut tx), (_frost_sig, schnorr_proof)) in reveals.into_iter().zip(signatures) {
+            tx.input[0].witness = Witness::from_vec(vec![
+                schnorr_proof.to_bytes(),
+                commit.reveal_script.to_bytes(),
+                commit.control_block.serialize(),
+            ]);
+            match self.bitcoin_node().broadcast_transaction(&tx) {
+                Ok(_) => info!(
+                    "Revealed commit {} to the peg wallet: {}",
+                    commit.outpoint,
+                    tx.txid()
+                ),
+                Err(BitcoinNodeError::RPCError(message)) if is_already_broadcast(&message) => {
+                    info!(
+                        "Commit {} was already revealed or spent: {}",
+                        commit.outpoint, message
+                    );
+                }
+                Err(BitcoinNodeError::RPCError(message)) => {
+                    warn!(
+                        "Reveal {} of commit {} was rejected: {}",
+                        tx.txid(),
+                        commit.outpoint,
+                        message
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            }
+            self.peg_queue().record_reveal(&commit.outpoint, &tx)?;
+        }
+        Ok(())
+    }
+
+    /// Sign a transaction sweeping every utxo of the peg wallet to a new key, and broadcast it
+    /// once it is persisted
+    fn sweep_peg_wallet(
+        &mut self,
+        new_public_key: &XOnlyPublicKey,
+        reward_cycle: u64,
+    ) -> Result<()> {
+        let mut handoff = Handoff {
+            reward_cycle,
+            public_key: *new_public_key,
+            status: HandoffStatus::SweepBroadcast,
+            sweep_tx: None,
+            stacks_tx: None,
+        };
+        let utxos = self
+            .bitcoin_node()
+            .list_unspent(self.fee_wallet().bitcoin().address())?;
+        if utxos.is_empty() {
+            info!("Peg wallet holds no utxos to sweep");
+            self.peg_queue().record_handoff(&handoff)?;
+            return Ok(());
+        }
+
+        let fee_rate = self.fee_rate()?;
+        let (tx, prevouts) =
+            self.fee_wallet()
+                .bitcoin()
+                .handoff(new_public_key, reward_cycle, utxos, fee_rate)?;
+        handoff.sweep_tx = Some(self.sign_fulfillment(tx, &prevouts)?);
+        handoff.status = HandoffStatus::Signed;
+        self.peg_queue().record_handoff(&handoff)?;
+        self.broadcast_sweep(handoff)
+    }
+
+    /// Broadcast the signed sweep of a handoff, tolerating the Bitcoin node already knowing it
+    fn broadcast_sweep(&mut self, mut handoff: Handoff) -> Result<()> {
+        let tx = handoff.sweep_tx.as_ref().ok_or(Error::IncompleteHandoff(
+            handoff.reward_cycle,
+            handoff.status,
+            "sweep transaction",
+        ))?;
+        match self.bitcoin_node().broadcast_transaction(tx) {
+            Ok(_) => info!(
+                "Broadcasted handoff BTC transaction {} sweeping {} utxos to {}",
+                tx.txid(),
+                tx.input.len(),
+                handoff.public_key
+            ),
+            Err(BitcoinNodeError::RPCError(message)) if is_already_broadcast(&message) => {
+                info!(
+                    "Handoff BTC transaction {} was already broadcast: {}",
+                    tx.txid(),
+                    message
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+        handoff.status = HandoffStatus::SweepBroadcast;
+        self.peg_queue().record_handoff(&handoff)?;
+        Ok(())
+    }
+
+    /// Take an unfinished handoff one step further. The new key is published once the sweep has
+    /// `min_confirmations`, and the coordinator switches wallets once the sBTC contract holds it.
+    fn follow_up_handoff(&mut self) -> Result<()> {
+        let Some(mut handoff) = self.peg_queue().handoff()? else {
+            return Ok(());
+        };
+        match handoff.status {
+            HandoffStatus::Signed => self.broadcast_sweep(handoff),
+            HandoffStatus::SweepBroadcast => {
+                if let Some(sweep_tx) = &handoff.sweep_tx {
+                    let sweep_txid = sweep_tx.txid();
+                    match self.bitcoin_node().transaction_confirmations(&sweep_txid)? {
+                        Some(confirmations) if confirmations < 0 => {
+                            warn!(
+                                "Handoff BTC transaction {} conflicts with a confirmed transaction, sweeping again...",
+                                sweep_txid
+                            );
+                            return self
+                                .sweep_peg_wallet(&handoff.public_key, handoff.reward_cycle);
+                        }
+                        Some(confirmations) if confirmations as u64 >= self.min_confirmations() => {
+                        }
+                        Some(_) => {
+                            debug!("Handoff BTC transaction {} is not final yet", sweep_txid);
+                            return Ok(());
+                        }
+                        None => {
+                            warn!(
+                                "Handoff BTC transaction {} is unknown to the bitcoin node",
+                                sweep_txid
+                            );
+                            return self.broadcast_sweep(handoff);
+                        }
+                    }
+                }
+                self.publish_handoff_key(handoff)
+            }
+            HandoffStatus::KeyBroadcast => {
+                let published_key = self
+                    .stacks_node()
+                    .bitcoin_wallet_public_key(self.fee_wallet().stacks().address())?;
+                if published_key == Some(handoff.public_key) {
+                    handoff.status = HandoffStatus::Complete;
+                    self.peg_queue().record_handoff(&handoff)?;
+                    return self.complete_handoff(&handoff.public_key, handoff.reward_cycle);
+                }
+                let stacks_txid = handoff
+                    .stacks_tx
+                    .as_ref()
+                    .ok_or(Error::IncompleteHandoff(
+                        handoff.reward_cycle,
+                        handoff.status,
+                        "Stacks transaction",
+                    ))?
+                    .txid();
+                match self.stacks_node().transaction_status(&stacks_txid)? {
+                    TransactionStatus::Aborted(reason) => {
+                        error!(
+                            "sBTC transaction {} setting the bitcoin wallet public key aborted ({}). Manual intervention required!",
+                            stacks_txid, reason
+                        );
+                        handoff.status = HandoffStatus::Failed;
+                        self.peg_queue().record_handoff(&handoff)?;
+                        Ok(())
+                    }
+                    TransactionStatus::Dropped(reason) => {
+                        warn!(
+                            "sBTC transaction {} setting the bitcoin wallet public key was dropped ({})",
+                            stacks_txid, reason
+                        );
+                        self.publish_handoff_key(handoff)
+                    }
+                    status => {
+                        debug!(
+                            "Waiting on sBTC transaction {} setting the bitcoin wallet public key: {:?}",
+                            stacks_txid, status
+                        );
+                        Ok(())
+                    }
+                }
+            }
+            HandoffStatus::Complete | HandoffStatus::Failed => Ok(()),
+        }
+    }
+
+    /// Point the sBTC contract at the new key of a handoff. Publishing the same key twice is
+    /// harmless, so the transaction is only persisted once the stacks node accepted it.
+    fn publish_handoff_key(&mut self, mut handoff: Handoff) -> Result<()> {
+        let nonce = self.nonce_manager().next_nonce()?;
+        let tx = match self
+            .fee_wallet()
+            .stacks()
+            .build_set_bitcoin_wallet_public_key_transaction(
+                &handoff.public_key,
+                nonce,
+                self.stacks_node(),
+            ) {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.nonce_manager().release(nonce)?;
+                return Err(e.into());
+            }
+        };
+        if let Err(e) = self.stacks_node().broadcast_transaction(&tx) {
+            self.nonce_manager().release(nonce)?;
+            return Err(e.into());
+        }
+        self.nonce_manager()
+            .record_broadcast(nonce, Some(&tx.txid()))?;
+        info!(
+            "Broadcasted sBTC transaction {} setting the bitcoin wallet public key",
+            tx.txid()
         );
+        handoff.status = HandoffStatus::KeyBroadcast;
+        handoff.stacks_tx = Some(tx);
+        self.peg_queue().record_handoff(&handoff)?;
+        Ok(())
+    }
+
+    /// Pick up every op whose processing was interrupted, continuing from the last persisted step
+    fn resume_unfinished_ops(&mut self) -> Result<()> {
+        for entry in self.peg_queue().unfinished_entries()? {
+            info!(
+                "Resuming sBTC op {} from status {:?}",
+                entry.txid, entry.status
+            );
+            match (&entry.op, entry.status) {
+                (SbtcOp::PegIn(op), PegQueueStatus::Pending) => self.peg_in(op.clone())?,
+                (SbtcOp::PegIn(op), PegQueueStatus::StacksBroadcast) => {
+                    self.rebroadcast_stacks_transaction(
+                        op,
+                        &op.txid,
+                        &op.burn_header_hash,
+                        stacks_tx(&entry)?,
+                    )?;
+                }
+                (SbtcOp::PegOutRequest(op), PegQueueStatus::Pending) => self.peg_out(op.clone())?,
+                (SbtcOp::PegOutRequest(op), PegQueueStatus::Signed) => {
+                    self.complete_peg_out(op, fulfillment_tx(&entry)?)?;
+                }
+                (SbtcOp::PegOutRequest(op), PegQueueStatus::StacksBroadcast) => {
+                    let fulfill_tx = fulfillment_tx(&entry)?;
+                    self.rebroadcast_stacks_transaction(
+                        op,
+                        &op.txid,
+                        &op.burn_header_hash,
+                        stacks_tx(&entry)?,
+                    )?;
+                    self.broadcast_fulfillment(op, fulfill_tx)?;
+                }
+                (SbtcOp::PegOutRequest(_), PegQueueStatus::BitcoinBroadcast) => {
+                    self.rebroadcast_fulfillment(fulfillment_tx(&entry)?)?;
+                }
+                (_, status) => {
+                    warn!(
+                        "Not resuming sBTC op {} in unexpected status {:?}",
+                        entry.txid, status
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow up on ops whose transactions have all been broadcast. An op is acknowledged once its
+    /// Stacks transaction succeeded and its fulfillment, if any, has `min_confirmations`.
+    /// Dropped transactions are broadcast again under their nonce, while aborted or double spent
+    /// ones fail the op.
+    /// Unconfirmed Stacks transactions and fulfillments paying less than the current fee estimate
+    /// get their fee bumped.
+    fn monitor_transactions(&mut self) -> Result<()> {
+        // Requests batched into one fulfillment only follow it up once per pass
+        let mut followed_up_fulfillments = HashSet::new();
+        for entry in self.peg_queue().broadcast_entries()? {
+            let stacks_txid = stacks_tx(&entry)?.txid();
+            let stacks_tx_final = match self.stacks_node().transaction_status(&stacks_txid)? {
+                TransactionStatus::Success => true,
+                TransactionStatus::Pending => {
+                    self.bump_stacks_fee(&entry)?;
+                    false
+                }
+                TransactionStatus::NotFound => {
+                    debug!("sBTC transaction {} is not indexed yet", stacks_txid);
+                    false
+                }
+                TransactionStatus::Aborted(reason) => {
+                    error!(
+                        "sBTC transaction {} for op {} aborted ({}). Manual intervention required!",
+                        stacks_txid, entry.txid, reason
+                    );
+                    self.peg_queue()
+                        .fail(&entry.txid, &entry.burn_header_hash)?;
+                    continue;
+                }
+                TransactionStatus::Dropped(reason) => {
+                    warn!(
+                        "sBTC transaction {} for op {} was dropped ({})",
+                        stacks_txid, entry.txid, reason
+                    );
+                    self.recover_dropped_stacks_transaction(&entry)?;
+                    continue;
+                }
+            };
+
+            let fulfillment_final = match &entry.op {
+                SbtcOp::PegIn(_) => true,
+                SbtcOp::PegOutRequest(_) => {
+                    let fulfill_tx = fulfillment_tx(&entry)?;
+                    let fulfill_txid = fulfill_tx.txid();
+                    match self
+                        .bitcoin_node()
+                        .transaction_confirmations(&fulfill_txid)?
+                    {
+                        Some(confirmations) if confirmations < 0 => {
+                            error!(
+                                "Fulfilled BTC transaction {} for op {} conflicts with a confirmed transaction. Manual intervention required!",
+                                fulfill_txid, entry.txid
+                            );
+                            self.peg_queue()
+                                .fail(&entry.txid, &entry.burn_header_hash)?;
+                            continue;
+                        }
+                        Some(0) => {
+                            if followed_up_fulfillments.insert(fulfill_txid) {
+                                self.bump_fulfillment_fee(&entry)?;
+                            }
+                            false
+                        }
+                        Some(confirmations) => confirmations as u64 >= self.min_confirmations(),
+                        None => {
+                            warn!(
+                                "Fulfilled BTC transaction {} is unknown to the bitcoin node",
+                                fulfill_txid
+                            );
+                            if followed_up_fulfillments.insert(fulfill_txid) {
+                                self.rebroadcast_fulfillment(fulfill_tx)?;
+                            }
+                            false
+                        }
+                    }
+                }
+            };
+
+            if stacks_tx_final && fulfillment_final {
+                self.peg_queue()
+                    .acknowledge(&entry.txid, &entry.burn_header_hash)?;
+                info!("Acknowledged sBTC op {}", entry.txid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow up on a Stacks transaction the node dropped from its mempool. While its nonce is not
+    /// consumed, no version of the transaction was mined, so it is rebuilt under the same nonce and
+    /// at most one version can ever be mined. Otherwise the dropped transaction is broadcast again
+    /// as is, and the op fails if its nonce went to another transaction.
+    fn recover_dropped_stacks_transaction(&mut self, entry: &PegQueueEntry) -> Result<()> {
+        let tx = stacks_tx(entry)?;
+        let nonce = tx.get_origin_nonce();
+        let confirmed_nonce = self
+            .stacks_node()
+            .account_nonces(self.fee_wallet().stacks().address())?
+            .confirmed_nonce;
+        let tx = if confirmed_nonce <= nonce {
+            let tx = match &entry.op {
+                SbtcOp::PegIn(op) => {
+                    self.fee_wallet()
+                        .stacks()
+                        .build_transaction(op, nonce, self.stacks_node())?
+                }
+                SbtcOp::PegOutRequest(op) => {
+                    self.fee_wallet()
+                        .stacks()
+                        .build_transaction(op, nonce, self.stacks_node())?
+                }
+            };
+            self.peg_queue()
+                .record_stacks_tx(&entry.txid, &entry.burn_header_hash, &tx)?;
+            if entry.status == PegQueueStatus::BitcoinBroadcast {
+                // The fulfillment is already out, so only the burn is rebuilt
+                self.peg_queue()
+                    .record_bitcoin_broadcast(&entry.txid, &entry.burn_header_hash)?;
+            }
+            tx
+        } else {
+            tx.clone()
+        };
+
+        match self.stacks_node().broadcast_transaction(&tx) {
+            Ok(()) => {
+                self.nonce_manager()
+                    .record_broadcast(nonce, Some(&tx.txid()))?;
+                info!(
+                    "Rebroadcasted dropped sBTC transaction {} with nonce {}",
+                    tx.txid(),
+                    nonce
+                );
+            }
+            Err(StacksNodeError::BroadcastError(BroadcastError::BadNonce)) => {
+                error!(
+                    "Nonce {} of dropped sBTC transaction {} for op {} was consumed by another transaction. Manual intervention required!",
+                    nonce,
+                    tx.txid(),
+                    entry.txid
+                );
+                self.peg_queue()
+                    .fail(&entry.txid, &entry.burn_header_hash)?;
+            }
+            // Followed up again next pass
+            Err(e) => warn!(
+                "Failed to rebroadcast dropped sBTC transaction {}: {}",
+                tx.txid(),
+                e
+            ),
+        }
         Ok(())
     }
 
-    fn fulfill_peg_out(&mut self, op: &stacks_node::PegOutRequestOp) -> Result<BitcoinTransaction> {
+    /// Replace the Stacks transaction of an entry waiting in the mempool if it pays less than
+    /// the fee the stacks node currently estimates
+    fn bump_stacks_fee(&mut self, entry: &PegQueueEntry) -> Result<()> {
+        let tx = stacks_tx(entry)?;
+        let replacement = match &entry.op {
+            SbtcOp::PegIn(op) => self
+                .fee_wallet()
+                .stacks()
+                .bump_fee(op, tx, self.stacks_node())?,
+            SbtcOp::PegOutRequest(op) => {
+                self.fee_wallet()
+                    .stacks()
+                    .bump_fee(op, tx, self.stacks_node())?
+            }
+        };
+        let Some(replacement) = replacement else {
+            return Ok(());
+        };
+
+        match self.stacks_node().broadcast_transaction(&replacement) {
+            Ok(()) => {
+                info!(
+                    "Replaced sBTC transaction {} with {} paying {} microSTX",
+                    tx.txid(),
+                    replacement.txid(),
+                    replacement.get_tx_fee()
+                );
+                self.peg_queue().record_stacks_tx(
+                    &entry.txid,
+                    &entry.burn_header_hash,
+                    &replacement,
+                )?;
+                if entry.status == PegQueueStatus::BitcoinBroadcast {
+                    // Recording the replacement must not undo the fulfillment's broadcast
+                    self.peg_queue()
+                        .record_bitcoin_broadcast(&entry.txid, &entry.burn_header_hash)?;
+                }
+                self.nonce_manager()
+                    .record_broadcast(replacement.get_origin_nonce(), Some(&replacement.txid()))?;
+            }
+            // The original may have been mined in the meantime, which is followed up next pass
+            Err(e) => warn!("Failed to replace sBTC transaction {}: {}", tx.txid(), e),
+        }
+        Ok(())
+    }
+
+    /// Broadcast a persisted Stacks transaction again. If the node rejects it, it is rebuilt
+    /// unless the node already knows the transaction itself. A rejected nonce alone may be held
+    /// by a different transaction.
+    fn rebroadcast_stacks_transaction<T: BuildStacksTransaction>(
+        &mut self,
+        op: &T,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &StacksTransaction,
+    ) -> Result<()> {
+        match self.stacks_node().broadcast_transaction(tx) {
+            Ok(()) => {
+                self.nonce_manager()
+                    .record_broadcast(tx.get_origin_nonce(), Some(&tx.txid()))?;
+                info!("Rebroadcasted sBTC transaction: {}", tx.txid());
+                Ok(())
+            }
+            Err(StacksNodeError::BroadcastError(
+                BroadcastError::ConflictingNonceInMempool | BroadcastError::BadNonce,
+            )) => match self.stacks_node().transaction_status(&tx.txid())? {
+                TransactionStatus::Pending
+                | TransactionStatus::Success
+                | TransactionStatus::Aborted(_) => {
+                    info!("sBTC transaction {} was already broadcast", tx.txid());
+                    self.nonce_manager()
+                        .record_broadcast(tx.get_origin_nonce(), Some(&tx.txid()))?;
+                    Ok(())
+                }
+                status => {
+                    warn!(
+                        "Nonce of persisted sBTC transaction {} is taken by another transaction ({:?}), rebuilding it...",
+                        tx.txid(),
+                        status
+                    );
+                    self.try_broadcast_transaction(op, txid, burn_header_hash)
+                }
+            },
+            Err(e) => {
+                warn!(
+                    "Persisted sBTC transaction {} was rejected ({}), rebuilding it...",
+                    tx.txid(),
+                    e
+                );
+                self.try_broadcast_transaction(op, txid, burn_header_hash)
+            }
+        }
+    }
+
+    /// Broadcast a persisted fulfillment again, tolerating the Bitcoin node already knowing it
+    fn rebroadcast_fulfillment(&mut self, fulfill_tx: &BitcoinTransaction) -> Result<()> {
+        match self.bitcoin_node().broadcast_transaction(fulfill_tx) {
+            Ok(_) => info!(
+                "Rebroadcasted fulfilled BTC transaction: {}",
+                fulfill_tx.txid()
+            ),
+            Err(BitcoinNodeError::RPCError(message)) if is_already_broadcast(&message) => {
+                info!(
+                    "Fulfilled BTC transaction {} was already broadcast: {}",
+                    fulfill_tx.txid(),
+                    message
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Replace an unconfirmed fulfillment paying less than the current fee rate with one paying
+    /// more out of its change, signed again through FROST. Failing to bump is not fatal, as the
+    /// original fulfillment may still confirm.
+    fn bump_fulfillment_fee(&mut self, entry: &PegQueueEntry) -> Result<()> {
+        let fulfill_tx = fulfillment_tx(entry)?;
+        let prevouts = &entry.fulfillment_prevouts;
+        if !fulfill_tx.is_explicitly_rbf() {
+            return Ok(());
+        }
+        if prevouts.len() != fulfill_tx.input.len() {
+            return Err(Error::IncompletePegQueueEntry(
+                entry.txid,
+                entry.status,
+                "fulfillment prevouts",
+            ));
+        }
+
+        let fee_rate = self.fee_rate()?;
+        let fee = prevouts
+            .iter()
+            .map(|prevout| prevout.value)
+            .sum::<u64>()
+            .saturating_sub(fulfill_tx.output.iter().map(|output| output.value).sum());
+        let vsize = (fulfill_tx.weight() as u64 + 3) / 4;
+        if fee >= vsize * fee_rate {
+            return Ok(());
+        }
+
+        // A batched fulfillment may spend the fulfillment fees of every request in the batch
+        let max_fee = self
+            .peg_queue()
+            .fulfillment_batch(&entry.txid, &entry.burn_header_hash)?
+            .iter()
+            .filter_map(|entry| entry.op.as_peg_out_request())
+            .map(|op| op.fulfillment_fee)
+            .sum();
+        let unsigned_tx = match self
+            .fee_wallet()
+            .bitcoin()
+            .bump_fee(fulfill_tx, prevouts, fee_rate, max_fee)
+        {
+            Ok(tx) => tx,
+            Err(e) => {
+                warn!(
+                    "Unable to bump fee of fulfilled BTC transaction {}: {}",
+                    fulfill_tx.txid(),
+                    e
+                );
+                return Ok(());
+            }
+        };
+        let bumped_tx = self.sign_fulfillment(unsigned_tx, prevouts)?;
+
+        // Only persist the replacement once the node accepted it, the original is tracked until then
+        match self.bitcoin_node().broadcast_transaction(&bumped_tx) {
+            Ok(_) => {}
+            Err(BitcoinNodeError::RPCError(message)) => {
+                warn!(
+                    "Fee bumped BTC transaction {} was rejected: {}",
+                    bumped_tx.txid(),
+                    message
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.peg_queue().replace_fulfillment_tx(
+            &entry.txid,
+            &entry.burn_header_hash,
+            &bumped_tx,
+        )?;
+        info!(
+            "Replaced fulfilled BTC transaction {} paying {} sat/vB with {}",
+            fulfill_tx.txid(),
+            fee / vsize,
+            bumped_tx.txid()
+        );
+        Ok(())
+    }
+
+    /// Fee rate in sat/vB for fulfillments, falling back to the node's estimate
+    fn fee_rate(&self) -> Result<u64> {
+        if let Some(fee_rate) = self.bitcoin_fee_rate() {
+            return Ok(fee_rate);
+        }
+        match self
+            .bitcoin_node()
+            .estimate_fee_rate(FEE_ESTIMATE_CONF_TARGET)
+        {
+            Ok(fee_rate) => Ok(fee_rate),
+            Err(BitcoinNodeError::NoFeeEstimate(reason)) => {
+                warn!(
+                    "Bitcoin node could not estimate a fee rate ({}), using {} sat/vB",
+                    reason, FALLBACK_FEE_RATE
+                );
+                Ok(FALLBACK_FEE_RATE)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn fulfill_peg_out(
+        &mut self,
+        op: &stacks_node::PegOutRequestOp,
+    ) -> Result<(BitcoinTransaction, Vec<TxOut>)> {
+        self.fulfill_peg_outs(std::slice::from_ref(op))
+    }
+
+    fn fulfill_peg_outs(
+        &mut self,
+        ops: &[stacks_node::PegOutRequestOp],
+    ) -> Result<(BitcoinTransaction, Vec<TxOut>)> {
         // Retreive the utxos
         let utxos = self
             .bitcoin_node()
             .list_unspent(self.fee_wallet().bitcoin().address())?;
 
         // Build unsigned fulfilled peg out transaction
-        let (mut tx, prevouts) = self.fee_wallet().bitcoin().fulfill_peg_out(op, utxos)?;
+        let fee_rate = self.fee_rate()?;
+        let (tx, prevouts) = self
+            .fee_wallet()
+            .bitcoin()
+            .fulfill_peg_outs(ops, utxos, fee_rate)?;
+        let tx = self.sign_fulfillment(tx, &prevouts)?;
+        Ok((tx, prevouts))
+    }
+
+    /// Sign every input of a fulfillment spending the given prevouts in a single FROST signing round
+    fn sign_fulfillment(
+        &mut self,
+        mut tx: BitcoinTransaction,
+        prevouts: &[TxOut],
+    ) -> Result<BitcoinTransaction> {
         let sighash_tx = tx.clone();
         let mut sighash_cache = SighashCache::new(&sighash_tx);
-        // Sign the transaction
-        for index in 0..tx.input.len() {
-            let taproot_sighash = sighash_cache
-                .taproot_key_spend_signature_hash(
-                    index,
-                    &Prevouts::All(&prevouts),
-                    SchnorrSighashType::Default,
-                )
-                .map_err(Error::SigningError)?;
-            let (_frost_sig, schnorr_proof) = self
-                .frost_coordinator_mut()
-                .sign_message(&taproot_sighash.as_hash())?;
+        let taproot_sighashes = (0..tx.input.len())
+            .map(|index| {
+                sighash_cache
+                    .taproot_key_spend_signature_hash(
+                        index,
+                        &Prevouts::All(prevouts),
+                        SchnorrSighashType::Default,
+                    )
+                    .map(|sighash| sighash.as_hash().to_vec())
+                    .map_err(Error::SigningError)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
+        // Sign the transaction for the wallet's output key, which is tweaked to commit to its
+        // script tree if it has one and the aggregate key itself otherwise
+        let signatures = match self.fee_wallet().bitcoin().merkle_root() {
+            Some(merkle_root) => self
+                .frost_coordinator_mut()
+                .sign_taproot_messages(&taproot_sighashes, Some(merkle_root.into_inner()))?,
+            None => self
+                .frost_coordinator_mut()
+                .sign_messages(&taproot_sighashes)?,
+        };
+        for (input, (_frost_sig, schnorr_proof)) in tx.input.iter_mut().zip(signatures) {
             debug!(
                 "Fulfill Tx {:?} SchnorrProof ({},{})",
-                &tx, schnorr_proof.r, schnorr_proof.s
+                &sighash_tx, schnorr_proof.r, schnorr_proof.s
             );
 
             let finalized = schnorr_proof.to_bytes();
             let finalized_b58 = base58::encode_slice(&finalized);
             debug!("CALC SIG ({}) {}", finalized.len(), finalized_b58);
 
-            tx.input[index].witness.push(finalized);
+            input.witness.push(finalized);
         }
         //Return the signed transaction
         Ok(tx)
     }
 
     /// Broadcast a transaction to the stacks node, retrying if the nonce is rejected or the fee set too low until a retry limit is reached
-    fn try_broadcast_transaction<T: BuildStacksTransaction>(&mut self, op: &T) -> Result<()> {
-        // Retrieve the nonce from the stacks node using the sBTC wallet address
-        let address = *self.fee_wallet().stacks().address();
-        let mut nonce = self.stacks_node_mut().next_nonce(&address)?;
+    /// Every built transaction is persisted in the peg queue for the op identified by `txid` and `burn_header_hash` before it is broadcast.
+    fn try_broadcast_transaction<T: BuildStacksTransaction>(
+        &mut self,
+        op: &T,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<()> {
+        let mut nonce = self.nonce_manager().next_nonce()?;
         let mut nonce_retries = 0;
         let mut fee_retries = 0;
+        // Fee demanded by the stacks node after rejecting the estimated one
+        let mut required_fee = None;
         loop {
             // Build a transaction using the peg in op and calculated nonce
-            let tx = self.fee_wallet().stacks().build_transaction(op, nonce)?;
+            let tx = match required_fee {
+                Some(fee) => self
+                    .fee_wallet()
+                    .stacks()
+                    .build_transaction_with_fee(op, nonce, fee)?,
+                None => {
+                    self.fee_wallet()
+                        .stacks()
+                        .build_transaction(op, nonce, self.stacks_node())?
+                }
+            };
+            self.peg_queue()
+                .record_stacks_tx(txid, burn_header_hash, &tx)?;
 
             // Broadcast the resulting sBTC transaction to the stacks node
             match self.stacks_node().broadcast_transaction(&tx) {
-                Err(StacksNodeError::BroadcastError(BroadcastError::ConflictingNonceInMempool)) => {
-                    warn!("Transaction rejected by stacks node due to conflicting nonce in mempool. Stacks node may be falling behind!");
+                Err(StacksNodeError::BroadcastError(
+                    error @ (BroadcastError::ConflictingNonceInMempool | BroadcastError::BadNonce),
+                )) => {
+                    warn!("Transaction rejected by stacks node: {}", error);
                     nonce_retries += 1;
                     if nonce_retries > MAX_NONCE_RETRIES {
+                        self.nonce_manager().release(nonce)?;
                         return Err(Error::MaxNonceRetriesExceeded);
                     }
-                    warn!("Incrementing nonce and retrying...");
-                    nonce = self.stacks_node_mut().next_nonce(&address)?;
+                    if matches!(error, BroadcastError::BadNonce) {
+                        // The nonce was already confirmed, so the nonce manager is behind the chain
+                        self.nonce_manager().reconcile(self.stacks_node())?;
+                    } else {
+                        // Another transaction holds the nonce in the mempool
+                        self.nonce_manager().record_broadcast(nonce, None)?;
+                    }
+                    nonce = self.nonce_manager().next_nonce()?;
+                    warn!("Retrying with nonce {}...", nonce);
                 }
                 Err(StacksNodeError::BroadcastError(BroadcastError::FeeTooLow(
                     expected,
@@ -234,6 +1173,7 @@ trait CoordinatorHelpers: Coordinator {
                 ))) => {
                     fee_retries += 1;
                     if fee_retries > MAX_FEE_RETRIES {
+                        self.nonce_manager().release(nonce)?;
                         return Err(Error::MaxFeeRetriesExceeded);
                     }
                     warn!(
@@ -241,10 +1181,15 @@ trait CoordinatorHelpers: Coordinator {
                         actual
                     );
                     warn!("Incrementing fee to {} and retrying...", expected);
-                    self.fee_wallet_mut().stacks_mut().set_fee(expected);
+                    required_fee = Some(expected);
+                }
+                Err(e) => {
+                    self.nonce_manager().release(nonce)?;
+                    return Err(e.into());
                 }
-                Err(e) => return Err(e.into()),
                 Ok(_) => {
+                    self.nonce_manager()
+                        .record_broadcast(nonce, Some(&tx.txid()))?;
                     info!("Broadcasted sBTC transaction: {}", tx.txid());
                     return Ok(());
                 }
@@ -255,6 +1200,33 @@ trait CoordinatorHelpers: Coordinator {
 
 impl<T: Coordinator> CoordinatorHelpers for T {}
 
+fn fulfillment_tx(entry: &PegQueueEntry) -> Result<&BitcoinTransaction> {
+    entry
+        .fulfillment_tx
+        .as_ref()
+        .ok_or(Error::IncompletePegQueueEntry(
+            entry.txid,
+            entry.status,
+            "fulfillment transaction",
+        ))
+}
+
+/// Whether the bitcoin node rejected a transaction because it already knows it
+fn is_already_broadcast(message: &str) -> bool {
+    message.contains("already") || message.contains("missingorspent")
+}
+
+fn stacks_tx(entry: &PegQueueEntry) -> Result<&StacksTransaction> {
+    entry
+        .stacks_tx
+        .as_ref()
+        .ok_or(Error::IncompletePegQueueEntry(
+            entry.txid,
+            entry.status,
+            "Stacks transaction",
+        ))
+}
+
 pub enum Command {
     Stop,
     Timeout,
@@ -264,8 +1236,146 @@ pub struct StacksCoordinator {
     frost_coordinator: FrostCoordinator,
     local_peg_queue: SqlitePegQueue,
     local_stacks_node: NodeClient,
-    local_bitcoin_node: LocalhostBitcoinNode,
+    local_bitcoin_node: Box<dyn BitcoinNode>,
+    local_nonce_manager: SqliteNonceManager,
     pub local_fee_wallet: WrapPegWallet,
+    min_confirmations: u64,
+    bitcoin_fee_rate: Option<u64>,
+    peg_out_batch_size: usize,
+    peg_out_batch_max_age: u64,
+    /// Set if the signer set is read from the sBTC contract rather than a signer config file
+    signer_rotation: Option<SignerRotation>,
+    /// Signer set that ran DKG and waits on the wallet handoff to its key
+    next_rotation: Option<NextRotation>,
+    data_directory: Option<String>,
+    key_refresh_interval: Option<Duration>,
+    /// When key shares were last refreshed, or replaced by a DKG round
+    last_key_refresh: Instant,
+}
+
+/// What it takes to follow the signer set of the sBTC contract from one reward cycle to the next
+struct SignerRotation {
+    stacks_address: StacksAddress,
+    network_private_key: Scalar,
+    http_relay_url: String,
+    data_directory: Option<String>,
+    dkg_public_timeout: Option<Duration>,
+    dkg_end_timeout: Option<Duration>,
+    sign_timeout: Option<Duration>,
+    wsts_version: WstsVersion,
+    nonce_batch_size: Option<u32>,
+    pox_info: PoxInfo,
+    /// Reward cycle the signer set was last read in
+    reward_cycle: u64,
+    signer_set: SignerSet,
+}
+
+/// A new signer set along with the coordinator of its DKG round, which takes over signing once
+/// the peg wallet is handed off to its key
+struct NextRotation {
+    rotation: SignerRotation,
+    frost_coordinator: FrostCoordinator,
+    public_key: XOnlyPublicKey,
+}
+
+/// Signer set behind an aggregate public key, as persisted in the data directory so a restarted
+/// coordinator signs with the set holding the peg wallet rather than the contract's latest one
+#[derive(Serialize, Deserialize)]
+struct SavedSignerSet {
+    /// Hex encoded aggregate public key of the signer set
+    public_key: String,
+    /// Reward cycle the signer set was read in
+    reward_cycle: u64,
+    keys_threshold: u128,
+    /// Compressed network public key of each signer
+    signers: BTreeMap<u32, Vec<u8>>,
+    signer_key_ids: BTreeMap<u32, Vec<u32>>,
+}
+
+impl SavedSignerSet {
+    fn new(signer_set: &SignerSet, public_key: XOnlyPublicKey, reward_cycle: u64) -> Self {
+        Self {
+            public_key: public_key.to_string(),
+            reward_cycle,
+            keys_threshold: signer_set.keys_threshold,
+            signers: signer_set
+                .public_keys
+                .signers
+                .iter()
+                .map(|(id, public_key)| (*id, public_key.to_bytes().to_vec()))
+                .collect(),
+            signer_key_ids: signer_set
+                .signer_key_ids
+                .iter()
+                .map(|(id, key_ids)| (*id, key_ids.clone()))
+                .collect(),
+        }
+    }
+
+    fn public_key(&self) -> Result<XOnlyPublicKey> {
+        XOnlyPublicKey::from_str(&self.public_key)
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))
+    }
+
+    fn signer_set(&self) -> Result<SignerSet> {
+        let mut public_keys = PublicKeys::default();
+        for (id, public_key) in &self.signers {
+            let public_key = ecdsa::PublicKey::try_from(public_key.as_slice())
+                .map_err(|_| Error::InvalidPublicKey(format!("signer #{}", id)))?;
+            public_keys.signers.insert(*id, public_key);
+            for key_id in self.signer_key_ids.get(id).into_iter().flatten() {
+                public_keys.key_ids.insert(*key_id, public_key);
+            }
+        }
+        Ok(SignerSet {
+            keys_threshold: self.keys_threshold,
+            public_keys,
+            signer_key_ids: self.signer_key_ids.clone().into_iter().collect(),
+        })
+    }
+}
+
+impl SignerRotation {
+    /// Same rotation settings for another signer set
+    fn with_signer_set(&self, signer_set: SignerSet, reward_cycle: u64) -> Self {
+        Self {
+            stacks_address: self.stacks_address,
+            network_private_key: self.network_private_key,
+            http_relay_url: self.http_relay_url.clone(),
+            data_directory: self.data_directory.clone(),
+            dkg_public_timeout: self.dkg_public_timeout,
+            dkg_end_timeout: self.dkg_end_timeout,
+            sign_timeout: self.sign_timeout,
+            wsts_version: self.wsts_version,
+            nonce_batch_size: self.nonce_batch_size,
+            pox_info: self.pox_info,
+            reward_cycle,
+            signer_set,
+        }
+    }
+
+    /// Coordinator of DKG and signing rounds among the signer set
+    fn frost_coordinator(&self, stacks_node: &NodeClient) -> Result<FrostCoordinator> {
+        let coordinator_public_key = stacks_node
+            .coordinator_public_key(&self.stacks_address)?
+            .ok_or_else(|| Error::NoCoordinator)?;
+        create_coordinator(&SignerConfig {
+            dkg_public_timeout: self.dkg_public_timeout,
+            dkg_end_timeout: self.dkg_end_timeout,
+            sign_timeout: self.sign_timeout,
+            wsts_version: self.wsts_version,
+            nonce_batch_size: self.nonce_batch_size,
+            ..SignerConfig::new(
+                self.signer_set.keys_threshold.try_into().unwrap(),
+                coordinator_public_key,
+                self.signer_set.public_keys.clone(),
+                self.signer_set.signer_key_ids.clone(),
+                self.network_private_key,
+                self.http_relay_url.clone(),
+            )
+        })
+        .map_err(|e| Error::ConfigError(e.to_string()))
+    }
 }
 
 impl StacksCoordinator {
@@ -278,12 +1388,26 @@ impl StacksCoordinator {
     pub fn sign_message(&mut self, message: &str) -> Result<(Signature, SchnorrProof)> {
         Ok(self.frost_coordinator.sign_message(message.as_bytes())?)
     }
+
+    /// Verify a commit and queue it to be revealed to the peg wallet by the running coordinator
+    pub fn add_commit(&self, commit: &Commit) -> Result<()> {
+        let peg_in = commit.verify(self.local_fee_wallet.bitcoin().x_only_pub_key())?;
+        // Nodes tracking wallets only list the utxos of addresses they were told about
+        self.local_bitcoin_node.load_wallet(&commit.address)?;
+        self.local_peg_queue.add_commit(commit)?;
+        info!(
+            "Queued commit {} of {} sats for {}",
+            commit.outpoint, commit.amount, peg_in.recipient
+        );
+        Ok(())
+    }
 }
 
 fn create_frost_coordinator_from_path(
     signer_config_path: &str,
     config: &Config,
-    stacks_node: &mut NodeClient,
+    stacks_node: &NodeClient,
+    nonce_manager: &SqliteNonceManager,
     stacks_wallet: &StacksWallet,
 ) -> Result<FrostCoordinator> {
     debug!("Creating frost coordinator from signer config path...");
@@ -307,7 +1431,7 @@ fn create_frost_coordinator_from_path(
         // so that subsequent runs of the coordinator don't need to load the data from a file again
         // until a stacking cyle has finished and a new signing set and coordinator are generated.
         debug!("loading coordinator data into sBTC contract...");
-        let nonce = stacks_node.next_nonce(&config.stacks_address)?;
+        let nonce = nonce_manager.next_nonce()?;
         let coordinator_public_key =
             Secp256k1PublicKey::from_slice(&coordinator.public_key().to_bytes())
                 .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
@@ -315,23 +1439,19 @@ fn create_frost_coordinator_from_path(
             &config.stacks_address,
             &coordinator_public_key,
             nonce,
+            stacks_node,
         )?;
         stacks_node.broadcast_transaction(&coordinator_tx)?;
+        nonce_manager.record_broadcast(nonce, Some(&coordinator_tx.txid()))?;
     }
     Ok(coordinator)
 }
 
 fn create_frost_coordinator_from_contract(
     config: &Config,
-    stacks_node: &mut NodeClient,
-) -> Result<FrostCoordinator> {
+    stacks_node: &NodeClient,
+) -> Result<(FrostCoordinator, SignerRotation, Option<NextRotation>)> {
     debug!("Creating frost coordinator from stacks node...");
-    let keys_threshold = stacks_node.keys_threshold(&config.stacks_address)?;
-    let coordinator_public_key = stacks_node
-        .coordinator_public_key(&config.stacks_address)?
-        .ok_or_else(|| Error::NoCoordinator)?;
-    let public_keys = stacks_node.public_keys(&config.stacks_address)?;
-    let signer_key_ids = stacks_node.signer_key_ids(&config.stacks_address)?;
     let network_private_key = Scalar::try_from(
         config
             .network_private_key
@@ -341,67 +1461,145 @@ fn create_frost_coordinator_from_contract(
     )
     .map_err(|_| Error::ConfigError("Invalid network_private_key.".to_string()))?;
     let http_relay_url = config.http_relay_url.clone().unwrap_or(String::new());
-    create_coordinator(&SignerConfig::new(
-        keys_threshold.try_into().unwrap(),
-        coordinator_public_key,
-        public_keys,
-        signer_key_ids,
+    let pox_info = stacks_node.pox_info()?;
+    let rotation = SignerRotation {
+        stacks_address: config.stacks_address,
         network_private_key,
         http_relay_url,
+        data_directory: config.data_directory.clone(),
+        dkg_public_timeout: config.dkg_public_timeout,
+        dkg_end_timeout: config.dkg_end_timeout,
+        sign_timeout: config.sign_timeout,
+        wsts_version: config.wsts_version,
+        nonce_batch_size: config.nonce_batch_size,
+        pox_info,
+        reward_cycle: pox_info.reward_cycle(stacks_node.burn_block_height()?),
+        signer_set: stacks_node.signer_set(&config.stacks_address)?,
+    };
+    let Some(data_directory) = &config.data_directory else {
+        return Ok((rotation.frost_coordinator(stacks_node)?, rotation, None));
+    };
+
+    // The contract's signer set may have changed while the coordinator was stopped, so signing
+    // stays with the set holding the peg wallet until the handoff to the new set completes
+    let wallet_public_key = stacks_node.bitcoin_wallet_public_key(&config.stacks_address)?;
+    let mut next_rotation = None;
+    if let Some(next_signer_set) = read_signer_set(data_directory, NEXT_SIGNER_SET_FILE)? {
+        let public_key = next_signer_set.public_key()?;
+        if Some(public_key) == wallet_public_key {
+            info!("Handoff to {} completed while stopped", public_key);
+            promote_next_signer_set(data_directory)?;
+        } else {
+            let next = rotation
+                .with_signer_set(next_signer_set.signer_set()?, next_signer_set.reward_cycle);
+            let mut frost_coordinator = next.frost_coordinator(stacks_node)?;
+            frost_coordinator
+                .set_dkg_public_shares(read_json(data_directory, NEXT_DKG_PUBLIC_SHARES_FILE)?);
+            frost_coordinator.set_aggregate_public_key(
+                Point::lift_x(&Element::from(public_key.serialize()))
+                    .map_err(|e| Error::PointError(format!("{:?}", e)))?,
+            );
+            info!(
+                "Resuming handoff to {} of the signers of reward cycle {}",
+                public_key, next.reward_cycle
+            );
+            next_rotation = Some(NextRotation {
+                rotation: next,
+                frost_coordinator,
+                public_key,
+            });
+        }
+    }
+    let rotation = match read_signer_set(data_directory, SIGNER_SET_FILE)? {
+        Some(signer_set) if Some(signer_set.public_key()?) == wallet_public_key => {
+            rotation.with_signer_set(signer_set.signer_set()?, signer_set.reward_cycle)
+        }
+        _ => rotation,
+    };
+    Ok((
+        rotation.frost_coordinator(stacks_node)?,
+        rotation,
+        next_rotation,
     ))
-    .map_err(|e| Error::ConfigError(e.to_string()))
 }
 
 fn create_frost_coordinator(
     config: &Config,
-    stacks_node: &mut NodeClient,
+    stacks_node: &NodeClient,
+    nonce_manager: &SqliteNonceManager,
     stacks_wallet: &StacksWallet,
-) -> Result<FrostCoordinator> {
+) -> Result<(
+    FrostCoordinator,
+    Option<SignerRotation>,
+    Option<NextRotation>,
+)> {
     debug!("Initializing frost coordinator...");
     // Create the frost coordinator and use it to generate the aggregate public key and corresponding bitcoin wallet address
     // Note: all errors returned from create_coordinator relate to configuration issues and should convert to this error type.
     if let Some(signer_config_path) = &config.signer_config_path {
-        create_frost_coordinator_from_path(signer_config_path, config, stacks_node, stacks_wallet)
+        let coordinator = create_frost_coordinator_from_path(
+            signer_config_path,
+            config,
+            stacks_node,
+            nonce_manager,
+            stacks_wallet,
+        )?;
+        Ok((coordinator, None, None))
     } else {
-        create_frost_coordinator_from_contract(config, stacks_node)
+        let (coordinator, rotation, next_rotation) =
+            create_frost_coordinator_from_contract(config, stacks_node)?;
+        Ok((coordinator, Some(rotation), next_rotation))
     }
 }
 
-fn read_dkg_public_shares(path: impl AsRef<Path>) -> Result<BTreeMap<u32, DkgPublicShare>> {
-    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+fn create_bitcoin_node(config: &Config) -> Result<Box<dyn BitcoinNode>> {
+    let url = config.bitcoin_node_rpc_url.clone();
+    Ok(match config.bitcoin_node_type {
+        BitcoinNodeType::Bitcoind => Box::new(LocalhostBitcoinNode::new(url)),
+        BitcoinNodeType::Esplora => Box::new(EsploraBitcoinNode::new(url)),
+        BitcoinNodeType::Electrum => Box::new(ElectrumBitcoinNode::new(&url)?),
+    })
+}
+
+const DKG_PUBLIC_SHARES_FILE: &str = "dkg_public_shares.json";
+const SIGNER_SET_FILE: &str = "signer_set.json";
+/// Files of the signer set waiting on the wallet handoff to its key
+const NEXT_DKG_PUBLIC_SHARES_FILE: &str = "next_dkg_public_shares.json";
+const NEXT_SIGNER_SET_FILE: &str = "next_signer_set.json";
+
+fn read_json<T: DeserializeOwned>(path: impl AsRef<Path>, file_name: &str) -> Result<T> {
+    let json_path = path.as_ref().join(file_name);
 
-    serde_json::from_reader(File::open(&dkg_public_shares_path).map_err(|err| {
+    serde_json::from_reader(File::open(&json_path).map_err(|err| {
         Error::ConfigError(format!(
-            "Unable to open DKG public shares file {}: {}",
-            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+            "Unable to open file {}: {}",
+            json_path.to_str().unwrap_or("Invalid path"),
             err
         ))
     })?)
-    .map_err(|err| Error::ConfigError(format!("Unable to parse DKG public shares JSON: {}", err)))
+    .map_err(|err| Error::ConfigError(format!("Unable to parse {} JSON: {}", file_name, err)))
 }
 
-fn write_dkg_public_shares(
-    path: impl AsRef<Path>,
-    dkg_public_shares: &BTreeMap<u32, DkgPublicShare>,
-) -> Result<()> {
-    let dkg_public_shares_path = path.as_ref().join("dkg_public_shares.json");
+fn write_json(path: impl AsRef<Path>, file_name: &str, value: &impl Serialize) -> Result<()> {
+    let json_path = path.as_ref().join(file_name);
 
-    let dkg_public_shares_file = File::options()
+    let json_file = File::options()
         .create(true)
         .write(true)
-        .open(&dkg_public_shares_path)
+        .truncate(true)
+        .open(&json_path)
         .map_err(|err| {
             Error::ConfigError(format!(
-                "Unable to open DKG public shares file {}: {}",
-                dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+                "Unable to open file {}: {}",
+                json_path.to_str().unwrap_or("Invalid path"),
                 err
             ))
         })?;
 
-    serde_json::to_writer_pretty(dkg_public_shares_file, dkg_public_shares).map_err(|err| {
+    serde_json::to_writer_pretty(json_file, value).map_err(|err| {
         Error::ConfigError(format!(
-            "Unable to write DKG public shares to file {}: {}",
-            dkg_public_shares_path.to_str().unwrap_or("Invalid path"),
+            "Unable to write to file {}: {}",
+            json_path.to_str().unwrap_or("Invalid path"),
             err
         ))
     })?;
@@ -409,10 +1607,44 @@ fn write_dkg_public_shares(
     Ok(())
 }
 
+fn read_dkg_public_shares(path: impl AsRef<Path>) -> Result<BTreeMap<u32, DkgPublicShare>> {
+    read_json(path, DKG_PUBLIC_SHARES_FILE)
+}
+
+fn write_dkg_public_shares(
+    path: impl AsRef<Path>,
+    dkg_public_shares: &BTreeMap<u32, DkgPublicShare>,
+) -> Result<()> {
+    write_json(path, DKG_PUBLIC_SHARES_FILE, dkg_public_shares)
+}
+
+/// Signer set saved in the given file, if any
+fn read_signer_set(path: impl AsRef<Path>, file_name: &str) -> Result<Option<SavedSignerSet>> {
+    if !path.as_ref().join(file_name).exists() {
+        return Ok(None);
+    }
+    read_json(path, file_name).map(Some)
+}
+
+/// Make the signer set waiting on a handoff the one holding the peg wallet
+fn promote_next_signer_set(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    for (next_file_name, file_name) in [
+        (NEXT_DKG_PUBLIC_SHARES_FILE, DKG_PUBLIC_SHARES_FILE),
+        (NEXT_SIGNER_SET_FILE, SIGNER_SET_FILE),
+    ] {
+        fs::rename(path.join(next_file_name), path.join(file_name)).map_err(|err| {
+            Error::ConfigError(format!("Unable to replace {}: {}", file_name, err))
+        })?;
+    }
+    Ok(())
+}
+
 fn load_dkg_data(
     data_directory: Option<&str>,
     frost_coordinator: &mut FrostCoordinator,
-    stacks_node: &mut NodeClient,
+    stacks_node: &NodeClient,
+    nonce_manager: &SqliteNonceManager,
     stacks_wallet: &StacksWallet,
     address: &StacksAddress,
 ) -> Result<XOnlyPublicKey> {
@@ -439,10 +1671,14 @@ fn load_dkg_data(
             .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
 
         // Set the bitcoin address using the sbtc contract
-        let nonce = stacks_node.next_nonce(address)?;
-        let tx =
-            stacks_wallet.build_set_bitcoin_wallet_public_key_transaction(&xonly_pubkey, nonce)?;
+        let nonce = nonce_manager.next_nonce()?;
+        let tx = stacks_wallet.build_set_bitcoin_wallet_public_key_transaction(
+            &xonly_pubkey,
+            nonce,
+            stacks_node,
+        )?;
         stacks_node.broadcast_transaction(&tx)?;
+        nonce_manager.record_broadcast(nonce, Some(&tx.txid()))?;
         Ok(xonly_pubkey)
     }
 }
@@ -451,11 +1687,22 @@ impl TryFrom<&Config> for StacksCoordinator {
     type Error = Error;
     fn try_from(config: &Config) -> Result<Self> {
         info!("Initializing stacks coordinator...");
-        let mut local_stacks_node = NodeClient::new(
+        let local_stacks_node = NodeClient::new(
             config.stacks_node_rpc_url.clone(),
             config.contract_name.clone(),
             config.contract_address,
-        );
+            config.stacks_node_request_policy.clone(),
+        )?;
+
+        // Nonces of the coordinator's Stacks account are tracked next to the peg queue
+        let local_nonce_manager = if let Some(path) = &config.data_directory {
+            let db_path = PathBuf::from(path).join("stacks_nonces.sqlite");
+            SqliteNonceManager::new(db_path, config.stacks_address)
+        } else {
+            SqliteNonceManager::in_memory(config.stacks_address)
+        }
+        .map_err(NonceManagerError::from)?;
+        local_nonce_manager.reconcile(&local_stacks_node)?;
 
         let stacks_wallet = StacksWallet::new(
             config.contract_name.clone(),
@@ -463,24 +1710,42 @@ impl TryFrom<&Config> for StacksCoordinator {
             config.stacks_private_key,
             config.stacks_address,
             config.stacks_version,
-            config.transaction_fee,
+            config.stacks_fee_policy.clone(),
         );
 
-        let mut frost_coordinator =
-            create_frost_coordinator(config, &mut local_stacks_node, &stacks_wallet)?;
+        let (mut frost_coordinator, signer_rotation, next_rotation) = create_frost_coordinator(
+            config,
+            &local_stacks_node,
+            &local_nonce_manager,
+            &stacks_wallet,
+        )?;
 
         // Load the public key from either the frost_coordinator or the sBTC contract
         let xonly_pubkey = load_dkg_data(
             config.data_directory.as_deref(),
             &mut frost_coordinator,
-            &mut local_stacks_node,
+            &local_stacks_node,
+            &local_nonce_manager,
             &stacks_wallet,
             &config.stacks_address,
         )?;
-        let bitcoin_wallet = BitcoinWallet::new(xonly_pubkey, config.bitcoin_network);
+        if let (Some(rotation), Some(data_directory)) = (&signer_rotation, &config.data_directory) {
+            if read_signer_set(data_directory, SIGNER_SET_FILE)?.is_none() {
+                write_json(
+                    data_directory,
+                    SIGNER_SET_FILE,
+                    &SavedSignerSet::new(&rotation.signer_set, xonly_pubkey, rotation.reward_cycle),
+                )?;
+            }
+        }
+        let mut bitcoin_wallet = BitcoinWallet::new(xonly_pubkey, config.bitcoin_network);
+        bitcoin_wallet.set_rbf(config.bitcoin_rbf);
+        bitcoin_wallet
+            .set_taproot_scripts(&config.taproot_scripts)
+            .map_err(PegWalletError::from)?;
 
         // Load the bitcoin wallet
-        let local_bitcoin_node = LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone());
+        let local_bitcoin_node = create_bitcoin_node(config)?;
         local_bitcoin_node.load_wallet(bitcoin_wallet.address())?;
 
         // If a user has not specified a start block height, begin from the current burn block height by default
@@ -488,20 +1753,39 @@ impl TryFrom<&Config> for StacksCoordinator {
         let current_block_height = local_stacks_node.burn_block_height()?;
         let local_peg_queue = if let Some(path) = &config.data_directory {
             let db_path = PathBuf::from(path).join("peg_queue.sqlite");
-            SqlitePegQueue::new(db_path, start_block_height, current_block_height)
+            SqlitePegQueue::new(
+                db_path,
+                start_block_height,
+                current_block_height,
+                config.min_confirmations,
+            )
         } else {
-            SqlitePegQueue::in_memory(start_block_height, current_block_height)
+            SqlitePegQueue::in_memory(
+                start_block_height,
+                current_block_height,
+                config.min_confirmations,
+            )
         }?;
 
         Ok(Self {
             local_peg_queue,
             local_stacks_node,
             local_bitcoin_node,
+            local_nonce_manager,
             frost_coordinator,
             local_fee_wallet: WrapPegWallet {
                 bitcoin_wallet,
                 stacks_wallet,
             },
+            min_confirmations: config.min_confirmations,
+            bitcoin_fee_rate: config.bitcoin_fee_rate,
+            peg_out_batch_size: config.peg_out_batch_size,
+            peg_out_batch_max_age: config.peg_out_batch_max_age,
+            signer_rotation,
+            next_rotation,
+            data_directory: config.data_directory.clone(),
+            key_refresh_interval: config.key_refresh_interval,
+            last_key_refresh: Instant::now(),
         })
     }
 }
@@ -510,7 +1794,8 @@ impl Coordinator for StacksCoordinator {
     type PegQueue = SqlitePegQueue;
     type FeeWallet = WrapPegWallet;
     type StacksNode = NodeClient;
-    type BitcoinNode = LocalhostBitcoinNode;
+    type BitcoinNode = Box<dyn BitcoinNode>;
+    type NonceManager = SqliteNonceManager;
 
     fn peg_queue(&self) -> &Self::PegQueue {
         &self.local_peg_queue
@@ -536,13 +1821,178 @@ impl Coordinator for StacksCoordinator {
         &self.local_stacks_node
     }
 
-    fn stacks_node_mut(&mut self) -> &mut Self::StacksNode {
-        &mut self.local_stacks_node
-    }
-
     fn bitcoin_node(&self) -> &Self::BitcoinNode {
         &self.local_bitcoin_node
     }
+
+    fn nonce_manager(&self) -> &Self::NonceManager {
+        &self.local_nonce_manager
+    }
+
+    fn min_confirmations(&self) -> u64 {
+        self.min_confirmations
+    }
+
+    fn bitcoin_fee_rate(&self) -> Option<u64> {
+        self.bitcoin_fee_rate
+    }
+
+    fn peg_out_batch_size(&self) -> usize {
+        self.peg_out_batch_size
+    }
+
+    fn peg_out_batch_max_age(&self) -> u64 {
+        self.peg_out_batch_max_age
+    }
+
+    fn rotate_signer_set(&mut self) -> Result<()> {
+        // The next signer set already ran DKG: start the handoff to its key if it never got
+        // persisted, which the handoff follow up otherwise drives to completion
+        if let Some(next) = &self.next_rotation {
+            let (public_key, reward_cycle) = (next.public_key, next.rotation.reward_cycle);
+            match self.local_peg_queue.handoff()? {
+                Some(handoff) if handoff.public_key == public_key => {}
+                _ => self.handoff(&public_key, reward_cycle)?,
+            }
+            return Ok(());
+        }
+        let Some(rotation) = &self.signer_rotation else {
+            return Ok(());
+        };
+        let reward_cycle = rotation
+            .pox_info
+            .reward_cycle(self.local_stacks_node.burn_block_height()?);
+        if reward_cycle <= rotation.reward_cycle {
+            return Ok(());
+        }
+        let signer_set = self
+            .local_stacks_node
+            .signer_set(&rotation.stacks_address)?;
+        if signer_set == rotation.signer_set {
+            debug!("Signer set is unchanged in reward cycle {}", reward_cycle);
+            if let Some(rotation) = &mut self.signer_rotation {
+                rotation.reward_cycle = reward_cycle;
+            }
+            return Ok(());
+        }
+
+        info!(
+            "Signer set changed in reward cycle {}, running DKG round...",
+            reward_cycle
+        );
+        let next_rotation = rotation.with_signer_set(signer_set, reward_cycle);
+        let mut frost_coordinator = next_rotation.frost_coordinator(&self.local_stacks_node)?;
+        frost_coordinator.set_dkg_id(self.frost_coordinator.get_dkg_id());
+        let point = frost_coordinator.run_distributed_key_generation()?;
+        let xonly_pubkey = XOnlyPublicKey::from_slice(&point.x().to_bytes())
+            .map_err(|e| Error::InvalidPublicKey(e.to_string()))?;
+        // Signers in both sets dropped the nonces they preprocessed when the DKG round began
+        self.frost_coordinator.discard_nonce_pool();
+
+        if let Some(data_directory) = &self.data_directory {
+            write_json(
+                data_directory,
+                NEXT_DKG_PUBLIC_SHARES_FILE,
+                frost_coordinator.get_dkg_public_shares(),
+            )?;
+            write_json(
+                data_directory,
+                NEXT_SIGNER_SET_FILE,
+                &SavedSignerSet::new(&next_rotation.signer_set, xonly_pubkey, reward_cycle),
+            )?;
+        }
+        self.next_rotation = Some(NextRotation {
+            rotation: next_rotation,
+            frost_coordinator,
+            public_key: xonly_pubkey,
+        });
+
+        // The outgoing signers, who kept their key shares, sweep their wallet to the new key,
+        // which is published once the sweep confirms
+        info!("Starting handoff of the peg wallet to the signers of reward cycle {reward_cycle}");
+        self.handoff(&xonly_pubkey, reward_cycle)
+    }
+
+    fn complete_handoff(
+        &mut self,
+        new_public_key: &XOnlyPublicKey,
+        reward_cycle: u64,
+    ) -> Result<()> {
+        let bitcoin_wallet = self
+            .local_fee_wallet
+            .bitcoin_wallet
+            .with_public_key(*new_public_key)
+            .map_err(PegWalletError::from)?;
+        self.local_bitcoin_node
+            .load_wallet(bitcoin_wallet.address())?;
+        self.local_fee_wallet.bitcoin_wallet = bitcoin_wallet;
+
+        // The signer set of the new key takes over signing
+        if self
+            .next_rotation
+            .as_ref()
+            .map_or(false, |next| next.public_key == *new_public_key)
+        {
+            if let Some(data_directory) = &self.data_directory {
+                promote_next_signer_set(data_directory)?;
+            }
+            if let Some(next) = self.next_rotation.take() {
+                self.frost_coordinator = next.frost_coordinator;
+                self.signer_rotation = Some(next.rotation);
+                self.last_key_refresh = Instant::now();
+            }
+        }
+        info!("Handed peg wallet off to the signers of reward cycle {reward_cycle}");
+        Ok(())
+    }
+
+    fn refresh_keys(&mut self) -> Result<()> {
+        let Some(key_refresh_interval) = self.key_refresh_interval else {
+            return Ok(());
+        };
+        if self.last_key_refresh.elapsed() < key_refresh_interval {
+            return Ok(());
+        }
+        // Signers only refresh their current key shares, which the outgoing coordinator no longer
+        // signs with once the next signer set ran DKG
+        if self.next_rotation.is_some() {
+            return Ok(());
+        }
+        info!("Refreshing the signers' key shares...");
+        self.last_key_refresh = Instant::now();
+        match self.frost_coordinator.run_key_refresh() {
+            Ok(()) => {}
+            // Signers that acknowledged the commit switched to the refreshed public shares, and
+            // the next refresh resends the commit to the others first
+            Err(FrostCoordinatorError::RefreshCommitUnacked(signer_ids)) => {
+                warn!(
+                    "Signers {:?} did not acknowledge the key refresh commit",
+                    signer_ids
+                );
+            }
+            // A failed refresh leaves every signer with its old shares, so it is retried next
+            // interval
+            Err(e) => {
+                warn!("Failed to refresh key shares: {}", e);
+                return Ok(());
+            }
+        }
+        if let Some(data_directory) = &self.data_directory {
+            write_dkg_public_shares(
+                data_directory,
+                self.frost_coordinator.get_dkg_public_shares(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn preprocess_nonces(&mut self) -> Result<()> {
+        // Signing falls back to requesting nonces, so a failed batch is retried when next idle
+        if let Err(e) = self.frost_coordinator.preprocess_nonces() {
+            warn!("Failed to preprocess signer nonces: {}", e);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -582,7 +2032,7 @@ mod tests {
         };
         let btc_tx_result = sc.fulfill_peg_out(&op);
         assert!(btc_tx_result.is_ok());
-        let btc_tx = btc_tx_result.unwrap();
+        let (btc_tx, _) = btc_tx_result.unwrap();
         let mut btc_tx_encoded: Vec<u8> = vec![];
         btc_tx.consensus_encode(&mut btc_tx_encoded).unwrap();
         let verify_result = bitcoin::bitcoinconsensus::verify(&[], 100, &btc_tx_encoded, 0);
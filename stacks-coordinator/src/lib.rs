@@ -1,8 +1,11 @@
 pub mod bitcoin_node;
 pub mod bitcoin_wallet;
 pub mod cli;
+mod coin_selection;
+pub mod commit_reveal;
 pub mod config;
 pub mod coordinator;
+pub mod nonce_manager;
 pub mod peg_queue;
 pub mod peg_wallet;
 pub mod stacks_node;
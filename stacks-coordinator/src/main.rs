@@ -1,6 +1,10 @@
+use std::str::FromStr;
+
+use bitcoin::{util::taproot::ControlBlock, Address, OutPoint, Script, Txid, XOnlyPublicKey};
 use clap::Parser;
 use frost_signer::logging;
 use stacks_coordinator::cli::{Cli, Command};
+use stacks_coordinator::commit_reveal::Commit;
 use stacks_coordinator::config::Config;
 use stacks_coordinator::coordinator::{Coordinator, StacksCoordinator};
 use tracing::{error, info, warn};
@@ -54,6 +58,52 @@ fn main() {
                                 &signature.R, &signature.z, &schnorr_proof.r, &schnorr_proof.s
                             );
                         }
+                        Command::Handoff {
+                            public_key,
+                            reward_cycle,
+                        } => {
+                            let public_key = match XOnlyPublicKey::from_str(&public_key) {
+                                Ok(public_key) => public_key,
+                                Err(e) => {
+                                    error!("Invalid public key {}: {}", public_key, e);
+                                    return;
+                                }
+                            };
+                            if config.data_directory.is_none() {
+                                warn!("No data directory configured, the handoff cannot be resumed by the running coordinator");
+                            }
+                            info!("Handing off peg wallet to {}", public_key);
+                            match coordinator.handoff(&public_key, reward_cycle) {
+                                Ok(()) => info!("Handoff to {} started, the running coordinator publishes the key once the sweep confirms", public_key),
+                                Err(e) => error!("An error occurred during wallet handoff: {}", e),
+                            }
+                        }
+                        Command::Commit {
+                            txid,
+                            vout,
+                            amount,
+                            address,
+                            reveal_script,
+                            control_block,
+                        } => {
+                            let commit = match parse_commit(
+                                &txid,
+                                vout,
+                                amount,
+                                &address,
+                                &reveal_script,
+                                &control_block,
+                            ) {
+                                Ok(commit) => commit,
+                                Err(e) => {
+                                    error!("Invalid commit: {}", e);
+                                    return;
+                                }
+                            };
+                            if let Err(e) = coordinator.add_commit(&commit) {
+                                error!("An error occurred queueing commit: {}", e);
+                            }
+                        }
                     };
                 }
                 Err(e) => {
@@ -69,3 +119,29 @@ fn main() {
         }
     }
 }
+
+fn parse_commit(
+    txid: &str,
+    vout: u32,
+    amount: u64,
+    address: &str,
+    reveal_script: &str,
+    control_block: &str,
+) -> Result<Commit, String> {
+    let txid = Txid::from_str(txid).map_err(|e| format!("txid: {e}"))?;
+    let address = Address::from_str(address).map_err(|e| format!("address: {e}"))?;
+    let reveal_script = hex::decode(reveal_script)
+        .map(Script::from)
+        .map_err(|e| format!("reveal script: {e}"))?;
+    let control_block = hex::decode(control_block)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| ControlBlock::from_slice(&bytes).map_err(|e| e.to_string()))
+        .map_err(|e| format!("control block: {e}"))?;
+    Ok(Commit {
+        outpoint: OutPoint { txid, vout },
+        amount,
+        address,
+        reveal_script,
+        control_block,
+    })
+}
@@ -0,0 +1,36 @@
+use blockstack_lib::burnchains::Txid;
+
+use crate::stacks_node;
+use crate::stacks_node::Error as StacksNodeError;
+mod sqlite_nonce_manager;
+
+pub use sqlite_nonce_manager::{Error as SqliteNonceManagerError, SqliteNonceManager};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Sqlite Nonce Manager Error: {0}")]
+    SqliteNonceManagerError(#[from] SqliteNonceManagerError),
+    #[error("Stacks Node Error: {0}")]
+    StacksNodeError(#[from] StacksNodeError),
+    #[error("Nonces must be reconciled with the stacks node before any is handed out")]
+    NotReconciled,
+}
+
+/// Hands out nonces for the transactions of a single Stacks account, so several of them can wait
+/// in the mempool at once. Every nonce handed out is tracked until the chain confirms it.
+pub trait NonceManager {
+    /// Reserve a nonce for the next transaction. Gaps left by transactions that never made it
+    /// into or out of the mempool are filled before any new nonce is used.
+    fn next_nonce(&self) -> Result<u64, Error>;
+
+    /// Record that a transaction with the nonce was accepted by the stacks node.
+    /// `None` marks a nonce already taken by a transaction this manager did not broadcast.
+    fn record_broadcast(&self, nonce: u64, txid: Option<&Txid>) -> Result<(), Error>;
+
+    /// Hand a reserved nonce out again because its transaction was never broadcast
+    fn release(&self, nonce: u64) -> Result<(), Error>;
+
+    /// Catch up with the account's nonces on the stacks node. Confirmed nonces are forgotten,
+    /// while those of transactions that left the mempool unmined become gaps to fill.
+    fn reconcile<N: stacks_node::StacksNode>(&self, stacks_node: &N) -> Result<(), Error>;
+}
@@ -0,0 +1,483 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::types::chainstate::StacksAddress;
+use blockstack_lib::util::HexError;
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError};
+use tracing::{debug, warn};
+
+use crate::nonce_manager::{Error as NonceManagerError, NonceManager};
+use crate::stacks_node::{AccountNonces, StacksNode, TransactionStatus};
+
+/// How long a nonce may be missing from the mempool nonces reported by the stacks node before it
+/// is reused, as the node's view of its mempool lags behind the mempool itself
+const MEMPOOL_GRACE_PERIOD: Duration = Duration::from_secs(600);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Did not recognize nonce status: {0}")]
+    InvalidStatusError(String),
+    #[error("Hex Error: {0}")]
+    HexError(#[from] HexError),
+}
+
+/// What a tracked nonce is used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NonceStatus {
+    /// Handed out for a transaction that is about to be broadcast
+    Reserved,
+    /// Used by a transaction waiting in the mempool
+    Broadcast,
+    /// A gap below nonces in use, handed out before any new nonce
+    Free,
+}
+
+impl NonceStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reserved => "reserved",
+            Self::Broadcast => "broadcast",
+            Self::Free => "free",
+        }
+    }
+}
+
+impl FromStr for NonceStatus {
+    type Err = Error;
+
+    fn from_str(status: &str) -> Result<Self, Error> {
+        match status {
+            "reserved" => Ok(Self::Reserved),
+            "broadcast" => Ok(Self::Broadcast),
+            "free" => Ok(Self::Free),
+            other => Err(Error::InvalidStatusError(other.to_string())),
+        }
+    }
+}
+
+/// Nonce manager persisting the unconfirmed nonces of an account in sqlite
+pub struct SqliteNonceManager {
+    conn: rusqlite::Connection,
+    address: StacksAddress,
+    mempool_grace_period: Duration,
+}
+
+impl SqliteNonceManager {
+    pub fn new<P: AsRef<Path>>(path: P, address: StacksAddress) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?, address)
+    }
+
+    pub fn in_memory(address: StacksAddress) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?, address)
+    }
+
+    fn from_connection(conn: RusqliteConnection, address: StacksAddress) -> Result<Self, Error> {
+        let this = Self {
+            conn,
+            address,
+            mempool_grace_period: MEMPOOL_GRACE_PERIOD,
+        };
+        this.conn
+            .execute(Self::create_nonces_table(), rusqlite::params![])?;
+        this.conn
+            .execute(Self::create_metadata_table(), rusqlite::params![])?;
+        Ok(this)
+    }
+
+    fn confirmed_nonce(&self) -> Result<Option<u64>, Error> {
+        let mut statement = self.conn.prepare(Self::sql_select_confirmed_nonce())?;
+        let mut rows = statement.query(rusqlite::params![])?;
+        Ok(match rows.next()? {
+            Some(row) => Some(row.get::<_, i64>(0)? as u64),
+            None => None,
+        })
+    }
+
+    /// Unconfirmed nonces in ascending order
+    fn tracked_nonces(&self) -> Result<BTreeMap<u64, NonceStatus>, Error> {
+        let mut statement = self.conn.prepare(Self::sql_select_nonces())?;
+        let rows = statement.query_map(rusqlite::params![], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.map(|row| {
+            let (nonce, status) = row?;
+            Ok((nonce as u64, NonceStatus::from_str(&status)?))
+        })
+        .collect()
+    }
+
+    /// Transaction tracked for a nonce, along with how long ago its status last changed
+    fn tracked_tx(&self, nonce: u64) -> Result<(Option<Txid>, Duration), Error> {
+        let (txid, updated_at) = self.conn.query_row(
+            Self::sql_select_nonce_tx(),
+            rusqlite::params![nonce as i64],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, i64>(1)?)),
+        )?;
+        let txid = txid.map(|txid| Txid::from_hex(&txid)).transpose()?;
+        let age = now().saturating_sub(Duration::from_secs(updated_at as u64));
+        Ok((txid, age))
+    }
+
+    /// Whether a transaction missing from the mempool nonces reported by the stacks node left
+    /// the mempool. A tracked transaction is only given up once the node reports it dropped, or
+    /// never knew it after the grace period.
+    fn left_mempool<N: StacksNode>(
+        &self,
+        stacks_node: &N,
+        nonce: u64,
+    ) -> Result<bool, NonceManagerError> {
+        let (txid, age) = self.tracked_tx(nonce)?;
+        let Some(txid) = txid else {
+            return Ok(age >= self.mempool_grace_period);
+        };
+        Ok(match stacks_node.transaction_status(&txid)? {
+            TransactionStatus::Dropped(_) => true,
+            TransactionStatus::NotFound => age >= self.mempool_grace_period,
+            TransactionStatus::Pending
+            | TransactionStatus::Success
+            | TransactionStatus::Aborted(_) => false,
+        })
+    }
+
+    fn set_status(
+        &self,
+        nonce: u64,
+        status: NonceStatus,
+        txid: Option<&Txid>,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_nonce(),
+            rusqlite::params![
+                nonce as i64,
+                status.as_str(),
+                txid.map(|txid| txid.to_hex()),
+                now().as_secs() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Lowest nonce above every nonce in use
+    fn fresh_nonce(confirmed_nonce: u64, tracked_nonces: &BTreeMap<u64, NonceStatus>) -> u64 {
+        tracked_nonces
+            .keys()
+            .next_back()
+            .map_or(confirmed_nonce, |nonce| confirmed_nonce.max(nonce + 1))
+    }
+
+    const fn create_nonces_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS stacks_nonces (
+            nonce INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            txid TEXT,
+            updated_at INTEGER NOT NULL,
+
+            PRIMARY KEY(nonce)
+        )
+        "#
+    }
+
+    const fn create_metadata_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS nonce_manager_metadata (
+            id TEXT NOT NULL,
+
+            confirmed_nonce INTEGER NOT NULL,
+
+            PRIMARY KEY(id)
+        )
+        "#
+    }
+
+    const fn sql_select_nonces() -> &'static str {
+        r#"
+        SELECT nonce, status FROM stacks_nonces ORDER BY nonce ASC
+        "#
+    }
+
+    const fn sql_insert_nonce() -> &'static str {
+        r#"
+        REPLACE INTO stacks_nonces (nonce, status, txid, updated_at) VALUES (?1, ?2, ?3, ?4)
+        "#
+    }
+
+    const fn sql_select_nonce_tx() -> &'static str {
+        r#"
+        SELECT txid, updated_at FROM stacks_nonces WHERE nonce=?1
+        "#
+    }
+
+    const fn sql_delete_confirmed_nonces() -> &'static str {
+        r#"
+        DELETE FROM stacks_nonces WHERE nonce<?1
+        "#
+    }
+
+    const fn sql_select_confirmed_nonce() -> &'static str {
+        r#"
+            SELECT confirmed_nonce FROM nonce_manager_metadata WHERE id='nonce_manager'
+        "#
+    }
+
+    const fn sql_insert_confirmed_nonce() -> &'static str {
+        r#"
+            REPLACE INTO nonce_manager_metadata (id, confirmed_nonce) VALUES ('nonce_manager', ?1)
+        "#
+    }
+}
+
+impl NonceManager for SqliteNonceManager {
+    fn next_nonce(&self) -> Result<u64, NonceManagerError> {
+        let confirmed_nonce = self
+            .confirmed_nonce()?
+            .ok_or(NonceManagerError::NotReconciled)?;
+        let tracked_nonces = self.tracked_nonces()?;
+        let gap = tracked_nonces
+            .iter()
+            .find(|(_, status)| **status == NonceStatus::Free)
+            .map(|(nonce, _)| *nonce);
+
+        let nonce = match gap {
+            Some(nonce) => {
+                debug!("Filling nonce gap {}", nonce);
+                nonce
+            }
+            None => Self::fresh_nonce(confirmed_nonce, &tracked_nonces),
+        };
+        self.set_status(nonce, NonceStatus::Reserved, None)?;
+        Ok(nonce)
+    }
+
+    fn record_broadcast(&self, nonce: u64, txid: Option<&Txid>) -> Result<(), NonceManagerError> {
+        Ok(self.set_status(nonce, NonceStatus::Broadcast, txid)?)
+    }
+
+    fn release(&self, nonce: u64) -> Result<(), NonceManagerError> {
+        Ok(self.set_status(nonce, NonceStatus::Free, None)?)
+    }
+
+    fn reconcile<N: StacksNode>(&self, stacks_node: &N) -> Result<(), NonceManagerError> {
+        let AccountNonces {
+            confirmed_nonce,
+            mempool_nonces,
+        } = stacks_node.account_nonces(&self.address)?;
+        debug!(
+            "Reconciling nonces with confirmed nonce {} and mempool nonces {:?}",
+            confirmed_nonce, mempool_nonces
+        );
+        self.conn
+            .execute(
+                Self::sql_delete_confirmed_nonces(),
+                rusqlite::params![confirmed_nonce as i64],
+            )
+            .map_err(Error::from)?;
+        self.conn
+            .execute(
+                Self::sql_insert_confirmed_nonce(),
+                rusqlite::params![confirmed_nonce as i64],
+            )
+            .map_err(Error::from)?;
+
+        let mut tracked_nonces = self.tracked_nonces()?;
+        if let Some(mempool_nonces) = mempool_nonces {
+            let mempool_nonces: HashSet<u64> = mempool_nonces
+                .into_iter()
+                .filter(|nonce| *nonce >= confirmed_nonce)
+                .collect();
+            // Transactions that left the mempool without being mined, or never got there
+            for (nonce, status) in tracked_nonces.iter_mut() {
+                if *status != NonceStatus::Free
+                    && !mempool_nonces.contains(nonce)
+                    && self.left_mempool(stacks_node, *nonce)?
+                {
+                    warn!(
+                        "No transaction with nonce {} in the mempool, reusing it",
+                        nonce
+                    );
+                    self.set_status(*nonce, NonceStatus::Free, None)?;
+                    *status = NonceStatus::Free;
+                }
+            }
+            // Transactions broadcast by someone else using the same account
+            for nonce in mempool_nonces {
+                if tracked_nonces.get(&nonce) != Some(&NonceStatus::Broadcast) {
+                    self.set_status(nonce, NonceStatus::Broadcast, None)?;
+                    tracked_nonces.insert(nonce, NonceStatus::Broadcast);
+                }
+            }
+        }
+
+        // Anything below the highest nonce in use that is not tracked blocks the rest
+        for nonce in confirmed_nonce..Self::fresh_nonce(confirmed_nonce, &tracked_nonces) {
+            if !tracked_nonces.contains_key(&nonce) {
+                warn!("Found nonce gap {}", nonce);
+                self.set_status(nonce, NonceStatus::Free, None)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stacks_node::MockStacksNode;
+
+    fn stacks_node_mock(confirmed_nonce: u64, mempool_nonces: Vec<u64>) -> MockStacksNode {
+        let mut stacks_node_mock = MockStacksNode::new();
+        stacks_node_mock
+            .expect_account_nonces()
+            .returning(move |_| {
+                Ok(AccountNonces {
+                    confirmed_nonce,
+                    mempool_nonces: Some(mempool_nonces.clone()),
+                })
+            });
+        stacks_node_mock
+    }
+
+    fn nonce_manager() -> SqliteNonceManager {
+        let address =
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap();
+        SqliteNonceManager::in_memory(address).unwrap()
+    }
+
+    #[test]
+    fn should_not_hand_out_nonces_before_reconciling() {
+        assert!(matches!(
+            nonce_manager().next_nonce(),
+            Err(NonceManagerError::NotReconciled)
+        ));
+    }
+
+    #[test]
+    fn should_hand_out_consecutive_nonces() {
+        let nonce_manager = nonce_manager();
+        nonce_manager
+            .reconcile(&stacks_node_mock(5, vec![5, 6]))
+            .unwrap();
+
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 7);
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 8);
+
+        // A released nonce is handed out again before new ones
+        nonce_manager.release(7).unwrap();
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 7);
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 9);
+    }
+
+    #[test]
+    fn should_fill_gaps_of_dropped_transactions() {
+        let nonce_manager = nonce_manager();
+        nonce_manager
+            .reconcile(&stacks_node_mock(0, vec![]))
+            .unwrap();
+        for nonce in 0..4 {
+            assert_eq!(nonce_manager.next_nonce().unwrap(), nonce);
+            nonce_manager
+                .record_broadcast(nonce, Some(&Txid([nonce as u8; 32])))
+                .unwrap();
+        }
+
+        // Nonce 0 was mined and 2 was dropped from the mempool
+        let mut stacks_node_mock = stacks_node_mock(1, vec![1, 3]);
+        stacks_node_mock
+            .expect_transaction_status()
+            .withf(|txid| *txid == Txid([2; 32]))
+            .returning(|_| Ok(TransactionStatus::Dropped("stale".to_string())));
+        nonce_manager.reconcile(&stacks_node_mock).unwrap();
+
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 2);
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 4);
+    }
+
+    #[test]
+    fn should_wait_for_lagging_mempool_nonces() {
+        let mut nonce_manager = nonce_manager();
+        nonce_manager
+            .reconcile(&stacks_node_mock(0, vec![]))
+            .unwrap();
+        for _ in 0..3 {
+            nonce_manager.next_nonce().unwrap();
+        }
+        nonce_manager
+            .record_broadcast(0, Some(&Txid([0; 32])))
+            .unwrap();
+        nonce_manager
+            .record_broadcast(1, Some(&Txid([1; 32])))
+            .unwrap();
+
+        // None of the nonces show up in the mempool yet
+        let mut stacks_node_mock = stacks_node_mock(0, vec![]);
+        stacks_node_mock
+            .expect_transaction_status()
+            .returning(|txid| {
+                Ok(if *txid == Txid([0; 32]) {
+                    TransactionStatus::Pending
+                } else {
+                    TransactionStatus::NotFound
+                })
+            });
+        nonce_manager.reconcile(&stacks_node_mock).unwrap();
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 3);
+
+        // Only a pending transaction keeps its nonce past the grace period
+        nonce_manager.mempool_grace_period = Duration::ZERO;
+        nonce_manager.reconcile(&stacks_node_mock).unwrap();
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 1);
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 2);
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 3);
+    }
+
+    #[test]
+    fn should_keep_nonces_when_mempool_is_unknown() {
+        let nonce_manager = nonce_manager();
+        nonce_manager
+            .reconcile(&stacks_node_mock(0, vec![]))
+            .unwrap();
+        nonce_manager.next_nonce().unwrap();
+        nonce_manager.record_broadcast(0, None).unwrap();
+
+        let mut stacks_node_mock = MockStacksNode::new();
+        stacks_node_mock.expect_account_nonces().returning(|_| {
+            Ok(AccountNonces {
+                confirmed_nonce: 0,
+                mempool_nonces: None,
+            })
+        });
+        nonce_manager.reconcile(&stacks_node_mock).unwrap();
+
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 1);
+    }
+
+    #[test]
+    fn should_resume_from_persisted_nonces() {
+        let dir = tempdir::TempDir::new("nonce_manager").unwrap();
+        let path = dir.path().join("stacks_nonces.sqlite");
+        let address =
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap();
+        {
+            let nonce_manager = SqliteNonceManager::new(&path, address).unwrap();
+            nonce_manager
+                .reconcile(&stacks_node_mock(3, vec![]))
+                .unwrap();
+            assert_eq!(nonce_manager.next_nonce().unwrap(), 3);
+            nonce_manager.record_broadcast(3, None).unwrap();
+        }
+
+        let nonce_manager = SqliteNonceManager::new(&path, address).unwrap();
+        assert_eq!(nonce_manager.next_nonce().unwrap(), 4);
+    }
+}
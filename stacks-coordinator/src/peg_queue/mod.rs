@@ -1,6 +1,12 @@
+use bitcoin::{OutPoint, TxOut, XOnlyPublicKey};
 use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::StacksTransaction;
 use blockstack_lib::types::chainstate::BurnchainHeaderHash;
 
+use crate::bitcoin_node;
+use crate::bitcoin_node::BitcoinTransaction;
+use crate::bitcoin_node::Error as BitcoinNodeError;
+use crate::commit_reveal::Commit;
 use crate::stacks_node;
 use crate::stacks_node::Error as StacksNodeError;
 mod sqlite_peg_queue;
@@ -13,14 +19,205 @@ pub enum Error {
     SqlitePegQueueError(#[from] SqlitePegQueueError),
     #[error("Stacks Node Error: {0}")]
     StacksNodeError(#[from] StacksNodeError),
+    #[error("Bitcoin Node Error: {0}")]
+    BitcoinNodeError(#[from] BitcoinNodeError),
 }
 
 pub trait PegQueue {
+    /// Claim the next sBTC op that is buried under enough burn blocks to be processed
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, Error>;
-    fn poll<N: stacks_node::StacksNode>(&self, stacks_node: &N) -> Result<(), Error>;
+    /// Claim the next confirmed peg-in, leaving peg-out requests to be batched
+    fn peg_in_op(&self) -> Result<Option<stacks_node::PegInOp>, Error>;
+    /// Claim up to `max_size` confirmed peg-out requests to be fulfilled by a single transaction.
+    /// Nothing is claimed until `max_size` requests are waiting or the oldest of them was mined
+    /// at least `max_age` burn blocks ago.
+    fn peg_out_request_batch(
+        &self,
+        max_size: usize,
+        max_age: u64,
+    ) -> Result<Vec<stacks_node::PegOutRequestOp>, Error>;
+    /// Fetch sBTC ops from new burn blocks. Blocks seen on an earlier poll that
+    /// are no longer part of the bitcoin node's best chain are rolled back first,
+    /// orphaning any ops they contained.
+    fn poll<N: stacks_node::StacksNode, B: bitcoin_node::BitcoinNode>(
+        &self,
+        stacks_node: &N,
+        bitcoin_node: &B,
+    ) -> Result<(), Error>;
 
     fn acknowledge(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash)
         -> Result<(), Error>;
+
+    /// Entries claimed by `sbtc_op` whose transactions have not all been broadcast yet
+    fn unfinished_entries(&self) -> Result<Vec<Entry>, Error>;
+
+    /// Persist the signed fulfillment of a peg-out request, along with the outputs it spends,
+    /// before anything is broadcast
+    fn record_fulfillment_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &BitcoinTransaction,
+        prevouts: &[TxOut],
+    ) -> Result<(), Error>;
+
+    /// Swap in a fee bumped fulfillment spending the same outputs for every entry sharing it,
+    /// keeping their statuses
+    fn replace_fulfillment_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &BitcoinTransaction,
+    ) -> Result<(), Error>;
+
+    /// Entries fulfilled by the same transaction as the given peg-out request, itself included
+    fn fulfillment_batch(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<Vec<Entry>, Error>;
+
+    /// Persist the Stacks transaction minting or burning sBTC for an op right before it is broadcast
+    fn record_stacks_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &StacksTransaction,
+    ) -> Result<(), Error>;
+
+    /// Mark the fulfillment of a peg-out request as about to be broadcast
+    fn record_bitcoin_broadcast(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), Error>;
+
+    /// Entries whose transactions have all been broadcast and await confirmation
+    fn broadcast_entries(&self) -> Result<Vec<Entry>, Error>;
+
+    /// Stop processing an op whose transactions can not complete on their own
+    fn fail(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash) -> Result<(), Error>;
+
+    /// Queue a verified commit to be revealed to the peg wallet. Commits already queued are
+    /// left as they are.
+    fn add_commit(&self, commit: &Commit) -> Result<(), Error>;
+
+    /// Commits that have been neither revealed nor rejected, oldest first
+    fn pending_commits(&self) -> Result<Vec<Commit>, Error>;
+
+    /// Mark a commit as revealed by a transaction the bitcoin node accepted. The peg-in it
+    /// reveals is picked up by `poll` like any other.
+    fn record_reveal(
+        &self,
+        outpoint: &OutPoint,
+        reveal_tx: &BitcoinTransaction,
+    ) -> Result<(), Error>;
+
+    /// Stop trying to reveal a commit that can never be revealed
+    fn reject_commit(&self, outpoint: &OutPoint) -> Result<(), Error>;
+
+    /// Latest wallet handoff that has not completed, if any
+    fn handoff(&self) -> Result<Option<Handoff>, Error>;
+
+    /// Persist the progress of a wallet handoff, replacing whatever was recorded for its reward
+    /// cycle
+    fn record_handoff(&self, handoff: &Handoff) -> Result<(), Error>;
+}
+
+/// An sBTC op tracked by the peg queue, along with the transactions built to process it
+#[derive(Debug)]
+pub struct Entry {
+    pub burn_header_hash: BurnchainHeaderHash,
+    pub txid: Txid,
+    pub block_height: u64,
+    pub op: SbtcOp,
+    pub status: Status,
+    /// Signed BTC transaction fulfilling a peg-out request
+    pub fulfillment_tx: Option<BitcoinTransaction>,
+    /// Outputs spent by the fulfillment, needed to sign a replacement
+    pub fulfillment_prevouts: Vec<TxOut>,
+    /// Latest signed `mint!` or `burn!` transaction for the op
+    pub stacks_tx: Option<StacksTransaction>,
+}
+
+/// Processing state of an entry. Every transaction is persisted before it is broadcast,
+/// so an entry can be resumed from its status without double minting, burning or spending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Waiting to be claimed by `sbtc_op`
+    New,
+    /// Claimed, nothing has been signed or broadcast yet
+    Pending,
+    /// The peg-out fulfillment has been signed but nothing has been broadcast yet
+    Signed,
+    /// The Stacks transaction has been persisted and handed to the Stacks node
+    StacksBroadcast,
+    /// The peg-out fulfillment has been handed to the Bitcoin node
+    BitcoinBroadcast,
+    /// Every transaction of the op is final
+    Acknowledged,
+    /// A transaction of the op aborted or was double spent and needs manual intervention
+    Failed,
+    /// The op's burn block is no longer part of the best chain
+    Orphaned,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Pending => "pending",
+            Self::Signed => "signed",
+            Self::StacksBroadcast => "stacks_broadcast",
+            Self::BitcoinBroadcast => "bitcoin_broadcast",
+            Self::Acknowledged => "acknowledged",
+            Self::Failed => "failed",
+            Self::Orphaned => "orphaned",
+        }
+    }
+}
+
+/// Hand-over of the peg wallet to the aggregate public key of a new signer set, along with the
+/// transactions built for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handoff {
+    /// Reward cycle of the signer set taking over the wallet
+    pub reward_cycle: u64,
+    /// Aggregate public key the wallet is handed to
+    pub public_key: XOnlyPublicKey,
+    pub status: HandoffStatus,
+    /// Signed BTC transaction sweeping the wallet to the new key, if it held any utxos
+    pub sweep_tx: Option<BitcoinTransaction>,
+    /// Latest transaction pointing the sBTC contract at the new key
+    pub stacks_tx: Option<StacksTransaction>,
+}
+
+/// Processing state of a wallet handoff. The new key is only published once the sweep is
+/// final, so the contract never points at a wallet the funds may not reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandoffStatus {
+    /// The sweep has been signed but not broadcast yet
+    Signed,
+    /// The sweep has been handed to the Bitcoin node, or there was nothing to sweep
+    SweepBroadcast,
+    /// The transaction publishing the new key has been handed to the Stacks node
+    KeyBroadcast,
+    /// The sBTC contract holds the new key
+    Complete,
+    /// Publishing the new key aborted and needs manual intervention
+    Failed,
+}
+
+impl HandoffStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Signed => "signed",
+            Self::SweepBroadcast => "sweep_broadcast",
+            Self::KeyBroadcast => "key_broadcast",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -2,14 +2,29 @@ use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, Row as
 use std::path::Path;
 use std::str::FromStr;
 
+use bitcoin::consensus::encode::{
+    deserialize as deserialize_bitcoin, serialize_hex as serialize_bitcoin_hex,
+    Error as BitcoinEncodeError,
+};
+use bitcoin::{util::taproot::ControlBlock, Address, OutPoint, Script, TxOut, XOnlyPublicKey};
 use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::StacksTransaction;
+use blockstack_lib::codec::{Error as CodecError, StacksMessageCodec};
 use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+use blockstack_lib::util::hash::{hex_bytes, to_hex};
 use blockstack_lib::util::HexError;
 
-use crate::peg_queue::{Error as PegQueueError, PegQueue, SbtcOp};
-use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
+use crate::bitcoin_node::{BitcoinNode, BitcoinTransaction};
+use crate::commit_reveal::Commit;
+use crate::peg_queue::{
+    Entry, Error as PegQueueError, Handoff, HandoffStatus, PegQueue, SbtcOp, Status,
+};
+use crate::stacks_node::{BurnBlockOps, PegInOp, PegOutRequestOp, StacksNode};
 
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Max number of burn blocks whose ops are requested from the stacks node at once
+const POLL_CHUNK_SIZE: usize = 500;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,8 +34,16 @@ pub enum Error {
     JsonError(#[from] serde_json::Error),
     #[error("Hex codec error: {0}")]
     HexError(#[from] HexError),
+    #[error("Stacks codec error: {0}")]
+    CodecError(#[from] CodecError),
+    #[error("Bitcoin codec error: {0}")]
+    BitcoinEncodeError(#[from] BitcoinEncodeError),
     #[error("Did not recognize status: {0}")]
     InvalidStatusError(String),
+    #[error("Invalid commit: {0}")]
+    InvalidCommit(String),
+    #[error("Invalid handoff public key: {0}")]
+    InvalidHandoffPublicKey(String),
 }
 
 // Workaround to allow non-perfect conversions in `Entry::from_row`
@@ -30,8 +53,28 @@ impl From<Error> for rusqlite::Error {
     }
 }
 
+/// Reveal state of a queued commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommitStatus {
+    Pending,
+    Revealed,
+    Rejected,
+}
+
+impl CommitStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Revealed => "revealed",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
 pub struct SqlitePegQueue {
     conn: rusqlite::Connection,
+    /// Number of burn blocks, including its own, that must contain an op before it is handed out
+    min_confirmations: u64,
 }
 
 impl SqlitePegQueue {
@@ -39,22 +82,26 @@ impl SqlitePegQueue {
         path: P,
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
         Self::from_connection(
             RusqliteConnection::open(path)?,
             start_block_height,
             current_block_height,
+            min_confirmations,
         )
     }
 
     pub fn in_memory(
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
         Self::from_connection(
             RusqliteConnection::open_in_memory()?,
             start_block_height,
             current_block_height,
+            min_confirmations,
         )
     }
 
@@ -62,12 +109,23 @@ impl SqlitePegQueue {
         conn: RusqliteConnection,
         start_block_height: Option<u64>,
         current_block_height: u64,
+        min_confirmations: u64,
     ) -> Result<Self, Error> {
-        let this = Self { conn };
+        let this = Self {
+            conn,
+            min_confirmations,
+        };
         this.conn
             .execute(Self::create_sbtc_ops_table(), rusqlite::params![])?;
         this.conn
             .execute(Self::create_metadata_table(), rusqlite::params![])?;
+        this.conn
+            .execute(Self::create_burn_blocks_table(), rusqlite::params![])?;
+        this.conn
+            .execute(Self::create_commits_table(), rusqlite::params![])?;
+        this.conn
+            .execute(Self::create_handoffs_table(), rusqlite::params![])?;
+        this.add_missing_sbtc_ops_columns()?;
 
         // Prevent overflow by calling saturating sub to ensure we don't go below 0
         if let Some(start_block_height) = start_block_height {
@@ -79,45 +137,65 @@ impl SqlitePegQueue {
         Ok(this)
     }
 
-    fn poll_peg_in_ops<N: StacksNode>(
+    /// Queue the ops of a burn block, unless the stacks node reported them from a block that is
+    /// not on the best chain. Returns whether the ops were queued.
+    fn insert_burn_block_ops(
         &self,
-        stacks_node: &N,
-        block_height: u64,
-    ) -> Result<(), PegQueueError> {
-        match stacks_node.get_peg_in_ops(block_height) {
-            Err(StacksNodeError::UnknownBlockHeight(height)) => {
-                debug!("Failed to find burn block height {}", height);
-            }
-            Err(e) => return Err(PegQueueError::from(e)),
-            Ok(peg_in_ops) => {
-                for peg_in_op in peg_in_ops {
-                    let entry = Entry::from(peg_in_op);
-                    self.insert(&entry)?;
-                }
-            }
+        burn_block_ops: BurnBlockOps,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<bool, PegQueueError> {
+        let entries = burn_block_ops
+            .peg_in_ops
+            .into_iter()
+            .map(|op| (op.txid, op.burn_header_hash, Entry::from(op)))
+            .chain(
+                burn_block_ops
+                    .peg_out_request_ops
+                    .into_iter()
+                    .map(|op| (op.txid, op.burn_header_hash, Entry::from(op))),
+            )
+            .collect::<Vec<_>>();
+        if let Some((txid, op_burn_header_hash, _)) = entries
+            .iter()
+            .find(|(_, op_burn_header_hash, _)| op_burn_header_hash != burn_header_hash)
+        {
+            warn!(
+                "Stacks node reported op {} from burn block {} which is not on the best chain",
+                txid, op_burn_header_hash
+            );
+            return Ok(false);
         }
-        Ok(())
+        for (_, _, entry) in entries {
+            self.insert(&entry)?;
+        }
+        Ok(true)
     }
 
-    fn poll_peg_out_request_ops<N: StacksNode>(
-        &self,
-        stacks_node: &N,
-        block_height: u64,
-    ) -> Result<(), PegQueueError> {
-        match stacks_node.get_peg_out_request_ops(block_height) {
-            Err(StacksNodeError::UnknownBlockHeight(height)) => {
-                debug!("Failed to find burn block height {}", height);
-            }
-            Err(e) => return Err(PegQueueError::from(e)),
-            Ok(peg_out_request_ops) => {
-                for peg_out_request_op in peg_out_request_ops {
-                    let entry = Entry::from(peg_out_request_op);
-                    self.insert(&entry)?;
-                }
+    /// Queues created before transactions were persisted lack their columns
+    fn add_missing_sbtc_ops_columns(&self) -> Result<(), Error> {
+        let columns = self
+            .conn
+            .prepare(Self::sql_sbtc_ops_columns())?
+            .query_map(rusqlite::params![], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for column in [
+            "fulfillment_tx",
+            "bitcoin_txid",
+            "stacks_tx",
+            "stacks_txid",
+            "fulfillment_prevouts",
+        ] {
+            if !columns.iter().any(|existing| existing == column) {
+                self.conn.execute(
+                    &format!("ALTER TABLE sbtc_ops ADD COLUMN {column} TEXT"),
+                    rusqlite::params![],
+                )?;
             }
         }
         Ok(())
     }
+
     fn insert(&self, entry: &Entry) -> Result<(), Error> {
         self.conn.execute(
             Self::sql_insert(),
@@ -127,12 +205,37 @@ impl SqlitePegQueue {
                 entry.block_height as i64, // Stacks will crash before the coordinator if this is invalid
                 serde_json::to_string(&entry.op)?,
                 entry.status.as_str(),
+                entry.fulfillment_tx.as_ref().map(serialize_bitcoin_hex),
+                entry
+                    .fulfillment_tx
+                    .as_ref()
+                    .map(|tx| tx.txid().to_string()),
+                entry
+                    .stacks_tx
+                    .as_ref()
+                    .map(|tx| to_hex(&tx.serialize_to_vec())),
+                entry.stacks_tx.as_ref().map(|tx| tx.txid().to_hex()),
+                entry
+                    .fulfillment_tx
+                    .as_ref()
+                    .map(|_| serialize_bitcoin_hex(&entry.fulfillment_prevouts)),
             ],
         )?;
 
         Ok(())
     }
 
+    fn update_entry(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        update: impl FnOnce(&mut Entry),
+    ) -> Result<(), Error> {
+        let mut entry = self.get_entry(txid, burn_header_hash)?;
+        update(&mut entry);
+        self.insert(&entry)
+    }
+
     fn get_single_entry_with_status(&self, status: &Status) -> Result<Option<Entry>, Error> {
         Ok(self
             .conn
@@ -142,6 +245,73 @@ impl SqlitePegQueue {
             .transpose()?)
     }
 
+    /// Get the oldest entry with the given status that is buried under at least
+    /// `min_confirmations` burn blocks
+    fn get_single_confirmed_entry_with_status(
+        &self,
+        status: &Status,
+    ) -> Result<Option<Entry>, Error> {
+        let Some(max_block_height) = self.max_confirmed_block_height()? else {
+            return Ok(None)
+        };
+
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_status_up_to_height())?
+            .query_map(
+                rusqlite::params![status.as_str(), max_block_height as i64],
+                Entry::from_row,
+            )?
+            .next()
+            .transpose()?)
+    }
+
+    /// Get every entry with the given status that is buried under at least
+    /// `min_confirmations` burn blocks, oldest first
+    fn get_confirmed_entries_with_status(&self, status: &Status) -> Result<Vec<Entry>, Error> {
+        let Some(max_block_height) = self.max_confirmed_block_height()? else {
+            return Ok(vec![]);
+        };
+
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_status_up_to_height())?
+            .query_map(
+                rusqlite::params![status.as_str(), max_block_height as i64],
+                Entry::from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Height of the most recent burn block whose ops have `min_confirmations`
+    fn max_confirmed_block_height(&self) -> Result<Option<u64>, Error> {
+        let chain_tip = self.last_processed_block_height()?;
+        Ok((chain_tip + 1).checked_sub(self.min_confirmations))
+    }
+
+    /// Mark the given entry as claimed
+    fn claim(&self, mut entry: Entry) -> Result<SbtcOp, Error> {
+        entry.status = Status::Pending;
+        self.insert(&entry)?;
+        Ok(entry.op)
+    }
+
+    fn get_entries_with_statuses(&self, statuses: &[Status]) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(&Self::sql_select_statuses(statuses.len()))?
+            .query_map(statuses.iter().map(Status::as_str), Entry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn get_entries_with_fulfillment(&self, bitcoin_txid: &str) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_bitcoin_txid())?
+            .query_map(rusqlite::params![bitcoin_txid], Entry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
     fn get_entry(
         &self,
         txid: &Txid,
@@ -173,6 +343,87 @@ impl SqlitePegQueue {
         Ok(())
     }
 
+    fn burn_header_hash(&self, block_height: u64) -> Result<Option<BurnchainHeaderHash>, Error> {
+        self.conn
+            .prepare(Self::sql_select_burn_block())?
+            .query_map(rusqlite::params![block_height as i64], |row| {
+                Ok(
+                    BurnchainHeaderHash::from_hex(&row.get::<_, String>(0)?)
+                        .map_err(Error::from)?,
+                )
+            })?
+            .next()
+            .transpose()
+            .map_err(Error::from)
+    }
+
+    fn insert_burn_block(
+        &self,
+        block_height: u64,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_burn_block(),
+            rusqlite::params![block_height as i64, burn_header_hash.to_hex()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Mark all unprocessed ops from the given burn block as orphaned and forget the block
+    fn orphan_burn_block(
+        &self,
+        block_height: u64,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), Error> {
+        let orphaned = self.conn.execute(
+            Self::sql_orphan_burn_block_ops(),
+            rusqlite::params![
+                Status::Orphaned.as_str(),
+                block_height as i64,
+                burn_header_hash.to_hex(),
+                Status::New.as_str(),
+                Status::Pending.as_str(),
+            ],
+        )?;
+        self.conn.execute(
+            Self::sql_delete_burn_block(),
+            rusqlite::params![block_height as i64],
+        )?;
+
+        warn!(
+            "Burn block {} at height {} was orphaned, invalidated {} ops",
+            burn_header_hash, block_height, orphaned
+        );
+        Ok(())
+    }
+
+    /// Walk back from the last processed block until the recorded burn header hash matches
+    /// the bitcoin node's best chain, orphaning every diverging block along the way so the
+    /// following poll re-scans those heights.
+    fn rollback_orphaned_blocks<B: BitcoinNode>(
+        &self,
+        bitcoin_node: &B,
+    ) -> Result<(), PegQueueError> {
+        let mut block_height = self.last_processed_block_height()?;
+
+        while let Some(seen_burn_header_hash) = self.burn_header_hash(block_height)? {
+            let burn_header_hash = canonical_burn_header_hash(bitcoin_node, block_height)?;
+            if burn_header_hash == seen_burn_header_hash {
+                break;
+            }
+
+            self.orphan_burn_block(block_height, &seen_burn_header_hash)?;
+
+            let Some(previous_block_height) = block_height.checked_sub(1) else {
+                break;
+            };
+            block_height = previous_block_height;
+            self.insert_last_processed_block_height(block_height)?;
+        }
+        Ok(())
+    }
+
     const fn create_sbtc_ops_table() -> &'static str {
         r#"
         CREATE TABLE IF NOT EXISTS sbtc_ops (
@@ -181,6 +432,11 @@ impl SqlitePegQueue {
             block_height INTEGER NOT NULL,
             op TEXT NOT NULL,
             status TEXT NOT NULL,
+            fulfillment_tx TEXT,
+            bitcoin_txid TEXT,
+            stacks_tx TEXT,
+            stacks_txid TEXT,
+            fulfillment_prevouts TEXT,
 
             PRIMARY KEY(txid, burn_header_hash)
         )
@@ -199,21 +455,76 @@ impl SqlitePegQueue {
         "#
     }
 
+    const fn create_burn_blocks_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS burn_blocks (
+            block_height INTEGER NOT NULL,
+            burn_header_hash TEXT NOT NULL,
+
+            PRIMARY KEY(block_height)
+        )
+        "#
+    }
+
+    const fn create_commits_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS commits (
+            txid TEXT NOT NULL,
+            vout INTEGER NOT NULL,
+            amount INTEGER NOT NULL,
+            address TEXT NOT NULL,
+            reveal_script TEXT NOT NULL,
+            control_block TEXT NOT NULL,
+            status TEXT NOT NULL,
+            reveal_tx TEXT,
+
+            PRIMARY KEY(txid, vout)
+        )
+        "#
+    }
+
     const fn sql_insert() -> &'static str {
         r#"
-        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status) VALUES (?1, ?2, ?3, ?4, ?5)
+        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status, fulfillment_tx, bitcoin_txid, stacks_tx, stacks_txid, fulfillment_prevouts) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#
     }
 
     const fn sql_select_status() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
+        SELECT txid, burn_header_hash, block_height, op, status, fulfillment_tx, stacks_tx, fulfillment_prevouts FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
+        "#
+    }
+
+    const fn sql_select_status_up_to_height() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, fulfillment_tx, stacks_tx, fulfillment_prevouts FROM sbtc_ops WHERE status=?1 AND block_height<=?2 ORDER BY block_height, op ASC
+        "#
+    }
+
+    fn sql_select_statuses(count: usize) -> String {
+        let placeholders = vec!["?"; count].join(", ");
+        format!(
+            r#"
+        SELECT txid, burn_header_hash, block_height, op, status, fulfillment_tx, stacks_tx, fulfillment_prevouts FROM sbtc_ops WHERE status IN ({placeholders}) ORDER BY block_height, op ASC
+        "#
+        )
+    }
+
+    const fn sql_select_bitcoin_txid() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, fulfillment_tx, stacks_tx, fulfillment_prevouts FROM sbtc_ops WHERE bitcoin_txid=?1 ORDER BY block_height, op ASC
+        "#
+    }
+
+    const fn sql_sbtc_ops_columns() -> &'static str {
+        r#"
+        PRAGMA table_info(sbtc_ops)
         "#
     }
 
     const fn sql_select_pk() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
+        SELECT txid, burn_header_hash, block_height, op, status, fulfillment_tx, stacks_tx, fulfillment_prevouts FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
         "#
     }
 
@@ -228,23 +539,160 @@ impl SqlitePegQueue {
             REPLACE INTO peg_queue_metadata (id, last_processed_block_height) VALUES ('peg_queue', ?1)
         "#
     }
+
+    const fn sql_select_burn_block() -> &'static str {
+        r#"
+        SELECT burn_header_hash FROM burn_blocks WHERE block_height=?1
+        "#
+    }
+
+    const fn sql_insert_burn_block() -> &'static str {
+        r#"
+        REPLACE INTO burn_blocks (block_height, burn_header_hash) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_delete_burn_block() -> &'static str {
+        r#"
+        DELETE FROM burn_blocks WHERE block_height=?1
+        "#
+    }
+
+    const fn sql_orphan_burn_block_ops() -> &'static str {
+        r#"
+        UPDATE sbtc_ops SET status=?1 WHERE block_height=?2 AND burn_header_hash=?3 AND status IN (?4, ?5)
+        "#
+    }
+
+    const fn create_handoffs_table() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS handoffs (
+            reward_cycle INTEGER NOT NULL,
+            public_key TEXT NOT NULL,
+            status TEXT NOT NULL,
+            sweep_tx TEXT,
+            stacks_tx TEXT,
+
+            PRIMARY KEY(reward_cycle)
+        )
+        "#
+    }
+
+    const fn sql_select_unfinished_handoff() -> &'static str {
+        r#"
+        SELECT reward_cycle, public_key, status, sweep_tx, stacks_tx FROM handoffs WHERE status!=?1 ORDER BY reward_cycle DESC LIMIT 1
+        "#
+    }
+
+    const fn sql_insert_handoff() -> &'static str {
+        r#"
+        REPLACE INTO handoffs (reward_cycle, public_key, status, sweep_tx, stacks_tx) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#
+    }
+
+    const fn sql_insert_commit() -> &'static str {
+        r#"
+        INSERT OR IGNORE INTO commits (txid, vout, amount, address, reveal_script, control_block, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#
+    }
+
+    const fn sql_select_commits_with_status() -> &'static str {
+        r#"
+        SELECT txid, vout, amount, address, reveal_script, control_block FROM commits WHERE status=?1 ORDER BY rowid ASC
+        "#
+    }
+
+    const fn sql_update_commit_status() -> &'static str {
+        r#"
+        UPDATE commits SET status=?3, reveal_tx=?4 WHERE txid=?1 AND vout=?2
+        "#
+    }
+
+    fn update_commit_status(
+        &self,
+        outpoint: &OutPoint,
+        status: CommitStatus,
+        reveal_tx: Option<&BitcoinTransaction>,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_update_commit_status(),
+            rusqlite::params![
+                outpoint.txid.to_string(),
+                outpoint.vout,
+                status.as_str(),
+                reveal_tx.map(serialize_bitcoin_hex),
+            ],
+        )?;
+
+        Ok(())
+    }
 }
 
 impl PegQueue for SqlitePegQueue {
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, PegQueueError> {
-        let maybe_entry = self.get_single_entry_with_status(&Status::New)?;
+        let maybe_entry = self.get_single_confirmed_entry_with_status(&Status::New)?;
 
-        let Some(mut entry) = maybe_entry else {
+        let Some(entry) = maybe_entry else {
             return Ok(None)
         };
 
-        entry.status = Status::Pending;
-        self.insert(&entry)?;
+        Ok(Some(self.claim(entry)?))
+    }
 
-        Ok(Some(entry.op))
+    fn peg_in_op(&self) -> Result<Option<PegInOp>, PegQueueError> {
+        let maybe_entry = self
+            .get_confirmed_entries_with_status(&Status::New)?
+            .into_iter()
+            .find(|entry| entry.op.as_peg_in().is_some());
+
+        let Some(entry) = maybe_entry else {
+            return Ok(None)
+        };
+
+        Ok(self.claim(entry)?.as_peg_in().cloned())
+    }
+
+    fn peg_out_request_batch(
+        &self,
+        max_size: usize,
+        max_age: u64,
+    ) -> Result<Vec<PegOutRequestOp>, PegQueueError> {
+        let entries: Vec<Entry> = self
+            .get_confirmed_entries_with_status(&Status::New)?
+            .into_iter()
+            .filter(|entry| entry.op.as_peg_out_request().is_some())
+            .collect();
+
+        let Some(oldest) = entries.first() else {
+            return Ok(vec![]);
+        };
+        let chain_tip = self.last_processed_block_height()?;
+        let age = chain_tip.saturating_sub(oldest.block_height);
+        if entries.len() < max_size && age < max_age {
+            debug!(
+                "Holding back {} peg-out requests until the batch fills up or is {} blocks old",
+                entries.len(),
+                max_age
+            );
+            return Ok(vec![]);
+        }
+
+        let mut ops = vec![];
+        for entry in entries.into_iter().take(max_size) {
+            if let SbtcOp::PegOutRequest(op) = self.claim(entry)? {
+                ops.push(op);
+            }
+        }
+        Ok(ops)
     }
 
-    fn poll<N: StacksNode>(&self, stacks_node: &N) -> Result<(), PegQueueError> {
+    fn poll<N: StacksNode, B: BitcoinNode>(
+        &self,
+        stacks_node: &N,
+        bitcoin_node: &B,
+    ) -> Result<(), PegQueueError> {
+        self.rollback_orphaned_blocks(bitcoin_node)?;
+
         let target_block_height = stacks_node.burn_block_height()?;
         let start_block_height = self.last_processed_block_height().map(|count| count + 1)?;
 
@@ -258,11 +706,22 @@ impl PegQueue for SqlitePegQueue {
             start_block_height, target_block_height
         );
 
-        for block_height in start_block_height..=target_block_height {
-            self.poll_peg_in_ops(stacks_node, block_height)?;
-            self.poll_peg_out_request_ops(stacks_node, block_height)?;
-            self.insert_last_processed_block_height(block_height)?;
-            info!("Processed block height {}", block_height);
+        // Ops are fetched a chunk of blocks at a time so progress is saved along the way
+        for chunk_start in (start_block_height..=target_block_height).step_by(POLL_CHUNK_SIZE) {
+            let chunk_end = target_block_height.min(chunk_start + POLL_CHUNK_SIZE as u64 - 1);
+            for burn_block_ops in stacks_node.get_burn_ops(chunk_start..=chunk_end)? {
+                let block_height = burn_block_ops.block_height;
+                let burn_header_hash = canonical_burn_header_hash(bitcoin_node, block_height)?;
+                if !self.insert_burn_block_ops(burn_block_ops, &burn_header_hash)? {
+                    // The stacks node lags behind a reorg, so the height is scanned again once
+                    // it reports the ops of the best chain
+                    info!("Retrying block height {} on the next poll", block_height);
+                    return Ok(());
+                }
+                self.insert_burn_block(block_height, &burn_header_hash)?;
+                self.insert_last_processed_block_height(block_height)?;
+                info!("Processed block height {}", block_height);
+            }
         }
         Ok(())
     }
@@ -279,48 +738,318 @@ impl PegQueue for SqlitePegQueue {
 
         Ok(())
     }
-}
 
-#[derive(Debug)]
-struct Entry {
-    burn_header_hash: BurnchainHeaderHash,
-    txid: Txid,
-    block_height: u64,
-    op: SbtcOp,
-    status: Status,
-}
+    fn unfinished_entries(&self) -> Result<Vec<Entry>, PegQueueError> {
+        Ok(self.get_entries_with_statuses(&[
+            Status::Pending,
+            Status::Signed,
+            Status::StacksBroadcast,
+            Status::BitcoinBroadcast,
+        ])?)
+    }
 
-impl Entry {
-    fn from_row(row: &SqliteRow) -> Result<Self, RusqliteError> {
-        let txid = Txid::from_hex(&row.get::<_, String>(0)?).map_err(Error::from)?;
+    fn record_fulfillment_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &BitcoinTransaction,
+        prevouts: &[TxOut],
+    ) -> Result<(), PegQueueError> {
+        self.update_entry(txid, burn_header_hash, |entry| {
+            entry.fulfillment_tx = Some(tx.clone());
+            entry.fulfillment_prevouts = prevouts.to_vec();
+            entry.status = Status::Signed;
+        })?;
 
-        let burn_header_hash =
-            BurnchainHeaderHash::from_hex(&row.get::<_, String>(1)?).map_err(Error::from)?;
+        Ok(())
+    }
 
-        let block_height = row.get::<_, i64>(2)? as u64; // Stacks will crash before the coordinator if this is invalid
+    fn replace_fulfillment_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &BitcoinTransaction,
+    ) -> Result<(), PegQueueError> {
+        for mut entry in self.fulfillment_batch(txid, burn_header_hash)? {
+            entry.fulfillment_tx = Some(tx.clone());
+            self.insert(&entry)?;
+        }
 
-        let op: SbtcOp = serde_json::from_str(&row.get::<_, String>(3)?).map_err(Error::from)?;
+        Ok(())
+    }
 
-        let status: Status = row.get::<_, String>(4)?.parse()?;
+    fn fulfillment_batch(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<Vec<Entry>, PegQueueError> {
+        let entry = self.get_entry(txid, burn_header_hash)?;
+        let Some(fulfillment_tx) = &entry.fulfillment_tx else {
+            return Ok(vec![entry]);
+        };
 
-        Ok(Self {
-            burn_header_hash,
-            txid,
-            block_height,
-            op,
-            status,
-        })
+        Ok(self.get_entries_with_fulfillment(&fulfillment_tx.txid().to_string())?)
     }
-}
 
-impl From<PegInOp> for Entry {
-    fn from(op: PegInOp) -> Self {
-        Self {
-            block_height: op.block_height,
-            status: Status::New,
-            txid: op.txid,
-            burn_header_hash: op.burn_header_hash,
-            op: SbtcOp::PegIn(op),
+    fn record_stacks_tx(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+        tx: &StacksTransaction,
+    ) -> Result<(), PegQueueError> {
+        self.update_entry(txid, burn_header_hash, |entry| {
+            entry.stacks_tx = Some(tx.clone());
+            entry.status = Status::StacksBroadcast;
+        })?;
+
+        Ok(())
+    }
+
+    fn record_bitcoin_broadcast(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), PegQueueError> {
+        self.update_entry(txid, burn_header_hash, |entry| {
+            entry.status = Status::BitcoinBroadcast;
+        })?;
+
+        Ok(())
+    }
+
+    fn broadcast_entries(&self) -> Result<Vec<Entry>, PegQueueError> {
+        // Peg-outs are only complete once their fulfillment was broadcast after the burn
+        Ok(self
+            .get_entries_with_statuses(&[Status::StacksBroadcast, Status::BitcoinBroadcast])?
+            .into_iter()
+            .filter(|entry| {
+                matches!(
+                    (&entry.op, entry.status),
+                    (SbtcOp::PegIn(_), Status::StacksBroadcast)
+                        | (SbtcOp::PegOutRequest(_), Status::BitcoinBroadcast)
+                )
+            })
+            .collect())
+    }
+
+    fn fail(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<(), PegQueueError> {
+        self.update_entry(txid, burn_header_hash, |entry| {
+            entry.status = Status::Failed;
+        })?;
+
+        Ok(())
+    }
+
+    fn add_commit(&self, commit: &Commit) -> Result<(), PegQueueError> {
+        self.conn
+            .execute(
+                Self::sql_insert_commit(),
+                rusqlite::params![
+                    commit.outpoint.txid.to_string(),
+                    commit.outpoint.vout,
+                    commit.amount as i64,
+                    commit.address.to_string(),
+                    to_hex(commit.reveal_script.as_bytes()),
+                    to_hex(&commit.control_block.serialize()),
+                    CommitStatus::Pending.as_str(),
+                ],
+            )
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    fn pending_commits(&self) -> Result<Vec<Commit>, PegQueueError> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_commits_with_status())
+            .map_err(Error::from)?
+            .query_map(
+                rusqlite::params![CommitStatus::Pending.as_str()],
+                commit_from_row,
+            )
+            .map_err(Error::from)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::from)?)
+    }
+
+    fn record_reveal(
+        &self,
+        outpoint: &OutPoint,
+        reveal_tx: &BitcoinTransaction,
+    ) -> Result<(), PegQueueError> {
+        self.update_commit_status(outpoint, CommitStatus::Revealed, Some(reveal_tx))?;
+
+        Ok(())
+    }
+
+    fn reject_commit(&self, outpoint: &OutPoint) -> Result<(), PegQueueError> {
+        self.update_commit_status(outpoint, CommitStatus::Rejected, None)?;
+
+        Ok(())
+    }
+
+    fn handoff(&self) -> Result<Option<Handoff>, PegQueueError> {
+        let mut statement = self
+            .conn
+            .prepare(Self::sql_select_unfinished_handoff())
+            .map_err(Error::from)?;
+        let mut handoffs = statement
+            .query_map(
+                rusqlite::params![HandoffStatus::Complete.as_str()],
+                handoff_from_row,
+            )
+            .map_err(Error::from)?;
+        let handoff = handoffs.next().transpose().map_err(Error::from)?;
+        Ok(handoff)
+    }
+
+    fn record_handoff(&self, handoff: &Handoff) -> Result<(), PegQueueError> {
+        self.conn
+            .execute(
+                Self::sql_insert_handoff(),
+                rusqlite::params![
+                    handoff.reward_cycle as i64,
+                    handoff.public_key.to_string(),
+                    handoff.status.as_str(),
+                    handoff.sweep_tx.as_ref().map(serialize_bitcoin_hex),
+                    handoff
+                        .stacks_tx
+                        .as_ref()
+                        .map(|tx| to_hex(&tx.serialize_to_vec())),
+                ],
+            )
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+}
+
+fn handoff_from_row(row: &SqliteRow) -> Result<Handoff, RusqliteError> {
+    let reward_cycle = row.get::<_, i64>(0)? as u64;
+    let public_key = XOnlyPublicKey::from_str(&row.get::<_, String>(1)?)
+        .map_err(|e| Error::InvalidHandoffPublicKey(e.to_string()))?;
+    let status: HandoffStatus = row.get::<_, String>(2)?.parse()?;
+    let sweep_tx = row
+        .get::<_, Option<String>>(3)?
+        .map(|tx| -> Result<BitcoinTransaction, Error> {
+            Ok(deserialize_bitcoin(&hex_bytes(&tx)?)?)
+        })
+        .transpose()?;
+    let stacks_tx = row
+        .get::<_, Option<String>>(4)?
+        .map(|tx| -> Result<StacksTransaction, Error> {
+            Ok(StacksTransaction::consensus_deserialize(
+                &mut &hex_bytes(&tx)?[..],
+            )?)
+        })
+        .transpose()?;
+
+    Ok(Handoff {
+        reward_cycle,
+        public_key,
+        status,
+        sweep_tx,
+        stacks_tx,
+    })
+}
+
+fn commit_from_row(row: &SqliteRow) -> Result<Commit, RusqliteError> {
+    let txid = bitcoin::Txid::from_str(&row.get::<_, String>(0)?)
+        .map_err(|e| Error::InvalidCommit(e.to_string()))?;
+    let vout = row.get::<_, u32>(1)?;
+    let amount = row.get::<_, i64>(2)? as u64;
+    let address = Address::from_str(&row.get::<_, String>(3)?)
+        .map_err(|e| Error::InvalidCommit(e.to_string()))?;
+    let reveal_script = Script::from(hex_bytes(&row.get::<_, String>(4)?).map_err(Error::from)?);
+    let control_block =
+        ControlBlock::from_slice(&hex_bytes(&row.get::<_, String>(5)?).map_err(Error::from)?)
+            .map_err(|e| Error::InvalidCommit(e.to_string()))?;
+
+    Ok(Commit {
+        outpoint: OutPoint { txid, vout },
+        amount,
+        address,
+        reveal_script,
+        control_block,
+    })
+}
+
+/// Burn header hash of the block at the given height on the bitcoin node's best chain
+fn canonical_burn_header_hash<B: BitcoinNode>(
+    bitcoin_node: &B,
+    block_height: u64,
+) -> Result<BurnchainHeaderHash, PegQueueError> {
+    let block_hash = bitcoin_node.block_hash(block_height)?;
+    // Both hashes are displayed in the same (reversed) byte order
+    Ok(BurnchainHeaderHash::from_hex(&block_hash.to_string()).map_err(Error::from)?)
+}
+
+impl Entry {
+    fn from_row(row: &SqliteRow) -> Result<Self, RusqliteError> {
+        let txid = Txid::from_hex(&row.get::<_, String>(0)?).map_err(Error::from)?;
+
+        let burn_header_hash =
+            BurnchainHeaderHash::from_hex(&row.get::<_, String>(1)?).map_err(Error::from)?;
+
+        let block_height = row.get::<_, i64>(2)? as u64; // Stacks will crash before the coordinator if this is invalid
+
+        let op: SbtcOp = serde_json::from_str(&row.get::<_, String>(3)?).map_err(Error::from)?;
+
+        let status: Status = row.get::<_, String>(4)?.parse()?;
+
+        let fulfillment_tx = row
+            .get::<_, Option<String>>(5)?
+            .map(|tx| -> Result<BitcoinTransaction, Error> {
+                Ok(deserialize_bitcoin(&hex_bytes(&tx)?)?)
+            })
+            .transpose()?;
+
+        let stacks_tx = row
+            .get::<_, Option<String>>(6)?
+            .map(|tx| -> Result<StacksTransaction, Error> {
+                Ok(StacksTransaction::consensus_deserialize(
+                    &mut &hex_bytes(&tx)?[..],
+                )?)
+            })
+            .transpose()?;
+
+        let fulfillment_prevouts = row
+            .get::<_, Option<String>>(7)?
+            .map(|prevouts| -> Result<Vec<TxOut>, Error> {
+                Ok(deserialize_bitcoin(&hex_bytes(&prevouts)?)?)
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            burn_header_hash,
+            txid,
+            block_height,
+            op,
+            status,
+            fulfillment_tx,
+            fulfillment_prevouts,
+            stacks_tx,
+        })
+    }
+}
+
+impl From<PegInOp> for Entry {
+    fn from(op: PegInOp) -> Self {
+        Self {
+            block_height: op.block_height,
+            status: Status::New,
+            txid: op.txid,
+            burn_header_hash: op.burn_header_hash,
+            op: SbtcOp::PegIn(op),
+            fulfillment_tx: None,
+            fulfillment_prevouts: vec![],
+            stacks_tx: None,
         }
     }
 }
@@ -333,24 +1062,24 @@ impl From<PegOutRequestOp> for Entry {
             txid: op.txid,
             burn_header_hash: op.burn_header_hash,
             op: SbtcOp::PegOutRequest(op),
+            fulfillment_tx: None,
+            fulfillment_prevouts: vec![],
+            stacks_tx: None,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Status {
-    New,
-    Pending,
-    Acknowledged,
-}
-
-impl Status {
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::New => "new",
-            Self::Pending => "pending",
-            Self::Acknowledged => "acknowledged",
-        }
+impl FromStr for HandoffStatus {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "signed" => Self::Signed,
+            "sweep_broadcast" => Self::SweepBroadcast,
+            "key_broadcast" => Self::KeyBroadcast,
+            "complete" => Self::Complete,
+            "failed" => Self::Failed,
+            other => return Err(Error::InvalidStatusError(other.to_owned())),
+        })
     }
 }
 
@@ -360,7 +1089,12 @@ impl FromStr for Status {
         Ok(match s {
             "new" => Self::New,
             "pending" => Self::Pending,
+            "signed" => Self::Signed,
+            "stacks_broadcast" => Self::StacksBroadcast,
+            "bitcoin_broadcast" => Self::BitcoinBroadcast,
             "acknowledged" => Self::Acknowledged,
+            "failed" => Self::Failed,
+            "orphaned" => Self::Orphaned,
             other => return Err(Error::InvalidStatusError(other.to_owned())),
         })
     }
@@ -368,11 +1102,21 @@ impl FromStr for Status {
 
 #[cfg(test)]
 mod tests {
+    use crate::bitcoin_node;
     use crate::stacks_node;
 
+    use crate::util::test::{PRIVATE_KEY_HEX, PUBLIC_KEY_HEX};
+    use bitcoin::{
+        secp256k1::Secp256k1,
+        util::taproot::{LeafVersion, TaprootBuilder},
+        BlockHash, Network, PackedLockTime, Script, TxOut, XOnlyPublicKey,
+    };
     use blockstack_lib::{
-        chainstate::stacks::address::PoxAddress,
-        types::chainstate::StacksAddress,
+        chainstate::stacks::{
+            address::PoxAddress, TokenTransferMemo, TransactionAuth, TransactionPayload,
+            TransactionVersion,
+        },
+        types::chainstate::{StacksAddress, StacksPrivateKey},
         util::{hash::Hash160, secp256k1::MessageSignature},
     };
     use std::{collections::hash_map::DefaultHasher, hash::Hasher};
@@ -383,7 +1127,8 @@ mod tests {
 
     #[test]
     fn calling_sbtc_op_should_return_new_peg_ops() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
         let number_of_simulated_blocks: u64 = 3;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
@@ -392,7 +1137,9 @@ mod tests {
         assert!(peg_queue.sbtc_op().unwrap().is_none());
 
         // Should cause the peg_queue to fetch 3 peg in ops
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
 
         for height in 1..=number_of_simulated_blocks {
             let next_op = peg_queue.sbtc_op().unwrap().unwrap();
@@ -407,13 +1154,16 @@ mod tests {
 
     #[test]
     fn calling_poll_should_not_query_new_ops_if_at_block_height() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
         let number_of_simulated_blocks: u64 = 3;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
 
         // Fast forward past first poll
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
         for _ in 1..=number_of_simulated_blocks {
             peg_queue.sbtc_op().unwrap().unwrap();
             peg_queue.sbtc_op().unwrap().unwrap();
@@ -425,29 +1175,35 @@ mod tests {
             .expect_burn_block_height()
             .returning(move || Ok(number_of_simulated_blocks));
 
-        stacks_node_mock.expect_get_peg_in_ops().never();
-        stacks_node_mock.expect_get_peg_out_request_ops().never();
+        stacks_node_mock.expect_get_burn_ops().never();
 
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
     }
 
     #[test]
     fn calling_poll_should_find_new_ops_if_at_new_block_height() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
         let number_of_simulated_blocks: u64 = 3;
         let number_of_simulated_blocks_second_poll: u64 = 5;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
 
         // Fast forward past first poll
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
         for _ in 1..=number_of_simulated_blocks {
             peg_queue.sbtc_op().unwrap().unwrap();
             peg_queue.sbtc_op().unwrap().unwrap();
         }
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks_second_poll);
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
 
         for height in number_of_simulated_blocks + 1..=number_of_simulated_blocks_second_poll {
             let next_op = peg_queue.sbtc_op().unwrap().unwrap();
@@ -462,11 +1218,14 @@ mod tests {
 
     #[test]
     fn acknowledged_entries_should_have_acknowledge_status() {
-        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2).unwrap();
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
         let number_of_simulated_blocks: u64 = 1;
 
         let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
 
         let next_op = peg_queue.sbtc_op().unwrap().unwrap();
         let peg_in_op = next_op.as_peg_in().unwrap();
@@ -488,51 +1247,624 @@ mod tests {
         let second_poll_node_block_height: u64 = 40;
 
         let peg_queue =
-            SqlitePegQueue::in_memory(Some(start_block_height), initial_node_block_height).unwrap();
+            SqlitePegQueue::in_memory(Some(start_block_height), initial_node_block_height, 1)
+                .unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
 
         let stacks_node_mock = default_stacks_node_mock(initial_node_block_height);
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
 
         assert_eq!(peg_queue.last_processed_block_height().unwrap(), 20);
 
         let stacks_node_mock = stacks_node_mock_with_no_sbtc_ops(second_poll_node_block_height);
-        peg_queue.poll(&stacks_node_mock).unwrap();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
 
         assert_eq!(peg_queue.last_processed_block_height().unwrap(), 40);
     }
 
-    fn default_stacks_node_mock(block_height: u64) -> stacks_node::MockStacksNode {
-        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
+    #[test]
+    fn sbtc_op_should_hold_back_ops_until_min_confirmations() {
+        let min_confirmations: u64 = 3;
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, min_confirmations).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        // Ops at height 1 only have 2 confirmations at a chain tip of 2
+        let stacks_node_mock = default_stacks_node_mock(2);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+
+        // Ops at height 1 are buried deep enough at a chain tip of 3, but not those at height 2
+        let stacks_node_mock = stacks_node_mock_with_no_sbtc_ops(3);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_in().unwrap().block_height, 1);
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_out_request().unwrap().block_height, 1);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+    }
 
+    #[test]
+    fn calling_poll_should_orphan_ops_from_reorged_blocks() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let number_of_simulated_blocks: u64 = 3;
+        let fork_block_height: u64 = 2;
+
+        let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        // Claim the ops of the first block so only ops from later blocks remain new
+        peg_queue.sbtc_op().unwrap().unwrap();
+        peg_queue.sbtc_op().unwrap().unwrap();
+
+        // Blocks from the fork height onwards are replaced by a competing chain
+        let fork_burn_header_hash = move |height: u64| {
+            if height >= fork_block_height {
+                burn_header_hash(height, 1)
+            } else {
+                burn_header_hash(height, 0)
+            }
+        };
+
+        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
         stacks_node_mock
             .expect_burn_block_height()
-            .returning(move || Ok(block_height));
-
+            .returning(move || Ok(number_of_simulated_blocks));
         stacks_node_mock
-            .expect_get_peg_in_ops()
-            .returning(|height| Ok(vec![peg_in_op(height)]));
+            .expect_get_burn_ops()
+            .returning(move |heights| {
+                Ok(heights
+                    .map(|height| {
+                        let mut op = peg_in_op(height);
+                        op.burn_header_hash = fork_burn_header_hash(height);
+                        BurnBlockOps {
+                            block_height: height,
+                            peg_in_ops: vec![op],
+                            peg_out_request_ops: vec![],
+                        }
+                    })
+                    .collect())
+            });
+
+        let mut bitcoin_node_mock = bitcoin_node::MockBitcoinNode::new();
+        bitcoin_node_mock
+            .expect_block_hash()
+            .returning(move |height| Ok(block_hash(&fork_burn_header_hash(height))));
 
-        stacks_node_mock
-            .expect_get_peg_out_request_ops()
-            .returning(|height| Ok(vec![peg_out_request_op(height)]));
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        for height in fork_block_height..=number_of_simulated_blocks {
+            let orphaned_op = peg_in_op(height);
+            let entry = peg_queue
+                .get_entry(&orphaned_op.txid, &orphaned_op.burn_header_hash)
+                .unwrap();
+            assert_eq!(entry.status, Status::Orphaned);
+
+            let orphaned_op = peg_out_request_op(height);
+            let entry = peg_queue
+                .get_entry(&orphaned_op.txid, &orphaned_op.burn_header_hash)
+                .unwrap();
+            assert_eq!(entry.status, Status::Orphaned);
+        }
+
+        // Only the ops re-scanned from the new chain should be handed out
+        for height in fork_block_height..=number_of_simulated_blocks {
+            let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+            let peg_in_op = next_op.as_peg_in().unwrap();
+            assert_eq!(peg_in_op.block_height, height);
+            assert_eq!(peg_in_op.burn_header_hash, burn_header_hash(height, 1));
+        }
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+        assert_eq!(
+            peg_queue
+                .burn_header_hash(number_of_simulated_blocks)
+                .unwrap(),
+            Some(burn_header_hash(number_of_simulated_blocks, 1))
+        );
+    }
+
+    #[test]
+    fn calling_poll_should_retry_blocks_the_stacks_node_reports_from_a_stale_fork() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let number_of_simulated_blocks: u64 = 3;
+        let fork_block_height: u64 = 2;
+
+        // The bitcoin node already follows the new chain from the fork height onwards
+        let fork_burn_header_hash = move |height: u64| {
+            if height >= fork_block_height {
+                burn_header_hash(height, 1)
+            } else {
+                burn_header_hash(height, 0)
+            }
+        };
+        let mut bitcoin_node_mock = bitcoin_node::MockBitcoinNode::new();
+        bitcoin_node_mock
+            .expect_block_hash()
+            .returning(move |height| Ok(block_hash(&fork_burn_header_hash(height))));
 
+        // While the stacks node still reports the ops of the stale fork
+        let stacks_node_mock = default_stacks_node_mock(number_of_simulated_blocks);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+        assert_eq!(
+            peg_queue.last_processed_block_height().unwrap(),
+            fork_block_height - 1
+        );
+        let stale_op = peg_in_op(fork_block_height);
+        assert!(peg_queue
+            .get_entry(&stale_op.txid, &stale_op.burn_header_hash)
+            .is_err());
+
+        // Once it caught up, the same heights are scanned again
+        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
         stacks_node_mock
+            .expect_burn_block_height()
+            .returning(move || Ok(number_of_simulated_blocks));
+        stacks_node_mock
+            .expect_get_burn_ops()
+            .returning(move |heights| {
+                Ok(heights
+                    .map(|height| {
+                        let mut op = peg_in_op(height);
+                        op.burn_header_hash = fork_burn_header_hash(height);
+                        BurnBlockOps {
+                            block_height: height,
+                            peg_in_ops: vec![op],
+                            peg_out_request_ops: vec![],
+                        }
+                    })
+                    .collect())
+            });
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+        assert_eq!(
+            peg_queue.last_processed_block_height().unwrap(),
+            number_of_simulated_blocks
+        );
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_in().unwrap().block_height, 1);
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_out_request().unwrap().block_height, 1);
+        for height in fork_block_height..=number_of_simulated_blocks {
+            let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+            let peg_in_op = next_op.as_peg_in().unwrap();
+            assert_eq!(peg_in_op.block_height, height);
+            assert_eq!(peg_in_op.burn_header_hash, burn_header_hash(height, 1));
+        }
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
     }
 
-    fn stacks_node_mock_with_no_sbtc_ops(block_height: u64) -> stacks_node::MockStacksNode {
+    #[test]
+    fn calling_poll_should_not_orphan_ops_without_reorg() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        let stacks_node_mock = default_stacks_node_mock(2);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let stacks_node_mock = stacks_node_mock_with_no_sbtc_ops(4);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let op = peg_in_op(2);
+        let entry = peg_queue.get_entry(&op.txid, &op.burn_header_hash).unwrap();
+        assert_eq!(entry.status, Status::New);
+        assert_eq!(peg_queue.last_processed_block_height().unwrap(), 4);
+    }
+
+    #[test]
+    fn recorded_transactions_should_be_resumable() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let peg_in_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let peg_in_op = peg_in_op.as_peg_in().unwrap();
+        let peg_out_request_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let peg_out_request_op = peg_out_request_op.as_peg_out_request().unwrap();
+
+        let entries = peg_queue.unfinished_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.status == Status::Pending && entry.stacks_tx.is_none()));
+
+        let fulfillment_tx = fulfillment_tx();
+        let stacks_tx = stacks_tx();
+        peg_queue
+            .record_stacks_tx(&peg_in_op.txid, &peg_in_op.burn_header_hash, &stacks_tx)
+            .unwrap();
+        peg_queue
+            .record_fulfillment_tx(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+                &fulfillment_tx,
+                &fulfillment_prevouts(),
+            )
+            .unwrap();
+
+        let entry = peg_queue
+            .get_entry(&peg_in_op.txid, &peg_in_op.burn_header_hash)
+            .unwrap();
+        assert_eq!(entry.status, Status::StacksBroadcast);
+        assert_eq!(entry.stacks_tx, Some(stacks_tx.clone()));
+        assert_eq!(entry.fulfillment_tx, None);
+
+        let entry = peg_queue
+            .get_entry(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        assert_eq!(entry.status, Status::Signed);
+        assert_eq!(entry.fulfillment_tx, Some(fulfillment_tx.clone()));
+        assert_eq!(entry.fulfillment_prevouts, fulfillment_prevouts());
+
+        peg_queue
+            .record_stacks_tx(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+                &stacks_tx,
+            )
+            .unwrap();
+        peg_queue
+            .record_bitcoin_broadcast(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+
+        let entry = peg_queue
+            .get_entry(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        assert_eq!(entry.status, Status::BitcoinBroadcast);
+        assert_eq!(entry.fulfillment_tx, Some(fulfillment_tx.clone()));
+        assert_eq!(entry.stacks_tx, Some(stacks_tx));
+
+        // A fee bumped fulfillment replaces the broadcast one without changing the status
+        let mut bumped_fulfillment_tx = fulfillment_tx;
+        bumped_fulfillment_tx.output[0].value -= 1;
+        peg_queue
+            .replace_fulfillment_tx(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+                &bumped_fulfillment_tx,
+            )
+            .unwrap();
+        let entry = peg_queue
+            .get_entry(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        assert_eq!(entry.status, Status::BitcoinBroadcast);
+        assert_eq!(entry.fulfillment_tx, Some(bumped_fulfillment_tx));
+        assert_eq!(entry.fulfillment_prevouts, fulfillment_prevouts());
+
+        peg_queue
+            .acknowledge(&peg_in_op.txid, &peg_in_op.burn_header_hash)
+            .unwrap();
+        let entries = peg_queue.unfinished_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].txid, peg_out_request_op.txid);
+    }
+
+    #[test]
+    fn broadcast_entries_should_await_all_transactions() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let peg_in_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let peg_in_op = peg_in_op.as_peg_in().unwrap();
+        let peg_out_request_op = peg_queue.sbtc_op().unwrap().unwrap();
+        let peg_out_request_op = peg_out_request_op.as_peg_out_request().unwrap();
+
+        let stacks_tx = stacks_tx();
+        peg_queue
+            .record_stacks_tx(&peg_in_op.txid, &peg_in_op.burn_header_hash, &stacks_tx)
+            .unwrap();
+        peg_queue
+            .record_fulfillment_tx(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+                &fulfillment_tx(),
+                &fulfillment_prevouts(),
+            )
+            .unwrap();
+        peg_queue
+            .record_stacks_tx(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+                &stacks_tx,
+            )
+            .unwrap();
+
+        // The peg-out fulfillment has not been broadcast yet
+        let entries = peg_queue.broadcast_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].txid, peg_in_op.txid);
+
+        peg_queue
+            .record_bitcoin_broadcast(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        assert_eq!(peg_queue.broadcast_entries().unwrap().len(), 2);
+
+        // A failed peg-out is left alone
+        peg_queue
+            .fail(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        let entries = peg_queue.broadcast_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].txid, peg_in_op.txid);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+
+        let entry = peg_queue
+            .get_entry(
+                &peg_out_request_op.txid,
+                &peg_out_request_op.burn_header_hash,
+            )
+            .unwrap();
+        assert_eq!(entry.status, Status::Failed);
+    }
+
+    #[test]
+    fn peg_out_request_batch_should_wait_for_size_or_age() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        let stacks_node_mock = default_stacks_node_mock(3);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        // Three requests from blocks 1 to 3 neither fill a batch of 4 nor are 5 blocks old
+        assert!(peg_queue.peg_out_request_batch(4, 5).unwrap().is_empty());
+
+        let batch = peg_queue.peg_out_request_batch(2, 5).unwrap();
+        let txids: Vec<_> = batch.iter().map(|op| op.txid).collect();
+        assert_eq!(
+            txids,
+            vec![peg_out_request_op(1).txid, peg_out_request_op(2).txid]
+        );
+
+        // The last request is flushed once it is old enough
+        assert!(peg_queue.peg_out_request_batch(2, 1).unwrap().is_empty());
+        let batch = peg_queue.peg_out_request_batch(2, 0).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].txid, peg_out_request_op(3).txid);
+        assert!(peg_queue.peg_out_request_batch(2, 0).unwrap().is_empty());
+
+        // Peg-ins are claimed on their own
+        for block_height in 1..=3 {
+            let op = peg_queue.peg_in_op().unwrap().unwrap();
+            assert_eq!(op.txid, peg_in_op(block_height).txid);
+        }
+        assert!(peg_queue.peg_in_op().unwrap().is_none());
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+        assert_eq!(peg_queue.unfinished_entries().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn batched_fulfillments_should_be_replaced_together() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+
+        let stacks_node_mock = default_stacks_node_mock(2);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let batch = peg_queue.peg_out_request_batch(2, 0).unwrap();
+        assert_eq!(batch.len(), 2);
+
+        let fulfillment_tx = fulfillment_tx();
+        for op in &batch {
+            peg_queue
+                .record_fulfillment_tx(
+                    &op.txid,
+                    &op.burn_header_hash,
+                    &fulfillment_tx,
+                    &fulfillment_prevouts(),
+                )
+                .unwrap();
+        }
+
+        let entries = peg_queue
+            .fulfillment_batch(&batch[1].txid, &batch[1].burn_header_hash)
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].txid, batch[0].txid);
+
+        let mut bumped_fulfillment_tx = fulfillment_tx;
+        bumped_fulfillment_tx.output[0].value -= 1;
+        peg_queue
+            .replace_fulfillment_tx(
+                &batch[0].txid,
+                &batch[0].burn_header_hash,
+                &bumped_fulfillment_tx,
+            )
+            .unwrap();
+        for op in &batch {
+            let entry = peg_queue.get_entry(&op.txid, &op.burn_header_hash).unwrap();
+            assert_eq!(entry.status, Status::Signed);
+            assert_eq!(entry.fulfillment_tx, Some(bumped_fulfillment_tx.clone()));
+        }
+
+        // Unfulfilled requests are only batched with themselves
+        let peg_in_op = peg_in_op(1);
+        let entries = peg_queue
+            .fulfillment_batch(&peg_in_op.txid, &peg_in_op.burn_header_hash)
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn should_add_transaction_columns_to_existing_queue() {
+        let conn = RusqliteConnection::open_in_memory().unwrap();
+        conn.execute(
+            r#"
+            CREATE TABLE sbtc_ops (
+                txid TEXT NOT NULL,
+                burn_header_hash TEXT NOT NULL,
+                block_height INTEGER NOT NULL,
+                op TEXT NOT NULL,
+                status TEXT NOT NULL,
+
+                PRIMARY KEY(txid, burn_header_hash)
+            )
+            "#,
+            rusqlite::params![],
+        )
+        .unwrap();
+
+        let peg_queue = SqlitePegQueue::from_connection(conn, Some(1), 2, 1).unwrap();
+        let bitcoin_node_mock = default_bitcoin_node_mock();
+        let stacks_node_mock = default_stacks_node_mock(1);
+        peg_queue
+            .poll(&stacks_node_mock, &bitcoin_node_mock)
+            .unwrap();
+
+        let op = peg_queue.sbtc_op().unwrap().unwrap();
+        let op = op.as_peg_in().unwrap();
+        peg_queue
+            .record_stacks_tx(&op.txid, &op.burn_header_hash, &stacks_tx())
+            .unwrap();
+    }
+
+    #[test]
+    fn commits_should_stay_pending_until_revealed_or_rejected() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 2, 1).unwrap();
+        let first = commit(0);
+        let second = commit(1);
+
+        peg_queue.add_commit(&first).unwrap();
+        peg_queue.add_commit(&second).unwrap();
+        assert_eq!(
+            peg_queue.pending_commits().unwrap(),
+            vec![first.clone(), second.clone()]
+        );
+
+        peg_queue
+            .record_reveal(&first.outpoint, &fulfillment_tx())
+            .unwrap();
+        peg_queue.reject_commit(&second.outpoint).unwrap();
+        assert!(peg_queue.pending_commits().unwrap().is_empty());
+
+        // Adding a commit again does not revive it
+        peg_queue.add_commit(&first).unwrap();
+        assert!(peg_queue.pending_commits().unwrap().is_empty());
+    }
+
+    #[test]
+    fn handoff_should_stay_unfinished_until_complete() {
+        let peg_queue = SqlitePegQueue::in_memory(Some(1), 1, 1).unwrap();
+        assert_eq!(peg_queue.handoff().unwrap(), None);
+
+        let mut handoff = Handoff {
+            reward_cycle: 67,
+            public_key: XOnlyPublicKey::from_str(
+                "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+            )
+            .unwrap(),
+            status: HandoffStatus::Signed,
+            sweep_tx: Some(fulfillment_tx()),
+            stacks_tx: None,
+        };
+        peg_queue.record_handoff(&handoff).unwrap();
+        assert_eq!(peg_queue.handoff().unwrap(), Some(handoff.clone()));
+
+        handoff.status = HandoffStatus::KeyBroadcast;
+        handoff.stacks_tx = Some(stacks_tx());
+        peg_queue.record_handoff(&handoff).unwrap();
+        assert_eq!(peg_queue.handoff().unwrap(), Some(handoff.clone()));
+
+        handoff.status = HandoffStatus::Complete;
+        peg_queue.record_handoff(&handoff).unwrap();
+        assert_eq!(peg_queue.handoff().unwrap(), None);
+    }
+
+    fn default_bitcoin_node_mock() -> bitcoin_node::MockBitcoinNode {
+        let mut bitcoin_node_mock = bitcoin_node::MockBitcoinNode::new();
+
+        bitcoin_node_mock
+            .expect_block_hash()
+            .returning(|height| Ok(block_hash(&burn_header_hash(height, 0))));
+
+        bitcoin_node_mock
+    }
+
+    fn default_stacks_node_mock(block_height: u64) -> stacks_node::MockStacksNode {
         let mut stacks_node_mock = stacks_node::MockStacksNode::new();
 
         stacks_node_mock
             .expect_burn_block_height()
             .returning(move || Ok(block_height));
 
+        stacks_node_mock.expect_get_burn_ops().returning(|heights| {
+            Ok(heights
+                .map(|height| BurnBlockOps {
+                    block_height: height,
+                    peg_in_ops: vec![peg_in_op(height)],
+                    peg_out_request_ops: vec![peg_out_request_op(height)],
+                })
+                .collect())
+        });
+
         stacks_node_mock
-            .expect_get_peg_in_ops()
-            .returning(|_height| Ok(vec![]));
+    }
+
+    fn stacks_node_mock_with_no_sbtc_ops(block_height: u64) -> stacks_node::MockStacksNode {
+        let mut stacks_node_mock = stacks_node::MockStacksNode::new();
 
         stacks_node_mock
-            .expect_get_peg_out_request_ops()
-            .returning(|_height| Ok(vec![]));
+            .expect_burn_block_height()
+            .returning(move || Ok(block_height));
+
+        stacks_node_mock.expect_get_burn_ops().returning(|heights| {
+            Ok(heights
+                .map(|height| BurnBlockOps {
+                    block_height: height,
+                    ..Default::default()
+                })
+                .collect())
+        });
 
         stacks_node_mock
     }
@@ -548,7 +1880,7 @@ mod tests {
             amount: 1337,
             memo: vec![1, 3, 3, 7],
             txid: Txid(hash_and_expand(block_height, 1)),
-            burn_header_hash: BurnchainHeaderHash(hash_and_expand(block_height, 0)),
+            burn_header_hash: burn_header_hash(block_height, 0),
             block_height,
             vtxindex: 0,
         }
@@ -567,12 +1899,78 @@ mod tests {
             signature: MessageSignature([0; 65]),
             memo: vec![1, 3, 3, 7],
             txid: Txid(hash_and_expand(block_height, 2)),
-            burn_header_hash: BurnchainHeaderHash(hash_and_expand(block_height, 0)),
+            burn_header_hash: burn_header_hash(block_height, 0),
             block_height,
             vtxindex: 0,
         }
     }
 
+    fn fulfillment_tx() -> BitcoinTransaction {
+        BitcoinTransaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![],
+            output: vec![TxOut {
+                value: 1337,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    fn fulfillment_prevouts() -> Vec<TxOut> {
+        vec![TxOut {
+            value: 2674,
+            script_pubkey: Script::new(),
+        }]
+    }
+
+    fn commit(vout: u32) -> Commit {
+        let secp = Secp256k1::verification_only();
+        let internal_key = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
+        let reveal_script = Script::from(vec![0x51]);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap_or_else(|_| panic!("Incomplete script tree"));
+
+        Commit {
+            outpoint: OutPoint {
+                txid: fulfillment_tx().txid(),
+                vout,
+            },
+            amount: 10_000,
+            address: Address::p2tr_tweaked(spend_info.output_key(), Network::Regtest),
+            reveal_script: reveal_script.clone(),
+            control_block: spend_info
+                .control_block(&(reveal_script, LeafVersion::TapScript))
+                .unwrap(),
+        }
+    }
+
+    fn stacks_tx() -> StacksTransaction {
+        let private_key = StacksPrivateKey::from_hex(PRIVATE_KEY_HEX).unwrap();
+        let payload = TransactionPayload::TokenTransfer(
+            StacksAddress::new(26, Hash160([0; 20])).into(),
+            1337,
+            TokenTransferMemo([0; 34]),
+        );
+
+        StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&private_key).unwrap(),
+            payload,
+        )
+    }
+
+    fn burn_header_hash(block_height: u64, fork: u64) -> BurnchainHeaderHash {
+        BurnchainHeaderHash(hash_and_expand(block_height, fork.wrapping_neg()))
+    }
+
+    fn block_hash(burn_header_hash: &BurnchainHeaderHash) -> BlockHash {
+        burn_header_hash.to_hex().parse().unwrap()
+    }
+
     fn hash_and_expand(val: u64, nonce: u64) -> [u8; 32] {
         let mut hasher = DefaultHasher::new();
         hasher.write_u64(val);
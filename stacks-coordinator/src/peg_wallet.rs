@@ -1,10 +1,11 @@
 use crate::bitcoin_node::{self, UTXO};
 use crate::bitcoin_wallet::{BitcoinWallet as BitcoinWalletStruct, Error as BitcoinWalletError};
-use crate::stacks_node::PegOutRequestOp;
+use crate::commit_reveal::Commit;
+use crate::stacks_node::{PegOutRequestOp, StacksNode};
 use crate::stacks_wallet::{
     BuildStacksTransaction, Error as StacksWalletError, StacksWallet as StacksWalletStruct,
 };
-use bitcoin::XOnlyPublicKey;
+use bitcoin::{util::taproot::TapBranchHash, XOnlyPublicKey};
 use bitcoin::{Address as BitcoinAddress, TxOut};
 use blockstack_lib::{
     chainstate::stacks::StacksTransaction,
@@ -21,46 +22,105 @@ pub enum Error {
 }
 
 pub trait StacksWallet {
-    /// Builds a verified signed transaction for a given peg-in operation
-    fn build_transaction<T: BuildStacksTransaction>(
+    /// Builds a verified signed transaction for a given peg-in operation, paying the fee
+    /// estimated by the stacks node
+    fn build_transaction<T: BuildStacksTransaction, N: StacksNode>(
         &self,
         op: &T,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, Error>;
+    /// Builds a verified signed transaction for a given operation paying the given fee,
+    /// clamped to the wallet's fee caps
+    fn build_transaction_with_fee<T: BuildStacksTransaction>(
+        &self,
+        op: &T,
+        nonce: u64,
+        fee: u64,
+    ) -> Result<StacksTransaction, Error>;
+    /// Builds a replacement for the op's transaction `tx` paying a higher fee, if the stacks node
+    /// now estimates a higher fee than it pays and the wallet's max fee allows for it
+    fn bump_fee<T: BuildStacksTransaction, N: StacksNode>(
+        &self,
+        op: &T,
+        tx: &StacksTransaction,
+        stacks_node: &N,
+    ) -> Result<Option<StacksTransaction>, Error>;
     /// Builds a verified signed transaction for setting the sBTC wallet public key
-    fn build_set_bitcoin_wallet_public_key_transaction(
+    fn build_set_bitcoin_wallet_public_key_transaction<N: StacksNode>(
         &self,
         public_key: &XOnlyPublicKey,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, Error>;
     /// Builds a verified signed transaction for setting the sBTC coordinator data
-    fn build_set_coordinator_data_transaction(
+    fn build_set_coordinator_data_transaction<N: StacksNode>(
         &self,
         address: &StacksAddress,
         public_key: &StacksPublicKey,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, Error>;
     /// Returns the sBTC address for the wallet
     fn address(&self) -> &StacksAddress;
     /// Returns the sBTC public key for the wallet
     fn public_key(&self) -> &StacksPublicKey;
-    /// Sets the sBTC transaction fee
-    fn set_fee(&mut self, fee: u64);
 }
 
 pub trait BitcoinWallet {
     type Error: Debug;
 
     // Builds a fulfilled unsigned transaction using the provided utxos to cover the spend amount
+    // and a fee at the given rate in sat/vB, paid out of the op's fulfillment fee
     fn fulfill_peg_out(
         &self,
         op: &PegOutRequestOp,
         txouts: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
+
+    // Builds a single unsigned transaction fulfilling every op, paying each recipient from the
+    // provided utxos and a fee at the given rate out of the ops' combined fulfillment fees
+    fn fulfill_peg_outs(
+        &self,
+        ops: &[PegOutRequestOp],
+        txouts: Vec<UTXO>,
+        fee_rate: u64,
+    ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
+
+    /// Builds an unsigned replacement of a fulfillment that pays the given fee rate out of its change
+    fn bump_fee(
+        &self,
+        tx: &bitcoin_node::BitcoinTransaction,
+        prevouts: &[TxOut],
+        fee_rate: u64,
+        max_fee: u64,
+    ) -> Result<bitcoin_node::BitcoinTransaction, Error>;
+
+    /// Builds an unsigned transaction revealing a verified commit, which moves the committed
+    /// funds into the wallet less a fee at the given rate in sat/vB
+    fn reveal_commit(
+        &self,
+        commit: &Commit,
+        fee_rate: u64,
+    ) -> Result<bitcoin_node::BitcoinTransaction, Error>;
+
+    /// Builds an unsigned transaction sweeping the provided utxos to the wallet of a new aggregate
+    /// public key, carrying the handoff op of its reward cycle and paying a fee at the given rate
+    fn handoff(
+        &self,
+        new_public_key: &XOnlyPublicKey,
+        reward_cycle: u64,
+        utxos: Vec<UTXO>,
+        fee_rate: u64,
     ) -> Result<(bitcoin_node::BitcoinTransaction, Vec<TxOut>), Error>;
 
     /// Returns the BTC address for the wallet
     fn address(&self) -> &BitcoinAddress;
 
+    /// Root of the script tree the wallet's taproot output key commits to, if any
+    fn merkle_root(&self) -> Option<TapBranchHash>;
+
     fn x_only_pub_key(&self) -> &XOnlyPublicKey;
 }
 
@@ -0,0 +1,170 @@
+//! A non-blocking client for the burn op endpoints of a stacks node. Requests share a
+//! pooled connection per host, so fetching the ops of many burn blocks at once does not
+//! open a connection per request.
+
+use std::time::Instant;
+
+use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use reqwest::{Client, Response};
+use serde_json::Value;
+use tracing::debug;
+use url::Url;
+
+use crate::stacks_node::{
+    AsyncStacksNode, Error as StacksNodeError, PegInOp, PegOutRequestOp, RequestPolicy,
+};
+
+#[derive(Clone)]
+pub struct AsyncNodeClient {
+    node_url: Url,
+    client: Client,
+    request_policy: RequestPolicy,
+}
+
+impl AsyncNodeClient {
+    pub fn new(node_url: Url, request_policy: RequestPolicy) -> Result<Self, StacksNodeError> {
+        let client = Client::builder()
+            .timeout(request_policy.request_timeout)
+            .pool_max_idle_per_host(request_policy.max_concurrent_blocks * 2)
+            .build()?;
+        Ok(Self {
+            node_url,
+            client,
+            request_policy,
+        })
+    }
+
+    async fn get_response(&self, route: &str) -> Result<Response, StacksNodeError> {
+        let url = self.node_url.join(route)?;
+        debug!("Sending Request to Stacks Node: {}", &url);
+        let now = Instant::now();
+        let mut backoff_timer = backoff::ExponentialBackoffBuilder::new()
+            .with_initial_interval(std::time::Duration::from_millis(2))
+            .with_max_interval(std::time::Duration::from_millis(128))
+            .with_max_elapsed_time(None)
+            .build();
+
+        loop {
+            match self.client.get(url.as_str()).send().await {
+                Ok(response) => return Ok(response),
+                // Only failures to reach the node are worth retrying
+                Err(e) if e.is_connect() => {
+                    let delay = backoff_timer
+                        .next_backoff()
+                        .filter(|delay| now.elapsed() + *delay <= self.request_policy.retry_timeout)
+                        .ok_or_else(|| {
+                            debug!("Timeout exceeded.");
+                            StacksNodeError::Timeout
+                        })?;
+                    debug!("Failed to connect to {}. Next attempt in {:?}", &url, delay);
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn get_block_ops<T>(&self, block_height: u64, op: &str) -> Result<Vec<T>, StacksNodeError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let json = self
+            .get_response(&format!("/v2/burn_ops/{block_height}/{op}"))
+            .await?
+            .json::<Value>()
+            .await
+            .map_err(|_| StacksNodeError::UnknownBlockHeight(block_height))?;
+        Ok(serde_json::from_value(json[op].clone())?)
+    }
+}
+
+#[async_trait]
+impl AsyncStacksNode for AsyncNodeClient {
+    async fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, StacksNodeError> {
+        debug!("Retrieving peg-in ops...");
+        self.get_block_ops::<PegInOp>(block_height, "peg_in").await
+    }
+
+    async fn get_peg_out_request_ops(
+        &self,
+        block_height: u64,
+    ) -> Result<Vec<PegOutRequestOp>, StacksNodeError> {
+        debug!("Retrieving peg-out request ops...");
+        self.get_block_ops::<PegOutRequestOp>(block_height, "peg_out_request")
+            .await
+    }
+
+    async fn burn_block_height(&self) -> Result<u64, StacksNodeError> {
+        debug!("Retrieving burn block height...");
+        let json = self.get_response("/v2/info").await?.json::<Value>().await?;
+        let entry = "burn_block_height";
+        json[entry]
+            .as_u64()
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::util::test::mock_http_server;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn should_get_burn_ops_of_every_block_in_order() {
+        let url = mock_http_server(vec![
+            (
+                "GET /v2/burn_ops/1/peg_in",
+                200,
+                r#"{"peg_in": []}"#.to_string(),
+            ),
+            (
+                "GET /v2/burn_ops/1/peg_out_request",
+                200,
+                r#"{"peg_out_request": []}"#.to_string(),
+            ),
+            (
+                "GET /v2/burn_ops/2/peg_in",
+                200,
+                r#"{"peg_in": []}"#.to_string(),
+            ),
+        ]);
+        let client = AsyncNodeClient::new(url, RequestPolicy::default()).unwrap();
+
+        let burn_ops = runtime().block_on(client.get_burn_ops(1..=3, 2)).unwrap();
+
+        // Blocks the node does not know about come back without ops
+        let heights: Vec<u64> = burn_ops.iter().map(|ops| ops.block_height).collect();
+        assert_eq!(heights, vec![1, 2, 3]);
+        assert!(burn_ops
+            .iter()
+            .all(|ops| ops.peg_in_ops.is_empty() && ops.peg_out_request_ops.is_empty()));
+    }
+
+    #[test]
+    fn should_time_out_when_node_is_unreachable() {
+        // Nothing listens on a port that was just released
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+        let policy = RequestPolicy {
+            retry_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let client = AsyncNodeClient::new(url, policy).unwrap();
+
+        assert!(matches!(
+            runtime().block_on(client.burn_block_height()),
+            Err(StacksNodeError::Timeout)
+        ));
+    }
+}
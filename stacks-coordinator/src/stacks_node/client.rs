@@ -1,9 +1,17 @@
-use std::time::{Duration, Instant};
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
 
-use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
+use crate::stacks_node::{
+    async_client::AsyncNodeClient, AccountNonces, AsyncStacksNode, BurnBlockOps,
+    Error as StacksNodeError, PegInOp, PegOutRequestOp, PoxInfo, RequestPolicy, StacksNode,
+    TransactionStatus,
+};
 use bitcoin::XOnlyPublicKey;
 use blockstack_lib::{
-    chainstate::stacks::StacksTransaction,
+    burnchains::Txid,
+    chainstate::stacks::{StacksTransaction, TransactionPayload},
     codec::StacksMessageCodec,
     types::chainstate::StacksAddress,
     vm::{types::SequenceData, ClarityName, ContractName, Value as ClarityValue},
@@ -27,6 +35,8 @@ pub enum BroadcastError {
     NotEnoughFunds(String),
     #[error("Conflicting nonce in mempool")]
     ConflictingNonceInMempool,
+    #[error("Nonce already used by a confirmed transaction")]
+    BadNonce,
     #[error("{0}")]
     Other(String),
 }
@@ -54,6 +64,7 @@ impl From<&serde_json::Value> for BroadcastError {
                 reason_data.unwrap_or(&json!("No Reason Data")).to_string(),
             ),
             "ConflictingNonceInMempool" => BroadcastError::ConflictingNonceInMempool,
+            "BadNonce" => BroadcastError::BadNonce,
             _ => BroadcastError::Other(reason.to_string()),
         }
     }
@@ -64,7 +75,10 @@ pub struct NodeClient {
     client: Client,
     contract_name: ContractName,
     contract_address: StacksAddress,
-    next_nonce: Option<u64>,
+    request_policy: RequestPolicy,
+    /// Runtime driving the async client, which fetches burn ops concurrently
+    runtime: tokio::runtime::Runtime,
+    async_client: AsyncNodeClient,
 }
 
 impl NodeClient {
@@ -72,14 +86,24 @@ impl NodeClient {
         node_url: Url,
         contract_name: ContractName,
         contract_address: StacksAddress,
-    ) -> Self {
-        Self {
+        request_policy: RequestPolicy,
+    ) -> Result<Self, StacksNodeError> {
+        let client = Client::builder()
+            .timeout(request_policy.request_timeout)
+            .build()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let async_client = AsyncNodeClient::new(node_url.clone(), request_policy.clone())?;
+        Ok(Self {
             node_url,
-            client: Client::new(),
+            client,
             contract_name,
             contract_address,
-            next_nonce: None,
-        }
+            request_policy,
+            runtime,
+            async_client,
+        })
     }
 
     fn build_url(&self, route: &str) -> Result<Url, StacksNodeError> {
@@ -95,7 +119,7 @@ impl NodeClient {
         };
 
         let send_request = || {
-            if now.elapsed().as_secs() > 5 {
+            if now.elapsed() > self.request_policy.retry_timeout {
                 debug!("Timeout exceeded.");
                 return Err(backoff::Error::Permanent(StacksNodeError::Timeout));
             }
@@ -114,7 +138,7 @@ impl NodeClient {
         Ok(response)
     }
 
-    fn get_burn_ops<T>(&self, block_height: u64, op: &str) -> Result<Vec<T>, StacksNodeError>
+    fn get_block_ops<T>(&self, block_height: u64, op: &str) -> Result<Vec<T>, StacksNodeError>
     where
         T: serde::de::DeserializeOwned,
     {
@@ -193,6 +217,7 @@ impl NodeClient {
                     }
                     signer_key_ids.insert(id.try_into().unwrap(), this_signer_key_ids);
                 }
+                return Ok(());
             } else {
                 return Err(StacksNodeError::NoSignerData(id));
             }
@@ -250,7 +275,7 @@ impl NodeClient {
 impl StacksNode for NodeClient {
     fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, StacksNodeError> {
         debug!("Retrieving peg-in ops...");
-        self.get_burn_ops::<PegInOp>(block_height, "peg_in")
+        self.get_block_ops::<PegInOp>(block_height, "peg_in")
     }
 
     fn get_peg_out_request_ops(
@@ -258,7 +283,18 @@ impl StacksNode for NodeClient {
         block_height: u64,
     ) -> Result<Vec<PegOutRequestOp>, StacksNodeError> {
         debug!("Retrieving peg-out request ops...");
-        self.get_burn_ops::<PegOutRequestOp>(block_height, "peg_out_request")
+        self.get_block_ops::<PegOutRequestOp>(block_height, "peg_out_request")
+    }
+
+    fn get_burn_ops(
+        &self,
+        block_heights: RangeInclusive<u64>,
+    ) -> Result<Vec<BurnBlockOps>, StacksNodeError> {
+        debug!("Retrieving burn ops of blocks {:?}...", block_heights);
+        self.runtime.block_on(
+            self.async_client
+                .get_burn_ops(block_heights, self.request_policy.max_concurrent_blocks),
+        )
     }
 
     fn burn_block_height(&self) -> Result<u64, StacksNodeError> {
@@ -270,29 +306,51 @@ impl StacksNode for NodeClient {
             .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
     }
 
-    fn next_nonce(&mut self, address: &StacksAddress) -> Result<u64, StacksNodeError> {
-        debug!("Retrieving next nonce...");
-        if let Some(nonce) = self.next_nonce {
-            let next_nonce = nonce.wrapping_add(1);
-            self.next_nonce = Some(next_nonce);
-            return Ok(next_nonce);
-        }
+    fn pox_info(&self) -> Result<PoxInfo, StacksNodeError> {
+        debug!("Retrieving PoX info...");
+        let json = self.get_response("/v2/pox")?.json::<Value>()?;
+        let entry = |entry: &str| {
+            json[entry]
+                .as_u64()
+                .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
+        };
+        Ok(PoxInfo {
+            first_burnchain_block_height: entry("first_burnchain_block_height")?,
+            reward_cycle_length: entry("reward_cycle_length")?,
+        })
+    }
+
+    fn account_nonces(&self, address: &StacksAddress) -> Result<AccountNonces, StacksNodeError> {
+        debug!("Retrieving account nonces...");
         let address = address.to_string();
         let entry = "nonce";
-        let route = format!("/v2/accounts/{}", address);
-        let response = self.get_response(&route)?;
+        let response = self.get_response(&format!("/v2/accounts/{}", address))?;
         if response.status() == StatusCode::NOT_FOUND {
             return Err(StacksNodeError::UnknownAddress(address));
         }
         let json = response
             .json::<Value>()
             .map_err(|_| StacksNodeError::BehindChainTip)?;
-        let nonce = json
+        let confirmed_nonce = json
             .get(entry)
             .and_then(|nonce| nonce.as_u64())
             .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
-        self.next_nonce = Some(nonce);
-        Ok(nonce)
+
+        // Only the Stacks API tracks which nonces are waiting in the mempool
+        let response = self.get_response(&format!("/extended/v1/address/{}/nonces", address))?;
+        let mempool_nonces = if response.status() == StatusCode::OK {
+            let json = response.json::<Value>()?;
+            json["detected_mempool_nonces"]
+                .as_array()
+                .map(|nonces| nonces.iter().filter_map(Value::as_u64).collect())
+        } else {
+            debug!("Mempool nonces of {} are not available", address);
+            None
+        };
+        Ok(AccountNonces {
+            confirmed_nonce,
+            mempool_nonces,
+        })
     }
 
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), StacksNodeError> {
@@ -317,6 +375,61 @@ impl StacksNode for NodeClient {
         Ok(())
     }
 
+    /// Ask the node's fee estimator, picking the middle of its low, medium and high estimates
+    fn estimate_fee(
+        &self,
+        payload: &TransactionPayload,
+        estimated_len: u64,
+    ) -> Result<u64, StacksNodeError> {
+        debug!("Estimating transaction fee...");
+        let url = self.build_url("/v2/fees/transaction")?;
+        let body = json!({
+            "transaction_payload": hex::encode(payload.serialize_to_vec()),
+            "estimated_len": estimated_len,
+        });
+        let response = self.client.post(url).json(&body).send()?;
+        if response.status() != StatusCode::OK {
+            // Nodes without a fee estimator answer with a plain text reason
+            return Err(StacksNodeError::NoFeeEstimate(response.text()?));
+        }
+        let json = response.json::<Value>()?;
+        let entry = "estimations";
+        let estimations = json[entry]
+            .as_array()
+            .filter(|estimations| !estimations.is_empty())
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
+        estimations[estimations.len() / 2]["fee"]
+            .as_u64()
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry("fee".to_string()))
+    }
+
+    /// Look the transaction up through the Stacks API, which is the only place Clarity
+    /// results of mined transactions and mempool evictions are exposed
+    fn transaction_status(&self, txid: &Txid) -> Result<TransactionStatus, StacksNodeError> {
+        debug!("Retrieving status of transaction {}...", txid);
+        let response = self.get_response(&format!("/extended/v1/tx/0x{}", txid.to_hex()))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(TransactionStatus::NotFound);
+        }
+        let json = response.json::<Value>()?;
+        let entry = "tx_status";
+        let status = json[entry]
+            .as_str()
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))?;
+        Ok(match status {
+            "pending" => TransactionStatus::Pending,
+            "success" => TransactionStatus::Success,
+            "abort_by_response" | "abort_by_post_condition" => {
+                let result = json["tx_result"]["repr"].as_str().unwrap_or_default();
+                TransactionStatus::Aborted(format!("{status} {result}").trim_end().to_string())
+            }
+            dropped if dropped.starts_with("dropped_") => {
+                TransactionStatus::Dropped(dropped.to_string())
+            }
+            _ => return Err(StacksNodeError::InvalidJsonEntry(entry.to_string())),
+        })
+    }
+
     fn keys_threshold(&self, sender: &StacksAddress) -> Result<u128, StacksNodeError> {
         let function_name = "get-threshold";
         let threshold_hex = self.call_read(sender, function_name, &[])?;
@@ -447,7 +560,7 @@ mod tests {
         util::{hash::Hash160, secp256k1::MessageSignature},
     };
 
-    use crate::util::test::PRIVATE_KEY_HEX;
+    use crate::util::test::{mock_http_server, PRIVATE_KEY_HEX};
 
     use super::*;
 
@@ -481,7 +594,9 @@ mod tests {
                     .expect("Failed to parse mock server address"),
                 ContractName::from("sbtc-alpha"),
                 StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap(),
-            );
+                RequestPolicy::default(),
+            )
+            .unwrap();
             Self {
                 sender,
                 mock_server,
@@ -608,27 +723,43 @@ mod tests {
     }
 
     #[test]
-    fn next_nonce_success_test() {
-        let mut config = TestConfig::new();
-
-        let h = spawn(move || {
-            let nonce = config.client.next_nonce(&config.sender).unwrap();
-            let next_nonce = config.client.next_nonce(&config.sender).unwrap();
-            (nonce, next_nonce)
-        });
-        write_response(config.mock_server,
-                    b"HTTP/1.1 200 OK\n\n{\"balance\":\"0x00000000000000000000000000000000\",\"locked\":\"0x00000000000000000000000000000000\",\"unlock_height\":0,\"nonce\":20,\"balance_proof\":\"\",\"nonce_proof\":\"\"}"
-                );
-        let (nonce, next_nonce) = h.join().unwrap();
-        assert_eq!(nonce, 20);
-        assert_eq!(next_nonce, 21);
+    fn account_nonces_success_test() {
+        let address =
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap();
+        let url = mock_http_server(vec![
+            (
+                "GET /v2/accounts/SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE",
+                200,
+                "{\"balance\":\"0x00000000000000000000000000000000\",\"locked\":\"0x00000000000000000000000000000000\",\"unlock_height\":0,\"nonce\":20,\"balance_proof\":\"\",\"nonce_proof\":\"\"}".to_string(),
+            ),
+            (
+                "GET /extended/v1/address/SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE/nonces",
+                200,
+                "{\"last_mempool_tx_nonce\":23,\"last_executed_tx_nonce\":19,\"possible_next_nonce\":24,\"detected_missing_nonces\":[21],\"detected_mempool_nonces\":[20,22,23]}".to_string(),
+            ),
+        ]);
+        let client = NodeClient::new(
+            url,
+            ContractName::from("sbtc-alpha"),
+            address,
+            RequestPolicy::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            client.account_nonces(&address).unwrap(),
+            AccountNonces {
+                confirmed_nonce: 20,
+                mempool_nonces: Some(vec![20, 22, 23]),
+            }
+        );
     }
 
     #[test]
-    fn next_nonce_failure_test() {
-        let mut config = TestConfig::new();
+    fn account_nonces_failure_test() {
+        let config = TestConfig::new();
 
-        let h = spawn(move || config.client.next_nonce(&config.sender));
+        let h = spawn(move || config.client.account_nonces(&config.sender));
         write_response(
             config.mock_server,
             b"HTTP/1.1 404 Not Found\n\n/v2/accounts/SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE",
@@ -663,6 +794,67 @@ mod tests {
         assert!(matches!(result, Err(StacksNodeError::InvalidJsonEntry(_))));
     }
 
+    #[test]
+    fn transaction_status_success_test() {
+        let config = TestConfig::new();
+        let txid = Txid([1; 32]);
+
+        let h = spawn(move || config.client.transaction_status(&txid));
+        let request_bytes = write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"tx_id\":\"0x0101\",\"tx_status\":\"success\",\"tx_result\":{\"hex\":\"0x0703\",\"repr\":\"(ok true)\"}}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(result, TransactionStatus::Success);
+        assert!(String::from_utf8_lossy(&request_bytes)
+            .starts_with(&format!("GET /extended/v1/tx/0x{} ", txid.to_hex())));
+    }
+
+    #[test]
+    fn transaction_status_aborted_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.transaction_status(&Txid([1; 32])));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"tx_status\":\"abort_by_response\",\"tx_result\":{\"hex\":\"0x0801\",\"repr\":\"(err u1)\"}}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(
+            result,
+            TransactionStatus::Aborted("abort_by_response (err u1)".to_string())
+        );
+    }
+
+    #[test]
+    fn transaction_status_dropped_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.transaction_status(&Txid([1; 32])));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 200 OK\n\n{\"tx_status\":\"dropped_replace_by_fee\"}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(
+            result,
+            TransactionStatus::Dropped("dropped_replace_by_fee".to_string())
+        );
+    }
+
+    #[test]
+    fn transaction_status_not_found_test() {
+        let config = TestConfig::new();
+
+        let h = spawn(move || config.client.transaction_status(&Txid([1; 32])));
+        write_response(
+            config.mock_server,
+            b"HTTP/1.1 404 Not Found\n\n{\"error\":\"could not find transaction by ID\"}",
+        );
+        let result = h.join().unwrap().unwrap();
+        assert_eq!(result, TransactionStatus::NotFound);
+    }
+
     #[test]
     fn should_send_tx_bytes_to_node() {
         let config = TestConfig::new();
@@ -713,4 +905,56 @@ mod tests {
             "Request bytes did not contain the transaction bytes"
         );
     }
+
+    fn node_client(routes: Vec<(&'static str, u16, String)>) -> NodeClient {
+        NodeClient::new(
+            mock_http_server(routes),
+            ContractName::from("sbtc-alpha"),
+            StacksAddress::from_string("SP3FBR2AGK5H9QBDH3EEN6DF8EK8JY7RX8QJ5SVTE").unwrap(),
+            RequestPolicy::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn estimate_fee_test() {
+        let payload = TransactionPayload::Coinbase(CoinbasePayload([0; 32]), None);
+        let client = node_client(vec![(
+            "POST /v2/fees/transaction",
+            200,
+            r#"{"estimated_cost":{},"estimated_cost_scalar":14,"cost_scalar_change_by_byte":0.0,"estimations":[{"fee_rate":1.0,"fee":180},{"fee_rate":2.5,"fee":450},{"fee_rate":5.0,"fee":900}]}"#.to_string(),
+        )]);
+        assert_eq!(client.estimate_fee(&payload, 180).unwrap(), 450);
+
+        let client = node_client(vec![(
+            "POST /v2/fees/transaction",
+            400,
+            "Fee estimation not supported on this node".to_string(),
+        )]);
+        assert!(matches!(
+            client.estimate_fee(&payload, 180),
+            Err(StacksNodeError::NoFeeEstimate(reason)) if reason.contains("not supported")
+        ));
+    }
+
+    #[test]
+    fn pox_info_test() {
+        let client = node_client(vec![(
+            "GET /v2/pox",
+            200,
+            r#"{"contract_id":"ST000000000000000000002AMW42H.pox-3","first_burnchain_block_height":100,"reward_cycle_length":20,"current_cycle":{"id":2}}"#.to_string(),
+        )]);
+        let pox_info = client.pox_info().unwrap();
+
+        assert_eq!(
+            pox_info,
+            PoxInfo {
+                first_burnchain_block_height: 100,
+                reward_cycle_length: 20,
+            }
+        );
+        assert_eq!(pox_info.reward_cycle(99), 0);
+        assert_eq!(pox_info.reward_cycle(139), 1);
+        assert_eq!(pox_info.reward_cycle(140), 2);
+    }
 }
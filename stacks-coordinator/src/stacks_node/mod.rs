@@ -1,13 +1,23 @@
+pub mod async_client;
 pub mod client;
 
+use std::{collections::BTreeMap, ops::RangeInclusive, time::Duration};
+
+use async_trait::async_trait;
 use bitcoin::XOnlyPublicKey;
 use blockstack_lib::{
-    chainstate::{burn::operations as burn_ops, stacks::StacksTransaction},
+    burnchains::Txid,
+    chainstate::{
+        burn::operations as burn_ops,
+        stacks::{StacksTransaction, TransactionPayload},
+    },
     codec::Error as CodecError,
     types::chainstate::StacksAddress,
     vm::{types::serialization::SerializationError, Value as ClarityValue},
 };
 use frost_signer::config::{PublicKeys, SignerKeyIds};
+use futures::{StreamExt, TryStreamExt};
+use tracing::debug;
 use wsts::ecdsa::PublicKey;
 
 use self::client::BroadcastError;
@@ -47,18 +57,39 @@ pub enum Error {
     Utf8Error(#[from] std::str::Utf8Error),
     #[error("URL Parse Error: {0}")]
     UrlParseError(#[from] url::ParseError),
+    #[error("IO Error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Stacks node could not estimate a fee: {0}")]
+    NoFeeEstimate(String),
 }
 
 #[cfg_attr(test, mockall::automock)]
 pub trait StacksNode {
     fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error>;
     fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error>;
+    /// Get the sBTC ops of every burn block in the range, in order of block height.
+    /// Blocks the node does not know about are returned without ops.
+    fn get_burn_ops(&self, block_heights: RangeInclusive<u64>) -> Result<Vec<BurnBlockOps>, Error>;
     fn burn_block_height(&self) -> Result<u64, Error>;
-    fn next_nonce(&mut self, addr: &StacksAddress) -> Result<u64, Error>;
+    /// Reward cycle parameters of the burnchain the node follows
+    fn pox_info(&self) -> Result<PoxInfo, Error>;
+    /// Nonces of the account's confirmed and mempool transactions
+    fn account_nonces(&self, addr: &StacksAddress) -> Result<AccountNonces, Error>;
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error>;
+    /// Estimate in microSTX the fee a transaction with the payload and serialized length should pay
+    fn estimate_fee(&self, payload: &TransactionPayload, estimated_len: u64) -> Result<u64, Error>;
+    fn transaction_status(&self, txid: &Txid) -> Result<TransactionStatus, Error>;
     fn keys_threshold(&self, sender: &StacksAddress) -> Result<u128, Error>;
     fn public_keys(&self, sender: &StacksAddress) -> Result<PublicKeys, Error>;
     fn signer_key_ids(&self, sender: &StacksAddress) -> Result<SignerKeyIds, Error>;
+    /// Signers currently registered in the sBTC contract
+    fn signer_set(&self, sender: &StacksAddress) -> Result<SignerSet, Error> {
+        Ok(SignerSet {
+            keys_threshold: self.keys_threshold(sender)?,
+            public_keys: self.public_keys(sender)?,
+            signer_key_ids: self.signer_key_ids(sender)?,
+        })
+    }
     fn coordinator_public_key(&self, sender: &StacksAddress) -> Result<Option<PublicKey>, Error>;
     fn bitcoin_wallet_public_key(
         &self,
@@ -66,5 +97,146 @@ pub trait StacksNode {
     ) -> Result<Option<XOnlyPublicKey>, Error>;
 }
 
+/// PoX reward cycle parameters, which split the burnchain into cycles of equal length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoxInfo {
+    pub first_burnchain_block_height: u64,
+    pub reward_cycle_length: u64,
+}
+
+impl PoxInfo {
+    /// Reward cycle the burn block at the given height belongs to
+    pub fn reward_cycle(&self, burn_block_height: u64) -> u64 {
+        burn_block_height.saturating_sub(self.first_burnchain_block_height)
+            / self.reward_cycle_length.max(1)
+    }
+}
+
+/// Signers registered in the sBTC contract, along with the key ids each of them holds
+#[derive(Debug, Clone, Default)]
+pub struct SignerSet {
+    pub keys_threshold: u128,
+    pub public_keys: PublicKeys,
+    pub signer_key_ids: SignerKeyIds,
+}
+
+impl PartialEq for SignerSet {
+    fn eq(&self, other: &Self) -> bool {
+        let signers = |public_keys: &PublicKeys| {
+            public_keys
+                .signers
+                .iter()
+                .map(|(id, public_key)| (*id, public_key.to_bytes()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        self.keys_threshold == other.keys_threshold
+            && signers(&self.public_keys) == signers(&other.public_keys)
+            && self.signer_key_ids == other.signer_key_ids
+    }
+}
+
+/// Nonce state of a Stacks account as seen by the node
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountNonces {
+    /// Nonce the next mined transaction of the account must have
+    pub confirmed_nonce: u64,
+    /// Nonces of the account's transactions waiting in the mempool,
+    /// if the node exposes its mempool
+    pub mempool_nonces: Option<Vec<u64>>,
+}
+
+/// Where a broadcast Stacks transaction stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// Waiting in the mempool
+    Pending,
+    /// Mined and its contract call returned `ok`
+    Success,
+    /// Mined, but its contract call returned `err` or a post condition failed
+    Aborted(String),
+    /// Evicted from the mempool without being mined
+    Dropped(String),
+    /// Not known to the node (yet)
+    NotFound,
+}
+
+/// Non-blocking counterpart of the burn op queries of `StacksNode`, so ops of many burn
+/// blocks can be fetched concurrently
+#[async_trait]
+pub trait AsyncStacksNode: Sync {
+    async fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error>;
+    async fn get_peg_out_request_ops(
+        &self,
+        block_height: u64,
+    ) -> Result<Vec<PegOutRequestOp>, Error>;
+    async fn burn_block_height(&self) -> Result<u64, Error>;
+
+    /// Get the sBTC ops of every burn block in the range, in order of block height,
+    /// with requests for up to `max_concurrent_blocks` blocks in flight at once
+    async fn get_burn_ops(
+        &self,
+        block_heights: RangeInclusive<u64>,
+        max_concurrent_blocks: usize,
+    ) -> Result<Vec<BurnBlockOps>, Error> {
+        futures::stream::iter(block_heights)
+            .map(|block_height| async move {
+                let (peg_in_ops, peg_out_request_ops) = tokio::try_join!(
+                    ops_if_known(self.get_peg_in_ops(block_height)),
+                    ops_if_known(self.get_peg_out_request_ops(block_height)),
+                )?;
+                Ok::<_, Error>(BurnBlockOps {
+                    block_height,
+                    peg_in_ops,
+                    peg_out_request_ops,
+                })
+            })
+            .buffered(max_concurrent_blocks.max(1))
+            .try_collect()
+            .await
+    }
+}
+
+/// Ops of a burn block that are empty if the node does not know the block yet
+async fn ops_if_known<T>(
+    ops: impl std::future::Future<Output = Result<Vec<T>, Error>>,
+) -> Result<Vec<T>, Error> {
+    match ops.await {
+        Err(Error::UnknownBlockHeight(height)) => {
+            debug!("Failed to find burn block height {}", height);
+            Ok(vec![])
+        }
+        result => result,
+    }
+}
+
+/// The sBTC ops found in a single burn block
+#[derive(Debug, Clone, Default)]
+pub struct BurnBlockOps {
+    pub block_height: u64,
+    pub peg_in_ops: Vec<PegInOp>,
+    pub peg_out_request_ops: Vec<PegOutRequestOp>,
+}
+
+/// How requests to the stacks node are timed out and retried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestPolicy {
+    /// Time allowed for a single request to complete
+    pub request_timeout: Duration,
+    /// Time spent retrying a request that failed to connect before giving up
+    pub retry_timeout: Duration,
+    /// Max number of burn blocks whose ops are fetched at once
+    pub max_concurrent_blocks: usize,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            retry_timeout: Duration::from_secs(5),
+            max_concurrent_blocks: 16,
+        }
+    }
+}
+
 pub type PegInOp = burn_ops::PegInOp;
 pub type PegOutRequestOp = burn_ops::PegOutRequestOp;
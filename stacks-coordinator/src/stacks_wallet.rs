@@ -1,6 +1,6 @@
 use crate::{
     peg_wallet::{Error as PegWalletError, StacksWallet as StacksWalletTrait},
-    stacks_node::{PegInOp, PegOutRequestOp},
+    stacks_node::{PegInOp, PegOutRequestOp, StacksNode},
     util::address_version,
 };
 use bitcoin::XOnlyPublicKey;
@@ -10,6 +10,7 @@ use blockstack_lib::{
         TransactionContractCall, TransactionPayload, TransactionPostConditionMode,
         TransactionSpendingCondition, TransactionVersion,
     },
+    codec::StacksMessageCodec,
     core::{CHAIN_ID_MAINNET, CHAIN_ID_TESTNET},
     types::chainstate::{StacksAddress, StacksPrivateKey, StacksPublicKey},
     vm::{
@@ -18,6 +19,7 @@ use blockstack_lib::{
         ClarityName, ContractName, Value,
     },
 };
+use tracing::{debug, warn};
 
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum Error {
@@ -38,6 +40,32 @@ pub enum Error {
     BlockstackError(#[from] blockstack_lib::vm::errors::Error),
 }
 
+/// How the fees of Stacks transactions are picked. All fees are in microSTX.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StacksFeePolicy {
+    /// Fee paid when the stacks node can not estimate one
+    pub fallback_fee: u64,
+    /// Lowest fee ever paid
+    pub min_fee: u64,
+    /// Highest fee ever paid, including by replacements
+    pub max_fee: u64,
+    /// Percentage by which a replacement of a stuck transaction raises its fee, at least
+    pub bump_percent: u64,
+}
+
+impl StacksFeePolicy {
+    /// The fee within the policy's caps closest to `fee`
+    pub fn clamp(&self, fee: u64) -> u64 {
+        fee.max(self.min_fee).min(self.max_fee)
+    }
+
+    /// Least fee a replacement of a transaction paying `fee` should pay
+    pub fn bumped_fee(&self, fee: u64) -> u64 {
+        // The mempool only accepts replacements paying strictly more
+        (fee + fee * self.bump_percent / 100).max(fee + 1)
+    }
+}
+
 pub struct StacksWallet {
     contract_address: StacksAddress,
     contract_name: ContractName,
@@ -45,7 +73,7 @@ pub struct StacksWallet {
     public_key: StacksPublicKey,
     address: StacksAddress,
     version: TransactionVersion,
-    fee: u64,
+    fee_policy: StacksFeePolicy,
 }
 
 impl StacksWallet {
@@ -55,7 +83,7 @@ impl StacksWallet {
         sender_key: StacksPrivateKey,
         address: StacksAddress,
         version: TransactionVersion,
-        fee: u64,
+        fee_policy: StacksFeePolicy,
     ) -> Self {
         let public_key = StacksPublicKey::from_private(&sender_key);
         Self {
@@ -65,7 +93,41 @@ impl StacksWallet {
             public_key,
             address,
             version,
-            fee,
+            fee_policy,
+        }
+    }
+
+    /// Fee the stacks node estimates for a transaction like `tx`, clamped to the fee policy
+    fn estimate_fee<N: StacksNode>(&self, tx: &StacksTransaction, stacks_node: &N) -> u64 {
+        let estimated_len = tx.serialize_to_vec().len() as u64;
+        match stacks_node.estimate_fee(&tx.payload, estimated_len) {
+            Ok(fee) => {
+                debug!("Stacks node estimated a fee of {} microSTX", fee);
+                self.fee_policy.clamp(fee)
+            }
+            Err(e) => {
+                warn!(
+                    "Falling back to a fee of {} microSTX: {}",
+                    self.fee_policy.fallback_fee, e
+                );
+                self.fee_policy.clamp(self.fee_policy.fallback_fee)
+            }
+        }
+    }
+
+    /// Build a transaction with `build`, paying the fee the stacks node estimates for it
+    fn build_transaction_estimated<N: StacksNode>(
+        &self,
+        build: impl Fn(u64) -> Result<StacksTransaction, PegWalletError>,
+        stacks_node: &N,
+    ) -> Result<StacksTransaction, PegWalletError> {
+        // The estimate depends on the size of the transaction, so a draft is built first
+        let draft = build(self.fee_policy.clamp(self.fee_policy.fallback_fee))?;
+        let fee = self.estimate_fee(&draft, stacks_node);
+        if fee == draft.get_tx_fee() {
+            Ok(draft)
+        } else {
+            build(fee)
         }
     }
 
@@ -74,9 +136,11 @@ impl StacksWallet {
         function_name: impl Into<String>,
         function_args: Vec<Value>,
         nonce: u64,
+        fee: u64,
     ) -> Result<StacksTransaction, Error> {
         // First build an unsigned transaction
-        let unsigned_tx = self.build_transaction_unsigned(function_name, function_args, nonce)?;
+        let unsigned_tx =
+            self.build_transaction_unsigned(function_name, function_args, nonce, fee)?;
 
         // Do the signing
         let mut tx_signer = StacksTransactionSigner::new(&unsigned_tx);
@@ -96,6 +160,7 @@ impl StacksWallet {
         function_name: impl Into<String>,
         function_args: Vec<Value>,
         nonce: u64,
+        fee: u64,
     ) -> Result<StacksTransaction, Error> {
         // First build the payload from the provided function and its arguments
         let payload = self.build_transaction_payload(function_name, function_args)?;
@@ -110,7 +175,7 @@ impl StacksWallet {
                 )
             })?;
         spending_condition.set_nonce(nonce);
-        spending_condition.set_tx_fee(self.fee);
+        spending_condition.set_tx_fee(fee);
         let auth = TransactionAuth::Standard(spending_condition);
 
         // Viola! We have an unsigned transaction
@@ -142,12 +207,13 @@ impl StacksWallet {
     }
 }
 
-/// Build a StacksTransaction using the provided wallet and nonce
+/// Build a StacksTransaction using the provided wallet, nonce and fee
 pub trait BuildStacksTransaction {
     fn build_transaction(
         &self,
         wallet: &StacksWallet,
         nonce: u64,
+        fee: u64,
     ) -> Result<StacksTransaction, PegWalletError>;
 }
 
@@ -156,6 +222,7 @@ impl BuildStacksTransaction for PegInOp {
         &self,
         wallet: &StacksWallet,
         nonce: u64,
+        fee: u64,
     ) -> Result<StacksTransaction, PegWalletError> {
         let function_name = "mint!";
 
@@ -167,7 +234,7 @@ impl BuildStacksTransaction for PegInOp {
             data: self.txid.to_string().as_bytes().to_vec(),
         });
         let function_args: Vec<Value> = vec![amount, principal, tx_id];
-        let tx = wallet.build_transaction_signed(function_name, function_args, nonce)?;
+        let tx = wallet.build_transaction_signed(function_name, function_args, nonce, fee)?;
         Ok(tx)
     }
 }
@@ -177,6 +244,7 @@ impl BuildStacksTransaction for PegOutRequestOp {
         &self,
         wallet: &StacksWallet,
         nonce: u64,
+        fee: u64,
     ) -> Result<StacksTransaction, PegWalletError> {
         let function_name = "burn!";
 
@@ -198,23 +266,61 @@ impl BuildStacksTransaction for PegOutRequestOp {
         });
         let function_args: Vec<Value> = vec![amount, principal, tx_id];
 
-        let tx = wallet.build_transaction_signed(function_name, function_args, nonce)?;
+        let tx = wallet.build_transaction_signed(function_name, function_args, nonce, fee)?;
         Ok(tx)
     }
 }
 
 impl StacksWalletTrait for StacksWallet {
-    fn build_transaction<T: BuildStacksTransaction>(
+    fn build_transaction<T: BuildStacksTransaction, N: StacksNode>(
         &self,
         op: &T,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, PegWalletError> {
-        op.build_transaction(self, nonce)
+        self.build_transaction_estimated(|fee| op.build_transaction(self, nonce, fee), stacks_node)
+    }
+
+    fn build_transaction_with_fee<T: BuildStacksTransaction>(
+        &self,
+        op: &T,
+        nonce: u64,
+        fee: u64,
+    ) -> Result<StacksTransaction, PegWalletError> {
+        op.build_transaction(self, nonce, self.fee_policy.clamp(fee))
+    }
+
+    fn bump_fee<T: BuildStacksTransaction, N: StacksNode>(
+        &self,
+        op: &T,
+        tx: &StacksTransaction,
+        stacks_node: &N,
+    ) -> Result<Option<StacksTransaction>, PegWalletError> {
+        let fee = tx.get_tx_fee();
+        let estimate = self.estimate_fee(tx, stacks_node);
+        if estimate <= fee {
+            return Ok(None);
+        }
+        let bumped_fee = self
+            .fee_policy
+            .clamp(estimate.max(self.fee_policy.bumped_fee(fee)));
+        if bumped_fee <= fee {
+            warn!(
+                "Stacks transaction {} already pays the max fee of {} microSTX",
+                tx.txid(),
+                self.fee_policy.max_fee
+            );
+            return Ok(None);
+        }
+        op.build_transaction(self, tx.get_origin_nonce(), bumped_fee)
+            .map(Some)
     }
-    fn build_set_bitcoin_wallet_public_key_transaction(
+
+    fn build_set_bitcoin_wallet_public_key_transaction<N: StacksNode>(
         &self,
         public_key: &XOnlyPublicKey,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, PegWalletError> {
         let function_name = "set-bitcoin-wallet-public-key";
         // Build the function arguments
@@ -222,15 +328,25 @@ impl StacksWalletTrait for StacksWallet {
             data: public_key.serialize().to_vec(),
         }));
         let function_args = vec![key];
-        let tx = self.build_transaction_signed(function_name, function_args, nonce)?;
-        Ok(tx)
+        self.build_transaction_estimated(
+            |fee| {
+                Ok(self.build_transaction_signed(
+                    function_name,
+                    function_args.clone(),
+                    nonce,
+                    fee,
+                )?)
+            },
+            stacks_node,
+        )
     }
 
-    fn build_set_coordinator_data_transaction(
+    fn build_set_coordinator_data_transaction<N: StacksNode>(
         &self,
         address: &StacksAddress,
         public_key: &StacksPublicKey,
         nonce: u64,
+        stacks_node: &N,
     ) -> Result<StacksTransaction, PegWalletError> {
         let function_name = "set-coordinator-data";
         let principal = Value::Principal(address.to_account_principal());
@@ -242,9 +358,18 @@ impl StacksWalletTrait for StacksWallet {
             (ClarityName::from("key"), key),
         ])
         .map_err(Error::from)?;
-        let function_args = vec![data.into()];
-        let tx = self.build_transaction_signed(function_name, function_args, nonce)?;
-        Ok(tx)
+        let function_args: Vec<Value> = vec![data.into()];
+        self.build_transaction_estimated(
+            |fee| {
+                Ok(self.build_transaction_signed(
+                    function_name,
+                    function_args.clone(),
+                    nonce,
+                    fee,
+                )?)
+            },
+            stacks_node,
+        )
     }
 
     fn address(&self) -> &StacksAddress {
@@ -254,17 +379,14 @@ impl StacksWalletTrait for StacksWallet {
     fn public_key(&self) -> &StacksPublicKey {
         &self.public_key
     }
-
-    fn set_fee(&mut self, fee: u64) {
-        self.fee = fee;
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
         peg_wallet::StacksWallet as StacksWalletTrait,
-        stacks_wallet::StacksWallet,
+        stacks_node::{Error as StacksNodeError, MockStacksNode},
+        stacks_wallet::{StacksFeePolicy, StacksWallet},
         util::{
             address_version,
             test::{build_peg_out_request_op, PRIVATE_KEY_HEX, PUBLIC_KEY_HEX},
@@ -324,10 +446,23 @@ mod tests {
             sender_key,
             address,
             TransactionVersion::Testnet,
-            10,
+            StacksFeePolicy {
+                fallback_fee: 10,
+                min_fee: 5,
+                max_fee: 1_000,
+                bump_percent: 25,
+            },
         )
     }
 
+    fn stacks_node_mock(fee: u64) -> MockStacksNode {
+        let mut stacks_node_mock = MockStacksNode::new();
+        stacks_node_mock
+            .expect_estimate_fee()
+            .returning(move |_, _| Ok(fee));
+        stacks_node_mock
+    }
+
     #[test]
     fn build_mint_transaction_test() {
         let p = PegInOp {
@@ -342,7 +477,7 @@ mod tests {
         };
         let wallet = stacks_wallet();
         let tx = wallet
-            .build_transaction(&p, 0)
+            .build_transaction(&p, 0, &stacks_node_mock(10))
             .expect("Failed to construct mint transaction.");
         tx.verify()
             .expect("build_mint_transaction generated a transaction with an invalid signature");
@@ -353,7 +488,7 @@ mod tests {
         let wallet = stacks_wallet();
         let op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10, 1, 3);
         let tx = wallet
-            .build_transaction(&op, 0)
+            .build_transaction(&op, 0, &stacks_node_mock(10))
             .expect("Failed to construct burn transaction.");
         tx.verify()
             .expect("build_burn_transaction generated a transaction with an invalid signature.");
@@ -376,7 +511,11 @@ mod tests {
             burn_header_hash: BurnchainHeaderHash([0x00; 32]),
         };
         assert_eq!(
-            wallet.build_transaction(&op, 0).err().unwrap().to_string(),
+            wallet
+                .build_transaction(&op, 0, &stacks_node_mock(10))
+                .err()
+                .unwrap()
+                .to_string(),
             "Stacks Wallet Error: Failed to recover stx address from peg-out request op."
         );
     }
@@ -387,10 +526,77 @@ mod tests {
         let xonly_pubkey = XOnlyPublicKey::from_str(PUBLIC_KEY_HEX).unwrap();
 
         let tx = wallet
-            .build_set_bitcoin_wallet_public_key_transaction(&xonly_pubkey, 0)
+            .build_set_bitcoin_wallet_public_key_transaction(
+                &xonly_pubkey,
+                0,
+                &stacks_node_mock(10),
+            )
             .expect("Failed to construct a set btc address transaction.");
         tx.verify().expect(
             "build_set_btc_address_transaction generated a transaction with an invalid signature.",
         );
     }
+
+    #[test]
+    fn build_transaction_should_pay_estimated_fee_within_caps() {
+        let wallet = stacks_wallet();
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10, 1, 3);
+
+        let tx = wallet
+            .build_transaction(&op, 0, &stacks_node_mock(250))
+            .unwrap();
+        assert_eq!(tx.get_tx_fee(), 250);
+        tx.verify().unwrap();
+
+        let tx = wallet
+            .build_transaction(&op, 0, &stacks_node_mock(5_000))
+            .unwrap();
+        assert_eq!(tx.get_tx_fee(), 1_000);
+
+        let tx = wallet
+            .build_transaction(&op, 0, &stacks_node_mock(1))
+            .unwrap();
+        assert_eq!(tx.get_tx_fee(), 5);
+
+        // Nodes without a fee estimator leave the fallback fee
+        let mut stacks_node_mock = MockStacksNode::new();
+        stacks_node_mock
+            .expect_estimate_fee()
+            .returning(|_, _| Err(StacksNodeError::NoFeeEstimate("disabled".to_string())));
+        let tx = wallet.build_transaction(&op, 0, &stacks_node_mock).unwrap();
+        assert_eq!(tx.get_tx_fee(), 10);
+    }
+
+    #[test]
+    fn bump_fee_should_replace_underpaying_transactions() {
+        let wallet = stacks_wallet();
+        let op = build_peg_out_request_op(PRIVATE_KEY_HEX, 10, 1, 3);
+        let tx = wallet.build_transaction_with_fee(&op, 7, 100).unwrap();
+
+        // Paying at least the estimate needs no replacement
+        assert!(wallet
+            .bump_fee(&op, &tx, &stacks_node_mock(100))
+            .unwrap()
+            .is_none());
+
+        // A replacement pays the estimate, or at least the bump percentage more
+        let replacement = wallet
+            .bump_fee(&op, &tx, &stacks_node_mock(101))
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.get_tx_fee(), 125);
+        assert_eq!(replacement.get_origin_nonce(), 7);
+        let replacement = wallet
+            .bump_fee(&op, &tx, &stacks_node_mock(300))
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.get_tx_fee(), 300);
+
+        // Nothing is bumped past the max fee
+        let tx = wallet.build_transaction_with_fee(&op, 7, 1_000).unwrap();
+        assert!(wallet
+            .bump_fee(&op, &tx, &stacks_node_mock(2_000))
+            .unwrap()
+            .is_none());
+    }
 }
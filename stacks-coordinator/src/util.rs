@@ -24,6 +24,12 @@ pub mod test {
         util::{hash::Sha256Sum, secp256k1::Secp256k1PrivateKey},
     };
     use rand::Rng;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+    use url::Url;
 
     pub const PRIVATE_KEY_HEX: &str =
         "b244296d5907de9864c0b0d51f98a13c52890be0404e83f273144cd5b9960eed01";
@@ -112,4 +118,99 @@ pub mod test {
             outputs,
         })
     }
+
+    /// Serve canned HTTP responses from a background thread. Each route maps a request line
+    /// without its HTTP version, e.g. "GET /blocks/tip/height", to a status and body.
+    /// Unknown requests are answered with a 404.
+    pub fn mock_http_server(routes: Vec<(&'static str, u16, String)>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let request = read_http_request(&stream);
+                let (status, body) = routes
+                    .iter()
+                    .find(|(route, _, _)| *route == request)
+                    .map(|(_, status, body)| (*status, body.clone()))
+                    .unwrap_or((404, "Not Found".to_string()));
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = (&stream).write_all(response.as_bytes());
+            }
+        });
+        url
+    }
+
+    /// Read a request, returning its method and path
+    fn read_http_request(stream: &TcpStream) -> String {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap_or_default();
+
+        // Drain the headers and body so the client sees its whole request read
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or_default() == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or_default();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap_or_default();
+
+        request_line
+            .rsplit_once(' ')
+            .map(|(request, _version)| request.to_string())
+            .unwrap_or(request_line)
+    }
+
+    /// Serve canned Electrum JSON-RPC results by method from a background thread.
+    /// Unknown methods are answered with a "not found" error.
+    pub fn mock_electrum_server(responses: Vec<(&'static str, serde_json::Value)>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+        let url = Url::parse(&format!("tcp://{}", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                for line in BufReader::new(&stream).lines() {
+                    let Ok(line) = line else { break };
+                    let Ok(request) = serde_json::from_str::<serde_json::Value>(&line) else {
+                        break;
+                    };
+                    let response = match responses
+                        .iter()
+                        .find(|(method, _)| request["method"] == *method)
+                    {
+                        Some((_, result)) => serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "result": result,
+                        }),
+                        None => serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "error": {"code": 2, "message": "not found"},
+                        }),
+                    };
+                    if (&stream)
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+        url
+    }
 }
@@ -154,3 +154,30 @@ fn should_list_unspent() {
 
     assert!(!utxos.is_empty());
 }
+
+#[test]
+fn should_count_transaction_confirmations() {
+    let btcd = BitcoinProcess::new();
+
+    let (_, _, _, xonly_pubkey, _, _) = generate_wallet(true);
+
+    let wallet = BitcoinWallet::new(xonly_pubkey, Network::Regtest);
+
+    let local_btc_node = LocalhostBitcoinNode::new(btcd.url().clone());
+    local_btc_node.load_wallet(wallet.address()).unwrap();
+
+    // The coinbase is paid in the first of 100 mined blocks
+    let (coinbase_txid, _) = mine_and_get_coinbase_txid(&btcd, wallet.address());
+
+    let confirmations = local_btc_node
+        .transaction_confirmations(&coinbase_txid)
+        .unwrap();
+    assert_eq!(confirmations, Some(100));
+
+    // Transactions the wallet never saw are unknown
+    let (other_txid, _) = mine_and_get_coinbase_txid(&btcd, &generate_wallet(true).4);
+    let confirmations = local_btc_node
+        .transaction_confirmations(&other_txid)
+        .unwrap();
+    assert_eq!(confirmations, None);
+}
@@ -456,6 +456,19 @@ pub fn sign_transaction_taproot(
     signer: &mut SignerHelper,
     group_public_key: &Point,
     public_commitments: Vec<PolyCommitment>,
+) -> String {
+    // signing. Signers: 0 (parties: 0, 1) and 1 (parties: 2)
+    sign_transaction_taproot_with(tx, prev_output, group_public_key, |signing_payload| {
+        signer.signing_round(signing_payload, public_commitments)
+    })
+}
+
+/// Sign the taproot key spend of the first input of `tx` with whatever signs for the group key
+pub fn sign_transaction_taproot_with(
+    tx: &mut Transaction,
+    prev_output: &TxOut,
+    group_public_key: &Point,
+    sign: impl FnOnce(&[u8]) -> SchnorrProof,
 ) -> String {
     let mut sighash_cache = bitcoin::util::sighash::SighashCache::new(&*tx);
     let taproot_sighash = sighash_cache
@@ -466,8 +479,7 @@ pub fn sign_transaction_taproot(
         )
         .unwrap();
     let signing_payload = taproot_sighash.as_hash().to_vec();
-    // signing. Signers: 0 (parties: 0, 1) and 1 (parties: 2)
-    let schnorr_proof = signer.signing_round(&signing_payload, public_commitments);
+    let schnorr_proof = sign(&signing_payload);
     assert!(schnorr_proof.verify(&group_public_key.x(), &signing_payload));
 
     let mut frost_sig_bytes = vec![];